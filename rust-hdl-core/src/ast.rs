@@ -1,5 +1,7 @@
 use crate::bits::Bits;
 use crate::signed::Signed;
+use crate::synth::Synth;
+use crate::type_descriptor::{TypeDescriptor, TypeKind};
 use num_bigint::{BigInt, Sign};
 use std::fmt::{Display, Formatter, LowerHex};
 
@@ -231,11 +233,12 @@ use std::fmt::{Display, Formatter, LowerHex};
 ///
 /// In general RustHDL tries to avoid contention between modules with the same
 /// name by automatically namespacing them.  That means that if you have a
-/// module that is used in two different places in your code, it will get
-/// two different names.  This is because of the parametric nature of the
-/// generated code.  RustHDL does not assume (or know) that your two modules
-/// will generate identical Verilog.  So it assumes they will be different
-/// and creates two different named instances.
+/// module that is used in two different places in your code, it will be
+/// named after the first place it is used.  Because of the parametric nature
+/// of the generated code, two instances of the same Rust struct need not
+/// generate identical Verilog.  So RustHDL compares the generated modules,
+/// and only emits one definition for each unique module, which is then
+/// instantiated from every site that uses it.
 ///
 /// To see how that works, let's create a minimum example.  For test, we will
 /// use a single bit inverter.
@@ -284,10 +287,11 @@ use std::fmt::{Display, Formatter, LowerHex};
 /// x.connect_all();
 /// let v = generate_verilog(&x);
 /// // If you examine the generated code, you will see it contains
-/// // two instances of modules, one named `top$knot_1` and the
+/// // two instances of a single module named `top$knot_1`.
 /// assert!(v.contains("top$knot_1 knot_1"));
-/// // and the second is `top$knot_2`.
-/// assert!(v.contains("top$knot_2 knot_2"));
+/// // The second instance reuses the definition of the first.
+/// assert!(v.contains("top$knot_1 knot_2"));
+/// assert!(!v.contains("module top$knot_2"));
 /// ```
 /// The problem arises when you use a [BlackBox] Verilog declaration.
 /// In particular, RustHDL does not wrap your declaration (the Verilog is
@@ -602,6 +606,43 @@ pub enum VerilogStatement {
     Comment(String),
     Link(Vec<VerilogLink>),
     Macro(VerilogBlock),
+    Local(VerilogLocal),
+}
+
+/// A `let` binding in an HDL kernel.  It declares a local signal of the
+/// module, which is then written by an ordinary assignment.
+#[doc(hidden)]
+#[derive(Debug, Clone)]
+pub struct VerilogLocal {
+    pub name: String,
+    pub width: usize,
+    pub signed: bool,
+    pub descriptor: TypeDescriptor,
+}
+
+impl VerilogLocal {
+    /// The `value` is only used for its type.
+    pub fn new<T: Synth>(name: &str, _value: &T) -> Self {
+        let descriptor = T::descriptor();
+        Self {
+            name: name.into(),
+            width: T::BITS,
+            signed: matches!(descriptor.kind, TypeKind::Signed(_)),
+            descriptor,
+        }
+    }
+}
+
+/// An `#[hdl_function]`.  The arguments and the result are locals of the
+/// function, and the body computes the result by assigning to a signal with
+/// the name of the function.
+#[doc(hidden)]
+#[derive(Debug, Clone)]
+pub struct VerilogFunction {
+    pub name: String,
+    pub args: Vec<VerilogLocal>,
+    pub result: VerilogLocal,
+    pub body: VerilogBlock,
 }
 
 #[doc(hidden)]
@@ -675,6 +716,13 @@ pub struct VerilogLiteral {
 }
 
 impl VerilogLiteral {
+    /// A zero that is `bits` wide.
+    pub fn zero(bits: usize) -> Self {
+        VerilogLiteral {
+            val: BigInt::default(),
+            bits,
+        }
+    }
     pub fn as_usize(&self) -> usize {
         let m = self.val.to_u32_digits();
         assert!(m.0 != Sign::Minus);
@@ -685,6 +733,36 @@ impl VerilogLiteral {
             _ => panic!("Loop index is too large!"),
         }
     }
+    pub fn bits(&self) -> usize {
+        self.bits
+    }
+    /// The two's complement representation of the literal, MSB first,
+    /// with exactly `bits` binary digits.
+    pub fn to_binary_string(&self) -> String {
+        let modulus = BigInt::from(1_u32) << self.bits;
+        let mut val = &self.val % &modulus;
+        if val.sign() == Sign::Minus {
+            val += &modulus;
+        }
+        format!("{:0>width$}", format!("{:b}", val), width = self.bits)
+    }
+    /// The two's complement value of the literal, truncated to 128 bits.
+    pub(crate) fn as_u128(&self) -> u128 {
+        let bits = self.bits.min(128);
+        let modulus = BigInt::from(1_u32) << bits;
+        let mut val = &self.val % &modulus;
+        if val.sign() == Sign::Minus {
+            val += &modulus;
+        }
+        val.to_u64_digits()
+            .1
+            .iter()
+            .take(2)
+            .enumerate()
+            .fold(0, |acc, (ndx, digit)| {
+                acc | ((*digit as u128) << (64 * ndx))
+            })
+    }
 }
 
 impl From<bool> for VerilogLiteral {
@@ -780,6 +858,7 @@ pub enum VerilogExpression {
         Box<VerilogExpression>,
         Box<VerilogExpression>,
     ),
+    Call(Box<VerilogFunction>, Vec<VerilogExpression>),
 }
 
 #[doc(hidden)]
@@ -45,7 +45,10 @@ pub trait Atom {
 }
 
 pub fn is_atom_an_enum(atom: &dyn Atom) -> bool {
-    matches!(atom.descriptor().kind, TypeKind::Enum(_))
+    matches!(
+        atom.descriptor().kind,
+        TypeKind::Enum(_) | TypeKind::EncodedEnum(_)
+    )
 }
 
 pub fn is_atom_signed(atom: &dyn Atom) -> bool {
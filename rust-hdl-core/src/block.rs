@@ -1,3 +1,4 @@
+use crate::checkpoint::CircuitState;
 use crate::logic::Logic;
 use crate::probe::Probe;
 
@@ -16,6 +17,38 @@ pub trait Block: Logic {
     fn has_changed(&self) -> bool;
     /// The visitor pattern - allows a circuit to be probed by a [Probe] struct.
     fn accept(&self, name: &str, probe: &mut dyn Probe);
+    /// Save the simulation state of the circuit to a checkpoint, or restore it
+    /// from one (depending on [CircuitState::is_restoring]).  The default only
+    /// checkpoints the block itself (via [Logic::checkpoint]), so a block with
+    /// fields must visit them too.  The derived implementation does this.
+    fn checkpoint_all(&mut self, state: &mut CircuitState) {
+        self.checkpoint(state);
+    }
+    /// Returns `true` if this is a port of the enclosing block (i.e., a non-local
+    /// signal, or an interface of them) and it changed in the last update.
+    /// A block is never a port of its parent, so the derived implementation for
+    /// a block returns `false`.  Used by the event driven kernel, and defaults
+    /// to [Block::has_changed], which is always safe.
+    fn port_changed(&self) -> bool {
+        self.has_changed()
+    }
+    /// Returns `true` if anything that the `update` function of the enclosing
+    /// block can read has changed in the last update.  For a signal, this is
+    /// any change.  For a block, it is a change in one of its own ports (as
+    /// reported by [Block::port_changed] for its fields).
+    fn exposed_changed(&self) -> bool {
+        self.has_changed()
+    }
+    /// Like [Block::update_all], but only calls `update` on blocks for which
+    /// at least one of the signals they read has changed since the last pass.
+    fn update_changed(&mut self) {
+        self.update_all()
+    }
+    /// Record which bits of the signals in the circuit are unknown, as found
+    /// by a four-state simulation (see [Simulation::set_four_state](crate::simulate::Simulation::set_four_state)).
+    /// `unknown` gives the unknown bits of a signal from its id.  The derived
+    /// implementation visits all of the fields.
+    fn mark_unknown_all(&mut self, _unknown: &dyn Fn(usize) -> u128) {}
 }
 
 impl<B: Block> Block for Vec<B> {
@@ -46,6 +79,32 @@ impl<B: Block> Block for Vec<B> {
             x.1.accept(&name, probe);
         }
     }
+
+    fn checkpoint_all(&mut self, state: &mut CircuitState) {
+        for x in self {
+            x.checkpoint_all(state);
+        }
+    }
+
+    fn port_changed(&self) -> bool {
+        self.iter().any(|x| x.port_changed())
+    }
+
+    fn exposed_changed(&self) -> bool {
+        self.iter().any(|x| x.exposed_changed())
+    }
+
+    fn update_changed(&mut self) {
+        for x in self {
+            x.update_changed();
+        }
+    }
+
+    fn mark_unknown_all(&mut self, unknown: &dyn Fn(usize) -> u128) {
+        for x in self {
+            x.mark_unknown_all(unknown);
+        }
+    }
 }
 
 impl<B: Block, const P: usize> Block for [B; P] {
@@ -76,4 +135,30 @@ impl<B: Block, const P: usize> Block for [B; P] {
             x.1.accept(&name, probe);
         }
     }
+
+    fn checkpoint_all(&mut self, state: &mut CircuitState) {
+        for x in self {
+            x.checkpoint_all(state);
+        }
+    }
+
+    fn port_changed(&self) -> bool {
+        self.iter().any(|x| x.port_changed())
+    }
+
+    fn exposed_changed(&self) -> bool {
+        self.iter().any(|x| x.exposed_changed())
+    }
+
+    fn update_changed(&mut self) {
+        for x in self {
+            x.update_changed();
+        }
+    }
+
+    fn mark_unknown_all(&mut self, unknown: &dyn Fn(usize) -> u128) {
+        for x in self {
+            x.mark_unknown_all(unknown);
+        }
+    }
 }
@@ -1,9 +1,11 @@
 use crate::block::Block;
 use crate::check_connected::check_connected;
-use crate::check_logic_loops::check_logic_loops;
+use crate::check_function_names::check_function_names;
+use crate::check_logic_loops::{check_logic_loops, LogicLoop};
 use crate::check_write_inputs::check_inputs_not_written;
 
 use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
 
 /// A map of open connections, hashed on the signal ID
 pub type OpenMap = HashMap<usize, PathedName>;
@@ -17,23 +19,119 @@ pub struct PathedName {
     pub name: String,
 }
 
+impl Display for PathedName {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}::{}", self.path, self.name)
+    }
+}
+
 /// A list of [PathedName]
 pub type PathedNameList = Vec<PathedName>;
 
+fn list_names<'a, I: Iterator<Item = &'a PathedName>>(names: I) -> String {
+    let mut names = names.map(|x| x.to_string()).collect::<Vec<_>>();
+    names.sort();
+    names.join(", ")
+}
+
 /// The enum models the errors that can be returned from "checking"
 /// a circuit using [check_all].
 #[derive(Debug, Clone, PartialEq)]
 pub enum CheckError {
     /// The check failed because of one or more open signals (described by the [OpenMap])
     OpenSignal(OpenMap),
-    /// The circuit contains logical loops (i.e., `A <- B <- A`), and will not simulate
-    LogicLoops(PathedNameList),
+    /// The circuit contains logical loops (i.e., `A <- B <- A`), and will not simulate.
+    /// Carries the signals involved, and each loop as an ordered cycle of signals
+    /// (see [find_logic_loops](crate::check_logic_loops::find_logic_loops)).
+    LogicLoops(PathedNameList, Vec<LogicLoop>),
     /// The circuit attempts to write to the inputs, which is not allowed in RustHDL.
     WritesToInputs(PathedNameList),
+    /// The circuit calls `#[hdl_function]`s whose names in the HDL are also the names of
+    /// signals in the calling module.
+    FunctionNameClashes(PathedNameList),
 }
 
-/// This is a helper function used to check a [Block] for connection, loops, and
-/// writes to the inputs.  
+impl Display for CheckError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CheckError::OpenSignal(map) => {
+                write!(
+                    f,
+                    "Open (unconnected) signals: {}",
+                    list_names(map.values())
+                )
+            }
+            CheckError::LogicLoops(list, loops) => {
+                write!(
+                    f,
+                    "Logic loops through signals: {}",
+                    list_names(list.iter())
+                )?;
+                for x in loops {
+                    write!(f, "\n    {}", x.chain().join(" -> "))?;
+                }
+                Ok(())
+            }
+            CheckError::WritesToInputs(list) => {
+                write!(f, "Writes to input signals: {}", list_names(list.iter()))
+            }
+            CheckError::FunctionNameClashes(list) => {
+                write!(
+                    f,
+                    "HDL functions named like signals: {}",
+                    list_names(list.iter())
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for CheckError {}
+
+/// The errors that can be returned when generating HDL or toolchain constraint
+/// files for a circuit (for example, by [try_generate_verilog](crate::module_defines::try_generate_verilog)).
+#[derive(Debug, Clone, PartialEq)]
+pub enum GenerateError {
+    /// The circuit failed one of the checks in [check_all]
+    Check(CheckError),
+    /// A signal carries a constraint that the toolchain cannot express
+    UnsupportedConstraint {
+        /// The signal that carries the constraint
+        signal: PathedName,
+        /// The unsupported constraint (in its `Debug` form)
+        constraint: String,
+        /// The toolchain for which the constraints were being generated
+        toolchain: String,
+    },
+}
+
+impl From<CheckError> for GenerateError {
+    fn from(x: CheckError) -> Self {
+        GenerateError::Check(x)
+    }
+}
+
+impl Display for GenerateError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GenerateError::Check(x) => write!(f, "Circuit check failed.  {}", x),
+            GenerateError::UnsupportedConstraint {
+                signal,
+                constraint,
+                toolchain,
+            } => write!(
+                f,
+                "Constraint {} on signal {} is not supported by {}",
+                constraint, signal, toolchain
+            ),
+        }
+    }
+}
+
+impl std::error::Error for GenerateError {}
+
+/// This is a helper function used to check a [Block] for connection, loops,
+/// writes to the inputs, and functions named like signals.  
 /// ```rust
 /// use rust_hdl_core::prelude::*;
 ///
@@ -57,5 +155,6 @@ pub fn check_all(uut: &dyn Block) -> Result<(), CheckError> {
     check_connected(uut)?;
     check_logic_loops(uut)?;
     check_inputs_not_written(uut)?;
+    check_function_names(uut)?;
     Ok(())
 }
@@ -0,0 +1,70 @@
+use crate::ast::Verilog;
+use crate::atom::Atom;
+use crate::block::Block;
+use crate::check_error::{CheckError, PathedName, PathedNameList};
+use crate::named_path::NamedPath;
+use crate::probe::Probe;
+use crate::verilog_gen::{verilog_functions, verilog_locals};
+
+#[derive(Default)]
+struct CheckFunctionNames {
+    path: NamedPath,
+    namespace: NamedPath,
+    signals: Vec<Vec<String>>,
+    failures: PathedNameList,
+}
+
+impl Probe for CheckFunctionNames {
+    fn visit_start_scope(&mut self, name: &str, _node: &dyn Block) {
+        self.signals.push(vec![]);
+        self.path.push(name);
+        self.namespace.reset();
+    }
+
+    fn visit_start_namespace(&mut self, name: &str, _node: &dyn Block) {
+        self.namespace.push(name);
+    }
+
+    fn visit_atom(&mut self, name: &str, _signal: &dyn Atom) {
+        let namespace = self.namespace.flat("$");
+        let name = if namespace.is_empty() {
+            name.to_owned()
+        } else {
+            format!("{}${}", namespace, name)
+        };
+        self.signals.last_mut().unwrap().push(name);
+    }
+
+    fn visit_end_namespace(&mut self, _name: &str, _node: &dyn Block) {
+        self.namespace.pop();
+    }
+
+    fn visit_end_scope(&mut self, _name: &str, node: &dyn Block) {
+        let mut signals = self.signals.pop().unwrap();
+        if let Verilog::Combinatorial(code) = &node.hdl() {
+            signals.extend(verilog_locals(code).into_iter().map(|x| x.name));
+            for function in verilog_functions(code) {
+                if signals.contains(&function.name) {
+                    self.failures.push(PathedName {
+                        path: self.path.to_string(),
+                        name: function.name,
+                    })
+                }
+            }
+        }
+        self.path.pop();
+    }
+}
+
+/// Check a circuit to make sure that none of the `#[hdl_function]`s called
+/// by a module has the same name in the HDL as one of the signals of that
+/// module, since they share a namespace in the generated code.
+pub fn check_function_names(uut: &dyn Block) -> Result<(), CheckError> {
+    let mut visitor = CheckFunctionNames::default();
+    uut.accept("uut", &mut visitor);
+    if visitor.failures.is_empty() {
+        Ok(())
+    } else {
+        Err(CheckError::FunctionNameClashes(visitor.failures))
+    }
+}
@@ -1,11 +1,18 @@
-use crate::ast::{Verilog, VerilogExpression};
+use crate::ast::{
+    Verilog, VerilogConditional, VerilogExpression, VerilogLink, VerilogLoop, VerilogMatch,
+    VerilogStatement,
+};
 use crate::atom::{Atom, AtomKind};
 use crate::block::Block;
 use crate::check_error::{CheckError, PathedName, PathedNameList};
 use crate::named_path::NamedPath;
 use crate::probe::Probe;
-use crate::verilog_visitor::VerilogVisitor;
-use std::collections::HashSet;
+use crate::verilog_gen::{ident_fixup, verilog_statement, LoopVariable};
+use crate::verilog_visitor::{walk_block, VerilogVisitor};
+use petgraph::algo::tarjan_scc;
+use petgraph::prelude::*;
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::fmt::{Display, Formatter};
 
 #[derive(Copy, Clone, Debug, PartialEq)]
 enum Mode {
@@ -165,12 +172,454 @@ impl Probe for LocalVars {
 /// assert!(check_logic_loops(&uut).is_err());
 /// ```
 ///
+/// The error carries the cycles found by [find_logic_loops], in order.  To see
+/// the assignments that create them, use [find_logic_loops] directly.
 pub fn check_logic_loops(uut: &dyn Block) -> Result<(), CheckError> {
     let mut visitor = LocalVars::default();
     uut.accept("uut", &mut visitor);
     if visitor.loops.is_empty() {
         Ok(())
     } else {
-        Err(CheckError::LogicLoops(visitor.loops))
+        Err(CheckError::LogicLoops(visitor.loops, find_logic_loops(uut)))
     }
 }
+
+/// One step in a [LogicLoop].  The signal `to` is computed from the
+/// signal `from` by the `assignments` in `module`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LoopEdge {
+    /// The signal that is read
+    pub from: String,
+    /// The signal that is written
+    pub to: String,
+    /// Signals that are written and then read back (in the same block) on
+    /// the way from `from` to `to`
+    pub through: Vec<String>,
+    /// The path of the module that contains the assignments
+    pub module: String,
+    /// The assignments (as Verilog) that make `to` depend on `from`
+    pub assignments: Vec<String>,
+}
+
+/// A combinational logic loop, as the chain of dependencies that leads
+/// from a signal back to itself.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LogicLoop {
+    pub edges: Vec<LoopEdge>,
+}
+
+impl LogicLoop {
+    /// The signals on the loop in order, starting and ending with the same signal.
+    pub fn chain(&self) -> Vec<String> {
+        let mut chain = vec![];
+        if let Some(first) = self.edges.first() {
+            chain.push(first.from.clone());
+        }
+        for edge in &self.edges {
+            chain.extend(edge.through.iter().cloned());
+            chain.push(edge.to.clone());
+        }
+        chain
+    }
+}
+
+impl Display for LogicLoop {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{}", self.chain().join(" -> "))?;
+        for edge in &self.edges {
+            writeln!(
+                f,
+                "    {} -> {} in {}: {}",
+                edge.from,
+                edge.to,
+                edge.module,
+                edge.assignments.join(" ")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+// A source of the value of a signal at some point in a combinational block.
+// Signals that are written and then read back in the same block are traced
+// through to the signals they were computed from, since the read sees the
+// new value and not the signal itself.
+#[derive(Clone, Debug)]
+struct Dependency {
+    source: String,
+    through: Vec<String>,
+    assignments: Vec<String>,
+}
+
+impl Dependency {
+    // The value the signal had on entry to the block
+    fn current(name: &str) -> Self {
+        Self {
+            source: name.into(),
+            through: vec![],
+            assignments: vec![],
+        }
+    }
+    fn is_current(&self) -> bool {
+        self.assignments.is_empty()
+    }
+}
+
+type Definitions = HashMap<String, Vec<Dependency>>;
+
+fn merge_dependencies(into: &mut Vec<Dependency>, deps: &[Dependency]) {
+    for dep in deps {
+        if !into.iter().any(|x| x.source == dep.source) {
+            into.push(dep.clone());
+        }
+    }
+}
+
+// The base signal written by the left hand side of an assignment, and
+// whether the write only changes part of it.
+fn write_target(e: &VerilogExpression) -> Option<(&str, bool)> {
+    match e {
+        VerilogExpression::Signal(x) => Some((x.as_str(), false)),
+        VerilogExpression::Index(base, _) | VerilogExpression::Slice(base, _, _) => {
+            write_target(base).map(|(name, _)| (name, true))
+        }
+        _ => None,
+    }
+}
+
+// Builds a graph of the combinational dependencies between all of the
+// signals in the design.
+#[derive(Default)]
+struct DependencyScan {
+    path: NamedPath,
+    loops: Vec<LoopVariable>,
+    defs: Definitions,
+    conditions: Vec<Vec<String>>,
+    reads: Vec<String>,
+    nodes: HashMap<String, NodeIndex>,
+    graph: Graph<String, LoopEdge>,
+}
+
+impl DependencyScan {
+    fn full_name(&self, name: &str) -> String {
+        format!(
+            "{}${}",
+            self.path.to_string(),
+            ident_fixup(name, &self.loops)
+        )
+    }
+    fn reads_of(&mut self, e: &VerilogExpression) -> Vec<String> {
+        self.reads.clear();
+        self.visit_expression(e);
+        std::mem::take(&mut self.reads)
+    }
+    fn node(&mut self, name: &str) -> NodeIndex {
+        if let Some(ndx) = self.nodes.get(name) {
+            return *ndx;
+        }
+        let ndx = self.graph.add_node(name.to_string());
+        self.nodes.insert(name.to_string(), ndx);
+        ndx
+    }
+    fn add_edge(&mut self, dep: &Dependency, target: &str) {
+        let from = self.node(&dep.source);
+        let to = self.node(target);
+        if self.graph.find_edge(from, to).is_none() {
+            let edge = LoopEdge {
+                from: dep.source.clone(),
+                to: target.into(),
+                through: dep.through.clone(),
+                module: self.path.to_string(),
+                assignments: dep.assignments.clone(),
+            };
+            self.graph.add_edge(from, to, edge);
+        }
+    }
+    fn dependencies(&self, reads: &[String], assignment: &str) -> Vec<Dependency> {
+        let mut deps = vec![];
+        for read in reads {
+            let direct = [Dependency::current(read)];
+            for dep in self.defs.get(read).map(|x| x.as_slice()).unwrap_or(&direct) {
+                let mut dep = dep.clone();
+                if !dep.is_current() {
+                    dep.through.push(read.clone());
+                }
+                dep.assignments.push(assignment.into());
+                merge_dependencies(&mut deps, &[dep]);
+            }
+        }
+        deps
+    }
+    fn assign(
+        &mut self,
+        lhs: &VerilogExpression,
+        mut reads: Vec<String>,
+        statement: VerilogStatement,
+    ) {
+        reads.extend(self.conditions.iter().flatten().cloned());
+        let text = verilog_statement(&statement, &self.loops);
+        let deps = self.dependencies(&reads, &text);
+        let Some((target, partial)) = write_target(lhs) else {
+            return;
+        };
+        let target = self.full_name(target);
+        for dep in &deps {
+            self.add_edge(dep, &target);
+        }
+        let mut value = if partial {
+            self.defs
+                .get(&target)
+                .cloned()
+                .unwrap_or_else(|| vec![Dependency::current(&target)])
+        } else {
+            vec![]
+        };
+        merge_dependencies(&mut value, &deps);
+        self.defs.insert(target, value);
+    }
+    // After a conditional, a signal may hold the value from any of the branches
+    fn merge_branches(&mut self, before: Definitions, branches: Vec<Definitions>) {
+        let keys = branches
+            .iter()
+            .flat_map(|x| x.keys().cloned())
+            .collect::<BTreeSet<_>>();
+        let mut merged = before.clone();
+        for key in keys {
+            let mut value = vec![];
+            for branch in &branches {
+                let deps = branch
+                    .get(&key)
+                    .or_else(|| before.get(&key))
+                    .cloned()
+                    .unwrap_or_else(|| vec![Dependency::current(&key)]);
+                merge_dependencies(&mut value, &deps);
+            }
+            merged.insert(key, value);
+        }
+        self.defs = merged;
+    }
+    fn link_names(&self, owner: &str, other: &str, signal: &str) -> (String, String) {
+        let fixup = |x: &str| {
+            format!(
+                "{}${}${}",
+                self.path.to_string(),
+                x.replace('[', "$").replace(']', ""),
+                signal
+            )
+        };
+        (fixup(other), fixup(owner))
+    }
+}
+
+impl VerilogVisitor for DependencyScan {
+    fn visit_loop(&mut self, a: &VerilogLoop) {
+        for i in a.from.as_usize()..a.to.as_usize() {
+            self.loops.push(LoopVariable {
+                variable: a.index.clone(),
+                value: i,
+            });
+            walk_block(self, &a.block);
+            self.loops.pop();
+        }
+    }
+
+    fn visit_slice_assignment(
+        &mut self,
+        base: &VerilogExpression,
+        width: &usize,
+        offset: &VerilogExpression,
+        replacement: &VerilogExpression,
+    ) {
+        let mut reads = self.reads_of(offset);
+        reads.extend(self.reads_of(replacement));
+        let statement = VerilogStatement::SliceAssignment {
+            base: base.clone(),
+            width: *width,
+            offset: offset.clone(),
+            replacement: replacement.clone(),
+        };
+        // The slice keeps the rest of the bits of the base signal
+        let base =
+            VerilogExpression::Slice(Box::new(base.clone()), *width, Box::new(offset.clone()));
+        self.assign(&base, reads, statement);
+    }
+
+    fn visit_conditional(&mut self, c: &VerilogConditional) {
+        let test = self.reads_of(&c.test);
+        self.conditions.push(test);
+        let before = self.defs.clone();
+        self.visit_block(&c.then);
+        let then = std::mem::replace(&mut self.defs, before.clone());
+        self.visit_block_or_conditional(&c.otherwise);
+        let otherwise = std::mem::take(&mut self.defs);
+        self.merge_branches(before, vec![then, otherwise]);
+        self.conditions.pop();
+    }
+
+    fn visit_match(&mut self, m: &VerilogMatch) {
+        let test = self.reads_of(&m.test);
+        self.conditions.push(test);
+        let before = self.defs.clone();
+        let mut branches = vec![];
+        for case in &m.cases {
+            self.defs = before.clone();
+            self.visit_case(case);
+            branches.push(std::mem::take(&mut self.defs));
+        }
+        if !m.cases.iter().any(|x| x.condition == "default") {
+            branches.push(before.clone());
+        }
+        self.merge_branches(before, branches);
+        self.conditions.pop();
+    }
+
+    fn visit_signal(&mut self, c: &str) {
+        let name = self.full_name(c);
+        self.reads.push(name);
+    }
+
+    fn visit_link(&mut self, c: &[VerilogLink]) {
+        for link in c {
+            let (from, to) = match link {
+                VerilogLink::Forward(x) => {
+                    let (w, r) = self.link_names(&x.owner_name, &x.other_name, &x.my_name);
+                    (r, w)
+                }
+                VerilogLink::Backward(x) => {
+                    self.link_names(&x.owner_name, &x.other_name, &x.my_name)
+                }
+                // Bidirectional links are resolved by the tristate logic
+                VerilogLink::Bidirectional(_) => continue,
+            };
+            let text = format!("link {} -> {}", from, to);
+            let mut dep = Dependency::current(&from);
+            dep.assignments.push(text);
+            self.add_edge(&dep, &to);
+        }
+    }
+
+    fn visit_assignment(&mut self, l: &VerilogExpression, r: &VerilogExpression) {
+        let mut reads = self.reads_of(r);
+        // Dynamic indices on the left hand side are read too
+        let mut target = l;
+        while let VerilogExpression::Index(base, ndx) | VerilogExpression::Slice(base, _, ndx) =
+            target
+        {
+            reads.extend(self.reads_of(ndx));
+            target = base.as_ref();
+        }
+        self.assign(l, reads, VerilogStatement::Assignment(l.clone(), r.clone()));
+    }
+}
+
+impl Probe for DependencyScan {
+    fn visit_start_scope(&mut self, name: &str, node: &dyn Block) {
+        self.path.push(name);
+        if let Verilog::Combinatorial(code) = &node.hdl() {
+            self.defs.clear();
+            self.conditions.clear();
+            self.visit_block(code);
+        }
+    }
+
+    fn visit_end_scope(&mut self, _name: &str, _node: &dyn Block) {
+        self.path.pop();
+    }
+}
+
+/// Find the combinational logic loops in a circuit.  Each loop is reported
+/// once (as the shortest cycle through the first of its signals, by name),
+/// as a chain of the signals on the loop, along with the module and the
+/// assignments that create each step.  Registers break loops, as do blocks
+/// with custom HDL, since their internal logic is not visible.
+///
+/// ```rust
+/// use rust_hdl_core::prelude::*;
+/// use rust_hdl_core::check_logic_loops::find_logic_loops;
+///
+/// #[derive(LogicBlock, Default)]
+/// struct Circle {
+///    in1: Signal<In, Bit>,
+///    loc: Signal<Local, Bit>,
+///    out: Signal<Out, Bit>,
+/// }
+///
+/// impl Logic for Circle {
+///     #[hdl_gen]
+///     fn update(&mut self) {
+///         self.loc.next = self.out.val();
+///         self.out.next = self.loc.val();
+///     }
+/// }
+///
+/// let mut uut = Circle::default(); uut.connect_all();
+/// let loops = find_logic_loops(&uut);
+/// assert_eq!(loops[0].chain(), ["uut$out", "uut$loc", "uut$out"]);
+/// ```
+pub fn find_logic_loops(uut: &dyn Block) -> Vec<LogicLoop> {
+    let mut scan = DependencyScan::default();
+    uut.accept("uut", &mut scan);
+    let g = &scan.graph;
+    let mut loops = vec![];
+    for component in tarjan_scc(g) {
+        let Some(&start) = component.iter().min_by(|a, b| g[**a].cmp(&g[**b])) else {
+            continue;
+        };
+        if component.len() == 1 && g.find_edge(start, start).is_none() {
+            continue;
+        }
+        let members = component.iter().copied().collect::<HashSet<_>>();
+        // Search for the shortest way back to the start
+        let mut previous = HashMap::new();
+        let mut queue = VecDeque::from([start]);
+        let mut closing = None;
+        'search: while let Some(node) = queue.pop_front() {
+            for next in g.neighbors_directed(node, Outgoing) {
+                if next == start {
+                    closing = Some(node);
+                    break 'search;
+                }
+                if members.contains(&next) && !previous.contains_key(&next) {
+                    previous.insert(next, node);
+                    queue.push_back(next);
+                }
+            }
+        }
+        let Some(mut node) = closing else {
+            continue;
+        };
+        let mut nodes = vec![node];
+        while node != start {
+            node = previous[&node];
+            nodes.push(node);
+        }
+        nodes.reverse();
+        nodes.push(start);
+        let edges = nodes
+            .windows(2)
+            .filter_map(|x| g.find_edge(x[0], x[1]))
+            .map(|x| g[x].clone())
+            .collect();
+        loops.push(LogicLoop { edges });
+    }
+    loops.sort_by_key(|x| x.chain());
+    loops
+}
+
+/// Render logic loops (from [find_logic_loops]) as a Graphviz DOT graph.
+/// Each edge is labelled with the module and the assignments that create it.
+pub fn logic_loops_dot(loops: &[LogicLoop]) -> String {
+    let mut dot = String::from("digraph logic_loops {\n");
+    let mut seen = HashSet::new();
+    for edge in loops.iter().flat_map(|x| x.edges.iter()) {
+        if seen.insert((&edge.from, &edge.to)) {
+            let label = format!("{}: {}", edge.module, edge.assignments.join(" "));
+            dot += &format!(
+                "    {:?} -> {:?} [label={:?}];\n",
+                edge.from, edge.to, label
+            );
+        }
+    }
+    dot += "}\n";
+    dot
+}
@@ -3,16 +3,18 @@ use crate::ast::{
 };
 use crate::atom::{Atom, AtomKind};
 use crate::block::Block;
+use crate::check_error::PathedName;
 use crate::named_path::NamedPath;
 use crate::probe::Probe;
-use crate::type_descriptor::TypeKind;
 use crate::verilog_visitor::VerilogVisitor;
 use petgraph::algo::{connected_components, is_cyclic_directed};
 use petgraph::dot::Dot;
 use petgraph::prelude::*;
 use petgraph::unionfind::UnionFind;
 use petgraph::visit::NodeIndexable;
-use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::fmt::{Display, Formatter};
 
 #[derive(Clone, Debug, Copy, PartialEq)]
 enum SignalNodeKind {
@@ -71,12 +73,28 @@ enum ExpressionMode {
 
 type ReadScope = Vec<SignalNode>;
 
+// A register found in the design (from the [TimingInfo] of a block)
+#[derive(Clone, Debug)]
+struct RegisterNode {
+    path: String,
+    name: String,
+    synchronized: bool,
+}
+
+impl RegisterNode {
+    fn full_name(&self) -> String {
+        format!("{}${}", self.path, self.name)
+    }
+}
+
 struct TimingChecker {
     path: NamedPath,
     namespace: NamedPath,
     mode: ExpressionMode,
     write_name: String,
     read_names: Vec<ReadScope>,
+    synchronizers: Vec<bool>,
+    registers: Vec<RegisterNode>,
     pub graph: SignalGraph,
 }
 
@@ -88,6 +106,8 @@ impl Default for TimingChecker {
             mode: ExpressionMode::Write,
             write_name: "".to_string(),
             read_names: vec![],
+            synchronizers: vec![],
+            registers: vec![],
             graph: Default::default(),
         }
     }
@@ -133,7 +153,6 @@ impl TimingChecker {
         let write_id = self.graph.add_signal_node(&write_node);
         for scope in &self.read_names {
             for read in scope {
-                println!("Adding edge from {:?} -> {:?}", read, write_node);
                 self.graph.add_signal_edge(read, write_id, edge);
             }
         }
@@ -238,11 +257,18 @@ impl VerilogVisitor for TimingChecker {
 
 impl Probe for TimingChecker {
     fn visit_start_scope(&mut self, name: &str, node: &dyn Block) {
-        println!("Start scope {}", name);
         self.path.push(name);
         self.namespace.reset();
         self.add_code(&self.path.to_string(), node.hdl());
+        let synchronized =
+            node.is_synchronizer() || self.synchronizers.last().copied().unwrap_or(false);
+        self.synchronizers.push(synchronized);
         for info in &node.timing() {
+            self.registers.push(RegisterNode {
+                path: self.path.to_string(),
+                name: info.name.clone(),
+                synchronized,
+            });
             // The timing info represents a register.  A register
             // adds a write dependency based on the clock
             // The use of the clock decouples the outputs from the inputs.
@@ -282,7 +308,6 @@ impl Probe for TimingChecker {
         self.clear_scope();
     }
     fn visit_start_namespace(&mut self, name: &str, _node: &dyn Block) {
-        println!("Start Namespace {}", name);
         self.namespace.push(name);
     }
     fn visit_atom(&mut self, name: &str, signal: &dyn Atom) {
@@ -330,9 +355,9 @@ impl Probe for TimingChecker {
             _ => {}
         }
         let descriptor = &signal.descriptor();
-        if let TypeKind::Enum(x) = &descriptor.kind {
+        if let Some(x) = descriptor.kind.enum_labels() {
             for label in x {
-                let label = label.replace("::", "$");
+                let label = label.name.replace("::", "$");
                 let my_id = self.graph.add_signal_node(&SignalNode {
                     name: format!("{}${}", module_path, label),
                     kind: SignalNodeKind::Normal,
@@ -364,6 +389,7 @@ impl Probe for TimingChecker {
         self.namespace.pop();
     }
     fn visit_end_scope(&mut self, _name: &str, _node: &dyn Block) {
+        self.synchronizers.pop();
         self.path.pop();
     }
 }
@@ -418,3 +444,214 @@ pub fn check_timing<U: Block>(uut: &U) {
     }
     //std::fs::write("dag.dot", dot).unwrap();
 }
+
+/// A signal that is launched by a register in one clock domain and sampled
+/// by a register in a different clock domain, without passing through a
+/// synchronizer.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClockDomainCrossing {
+    /// The register that launches the signal
+    pub source: PathedName,
+    /// The clock domain of the launching register
+    pub source_domain: String,
+    /// The register that samples the signal
+    pub target: PathedName,
+    /// The clock domain of the sampling register
+    pub target_domain: String,
+    /// The signals along the path from the source register to the target register
+    pub path: Vec<String>,
+}
+
+impl Display for ClockDomainCrossing {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} ({}) -> {} ({}) via {}",
+            self.source,
+            self.source_domain,
+            self.target,
+            self.target_domain,
+            self.path.join(" -> ")
+        )
+    }
+}
+
+/// The result of a clock domain crossing check.  See [check_cdc].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CdcReport {
+    /// The clock domains found in the design.  Each domain is named after
+    /// the signal that drives the clock (e.g., `top$clock`).
+    pub domains: Vec<String>,
+    /// The unsynchronized crossings found in the design
+    pub crossings: Vec<ClockDomainCrossing>,
+}
+
+impl CdcReport {
+    /// Returns `true` if no unsynchronized crossings were found.
+    pub fn is_clean(&self) -> bool {
+        self.crossings.is_empty()
+    }
+}
+
+impl Display for CdcReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Clock domains: {}", self.domains.join(", "))?;
+        for crossing in &self.crossings {
+            writeln!(f, "Unsynchronized crossing: {}", crossing)?;
+        }
+        Ok(())
+    }
+}
+
+fn find_node(
+    g: &Graph<SignalNode, SignalEdgeKind>,
+    name: &str,
+    kind: SignalNodeKind,
+) -> Option<NodeIndex> {
+    g.node_indices()
+        .find(|i| g[*i].name == name && g[*i].kind == kind)
+}
+
+// The clock domain of a clock signal is the signal (or signals) it is
+// assigned from, traced back to a signal that has no driver in the design.
+// Usually that is a top level input, or the output of a register when the
+// clock is divided.
+fn clock_domain(g: &Graph<SignalNode, SignalEdgeKind>, clock: NodeIndex) -> String {
+    let mut roots = BTreeSet::new();
+    let mut seen = HashSet::new();
+    let mut queue = VecDeque::from([clock]);
+    while let Some(node) = queue.pop_front() {
+        if !seen.insert(node) {
+            continue;
+        }
+        let drivers = g
+            .edges_directed(node, Incoming)
+            .filter(|e| *e.weight() == SignalEdgeKind::Assign)
+            .map(|e| e.source())
+            .collect::<Vec<_>>();
+        if drivers.is_empty() {
+            roots.insert(g[node].name.clone());
+        }
+        queue.extend(drivers);
+    }
+    roots.into_iter().collect::<Vec<_>>().join(",")
+}
+
+/// Check a design for clock domain crossings.
+///
+/// The clock domain of every register (i.e., every block that reports
+/// [TimingInfo](crate::timing::TimingInfo) such as a `DFF`) is found by
+/// tracing its clock input back to the signal that drives it.  Each
+/// register input is then traced back through the combinational logic to
+/// the registers that launch it.  If the launching register is clocked
+/// from a different domain, the path is reported as a [ClockDomainCrossing].
+///
+/// Registers inside a block that is designed to cross clock domains (one
+/// for which [Logic::is_synchronizer](crate::logic::Logic::is_synchronizer)
+/// returns `true`, such as a `BitSynchronizer` or an `AsynchronousFIFO`) are
+/// not checked, so routing a signal through one of those is enough to keep
+/// it out of the report.  Signals that come from top level inputs are
+/// assumed to be asynchronous, and are not reported either.
+pub fn check_cdc<U: Block>(uut: &U) -> CdcReport {
+    let mut scan = TimingChecker::default();
+    uut.accept("top", &mut scan);
+    let g = &scan.graph.graph;
+    let domains = scan
+        .registers
+        .iter()
+        .map(|reg| {
+            let sink = find_node(g, &reg.full_name(), SignalNodeKind::Sink)?;
+            let clock = g
+                .edges_directed(sink, Incoming)
+                .find(|e| *e.weight() == SignalEdgeKind::Clock)?
+                .source();
+            Some(clock_domain(g, clock))
+        })
+        .collect::<Vec<_>>();
+    let launchers: HashMap<String, usize> = scan
+        .registers
+        .iter()
+        .enumerate()
+        .map(|(ndx, reg)| (reg.full_name(), ndx))
+        .collect();
+    let mut report = CdcReport {
+        domains: domains
+            .iter()
+            .flatten()
+            .cloned()
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect(),
+        crossings: vec![],
+    };
+    for (target, reg) in scan.registers.iter().enumerate() {
+        if reg.synchronized {
+            continue;
+        }
+        let (Some(target_domain), Some(sink)) = (
+            &domains[target],
+            find_node(g, &reg.full_name(), SignalNodeKind::Sink),
+        ) else {
+            continue;
+        };
+        // Walk backwards from the register inputs.  `toward` records the
+        // next node on the way to the sink, so the path can be recovered.
+        let mut toward: HashMap<NodeIndex, NodeIndex> = HashMap::new();
+        let mut queue = VecDeque::new();
+        for edge in g.edges_directed(sink, Incoming) {
+            if *edge.weight() != SignalEdgeKind::Input {
+                continue;
+            }
+            if let Entry::Vacant(entry) = toward.entry(edge.source()) {
+                entry.insert(sink);
+                queue.push_back(edge.source());
+            }
+        }
+        let mut reported = HashSet::new();
+        while let Some(node) = queue.pop_front() {
+            for edge in g.edges_directed(node, Incoming) {
+                match edge.weight() {
+                    SignalEdgeKind::Assign => {
+                        if let Entry::Vacant(entry) = toward.entry(edge.source()) {
+                            entry.insert(node);
+                            queue.push_back(edge.source());
+                        }
+                        continue;
+                    }
+                    SignalEdgeKind::Output => {}
+                    _ => continue,
+                }
+                let Some(&source) = launchers.get(&g[edge.source()].name) else {
+                    continue;
+                };
+                let Some(source_domain) = &domains[source] else {
+                    continue;
+                };
+                if source_domain == target_domain || !reported.insert(source) {
+                    continue;
+                }
+                let mut path = vec![];
+                let mut step = node;
+                while step != sink {
+                    path.push(g[step].name.clone());
+                    step = toward[&step];
+                }
+                let launcher = &scan.registers[source];
+                report.crossings.push(ClockDomainCrossing {
+                    source: PathedName {
+                        path: launcher.path.clone(),
+                        name: launcher.name.clone(),
+                    },
+                    source_domain: source_domain.clone(),
+                    target: PathedName {
+                        path: reg.path.clone(),
+                        name: reg.name.clone(),
+                    },
+                    target_domain: target_domain.clone(),
+                    path,
+                });
+            }
+        }
+    }
+    report
+}
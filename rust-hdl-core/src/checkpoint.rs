@@ -0,0 +1,248 @@
+use crate::block::Block;
+use crate::synth::Synth;
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+const HEADER: &str = "rust-hdl checkpoint 1";
+
+#[derive(Debug)]
+pub enum CheckpointError {
+    /// The file is not a checkpoint
+    Malformed(String),
+    /// The checkpoint does not fit the circuit it is being restored into
+    Mismatch(String),
+    IOError(std::io::Error),
+}
+
+impl From<std::io::Error> for CheckpointError {
+    fn from(x: std::io::Error) -> Self {
+        CheckpointError::IOError(x)
+    }
+}
+
+impl Display for CheckpointError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CheckpointError::Malformed(x) => write!(f, "malformed checkpoint: {}", x),
+            CheckpointError::Mismatch(x) => {
+                write!(f, "checkpoint does not match the circuit: {}", x)
+            }
+            CheckpointError::IOError(x) => write!(f, "{}", x),
+        }
+    }
+}
+
+impl std::error::Error for CheckpointError {}
+
+/// The saved state of a circuit.  Each block saves (or restores) its state
+/// in [Block::checkpoint_all], and values are restored in the order they
+/// were saved, so a checkpoint can only be restored into a circuit with the
+/// same structure as the one it was taken from.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CircuitState {
+    records: Vec<String>,
+    cursor: usize,
+    restoring: bool,
+    error: Option<String>,
+}
+
+fn binary_to_value<T: Synth>(x: &str) -> Option<T> {
+    if T::BITS == 0 {
+        return T::from_bits(&[]);
+    }
+    let bits = x
+        .bytes()
+        .rev()
+        .map(|c| match c {
+            b'0' => Some(false),
+            b'1' => Some(true),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>()?;
+    T::from_bits(&bits)
+}
+
+impl CircuitState {
+    /// Returns `true` if the state is being restored into the circuit,
+    /// and `false` if it is being saved from the circuit.
+    pub fn is_restoring(&self) -> bool {
+        self.restoring
+    }
+
+    fn fail(&mut self, msg: String) {
+        if self.error.is_none() {
+            self.error = Some(msg);
+        }
+    }
+
+    fn next_record(&mut self) -> Option<String> {
+        if self.error.is_some() {
+            return None;
+        }
+        let record = self.records.get(self.cursor).cloned();
+        self.cursor += 1;
+        if record.is_none() {
+            self.fail("the circuit has more state than the checkpoint".into());
+        }
+        record
+    }
+
+    /// Save or restore a single value.
+    pub fn value<T: Synth>(&mut self, x: &mut T) {
+        if !self.restoring {
+            self.records.push(x.verilog().to_binary_string());
+        } else if let Some(record) = self.next_record() {
+            match binary_to_value(&record) {
+                Some(y) => *x = y,
+                None => self.fail(format!(
+                    "cannot restore {} into a value of type {}",
+                    record,
+                    T::descriptor().name
+                )),
+            }
+        }
+    }
+
+    /// Save or restore the contents of a map, such as the simulated
+    /// contents of a memory.
+    pub fn map<K: Synth + Ord, V: Synth>(&mut self, x: &mut BTreeMap<K, V>) {
+        if !self.restoring {
+            self.records.push(x.len().to_string());
+            for (key, value) in x.iter() {
+                let (mut key, mut value) = (*key, *value);
+                self.value(&mut key);
+                self.value(&mut value);
+            }
+        } else if let Some(record) = self.next_record() {
+            let Ok(len) = record.parse::<usize>() else {
+                self.fail(format!("expected the size of a map, found {}", record));
+                return;
+            };
+            x.clear();
+            for _ in 0..len {
+                let mut key = K::default();
+                let mut value = V::default();
+                self.value(&mut key);
+                self.value(&mut value);
+                x.insert(key, value);
+            }
+        }
+    }
+}
+
+/// A snapshot of a simulation, consisting of the state of the circuit
+/// (the values of all of the signals, and the contents of memories) and
+/// the simulation time.  See [Simulation::run_to_checkpoint](crate::simulate::Simulation::run_to_checkpoint)
+/// and [Simulation::restore](crate::simulate::Simulation::restore).
+#[derive(Clone, Debug, PartialEq)]
+pub struct Checkpoint {
+    pub time: u64,
+    state: CircuitState,
+}
+
+impl Checkpoint {
+    /// Take a snapshot of the circuit at the given time.
+    pub fn capture<B: Block>(time: u64, uut: &mut B) -> Self {
+        let mut state = CircuitState::default();
+        uut.checkpoint_all(&mut state);
+        Self { time, state }
+    }
+
+    /// Put the circuit back into the state it was in when the snapshot was taken.
+    pub fn restore<B: Block>(&self, uut: &mut B) -> Result<(), CheckpointError> {
+        let mut state = CircuitState {
+            records: self.state.records.clone(),
+            cursor: 0,
+            restoring: true,
+            error: None,
+        };
+        uut.checkpoint_all(&mut state);
+        if let Some(err) = state.error {
+            return Err(CheckpointError::Mismatch(err));
+        }
+        if state.cursor != state.records.len() {
+            return Err(CheckpointError::Mismatch(
+                "the checkpoint has more state than the circuit".into(),
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn write<W: Write>(&self, mut w: W) -> std::io::Result<()> {
+        writeln!(w, "{}", HEADER)?;
+        writeln!(w, "time {}", self.time)?;
+        for record in &self.state.records {
+            writeln!(w, "{}", record)?;
+        }
+        w.flush()
+    }
+
+    pub fn read<R: Read>(r: R) -> Result<Self, CheckpointError> {
+        let mut lines = BufReader::new(r).lines();
+        if lines.next().transpose()?.as_deref() != Some(HEADER) {
+            return Err(CheckpointError::Malformed("missing header".into()));
+        }
+        let time = lines
+            .next()
+            .transpose()?
+            .as_deref()
+            .and_then(|x| x.strip_prefix("time "))
+            .and_then(|x| x.parse().ok())
+            .ok_or_else(|| CheckpointError::Malformed("missing simulation time".into()))?;
+        let records = lines.collect::<std::io::Result<Vec<_>>>()?;
+        Ok(Self {
+            time,
+            state: CircuitState {
+                records,
+                ..Default::default()
+            },
+        })
+    }
+
+    /// Save the checkpoint to a file.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), CheckpointError> {
+        Ok(self.write(BufWriter::new(File::create(path)?))?)
+    }
+
+    /// Load a checkpoint from a file.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, CheckpointError> {
+        Self::read(File::open(path)?)
+    }
+}
+
+#[test]
+fn test_checkpoint_round_trip() {
+    use crate::bits::Bits;
+    let mut state = CircuitState::default();
+    let mut x: Bits<12> = 0x5A3.into();
+    let mut flag = true;
+    let mut memory: BTreeMap<Bits<4>, Bits<8>> = BTreeMap::new();
+    memory.insert(3.into(), 42.into());
+    memory.insert(9.into(), 7.into());
+    state.value(&mut x);
+    state.value(&mut flag);
+    state.map(&mut memory);
+    let checkpoint = Checkpoint { time: 1234, state };
+    let mut file = vec![];
+    checkpoint.write(&mut file).unwrap();
+    let loaded = Checkpoint::read(file.as_slice()).unwrap();
+    assert_eq!(loaded.time, 1234);
+    let mut state = CircuitState {
+        restoring: true,
+        ..loaded.state
+    };
+    let mut x = Bits::<12>::default();
+    let mut flag = false;
+    let mut memory: BTreeMap<Bits<4>, Bits<8>> = BTreeMap::new();
+    state.value(&mut x);
+    state.value(&mut flag);
+    state.map(&mut memory);
+    assert_eq!(state.error, None);
+    assert_eq!(x, 0x5A3);
+    assert!(flag);
+    assert_eq!(memory.len(), 2);
+    assert_eq!(memory[&Bits::<4>::from(9)], 7);
+}
@@ -0,0 +1,1465 @@
+use crate::ast::{
+    Verilog, VerilogBlock, VerilogBlockOrConditional, VerilogConditional, VerilogExpression,
+    VerilogFunction, VerilogLink, VerilogLinkDetails, VerilogLocal, VerilogMatch, VerilogOp,
+    VerilogOpUnary, VerilogStatement,
+};
+use crate::atom::{is_atom_signed, Atom, AtomKind};
+use crate::block::Block;
+use crate::module_details::link_assignment;
+use crate::named_path::NamedPath;
+use crate::probe::Probe;
+use crate::simulate::SimError;
+use crate::synth::Synth;
+use crate::type_descriptor::{TypeDescriptor, TypeKind};
+use crate::verilog_gen::{ident_fixup, verilog_functions, verilog_locals, LoopVariable};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{Display, Formatter};
+use std::io::Write;
+
+/// A simulation model for a block with custom HDL.  The [CompiledSimulation]
+/// cannot execute custom Verilog, so blocks that use it need to describe
+/// what they do with one of these (see [Logic::primitive](crate::logic::Logic::primitive)).
+#[derive(Clone, Debug, PartialEq)]
+pub enum Primitive {
+    /// A register that copies `d` to `q` on the positive edge of `clock`.
+    /// The fields are the names of the signals in the block.
+    Register { clock: String, d: String, q: String },
+}
+
+/// The reasons a design cannot be turned into a [CompiledSimulation].
+#[derive(Clone, Debug, PartialEq)]
+pub enum CompileError {
+    /// The module has no HDL that the compiled simulation can execute.
+    UnsupportedModule { module: String, reason: String },
+    /// A signal referenced in the HDL of the module does not exist.
+    UnknownSignal { module: String, name: String },
+    /// The compiled simulation is limited to signals of 128 bits or less.
+    SignalTooWide {
+        module: String,
+        name: String,
+        width: usize,
+    },
+    /// An expression (a literal, cast or slice) in the HDL of the module is
+    /// wider than the 128 bits that the compiled simulation supports.
+    ExpressionTooWide { module: String, width: usize },
+    /// A statement that the compiled simulation does not handle.
+    UnsupportedStatement { module: String, statement: String },
+}
+
+impl Display for CompileError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CompileError::UnsupportedModule { module, reason } => {
+                write!(f, "Cannot compile module {}: {}", module, reason)
+            }
+            CompileError::UnknownSignal { module, name } => {
+                write!(f, "Unknown signal {} in module {}", name, module)
+            }
+            CompileError::SignalTooWide {
+                module,
+                name,
+                width,
+            } => write!(
+                f,
+                "Signal {} in module {} is {} bits wide, and the compiled simulation supports at most 128",
+                name, module, width
+            ),
+            CompileError::ExpressionTooWide { module, width } => write!(
+                f,
+                "An expression in module {} is {} bits wide, and the compiled simulation supports at most 128",
+                module, width
+            ),
+            CompileError::UnsupportedStatement { module, statement } => {
+                write!(f, "Unsupported statement in module {}: {}", module, statement)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CompileError {}
+
+/// A handle to a signal of a [CompiledSimulation].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SignalId(usize);
+
+#[derive(Copy, Clone, Debug)]
+enum Op {
+    Const(u128),
+    Load(usize),
+    Store(usize, u128),
+    Dup,
+    Pop,
+    Mask(u128),
+    SignExtend(usize, u128),
+    Add(u128),
+    Sub(u128),
+    Mul(u128),
+    And,
+    Or,
+    Xor,
+    LogicalAnd,
+    LogicalOr,
+    Shl(u128),
+    Shr,
+    Eq,
+    Ne,
+    Lt(Option<usize>),
+    Le(Option<usize>),
+    Gt(Option<usize>),
+    Ge(Option<usize>),
+    Not(u128),
+    Neg(u128),
+    All(u128),
+    Any,
+    Parity,
+    Index,
+    Slice(u128),
+    IndexReplace,
+    SliceReplace(u128),
+    Jump(usize),
+    JumpIfZero(usize),
+}
+
+#[derive(Clone, Debug)]
+struct SlotDetails {
+    name: String,
+    scope: usize,
+    width: usize,
+    signed: bool,
+    constant: bool,
+}
+
+#[derive(Clone, Debug)]
+struct ScopeDetails {
+    name: String,
+    parent: Option<usize>,
+    path: String,
+    code: Verilog,
+    primitive: Option<Primitive>,
+    has_outputs: bool,
+    enums: BTreeMap<String, u128>,
+}
+
+#[derive(Clone, Debug)]
+struct Register {
+    clock: usize,
+    d: usize,
+    q: usize,
+    last_clock: bool,
+}
+
+#[derive(Copy, Clone, Debug)]
+struct Operand {
+    width: usize,
+    signed: bool,
+}
+
+fn mask(width: usize) -> u128 {
+    if width >= 128 {
+        !0
+    } else {
+        (1 << width) - 1
+    }
+}
+
+fn sign_extend(x: u128, width: usize) -> i128 {
+    if width >= 128 || width == 0 {
+        x as i128
+    } else {
+        let shift = 128 - width;
+        ((x << shift) as i128) >> shift
+    }
+}
+
+fn shift_left(x: u128, n: u128) -> u128 {
+    if n >= 128 {
+        0
+    } else {
+        x << n
+    }
+}
+
+fn shift_right(x: u128, n: u128) -> u128 {
+    if n >= 128 {
+        0
+    } else {
+        x >> n
+    }
+}
+
+fn compare(a: u128, b: u128, signed: Option<usize>) -> std::cmp::Ordering {
+    match signed {
+        Some(width) => sign_extend(a, width).cmp(&sign_extend(b, width)),
+        None => a.cmp(&b),
+    }
+}
+
+// Loop indices and case labels show up as names in the AST, so
+// accept anything that looks like a Rust integer literal.
+fn parse_number(x: &str) -> Option<u128> {
+    match x {
+        "true" => return Some(1),
+        "false" => return Some(0),
+        _ => {}
+    }
+    let x = x.replace('_', "");
+    let (radix, digits) = if let Some(y) = x.strip_prefix("0x") {
+        (16, y)
+    } else if let Some(y) = x.strip_prefix("0b") {
+        (2, y)
+    } else if let Some(y) = x.strip_prefix("0o") {
+        (8, y)
+    } else {
+        (10, x.as_str())
+    };
+    let digits = match digits.find(['u', 'i']) {
+        Some(0) => return None,
+        Some(n) => &digits[0..n],
+        None => digits,
+    };
+    u128::from_str_radix(digits, radix).ok()
+}
+
+fn add_enums(enums: &mut BTreeMap<String, u128>, descriptor: &TypeDescriptor) {
+    if let Some(labels) = descriptor.kind.enum_labels() {
+        for label in labels {
+            enums
+                .entry(label.name.replace("::", "$"))
+                .or_insert(label.value);
+        }
+    }
+    if let TypeKind::Composite(fields) = &descriptor.kind {
+        for field in fields {
+            add_enums(enums, &field.kind);
+        }
+    }
+}
+
+// Collects the signals and code of every module in the design.  The
+// signals of a module are named as the module is in the generated
+// Verilog, so that the stub `child$port` in a parent refers to the
+// same signal as `port` in the child.
+#[derive(Default)]
+struct Flattener {
+    path: NamedPath,
+    namespace: NamedPath,
+    scopes: Vec<ScopeDetails>,
+    active: Vec<usize>,
+    slots: Vec<SlotDetails>,
+    init: Vec<u128>,
+    names: HashMap<String, usize>,
+    ids: HashMap<usize, usize>,
+    inputs: HashMap<usize, usize>,
+    error: Option<CompileError>,
+}
+
+impl Flattener {
+    // The `let` bindings of a kernel are local signals of its module, as are
+    // the arguments, locals and result of the functions it calls (which are
+    // named `function$name`).
+    fn add_local(&mut self, name: String, local: &VerilogLocal) {
+        let module = self.path.to_string();
+        let scope = *self.active.last().unwrap();
+        if local.width > 128 && self.error.is_none() {
+            self.error = Some(CompileError::SignalTooWide {
+                module: module.clone(),
+                name: name.clone(),
+                width: local.width,
+            });
+        }
+        self.names
+            .insert(format!("{}${}", module, name), self.slots.len());
+        self.slots.push(SlotDetails {
+            name,
+            scope,
+            width: local.width,
+            signed: local.signed,
+            constant: false,
+        });
+        self.init.push(0);
+        add_enums(&mut self.scopes[scope].enums, &local.descriptor);
+    }
+}
+
+impl Probe for Flattener {
+    fn visit_start_scope(&mut self, name: &str, node: &dyn Block) {
+        self.path.push(name);
+        self.namespace.reset();
+        let parent = self.active.last().copied();
+        self.active.push(self.scopes.len());
+        self.scopes.push(ScopeDetails {
+            name: name.to_owned(),
+            parent,
+            path: self.path.to_string(),
+            code: node.hdl(),
+            primitive: node.primitive(),
+            has_outputs: false,
+            enums: Default::default(),
+        });
+        if let Verilog::Combinatorial(code) = &node.hdl() {
+            verilog_locals(code)
+                .iter()
+                .for_each(|local| self.add_local(local.name.clone(), local));
+            for function in verilog_functions(code) {
+                for local in function
+                    .args
+                    .iter()
+                    .chain(verilog_locals(&function.body).iter())
+                    .chain(std::iter::once(&function.result))
+                {
+                    self.add_local(format!("{}${}", function.name, local.name), local);
+                }
+            }
+        }
+    }
+
+    fn visit_start_namespace(&mut self, name: &str, _node: &dyn Block) {
+        self.namespace.push(name);
+    }
+
+    fn visit_atom(&mut self, name: &str, signal: &dyn Atom) {
+        let module = self.path.to_string();
+        let name = if self.namespace.is_empty() {
+            name.to_owned()
+        } else {
+            format!("{}${}", self.namespace.flat("$"), name)
+        };
+        if signal.bits() > 128 && self.error.is_none() {
+            self.error = Some(CompileError::SignalTooWide {
+                module: module.clone(),
+                name: name.clone(),
+                width: signal.bits(),
+            });
+        }
+        self.names
+            .insert(format!("{}${}", module, name), self.slots.len());
+        self.ids.insert(signal.id(), self.slots.len());
+        let depth = self.active.len();
+        if depth == 1 && signal.kind() == AtomKind::InputParameter {
+            self.inputs.insert(signal.id(), self.slots.len());
+        }
+        self.slots.push(SlotDetails {
+            name,
+            scope: self.active[depth - 1],
+            width: signal.bits(),
+            signed: is_atom_signed(signal),
+            constant: signal.kind() == AtomKind::Constant,
+        });
+        self.init.push(signal.verilog().as_u128());
+        let descriptor = signal.descriptor();
+        for scope in &self.active[depth.saturating_sub(2)..depth] {
+            add_enums(&mut self.scopes[*scope].enums, &descriptor);
+        }
+        if signal.kind() == AtomKind::OutputParameter {
+            self.scopes[self.active[depth - 1]].has_outputs = true;
+        }
+    }
+
+    fn visit_end_namespace(&mut self, _name: &str, _node: &dyn Block) {
+        self.namespace.pop();
+    }
+
+    fn visit_end_scope(&mut self, _name: &str, _node: &dyn Block) {
+        self.path.pop();
+        self.active.pop();
+    }
+}
+
+struct InputReader<'a> {
+    inputs: &'a HashMap<usize, usize>,
+    values: Vec<(usize, u128)>,
+}
+
+impl Probe for InputReader<'_> {
+    fn visit_atom(&mut self, _name: &str, signal: &dyn Atom) {
+        if let Some(slot) = self.inputs.get(&signal.id()) {
+            self.values.push((*slot, signal.verilog().as_u128()));
+        }
+    }
+}
+
+struct Compiler<'a> {
+    module: &'a str,
+    // The module that holds the state of the functions called, which is
+    // `module` unless this is compiling the body of a function
+    owner: &'a str,
+    names: &'a HashMap<String, usize>,
+    slots: &'a [SlotDetails],
+    enums: &'a BTreeMap<String, u128>,
+    loops: Vec<LoopVariable>,
+    links: Vec<VerilogLink>,
+    ops: &'a mut Vec<Op>,
+}
+
+type CompileResult<T> = Result<T, CompileError>;
+
+impl Compiler<'_> {
+    fn unknown(&self, name: &str) -> CompileError {
+        CompileError::UnknownSignal {
+            module: self.module.to_owned(),
+            name: name.to_owned(),
+        }
+    }
+
+    fn checked_width(&self, width: usize) -> CompileResult<usize> {
+        if width > 128 {
+            Err(CompileError::ExpressionTooWide {
+                module: self.module.to_owned(),
+                width,
+            })
+        } else {
+            Ok(width)
+        }
+    }
+
+    fn slot(&self, name: &str) -> Option<usize> {
+        self.names
+            .get(&format!("{}${}", self.module, name))
+            .copied()
+    }
+
+    fn target(&self, x: &VerilogExpression) -> CompileResult<(usize, u128)> {
+        match x {
+            VerilogExpression::Signal(name) => {
+                let name = ident_fixup(name, &self.loops);
+                let slot = self.slot(&name).ok_or_else(|| self.unknown(&name))?;
+                Ok((slot, mask(self.slots[slot].width)))
+            }
+            _ => Err(CompileError::UnsupportedStatement {
+                module: self.module.to_owned(),
+                statement: format!("assignment to {:?}", x),
+            }),
+        }
+    }
+
+    fn emit(&mut self, op: Op) -> usize {
+        self.ops.push(op);
+        self.ops.len() - 1
+    }
+
+    fn patch(&mut self, jump: usize) {
+        let here = self.ops.len();
+        self.ops[jump] = match self.ops[jump] {
+            Op::Jump(_) => Op::Jump(here),
+            Op::JumpIfZero(_) => Op::JumpIfZero(here),
+            x => x,
+        };
+    }
+
+    fn block(&mut self, b: &VerilogBlock) -> CompileResult<()> {
+        for statement in b {
+            self.statement(statement)?;
+        }
+        Ok(())
+    }
+
+    fn statement(&mut self, s: &VerilogStatement) -> CompileResult<()> {
+        match s {
+            VerilogStatement::Assignment(target, value) => {
+                let (slot, mask) = self.target(target)?;
+                self.expression(value)?;
+                self.emit(Op::Store(slot, mask));
+            }
+            VerilogStatement::SliceAssignment {
+                base,
+                width,
+                offset,
+                replacement,
+            } => {
+                let width = self.checked_width(*width)?;
+                let (slot, slot_mask) = self.target(base)?;
+                self.emit(Op::Load(slot));
+                self.expression(offset)?;
+                self.expression(replacement)?;
+                self.emit(Op::SliceReplace(mask(width)));
+                self.emit(Op::Store(slot, slot_mask));
+            }
+            VerilogStatement::If(c) => self.conditional(c)?,
+            VerilogStatement::Match(m) => self.case(m)?,
+            VerilogStatement::Loop(l) => {
+                for value in l.from.as_usize()..l.to.as_usize() {
+                    self.loops.push(LoopVariable {
+                        variable: l.index.clone(),
+                        value,
+                    });
+                    self.block(&l.block)?;
+                    self.loops.pop();
+                }
+            }
+            VerilogStatement::Comment(_) => {}
+            VerilogStatement::Link(links) => {
+                for link in links {
+                    let fixup = |x: &VerilogLinkDetails| VerilogLinkDetails {
+                        my_name: ident_fixup(&x.my_name, &self.loops),
+                        owner_name: ident_fixup(&x.owner_name, &self.loops),
+                        other_name: ident_fixup(&x.other_name, &self.loops),
+                    };
+                    let link = match link {
+                        VerilogLink::Forward(x) => VerilogLink::Forward(fixup(x)),
+                        VerilogLink::Backward(x) => VerilogLink::Backward(fixup(x)),
+                        VerilogLink::Bidirectional(x) => VerilogLink::Bidirectional(fixup(x)),
+                    };
+                    self.links.push(link);
+                }
+            }
+            VerilogStatement::Macro(b) => self.block(b)?,
+            VerilogStatement::Local(_) => {}
+        }
+        Ok(())
+    }
+
+    fn conditional(&mut self, c: &VerilogConditional) -> CompileResult<()> {
+        self.expression(&c.test)?;
+        let skip_then = self.emit(Op::JumpIfZero(0));
+        self.block(&c.then)?;
+        match &c.otherwise {
+            VerilogBlockOrConditional::None => self.patch(skip_then),
+            VerilogBlockOrConditional::Block(b) => {
+                let skip_else = self.emit(Op::Jump(0));
+                self.patch(skip_then);
+                self.block(b)?;
+                self.patch(skip_else);
+            }
+            VerilogBlockOrConditional::Conditional(s) => {
+                let skip_else = self.emit(Op::Jump(0));
+                self.patch(skip_then);
+                self.statement(s)?;
+                self.patch(skip_else);
+            }
+        }
+        Ok(())
+    }
+
+    // The value under test stays on the stack until a case matches.
+    fn case(&mut self, m: &VerilogMatch) -> CompileResult<()> {
+        self.expression(&m.test)?;
+        let mut exits = vec![];
+        for case in &m.cases {
+            if case.condition == "default" {
+                self.emit(Op::Pop);
+                self.block(&case.block)?;
+                exits.push(self.emit(Op::Jump(0)));
+            } else {
+                self.emit(Op::Dup);
+                self.signal(&case.condition)?;
+                self.emit(Op::Eq);
+                let next = self.emit(Op::JumpIfZero(0));
+                self.emit(Op::Pop);
+                self.block(&case.block)?;
+                exits.push(self.emit(Op::Jump(0)));
+                self.patch(next);
+            }
+        }
+        self.emit(Op::Pop);
+        for exit in exits {
+            self.patch(exit);
+        }
+        Ok(())
+    }
+
+    fn signal(&mut self, name: &str) -> CompileResult<Operand> {
+        let name = ident_fixup(name, &self.loops);
+        if let Some(slot) = self.slot(&name) {
+            self.emit(Op::Load(slot));
+            return Ok(Operand {
+                width: self.slots[slot].width,
+                signed: self.slots[slot].signed,
+            });
+        }
+        let value = self
+            .enums
+            .get(&name)
+            .copied()
+            .or_else(|| parse_number(&name))
+            .ok_or_else(|| self.unknown(&name))?;
+        self.emit(Op::Const(value));
+        Ok(Operand {
+            width: 32,
+            signed: false,
+        })
+    }
+
+    fn expression(&mut self, e: &VerilogExpression) -> CompileResult<Operand> {
+        Ok(match e {
+            VerilogExpression::Signal(name) => self.signal(name)?,
+            VerilogExpression::Literal(x) => {
+                let width = self.checked_width(x.bits())?;
+                self.emit(Op::Const(x.as_u128()));
+                Operand {
+                    width,
+                    signed: false,
+                }
+            }
+            VerilogExpression::Cast(x, bits) => {
+                let width = self.checked_width(*bits)?;
+                self.expression(x)?;
+                self.emit(Op::Mask(mask(width)));
+                Operand {
+                    width,
+                    signed: false,
+                }
+            }
+            VerilogExpression::Signed(x) => Operand {
+                signed: true,
+                ..self.expression(x)?
+            },
+            VerilogExpression::Unsigned(x) => Operand {
+                signed: false,
+                ..self.expression(x)?
+            },
+            VerilogExpression::Paren(x) => self.expression(x)?,
+            VerilogExpression::Binary(l, op, r) => self.binary(l, op, r)?,
+            VerilogExpression::Unary(op, x) => {
+                let arg = self.expression(x)?;
+                match op {
+                    VerilogOpUnary::Not => {
+                        self.emit(Op::Not(mask(arg.width)));
+                        arg
+                    }
+                    VerilogOpUnary::Neg => {
+                        self.emit(Op::Neg(mask(arg.width)));
+                        arg
+                    }
+                    VerilogOpUnary::All => {
+                        self.emit(Op::All(mask(arg.width)));
+                        Operand {
+                            width: 1,
+                            signed: false,
+                        }
+                    }
+                    VerilogOpUnary::Any => {
+                        self.emit(Op::Any);
+                        Operand {
+                            width: 1,
+                            signed: false,
+                        }
+                    }
+                    VerilogOpUnary::Xor => {
+                        self.emit(Op::Parity);
+                        Operand {
+                            width: 1,
+                            signed: false,
+                        }
+                    }
+                }
+            }
+            VerilogExpression::Index(x, ndx) => {
+                self.expression(x)?;
+                self.expression(ndx)?;
+                self.emit(Op::Index);
+                Operand {
+                    width: 1,
+                    signed: false,
+                }
+            }
+            VerilogExpression::Slice(x, width, offset) => {
+                let width = self.checked_width(*width)?;
+                self.expression(x)?;
+                self.expression(offset)?;
+                self.emit(Op::Slice(mask(width)));
+                Operand {
+                    width,
+                    signed: false,
+                }
+            }
+            VerilogExpression::IndexReplace(x, ndx, val) => {
+                let arg = self.expression(x)?;
+                self.expression(ndx)?;
+                self.expression(val)?;
+                self.emit(Op::IndexReplace);
+                arg
+            }
+            VerilogExpression::Call(f, args) => self.call(f, args)?,
+        })
+    }
+
+    // Functions are inlined at each call.  The arguments are all evaluated
+    // before any is stored, since they may call the same function.
+    fn call(&mut self, f: &VerilogFunction, args: &[VerilogExpression]) -> CompileResult<Operand> {
+        let module = format!("{}${}", self.owner, f.name);
+        let mut params = vec![];
+        for (param, arg) in f.args.iter().zip(args) {
+            let operand = self.expression(arg)?;
+            if operand.signed && operand.width < param.width {
+                self.emit(Op::SignExtend(operand.width, mask(param.width)));
+            }
+            let slot = self
+                .names
+                .get(&format!("{}${}", module, param.name))
+                .copied()
+                .ok_or_else(|| self.unknown(&format!("{}${}", f.name, param.name)))?;
+            params.push((slot, mask(param.width)));
+        }
+        for (slot, mask) in params.into_iter().rev() {
+            self.emit(Op::Store(slot, mask));
+        }
+        let mut body = Compiler {
+            module: &module,
+            owner: self.owner,
+            names: self.names,
+            slots: self.slots,
+            enums: self.enums,
+            loops: vec![],
+            links: vec![],
+            ops: &mut *self.ops,
+        };
+        body.block(&f.body)?;
+        let result = body
+            .slot(&f.result.name)
+            .ok_or_else(|| self.unknown(&format!("{}${}", f.name, f.result.name)))?;
+        self.emit(Op::Load(result));
+        Ok(Operand {
+            width: f.result.width,
+            signed: f.result.signed,
+        })
+    }
+
+    // Follows the Verilog rules: the operation is signed only if both
+    // operands are, in which case the narrower one is sign extended.
+    fn binary(
+        &mut self,
+        l: &VerilogExpression,
+        op: &VerilogOp,
+        r: &VerilogExpression,
+    ) -> CompileResult<Operand> {
+        let left = self.expression(l)?;
+        let left_end = self.ops.len();
+        let right = self.expression(r)?;
+        let width = left.width.max(right.width);
+        let signed = left.signed && right.signed;
+        let is_shift = matches!(op, VerilogOp::Shl | VerilogOp::Shr);
+        if signed && !is_shift {
+            if left.width < width {
+                self.ops
+                    .insert(left_end, Op::SignExtend(left.width, mask(width)));
+            }
+            if right.width < width {
+                self.emit(Op::SignExtend(right.width, mask(width)));
+            }
+        }
+        let boolean = Operand {
+            width: 1,
+            signed: false,
+        };
+        let compare_width = if signed { Some(width) } else { None };
+        let result = Operand { width, signed };
+        Ok(match op {
+            VerilogOp::Add => {
+                self.emit(Op::Add(mask(width)));
+                result
+            }
+            VerilogOp::Sub => {
+                self.emit(Op::Sub(mask(width)));
+                result
+            }
+            VerilogOp::Mul => {
+                self.emit(Op::Mul(mask(width)));
+                result
+            }
+            VerilogOp::BitAnd => {
+                self.emit(Op::And);
+                result
+            }
+            VerilogOp::BitOr => {
+                self.emit(Op::Or);
+                result
+            }
+            VerilogOp::BitXor => {
+                self.emit(Op::Xor);
+                result
+            }
+            VerilogOp::LogicalAnd => {
+                self.emit(Op::LogicalAnd);
+                boolean
+            }
+            VerilogOp::LogicalOr => {
+                self.emit(Op::LogicalOr);
+                boolean
+            }
+            VerilogOp::Shl => {
+                self.emit(Op::Shl(mask(left.width)));
+                left
+            }
+            VerilogOp::Shr => {
+                self.emit(Op::Shr);
+                left
+            }
+            VerilogOp::Eq => {
+                self.emit(Op::Eq);
+                boolean
+            }
+            VerilogOp::Ne => {
+                self.emit(Op::Ne);
+                boolean
+            }
+            VerilogOp::Lt => {
+                self.emit(Op::Lt(compare_width));
+                boolean
+            }
+            VerilogOp::Le => {
+                self.emit(Op::Le(compare_width));
+                boolean
+            }
+            VerilogOp::Gt => {
+                self.emit(Op::Gt(compare_width));
+                boolean
+            }
+            VerilogOp::Ge => {
+                self.emit(Op::Ge(compare_width));
+                boolean
+            }
+        })
+    }
+}
+
+/// A simulation of the Verilog that RustHDL generates for a design, compiled
+/// into a flat program over an array of signal values.  Instead of calling
+/// `update` on each block of the circuit through the [Block] trait, every
+/// `#[hdl_gen]` kernel in the design is translated once into straight-line
+/// bytecode, which makes long simulations much faster.
+///
+/// There are some limitations:
+/// - Signals (and the literals, casts and slices in the HDL) can be at most
+///   128 bits wide.  Wider designs are rejected with a [CompileError].
+/// - Blocks with custom HDL must describe themselves with a [Primitive].
+/// - The semantics are those of the generated Verilog (e.g., a signal that
+///   is assigned and then read in the same kernel sees the new value).
+///
+/// Signals are referred to by their flattened names, relative to the top
+/// of the design (e.g., `clock` or `counter$dff$q`).  A simulation created
+/// with [CompiledSimulation::new_four_state] also tracks which bits are
+/// unknown, which is useful for finding registers that are never reset.
+/// ```rust
+/// # use rust_hdl_core::prelude::*;
+/// #[derive(LogicBlock, Default)]
+/// struct Inverter {
+///    sig_in: Signal<In, Bit>,
+///    sig_out: Signal<Out, Bit>,
+/// }
+///
+/// impl Logic for Inverter {
+///    #[hdl_gen]
+///    fn update(&mut self) {
+///        self.sig_out.next = !self.sig_in.val();
+///    }
+/// }
+///
+/// let mut uut = Inverter::default();
+/// uut.connect_all();
+/// let mut sim = CompiledSimulation::new(&uut).unwrap();
+/// let sig_in = sim.signal("sig_in").unwrap();
+/// let sig_out = sim.signal("sig_out").unwrap();
+/// sim.write(sig_in, false);
+/// sim.settle().unwrap();
+/// assert_eq!(sim.get(sig_out), 1);
+/// ```
+pub struct CompiledSimulation {
+    program: Vec<Op>,
+    registers: Vec<Register>,
+    slots: Vec<SlotDetails>,
+    scopes: Vec<TraceScope>,
+    names: HashMap<String, usize>,
+    ids: HashMap<usize, usize>,
+    inputs: HashMap<usize, usize>,
+    four_state: bool,
+    state: Vec<u128>,
+    unknown: Vec<u128>,
+    stack: Vec<u128>,
+    xstack: Vec<(u128, u128)>,
+    pending: Vec<(usize, u128, u128)>,
+    previous: Vec<u128>,
+    previous_unknown: Vec<u128>,
+}
+
+#[derive(Clone, Debug, Default)]
+struct TraceScope {
+    name: String,
+    children: Vec<usize>,
+    slots: Vec<usize>,
+}
+
+// The result of an operation is entirely unknown if any bit of its
+// inputs is unknown.
+fn poison(value: u128, unknown: u128, mask: u128) -> (u128, u128) {
+    if unknown != 0 {
+        (0, mask)
+    } else {
+        (value, 0)
+    }
+}
+
+impl CompiledSimulation {
+    /// Compile the design.  The current values of the signals in `uut` are
+    /// the initial state of the simulation.
+    pub fn new<U: Block>(uut: &U) -> Result<Self, CompileError> {
+        Self::compile(uut, false)
+    }
+
+    /// Compile the design for a four-state simulation.  Every signal except
+    /// for constants starts out as unknown (`X`), and stays that way until it
+    /// is driven with a known value.  In particular, registers are `X` until
+    /// they are clocked with a known value, so a design that relies on the
+    /// power up value of a register instead of a reset will show `X` on its
+    /// outputs.  Unknown bits propagate through the logic following the
+    /// Verilog rules (e.g., `0 & X` is `0`, but `0 + X` is `X`), and an `if`
+    /// with an unknown condition takes the `else` branch.  To track unknown
+    /// values while running testbenches, use
+    /// [Simulation::set_four_state](crate::simulate::Simulation::set_four_state).
+    pub fn new_four_state<U: Block>(uut: &U) -> Result<Self, CompileError> {
+        Self::compile(uut, true)
+    }
+
+    fn compile<U: Block>(uut: &U, four_state: bool) -> Result<Self, CompileError> {
+        let mut flat = Flattener::default();
+        uut.accept("top", &mut flat);
+        if let Some(err) = flat.error {
+            return Err(err);
+        }
+        let mut program = vec![];
+        let mut registers = vec![];
+        for scope in &flat.scopes {
+            let lookup = |name: &str| {
+                flat.names
+                    .get(&format!("{}${}", scope.path, name))
+                    .copied()
+                    .ok_or_else(|| CompileError::UnknownSignal {
+                        module: scope.path.clone(),
+                        name: name.to_owned(),
+                    })
+            };
+            if let Some(Primitive::Register { clock, d, q }) = &scope.primitive {
+                let clock = lookup(clock)?;
+                registers.push(Register {
+                    clock,
+                    d: lookup(d)?,
+                    q: lookup(q)?,
+                    last_clock: !four_state && (flat.init[clock] & 1) != 0,
+                });
+                continue;
+            }
+            let code = match &scope.code {
+                Verilog::Combinatorial(code) => code,
+                Verilog::Empty if !scope.has_outputs => continue,
+                Verilog::Empty => {
+                    return Err(CompileError::UnsupportedModule {
+                        module: scope.path.clone(),
+                        reason: "it drives outputs but has no HDL".into(),
+                    })
+                }
+                _ => {
+                    return Err(CompileError::UnsupportedModule {
+                        module: scope.path.clone(),
+                        reason: "it has custom HDL and no simulation primitive".into(),
+                    })
+                }
+            };
+            let mut compiler = Compiler {
+                module: &scope.path,
+                owner: &scope.path,
+                names: &flat.names,
+                slots: &flat.slots,
+                enums: &scope.enums,
+                loops: vec![],
+                links: vec![],
+                ops: &mut program,
+            };
+            compiler.block(code)?;
+            for link in std::mem::take(&mut compiler.links) {
+                let (target, source) = link_assignment(&link);
+                let target = lookup(&target)?;
+                compiler.emit(Op::Load(lookup(&source)?));
+                compiler.emit(Op::Store(target, mask(flat.slots[target].width)));
+            }
+        }
+        let mut scopes = flat
+            .scopes
+            .iter()
+            .map(|x| TraceScope {
+                name: x.name.clone(),
+                ..Default::default()
+            })
+            .collect::<Vec<_>>();
+        for (ndx, scope) in flat.scopes.iter().enumerate() {
+            if let Some(parent) = scope.parent {
+                scopes[parent].children.push(ndx);
+            }
+        }
+        for (ndx, slot) in flat.slots.iter().enumerate() {
+            scopes[slot.scope].slots.push(ndx);
+        }
+        let (state, unknown) = if four_state {
+            let unknown = flat
+                .slots
+                .iter()
+                .map(|x| if x.constant { 0 } else { mask(x.width) })
+                .collect::<Vec<_>>();
+            let state = flat
+                .init
+                .iter()
+                .zip(&unknown)
+                .map(|(value, unknown)| value & !unknown)
+                .collect();
+            (state, unknown)
+        } else {
+            let unknown = vec![0; flat.init.len()];
+            (flat.init, unknown)
+        };
+        Ok(Self {
+            program,
+            registers,
+            slots: flat.slots,
+            scopes,
+            names: flat.names,
+            ids: flat.ids,
+            inputs: flat.inputs,
+            four_state,
+            state,
+            unknown,
+            stack: vec![],
+            xstack: vec![],
+            pending: vec![],
+            previous: vec![],
+            previous_unknown: vec![],
+        })
+    }
+
+    /// Look up a signal by its flattened name, relative to the top of the design.
+    pub fn signal(&self, name: &str) -> Option<SignalId> {
+        self.names
+            .get(&format!("top${}", name))
+            .copied()
+            .map(SignalId)
+    }
+
+    /// The width in bits of a signal.
+    pub fn width(&self, signal: SignalId) -> usize {
+        self.slots[signal.0].width
+    }
+
+    /// The current value of a signal.  Signed values are in two's complement.
+    /// Bits that are unknown read as zero.
+    pub fn get(&self, signal: SignalId) -> u128 {
+        self.state[signal.0]
+    }
+
+    /// The bits of a signal that are unknown.  This is always zero unless the
+    /// simulation was created with [CompiledSimulation::new_four_state].
+    pub fn unknown(&self, signal: SignalId) -> u128 {
+        self.unknown[signal.0]
+    }
+
+    /// Set the value of a signal (truncated to its width).  The change is
+    /// only propagated through the design by [CompiledSimulation::settle].
+    pub fn set(&mut self, signal: SignalId, value: u128) {
+        self.state[signal.0] = value & mask(self.slots[signal.0].width);
+        self.unknown[signal.0] = 0;
+    }
+
+    /// Set a signal from a value of the type used by the design.
+    pub fn write<T: Synth>(&mut self, signal: SignalId, value: T) {
+        self.set(signal, value.verilog().as_u128());
+    }
+
+    /// Propagate changes through the design until nothing changes.
+    pub fn settle(&mut self) -> Result<(), SimError> {
+        for _ in 0..100 {
+            // A signal may be assigned several times in one pass (e.g., a
+            // default followed by an override), so compare whole passes.
+            self.previous.clone_from(&self.state);
+            self.previous_unknown.clone_from(&self.unknown);
+            if self.four_state {
+                self.execute_four_state();
+            } else {
+                self.execute();
+            }
+            self.pending.clear();
+            for reg in &mut self.registers {
+                // Unknown bits are stored as zero, so an unknown clock
+                // never produces an edge.
+                let clock = (self.state[reg.clock] & 1) != 0;
+                if clock && !reg.last_clock {
+                    self.pending
+                        .push((reg.q, self.state[reg.d], self.unknown[reg.d]));
+                }
+                reg.last_clock = clock;
+            }
+            for (q, value, unknown) in &self.pending {
+                self.state[*q] = *value;
+                self.unknown[*q] = *unknown;
+            }
+            if self.state == self.previous && self.unknown == self.previous_unknown {
+                return Ok(());
+            }
+        }
+        Err(SimError::FailedToConverge)
+    }
+
+    // Copy the values of the inputs of the top level block from the native
+    // simulation of the design, which drives them from its testbenches.
+    pub(crate) fn drive_inputs<U: Block>(&mut self, uut: &U) {
+        let mut reader = InputReader {
+            inputs: &self.inputs,
+            values: vec![],
+        };
+        uut.accept("top", &mut reader);
+        for (slot, value) in reader.values {
+            self.set(SignalId(slot), value);
+        }
+    }
+
+    // Record the unknown bits of each signal in the native simulation of
+    // the design.
+    pub(crate) fn mark_unknown<U: Block>(&self, uut: &mut U) {
+        uut.mark_unknown_all(&|id| {
+            self.ids
+                .get(&id)
+                .map(|slot| self.unknown[*slot])
+                .unwrap_or(0)
+        });
+    }
+
+    /// Start a VCD trace of the simulation.  Call [CompiledTrace::sample]
+    /// to record the state of the simulation at a given time.
+    pub fn trace<W: Write>(&self, w: W) -> std::io::Result<CompiledTrace<W>> {
+        let mut vcd = vcd::Writer::new(w);
+        vcd.timescale(1, vcd::TimescaleUnit::PS)?;
+        let mut ids = vec![];
+        if !self.scopes.is_empty() {
+            self.declare(&mut vcd, 0, &mut ids)?;
+        }
+        vcd.enddefinitions()?;
+        Ok(CompiledTrace {
+            vcd,
+            ids,
+            last: vec![None; self.slots.len()],
+        })
+    }
+
+    fn declare<W: Write>(
+        &self,
+        vcd: &mut vcd::Writer<W>,
+        scope: usize,
+        ids: &mut Vec<(usize, vcd::IdCode)>,
+    ) -> std::io::Result<()> {
+        let scope = &self.scopes[scope];
+        vcd.add_module(&scope.name)?;
+        for slot in &scope.slots {
+            let details = &self.slots[*slot];
+            ids.push((*slot, vcd.add_wire(details.width as u32, &details.name)?));
+        }
+        for child in &scope.children {
+            self.declare(vcd, *child, ids)?;
+        }
+        vcd.upscope()
+    }
+
+    // Run the combinatorial logic once.
+    fn execute(&mut self) {
+        let Self {
+            program,
+            state,
+            stack,
+            ..
+        } = self;
+        let mut pc = 0;
+        stack.clear();
+        macro_rules! binary {
+            (|$a: ident, $b: ident| $e: expr) => {{
+                let $b = stack.pop().unwrap();
+                let $a = stack.pop().unwrap();
+                stack.push($e);
+            }};
+        }
+        macro_rules! unary {
+            (|$a: ident| $e: expr) => {{
+                let $a = stack.pop().unwrap();
+                stack.push($e);
+            }};
+        }
+        while pc < program.len() {
+            match program[pc] {
+                Op::Const(x) => stack.push(x),
+                Op::Load(slot) => stack.push(state[slot]),
+                Op::Store(slot, mask) => {
+                    let x = stack.pop().unwrap() & mask;
+                    state[slot] = x;
+                }
+                Op::Dup => stack.push(*stack.last().unwrap()),
+                Op::Pop => {
+                    stack.pop();
+                }
+                Op::Mask(mask) => unary!(|a| a & mask),
+                Op::SignExtend(width, mask) => unary!(|a| (sign_extend(a, width) as u128) & mask),
+                Op::Add(mask) => binary!(|a, b| a.wrapping_add(b) & mask),
+                Op::Sub(mask) => binary!(|a, b| a.wrapping_sub(b) & mask),
+                Op::Mul(mask) => binary!(|a, b| a.wrapping_mul(b) & mask),
+                Op::And => binary!(|a, b| a & b),
+                Op::Or => binary!(|a, b| a | b),
+                Op::Xor => binary!(|a, b| a ^ b),
+                Op::LogicalAnd => binary!(|a, b| ((a != 0) && (b != 0)) as u128),
+                Op::LogicalOr => binary!(|a, b| ((a != 0) || (b != 0)) as u128),
+                Op::Shl(mask) => binary!(|a, b| shift_left(a, b) & mask),
+                Op::Shr => binary!(|a, b| shift_right(a, b)),
+                Op::Eq => binary!(|a, b| (a == b) as u128),
+                Op::Ne => binary!(|a, b| (a != b) as u128),
+                Op::Lt(s) => binary!(|a, b| compare(a, b, s).is_lt() as u128),
+                Op::Le(s) => binary!(|a, b| compare(a, b, s).is_le() as u128),
+                Op::Gt(s) => binary!(|a, b| compare(a, b, s).is_gt() as u128),
+                Op::Ge(s) => binary!(|a, b| compare(a, b, s).is_ge() as u128),
+                Op::Not(mask) => unary!(|a| !a & mask),
+                Op::Neg(mask) => unary!(|a| a.wrapping_neg() & mask),
+                Op::All(mask) => unary!(|a| (a == mask) as u128),
+                Op::Any => unary!(|a| (a != 0) as u128),
+                Op::Parity => unary!(|a| (a.count_ones() & 1) as u128),
+                Op::Index => binary!(|a, b| shift_right(a, b) & 1),
+                Op::Slice(mask) => binary!(|a, b| shift_right(a, b) & mask),
+                Op::IndexReplace => {
+                    let value = stack.pop().unwrap() & 1;
+                    binary!(|a, b| (a & !shift_left(1, b)) | shift_left(value, b))
+                }
+                Op::SliceReplace(mask) => {
+                    let value = stack.pop().unwrap() & mask;
+                    binary!(|a, b| (a & !shift_left(mask, b)) | shift_left(value, b))
+                }
+                Op::Jump(target) => {
+                    pc = target;
+                    continue;
+                }
+                Op::JumpIfZero(target) => {
+                    if stack.pop().unwrap() == 0 {
+                        pc = target;
+                        continue;
+                    }
+                }
+            }
+            pc += 1;
+        }
+    }
+    // The four-state version of execute.  Each value on the stack is paired
+    // with a mask of its unknown bits, and the unknown bits of the value
+    // itself are kept at zero.
+    fn execute_four_state(&mut self) {
+        let Self {
+            program,
+            state,
+            unknown,
+            xstack: stack,
+            ..
+        } = self;
+        let mut pc = 0;
+        stack.clear();
+        macro_rules! binary {
+            (|$a: ident, $xa: ident, $b: ident, $xb: ident| $e: expr) => {{
+                let ($b, $xb) = stack.pop().unwrap();
+                let ($a, $xa) = stack.pop().unwrap();
+                stack.push($e);
+            }};
+        }
+        macro_rules! unary {
+            (|$a: ident, $xa: ident| $e: expr) => {{
+                let ($a, $xa) = stack.pop().unwrap();
+                stack.push($e);
+            }};
+        }
+        while pc < program.len() {
+            match program[pc] {
+                Op::Const(x) => stack.push((x, 0)),
+                Op::Load(slot) => stack.push((state[slot], unknown[slot])),
+                Op::Store(slot, mask) => {
+                    let (x, xx) = stack.pop().unwrap();
+                    let (x, xx) = (x & mask, xx & mask);
+                    state[slot] = x;
+                    unknown[slot] = xx;
+                }
+                Op::Dup => stack.push(*stack.last().unwrap()),
+                Op::Pop => {
+                    stack.pop();
+                }
+                Op::Mask(mask) => unary!(|a, xa| (a & mask, xa & mask)),
+                Op::SignExtend(width, mask) => unary!(|a, xa| (
+                    (sign_extend(a, width) as u128) & mask,
+                    (sign_extend(xa, width) as u128) & mask
+                )),
+                Op::Add(mask) => {
+                    binary!(|a, xa, b, xb| poison(a.wrapping_add(b) & mask, xa | xb, mask))
+                }
+                Op::Sub(mask) => {
+                    binary!(|a, xa, b, xb| poison(a.wrapping_sub(b) & mask, xa | xb, mask))
+                }
+                Op::Mul(mask) => {
+                    binary!(|a, xa, b, xb| poison(a.wrapping_mul(b) & mask, xa | xb, mask))
+                }
+                // A known zero on either side forces the bit to zero
+                Op::And => binary!(|a, xa, b, xb| {
+                    let x = (xa | xb) & (a | xa) & (b | xb);
+                    (a & b, x)
+                }),
+                // A known one on either side forces the bit to one
+                Op::Or => binary!(|a, xa, b, xb| {
+                    let x = (xa | xb) & !a & !b;
+                    (a | b, x)
+                }),
+                Op::Xor => binary!(|a, xa, b, xb| {
+                    let x = xa | xb;
+                    ((a ^ b) & !x, x)
+                }),
+                Op::LogicalAnd => binary!(|a, xa, b, xb| {
+                    if (a == 0 && xa == 0) || (b == 0 && xb == 0) {
+                        (0, 0)
+                    } else if a != 0 && b != 0 {
+                        (1, 0)
+                    } else {
+                        (0, 1)
+                    }
+                }),
+                Op::LogicalOr => binary!(|a, xa, b, xb| {
+                    if a != 0 || b != 0 {
+                        (1, 0)
+                    } else if xa == 0 && xb == 0 {
+                        (0, 0)
+                    } else {
+                        (0, 1)
+                    }
+                }),
+                Op::Shl(mask) => binary!(|a, xa, b, xb| if xb != 0 {
+                    (0, mask)
+                } else {
+                    (shift_left(a, b) & mask, shift_left(xa, b) & mask)
+                }),
+                Op::Shr => binary!(|a, xa, b, xb| if xb != 0 {
+                    (0, !0)
+                } else {
+                    (shift_right(a, b), shift_right(xa, b))
+                }),
+                // Two values are definitely different if a known bit differs
+                Op::Eq => binary!(|a, xa, b, xb| if ((a ^ b) & !(xa | xb)) != 0 {
+                    (0, 0)
+                } else {
+                    poison(1, xa | xb, 1)
+                }),
+                Op::Ne => binary!(|a, xa, b, xb| if ((a ^ b) & !(xa | xb)) != 0 {
+                    (1, 0)
+                } else {
+                    poison(0, xa | xb, 1)
+                }),
+                Op::Lt(s) => {
+                    binary!(|a, xa, b, xb| poison(compare(a, b, s).is_lt() as u128, xa | xb, 1))
+                }
+                Op::Le(s) => {
+                    binary!(|a, xa, b, xb| poison(compare(a, b, s).is_le() as u128, xa | xb, 1))
+                }
+                Op::Gt(s) => {
+                    binary!(|a, xa, b, xb| poison(compare(a, b, s).is_gt() as u128, xa | xb, 1))
+                }
+                Op::Ge(s) => {
+                    binary!(|a, xa, b, xb| poison(compare(a, b, s).is_ge() as u128, xa | xb, 1))
+                }
+                Op::Not(mask) => unary!(|a, xa| (!a & !xa & mask, xa & mask)),
+                Op::Neg(mask) => unary!(|a, xa| poison(a.wrapping_neg() & mask, xa, mask)),
+                Op::All(mask) => unary!(|a, xa| if (!a & !xa & mask) != 0 {
+                    (0, 0)
+                } else {
+                    poison(1, xa & mask, 1)
+                }),
+                Op::Any => unary!(|a, xa| if a != 0 { (1, 0) } else { poison(0, xa, 1) }),
+                Op::Parity => unary!(|a, xa| poison((a.count_ones() & 1) as u128, xa, 1)),
+                Op::Index => binary!(|a, xa, b, xb| if xb != 0 {
+                    (0, 1)
+                } else {
+                    (shift_right(a, b) & 1, shift_right(xa, b) & 1)
+                }),
+                Op::Slice(mask) => binary!(|a, xa, b, xb| if xb != 0 {
+                    (0, mask)
+                } else {
+                    (shift_right(a, b) & mask, shift_right(xa, b) & mask)
+                }),
+                Op::IndexReplace => {
+                    let (value, xvalue) = stack.pop().unwrap();
+                    binary!(|a, xa, b, xb| if xb != 0 {
+                        (0, !0)
+                    } else {
+                        let keep = !shift_left(1, b);
+                        (
+                            (a & keep) | shift_left(value & 1, b),
+                            (xa & keep) | shift_left(xvalue & 1, b),
+                        )
+                    })
+                }
+                Op::SliceReplace(mask) => {
+                    let (value, xvalue) = stack.pop().unwrap();
+                    binary!(|a, xa, b, xb| if xb != 0 {
+                        (0, !0)
+                    } else {
+                        let keep = !shift_left(mask, b);
+                        (
+                            (a & keep) | shift_left(value & mask, b),
+                            (xa & keep) | shift_left(xvalue & mask, b),
+                        )
+                    })
+                }
+                Op::Jump(target) => {
+                    pc = target;
+                    continue;
+                }
+                // As in Verilog, a condition that is not known to be true is false
+                Op::JumpIfZero(target) => {
+                    if stack.pop().unwrap().0 == 0 {
+                        pc = target;
+                        continue;
+                    }
+                }
+            }
+            pc += 1;
+        }
+    }
+}
+
+/// A VCD trace of a [CompiledSimulation], created by [CompiledSimulation::trace].
+/// Unknown bits are recorded as `x`.
+pub struct CompiledTrace<W: Write> {
+    vcd: vcd::Writer<W>,
+    ids: Vec<(usize, vcd::IdCode)>,
+    last: Vec<Option<(u128, u128)>>,
+}
+
+impl<W: Write> CompiledTrace<W> {
+    /// Record the signals that have changed since the last sample.
+    pub fn sample(&mut self, time: u64, sim: &CompiledSimulation) -> std::io::Result<()> {
+        self.vcd.timestamp(time)?;
+        for (slot, id) in &self.ids {
+            let now = (sim.state[*slot], sim.unknown[*slot]);
+            if self.last[*slot] == Some(now) {
+                continue;
+            }
+            self.last[*slot] = Some(now);
+            let bits = (0..sim.slots[*slot].width)
+                .rev()
+                .map(|bit| {
+                    if ((now.1 >> bit) & 1) != 0 {
+                        vcd::Value::X
+                    } else if ((now.0 >> bit) & 1) != 0 {
+                        vcd::Value::V1
+                    } else {
+                        vcd::Value::V0
+                    }
+                })
+                .collect::<Vec<_>>();
+            if bits.len() == 1 {
+                self.vcd.change_scalar(*id, bits[0])?;
+            } else {
+                self.vcd.change_vector(*id, &bits)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_parse_number() {
+    assert_eq!(parse_number("42"), Some(42));
+    assert_eq!(parse_number("0x1F_u8"), Some(31));
+    assert_eq!(parse_number("0b101"), Some(5));
+    assert_eq!(parse_number("3usize"), Some(3));
+    assert_eq!(parse_number("true"), Some(1));
+    assert_eq!(parse_number("State$Idle"), None);
+}
@@ -0,0 +1,650 @@
+use crate::atom::{Atom, AtomKind};
+use crate::block::Block;
+use crate::compiled_sim::Primitive;
+use crate::probe::Probe;
+use crate::synth::VCDValue;
+use crate::type_descriptor::TypeKind;
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{Display, Formatter};
+use std::io::{Result, Write};
+
+/// An `if` chain or a `match` statement in an `#[hdl_gen]` kernel.  The
+/// location is the position of the statement in the Rust source.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BranchSite {
+    pub file: &'static str,
+    pub line: u32,
+    pub column: u32,
+    /// The labels of the arms.  For a conditional these are `if`,
+    /// `else if` and `else` (which is always present), and for a `match`
+    /// they are the patterns.
+    pub arms: &'static [&'static str],
+}
+
+type SiteKey = (&'static str, u32, u32);
+
+thread_local! {
+    static RECORDING: Cell<bool> = const { Cell::new(false) };
+    static BRANCH_HITS: RefCell<HashMap<(SiteKey, usize), u64>> = RefCell::new(HashMap::new());
+}
+
+/// Record that an arm of a [BranchSite] was taken.  Calls to this function
+/// are inserted at the top of each arm by `#[hdl_gen(coverage)]`.  They are
+/// only counted while a simulation is collecting coverage on the current
+/// thread.
+#[inline]
+pub fn branch(file: &'static str, line: u32, column: u32, arm: usize) {
+    if RECORDING.with(|x| x.get()) {
+        BRANCH_HITS.with(|hits| {
+            *hits
+                .borrow_mut()
+                .entry(((file, line, column), arm))
+                .or_default() += 1
+        });
+    }
+}
+
+// Count the branches taken by `f`, which should update a settled circuit.
+// Branches taken while the circuit settles are not counted, since they
+// depend on the order in which the blocks are updated.
+pub(crate) fn record_branches<F: FnOnce()>(f: F) {
+    RECORDING.with(|x| x.set(true));
+    f();
+    RECORDING.with(|x| x.set(false));
+}
+
+fn take_branch_hits() -> HashMap<(SiteKey, usize), u64> {
+    BRANCH_HITS.with(|hits| std::mem::take(&mut *hits.borrow_mut()))
+}
+
+/// Toggle coverage of a signal.  Bit `0` is the least significant bit.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ToggleCoverage {
+    /// The number of times each bit changed from `0` to `1`
+    pub rose: Vec<u64>,
+    /// The number of times each bit changed from `1` to `0`
+    pub fell: Vec<u64>,
+}
+
+impl ToggleCoverage {
+    fn new(width: usize) -> Self {
+        Self {
+            rose: vec![0; width],
+            fell: vec![0; width],
+        }
+    }
+    /// The width of the signal in bits.
+    pub fn width(&self) -> usize {
+        self.rose.len()
+    }
+    /// Returns `true` if the bit has both risen and fallen.
+    pub fn is_covered(&self, bit: usize) -> bool {
+        self.rose[bit] != 0 && self.fell[bit] != 0
+    }
+    /// The number of bits that have both risen and fallen.
+    pub fn covered(&self) -> usize {
+        (0..self.width()).filter(|x| self.is_covered(*x)).count()
+    }
+    fn merge(&mut self, other: &ToggleCoverage) {
+        for (list, more) in [(&mut self.rose, &other.rose), (&mut self.fell, &other.fell)] {
+            if list.len() < more.len() {
+                list.resize(more.len(), 0);
+            }
+            for (x, y) in list.iter_mut().zip(more) {
+                *x += y;
+            }
+        }
+    }
+}
+
+/// State and transition coverage of a register (`DFF`) that holds a
+/// `LogicState` enum.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FsmCoverage {
+    /// The states of the enum, and the number of times each was entered.
+    pub states: Vec<(String, u64)>,
+    /// The number of times each `(from, to)` transition was taken.  The
+    /// transitions that are possible are not known, so only those that
+    /// were taken are listed.
+    pub transitions: BTreeMap<(String, String), u64>,
+}
+
+impl FsmCoverage {
+    /// The number of states that were entered at least once.
+    pub fn covered(&self) -> usize {
+        self.states.iter().filter(|x| x.1 != 0).count()
+    }
+    fn merge(&mut self, other: &FsmCoverage) {
+        for (state, count) in &other.states {
+            match self.states.iter_mut().find(|x| x.0 == *state) {
+                Some(x) => x.1 += count,
+                None => self.states.push((state.clone(), *count)),
+            }
+        }
+        for (transition, count) in &other.transitions {
+            *self.transitions.entry(transition.clone()).or_default() += count;
+        }
+    }
+}
+
+/// Branch coverage of an `if` chain or a `match` in an `#[hdl_gen]` kernel.
+/// Coverage is collected per statement in the source, so all instances of
+/// a block contribute to the same [BranchCoverage].
+#[derive(Clone, Debug, PartialEq)]
+pub struct BranchCoverage {
+    pub file: String,
+    pub line: u32,
+    pub column: u32,
+    /// The label of each arm, and the number of times it was taken.  An arm
+    /// is counted at most once per update of the circuit by the simulation.
+    pub arms: Vec<(String, u64)>,
+}
+
+impl BranchCoverage {
+    /// The number of arms that were taken at least once.
+    pub fn covered(&self) -> usize {
+        self.arms.iter().filter(|x| x.1 != 0).count()
+    }
+}
+
+/// The number of items covered, and the total number of items, for each
+/// kind of coverage in a [Coverage].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct CoverageSummary {
+    /// Signal bits that have both risen and fallen
+    pub toggles: (usize, usize),
+    /// States of the state machines that have been entered
+    pub states: (usize, usize),
+    /// The number of distinct state machine transitions taken
+    pub transitions: usize,
+    /// Arms of the `if` and `match` statements that have been taken
+    pub branches: (usize, usize),
+}
+
+/// Coverage collected by [Simulation::run_covered](crate::simulate::Simulation::run_covered).
+/// Three kinds of coverage are collected:
+///
+/// * toggle coverage for every bit of every signal (except for constants
+///   and `LogicState` enums),
+/// * state and transition coverage for every `DFF` that holds a `LogicState`
+///   enum, which is how state machines are written in RustHDL,
+/// * branch coverage for the arms of the `if` and `match` statements in
+///   kernels marked with `#[hdl_gen(coverage)]`.  The instrumented kernels
+///   refer to `rust_hdl::core::coverage`, so this is only available to
+///   crates that use `rust_hdl`.
+///
+/// Signals are named by their hierarchical path, as in the VCD file (e.g.,
+/// `uut.state.q`).  Running several simulations with the same [Coverage]
+/// merges the results, so that the coverage of a set of testbenches can be
+/// reported together.  The report is available through [Display], or in an
+/// LCOV compatible format with [Coverage::write_lcov].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Coverage {
+    toggles: BTreeMap<String, ToggleCoverage>,
+    fsms: BTreeMap<String, FsmCoverage>,
+    branches: BTreeMap<(String, u32, u32), BranchCoverage>,
+}
+
+impl Coverage {
+    /// The toggle coverage of the signal with the given path.
+    pub fn toggle(&self, path: &str) -> Option<&ToggleCoverage> {
+        self.toggles.get(path)
+    }
+    /// The toggle coverage of all of the signals, by path.
+    pub fn toggles(&self) -> impl Iterator<Item = (&String, &ToggleCoverage)> {
+        self.toggles.iter()
+    }
+    /// The state coverage of the `DFF` whose output has the given path.
+    pub fn fsm(&self, path: &str) -> Option<&FsmCoverage> {
+        self.fsms.get(path)
+    }
+    /// The state coverage of all of the state machines, by path.
+    pub fn fsms(&self) -> impl Iterator<Item = (&String, &FsmCoverage)> {
+        self.fsms.iter()
+    }
+    /// The branch coverage of all of the `if` and `match` statements,
+    /// ordered by their location in the source.
+    pub fn branches(&self) -> impl Iterator<Item = &BranchCoverage> {
+        self.branches.values()
+    }
+    /// Add the coverage in `other` to this one.
+    pub fn merge(&mut self, other: &Coverage) {
+        for (path, toggle) in &other.toggles {
+            self.toggles.entry(path.clone()).or_default().merge(toggle);
+        }
+        for (path, fsm) in &other.fsms {
+            self.fsms.entry(path.clone()).or_default().merge(fsm);
+        }
+        for (key, site) in &other.branches {
+            match self.branches.get_mut(key) {
+                Some(x) => {
+                    for (arm, count) in x.arms.iter_mut().zip(&site.arms) {
+                        arm.1 += count.1;
+                    }
+                }
+                None => {
+                    self.branches.insert(key.clone(), site.clone());
+                }
+            }
+        }
+    }
+    /// Count the items covered for each kind of coverage.
+    pub fn summary(&self) -> CoverageSummary {
+        let sum = |x: Vec<(usize, usize)>| {
+            x.iter()
+                .fold((0, 0), |acc, item| (acc.0 + item.0, acc.1 + item.1))
+        };
+        CoverageSummary {
+            toggles: sum(self
+                .toggles
+                .values()
+                .map(|x| (x.covered(), x.width()))
+                .collect()),
+            states: sum(self
+                .fsms
+                .values()
+                .map(|x| (x.covered(), x.states.len()))
+                .collect()),
+            transitions: self.fsms.values().map(|x| x.transitions.len()).sum(),
+            branches: sum(self
+                .branches
+                .values()
+                .map(|x| (x.covered(), x.arms.len()))
+                .collect()),
+        }
+    }
+    /// Write the coverage as an LCOV tracefile (as read by `genhtml` and
+    /// most coverage viewers).  Branch coverage is written against the
+    /// Rust source files, with a `BRDA` entry for every arm.  Toggle and
+    /// state coverage have no source to point at, so each signal gets a
+    /// record of its own, named by the path of the signal:
+    ///
+    /// * for a toggled signal, line `n` is bit `n - 1`, with a hit count of
+    ///   the number of times it went through a full `0 -> 1 -> 0` cycle,
+    ///   and branches `0` and `1` counting the rising and falling edges,
+    /// * for a state machine, line `n` is the `n`th state, with a hit count
+    ///   of the number of times it was entered, and a `BRDA` entry for each
+    ///   transition taken out of that state.
+    pub fn write_lcov<W: Write>(&self, mut w: W) -> Result<()> {
+        let mut files: BTreeMap<&str, Vec<&BranchCoverage>> = BTreeMap::new();
+        for site in self.branches.values() {
+            files.entry(&site.file).or_default().push(site);
+        }
+        for (file, sites) in files {
+            let mut lines: BTreeMap<u32, u64> = BTreeMap::new();
+            let mut brda = vec![];
+            for (block, site) in sites.iter().enumerate() {
+                *lines.entry(site.line).or_default() += site.arms.iter().map(|x| x.1).sum::<u64>();
+                for (arm, (_, count)) in site.arms.iter().enumerate() {
+                    brda.push((site.line, block, arm, *count));
+                }
+            }
+            write_record(&mut w, file, lines.into_iter().collect(), brda)?;
+        }
+        for (path, toggle) in &self.toggles {
+            let lines = (0..toggle.width())
+                .map(|bit| (bit as u32 + 1, toggle.rose[bit].min(toggle.fell[bit])))
+                .collect();
+            let brda = (0..toggle.width())
+                .flat_map(|bit| {
+                    [
+                        (bit as u32 + 1, 0, 0, toggle.rose[bit]),
+                        (bit as u32 + 1, 0, 1, toggle.fell[bit]),
+                    ]
+                })
+                .collect();
+            write_record(&mut w, path, lines, brda)?;
+        }
+        for (path, fsm) in &self.fsms {
+            let line =
+                |state: &str| fsm.states.iter().position(|x| x.0 == state).unwrap_or(0) as u32 + 1;
+            let lines = fsm
+                .states
+                .iter()
+                .enumerate()
+                .map(|(ndx, x)| (ndx as u32 + 1, x.1))
+                .collect();
+            let brda = fsm
+                .transitions
+                .iter()
+                .map(|((from, to), count)| (line(from), 0, line(to) as usize - 1, *count))
+                .collect();
+            write_record(&mut w, path, lines, brda)?;
+        }
+        Ok(())
+    }
+}
+
+fn write_record<W: Write>(
+    w: &mut W,
+    name: &str,
+    lines: Vec<(u32, u64)>,
+    brda: Vec<(u32, usize, usize, u64)>,
+) -> Result<()> {
+    writeln!(w, "TN:")?;
+    writeln!(w, "SF:{}", name)?;
+    for (line, block, arm, count) in &brda {
+        writeln!(w, "BRDA:{},{},{},{}", line, block, arm, count)?;
+    }
+    writeln!(w, "BRF:{}", brda.len())?;
+    writeln!(w, "BRH:{}", brda.iter().filter(|x| x.3 != 0).count())?;
+    for (line, count) in &lines {
+        writeln!(w, "DA:{},{}", line, count)?;
+    }
+    writeln!(w, "LF:{}", lines.len())?;
+    writeln!(w, "LH:{}", lines.iter().filter(|x| x.1 != 0).count())?;
+    writeln!(w, "end_of_record")
+}
+
+fn percent(covered: usize, total: usize) -> f64 {
+    if total == 0 {
+        100.0
+    } else {
+        covered as f64 * 100.0 / total as f64
+    }
+}
+
+impl Display for Coverage {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let summary = self.summary();
+        let (covered, total) = summary.toggles;
+        writeln!(
+            f,
+            "Toggle coverage: {}/{} bits ({:.1}%)",
+            covered,
+            total,
+            percent(covered, total)
+        )?;
+        for (path, toggle) in &self.toggles {
+            let missed = (0..toggle.width())
+                .rev()
+                .filter(|x| !toggle.is_covered(*x))
+                .map(|x| x.to_string())
+                .collect::<Vec<_>>();
+            if !missed.is_empty() {
+                writeln!(f, "  {}: bits {} not toggled", path, missed.join(", "))?;
+            }
+        }
+        let (covered, total) = summary.states;
+        writeln!(
+            f,
+            "State coverage: {}/{} states ({:.1}%), {} transitions",
+            covered,
+            total,
+            percent(covered, total),
+            summary.transitions
+        )?;
+        for (path, fsm) in &self.fsms {
+            let missed = fsm
+                .states
+                .iter()
+                .filter(|x| x.1 == 0)
+                .map(|x| x.0.as_str())
+                .collect::<Vec<_>>();
+            if !missed.is_empty() {
+                writeln!(f, "  {}: {} never entered", path, missed.join(", "))?;
+            }
+            let taken = fsm
+                .transitions
+                .iter()
+                .map(|((from, to), count)| format!("{} -> {} ({})", from, to, count))
+                .collect::<Vec<_>>();
+            writeln!(f, "  {}: {}", path, taken.join(", "))?;
+        }
+        let (covered, total) = summary.branches;
+        writeln!(
+            f,
+            "Branch coverage: {}/{} arms ({:.1}%)",
+            covered,
+            total,
+            percent(covered, total)
+        )?;
+        for site in self.branches.values() {
+            for (arm, _) in site.arms.iter().filter(|x| x.1 == 0) {
+                writeln!(
+                    f,
+                    "  {}:{}:{}: `{}` never taken",
+                    site.file, site.line, site.column, arm
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+enum Slot {
+    Toggle(usize),
+    Fsm(usize),
+    Ignore,
+    Composite(Vec<Slot>),
+}
+
+/// Collects the [Coverage] of a single simulation run.
+pub(crate) struct CoverageRecorder {
+    slots: HashMap<usize, Slot>,
+    toggles: Vec<(String, ToggleCoverage, Option<Vec<vcd::Value>>)>,
+    fsms: Vec<(String, FsmCoverage, Option<usize>)>,
+    sites: HashMap<SiteKey, BranchSite>,
+}
+
+struct CoverageHeader<'a> {
+    recorder: &'a mut CoverageRecorder,
+    scope: Vec<String>,
+    // The name of the output of each register in the scope stack
+    registers: Vec<Option<String>>,
+}
+
+impl CoverageHeader<'_> {
+    fn register(&mut self, path: String, kind: &TypeKind, is_register: bool) -> Slot {
+        let recorder = &mut *self.recorder;
+        match kind {
+            TypeKind::Bits(width) | TypeKind::Signed(width) => {
+                recorder
+                    .toggles
+                    .push((path, ToggleCoverage::new(*width), None));
+                Slot::Toggle(recorder.toggles.len() - 1)
+            }
+            TypeKind::Enum(_) | TypeKind::EncodedEnum(_) if is_register => {
+                let states = kind
+                    .enum_labels()
+                    .unwrap_or_default()
+                    .iter()
+                    .map(|x| (x.name.rsplit("::").next().unwrap().to_string(), 0))
+                    .collect();
+                recorder.fsms.push((
+                    path,
+                    FsmCoverage {
+                        states,
+                        transitions: Default::default(),
+                    },
+                    None,
+                ));
+                Slot::Fsm(recorder.fsms.len() - 1)
+            }
+            TypeKind::Enum(_) | TypeKind::EncodedEnum(_) => Slot::Ignore,
+            TypeKind::Composite(fields) => Slot::Composite(
+                fields
+                    .iter()
+                    .map(|field| {
+                        let path = format!("{}${}", path, field.fieldname);
+                        self.register(path, &field.kind.kind, false)
+                    })
+                    .collect(),
+            ),
+        }
+    }
+}
+
+impl Probe for CoverageHeader<'_> {
+    fn visit_start_scope(&mut self, name: &str, node: &dyn Block) {
+        self.scope.push(name.into());
+        self.registers.push(match node.primitive() {
+            Some(Primitive::Register { q, .. }) => Some(q),
+            _ => None,
+        });
+        for site in node.branch_sites() {
+            self.recorder
+                .sites
+                .insert((site.file, site.line, site.column), site);
+        }
+    }
+
+    fn visit_start_namespace(&mut self, name: &str, _node: &dyn Block) {
+        self.scope.push(name.into());
+        self.registers.push(None);
+    }
+
+    fn visit_atom(&mut self, name: &str, signal: &dyn Atom) {
+        if signal.kind() == AtomKind::Constant {
+            return;
+        }
+        let path = format!("{}.{}", self.scope.join("."), name);
+        let is_register = matches!(self.registers.last(), Some(Some(q)) if q == name);
+        let slot = self.register(path, &signal.descriptor().kind, is_register);
+        self.recorder.slots.insert(signal.id(), slot);
+    }
+
+    fn visit_end_namespace(&mut self, _name: &str, _node: &dyn Block) {
+        self.scope.pop();
+        self.registers.pop();
+    }
+
+    fn visit_end_scope(&mut self, _name: &str, _node: &dyn Block) {
+        self.scope.pop();
+        self.registers.pop();
+    }
+}
+
+struct CoverageSample<'a> {
+    recorder: &'a mut CoverageRecorder,
+}
+
+fn sample_value(
+    toggles: &mut [(String, ToggleCoverage, Option<Vec<vcd::Value>>)],
+    fsms: &mut [(String, FsmCoverage, Option<usize>)],
+    slot: &Slot,
+    value: VCDValue,
+) {
+    match (slot, value) {
+        (Slot::Toggle(ndx), VCDValue::Single(x)) => sample_bits(&mut toggles[*ndx], vec![x]),
+        (Slot::Toggle(ndx), VCDValue::Vector(x)) => sample_bits(&mut toggles[*ndx], x),
+        (Slot::Fsm(ndx), VCDValue::String(x)) => {
+            let (_, fsm, last) = &mut fsms[*ndx];
+            let state = fsm.states.iter().position(|s| s.0 == x);
+            if state != *last {
+                if let Some(state) = state {
+                    fsm.states[state].1 += 1;
+                    if let Some(last) = *last {
+                        let from = fsm.states[last].0.clone();
+                        *fsm.transitions.entry((from, x)).or_default() += 1;
+                    }
+                }
+                *last = state;
+            }
+        }
+        (Slot::Composite(slots), VCDValue::Composite(values)) => {
+            for (slot, value) in slots.iter().zip(values) {
+                sample_value(toggles, fsms, slot, *value);
+            }
+        }
+        _ => {}
+    }
+}
+
+// The bits are most significant bit first, as in the VCD file
+fn sample_bits(
+    (_, toggle, last): &mut (String, ToggleCoverage, Option<Vec<vcd::Value>>),
+    bits: Vec<vcd::Value>,
+) {
+    if let Some(last) = last {
+        for (ndx, (old, new)) in last.iter().zip(&bits).rev().enumerate() {
+            match (old, new) {
+                (vcd::Value::V0, vcd::Value::V1) => toggle.rose[ndx] += 1,
+                (vcd::Value::V1, vcd::Value::V0) => toggle.fell[ndx] += 1,
+                _ => {}
+            }
+        }
+    }
+    *last = Some(bits);
+}
+
+impl Probe for CoverageSample<'_> {
+    fn visit_atom(&mut self, _name: &str, signal: &dyn Atom) {
+        let recorder = &mut *self.recorder;
+        if let Some(slot) = recorder.slots.get(&signal.id()) {
+            sample_value(
+                &mut recorder.toggles,
+                &mut recorder.fsms,
+                slot,
+                signal.vcd(),
+            );
+        }
+    }
+}
+
+impl CoverageRecorder {
+    pub(crate) fn new(uut: &dyn Block) -> CoverageRecorder {
+        // Discard branches recorded by an earlier simulation on this thread
+        take_branch_hits();
+        let mut recorder = CoverageRecorder {
+            slots: Default::default(),
+            toggles: vec![],
+            fsms: vec![],
+            sites: Default::default(),
+        };
+        uut.accept(
+            "uut",
+            &mut CoverageHeader {
+                recorder: &mut recorder,
+                scope: vec![],
+                registers: vec![],
+            },
+        );
+        recorder
+    }
+    /// Record the state of `uut` once it has settled.
+    pub(crate) fn sample(&mut self, uut: &dyn Block) {
+        uut.accept("uut", &mut CoverageSample { recorder: self });
+    }
+    /// Finish the recording, and collect the branches taken.
+    pub(crate) fn finish(self) -> Coverage {
+        let hits = take_branch_hits();
+        let branches = self
+            .sites
+            .into_iter()
+            .map(|(key, site)| {
+                let arms = site
+                    .arms
+                    .iter()
+                    .enumerate()
+                    .map(|(ndx, arm)| {
+                        (arm.to_string(), hits.get(&(key, ndx)).copied().unwrap_or(0))
+                    })
+                    .collect();
+                (
+                    (site.file.to_string(), site.line, site.column),
+                    BranchCoverage {
+                        file: site.file.to_string(),
+                        line: site.line,
+                        column: site.column,
+                        arms,
+                    },
+                )
+            })
+            .collect();
+        Coverage {
+            toggles: self
+                .toggles
+                .into_iter()
+                .map(|(path, toggle, _)| (path, toggle))
+                .collect(),
+            fsms: self
+                .fsms
+                .into_iter()
+                .map(|(path, fsm, _)| (path, fsm))
+                .collect(),
+            branches,
+        }
+    }
+}
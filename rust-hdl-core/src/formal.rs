@@ -0,0 +1,365 @@
+use crate::block::Block;
+use crate::check_error::GenerateError;
+use crate::code_writer::CodeWriter;
+use crate::module_defines::try_generate_verilog;
+use std::collections::HashSet;
+use std::env::temp_dir;
+use std::fmt::{Display, Formatter};
+use std::fs::{create_dir_all, remove_dir_all, File};
+use std::io::{BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PropertyKind {
+    Assert,
+    Assume,
+    Cover,
+}
+
+/// A formal property of a block, returned from [Logic::properties](crate::logic::Logic::properties).
+///
+/// The expression is a Verilog expression written in terms of the signal
+/// names of the module generated for the block (so a field `full` of a
+/// child `fifo` is written `fifo$full`).  A property without a clock is
+/// emitted as an immediate assertion that is checked at all times.  A
+/// property with a clock is emitted as a concurrent assertion that is
+/// checked on each rising edge of that clock.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Property {
+    pub kind: PropertyKind,
+    pub name: String,
+    pub expression: String,
+    pub clock: Option<String>,
+}
+
+impl Property {
+    fn new(kind: PropertyKind, name: &str, expression: &str) -> Self {
+        Self {
+            kind,
+            name: name.into(),
+            expression: expression.into(),
+            clock: None,
+        }
+    }
+    /// A property that must always hold.
+    pub fn assert(name: &str, expression: &str) -> Self {
+        Self::new(PropertyKind::Assert, name, expression)
+    }
+    /// A constraint on the inputs of the design.
+    pub fn assume(name: &str, expression: &str) -> Self {
+        Self::new(PropertyKind::Assume, name, expression)
+    }
+    /// A condition that must be reachable.
+    pub fn cover(name: &str, expression: &str) -> Self {
+        Self::new(PropertyKind::Cover, name, expression)
+    }
+    /// Check the property on the rising edges of `clock`.
+    pub fn on_clock(self, clock: &str) -> Self {
+        Self {
+            clock: Some(clock.into()),
+            ..self
+        }
+    }
+}
+
+// The properties are placed behind `ifdef FORMAL`, so that they are only
+// seen by the formal tools, and not by synthesis or simulation.
+pub(crate) fn write_properties(properties: &[Property], io: &mut CodeWriter) {
+    if properties.is_empty() {
+        return;
+    }
+    io.add("\n// Formal properties");
+    io.add("`ifdef FORMAL");
+    for property in properties {
+        let verb = match property.kind {
+            PropertyKind::Assert => "assert",
+            PropertyKind::Assume => "assume",
+            PropertyKind::Cover => "cover",
+        };
+        match &property.clock {
+            Some(clock) => io.add(format!(
+                "{}: {} property (@(posedge {}) {});",
+                property.name, verb, clock, property.expression
+            )),
+            None => io.add(format!(
+                "always @(*) {}: {} ({});",
+                property.name, verb, property.expression
+            )),
+        }
+    }
+    io.add("`endif");
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum FormalMode {
+    /// Bounded model check - look for assertion failures within `depth` steps
+    Bmc,
+    /// Unbounded proof by k-induction
+    Prove,
+    /// Find traces that reach each of the cover properties
+    Cover,
+}
+
+#[derive(Clone, Debug)]
+pub struct FormalOptions {
+    pub mode: FormalMode,
+    pub depth: usize,
+    pub engine: String,
+}
+
+impl Default for FormalOptions {
+    fn default() -> Self {
+        Self {
+            mode: FormalMode::Bmc,
+            depth: 20,
+            engine: "smtbmc".into(),
+        }
+    }
+}
+
+/// Generate the SymbiYosys script that checks `top.sv`.
+pub fn generate_sby(options: &FormalOptions) -> String {
+    let mode = match options.mode {
+        FormalMode::Bmc => "bmc",
+        FormalMode::Prove => "prove",
+        FormalMode::Cover => "cover",
+    };
+    let mut io = CodeWriter::default();
+    io.add("[options]");
+    io.add(format!("mode {}", mode));
+    io.add(format!("depth {}", options.depth));
+    io.add_line("");
+    io.add("[engines]");
+    io.add(&options.engine);
+    io.add_line("");
+    io.add("[script]");
+    io.add("read -formal top.sv");
+    io.add("prep -top top");
+    io.add_line("");
+    io.add("[files]");
+    io.add("top.sv");
+    io.to_string()
+}
+
+/// Write the Verilog for `uut` (with its formal properties) to `top.sv`,
+/// and the SymbiYosys script to `top.sby`, both in `dir`.
+pub fn formal_export<U: Block>(
+    uut: &U,
+    dir: &Path,
+    options: &FormalOptions,
+) -> Result<(), FormalError> {
+    let verilog = try_generate_verilog(uut)?;
+    create_dir_all(dir)?;
+    write!(File::create(dir.join("top.sv"))?, "{}", verilog)?;
+    write!(
+        File::create(dir.join("top.sby"))?,
+        "{}",
+        generate_sby(options)
+    )?;
+    Ok(())
+}
+
+#[derive(Debug)]
+pub enum FormalError {
+    /// The `sby` executable could not be found
+    SbyNotFound,
+    /// The design could not be translated to Verilog
+    Generate(GenerateError),
+    /// SymbiYosys did not complete the check
+    Failed {
+        stdout: String,
+        stderr: String,
+    },
+    IOError(std::io::Error),
+}
+
+impl From<std::io::Error> for FormalError {
+    fn from(x: std::io::Error) -> Self {
+        FormalError::IOError(x)
+    }
+}
+
+impl From<GenerateError> for FormalError {
+    fn from(x: GenerateError) -> Self {
+        FormalError::Generate(x)
+    }
+}
+
+impl Display for FormalError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FormalError::SbyNotFound => write!(f, "sby is not installed"),
+            FormalError::Generate(x) => write!(f, "{}", x),
+            FormalError::Failed { stdout, stderr } => {
+                write!(f, "sby failed:\n{}\n{}", stdout, stderr)
+            }
+            FormalError::IOError(x) => write!(f, "{}", x),
+        }
+    }
+}
+
+impl std::error::Error for FormalError {}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum FormalResult {
+    /// All of the properties hold (or, in cover mode, all were reached)
+    Pass,
+    /// A property failed.  The counterexample (if SymbiYosys produced one)
+    /// is written as a VCD using the same scope names as [Simulation](crate::simulate::Simulation).
+    Fail {
+        property: Option<String>,
+        counterexample: Option<PathBuf>,
+    },
+}
+
+/// Returns `true` if `sby` can be run on this machine.
+pub fn symbiyosys_available() -> bool {
+    Command::new("sby")
+        .arg("--help")
+        .output()
+        .map(|x| x.status.success())
+        .unwrap_or(false)
+}
+
+/// Run SymbiYosys on the design in `uut`, checking the properties
+/// returned from [Logic::properties](crate::logic::Logic::properties) by
+/// each block.
+///
+/// The working files are kept in a directory named `prefix` under the
+/// temporary directory.  If a property fails, the counterexample trace
+/// is rewritten to `counterexample.vcd` in that directory, with the top
+/// scope named `uut` and the internal signals of the solver removed, so
+/// that it can be compared directly with a trace of the Rust simulation.
+/// Returns [FormalError::SbyNotFound] if SymbiYosys is not installed.
+pub fn formal_verify<U: Block>(
+    uut: &U,
+    prefix: &str,
+    options: &FormalOptions,
+) -> Result<FormalResult, FormalError> {
+    if !symbiyosys_available() {
+        return Err(FormalError::SbyNotFound);
+    }
+    let dir = temp_dir().as_path().join(prefix);
+    let _ = remove_dir_all(&dir);
+    formal_export(uut, &dir, options)?;
+    let output = Command::new("sby")
+        .current_dir(&dir)
+        .args(["-f", "top.sby"])
+        .output()?;
+    let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+    let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+    write!(
+        File::create(dir.join("sby.stdout"))?,
+        "{}\n{}",
+        stdout,
+        stderr
+    )?;
+    if stdout.contains("DONE (PASS") {
+        return Ok(FormalResult::Pass);
+    }
+    if !stdout.contains("DONE (FAIL") {
+        return Err(FormalError::Failed { stdout, stderr });
+    }
+    let trace = dir.join("top").join("engine_0").join("trace.vcd");
+    let counterexample = if trace.exists() {
+        let target = dir.join("counterexample.vcd");
+        remap_trace(BufReader::new(File::open(trace)?), File::create(&target)?)?;
+        Some(target)
+    } else {
+        None
+    };
+    Ok(FormalResult::Fail {
+        property: failed_property(&stdout),
+        counterexample,
+    })
+}
+
+// SymbiYosys reports a failure as (for example)
+//   summary: Assert failed in top: no_overflow
+// or, for an unnamed property, with the source location of the property.
+fn failed_property(stdout: &str) -> Option<String> {
+    let regex = regex::Regex::new(r"(?:Assert|Assumption|Cover) failed in (\S+): (\S+)").unwrap();
+    regex.captures(stdout).map(|x| x[2].to_string())
+}
+
+// Yosys adds signals of its own (names starting with `$`, and the
+// `_witness_` wires of the solver).  The generated Verilog also declares
+// a `child$port` wire in the parent for each port of a child instance,
+// which duplicates the `port` signal in the scope of the child.
+fn remap_scope(
+    items: Vec<vcd::ScopeItem>,
+    dropped: &mut HashSet<vcd::IdCode>,
+) -> Vec<vcd::ScopeItem> {
+    let children = items
+        .iter()
+        .filter_map(|x| match x {
+            vcd::ScopeItem::Scope(s) => Some(s.identifier.clone()),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    let is_internal = |name: &str| {
+        name.starts_with('$')
+            || name.starts_with("_witness_")
+            || children
+                .iter()
+                .any(|child| name.starts_with(&format!("{}$", child)))
+    };
+    items
+        .into_iter()
+        .filter_map(|x| match x {
+            vcd::ScopeItem::Scope(mut s) => {
+                s.children = remap_scope(s.children, dropped);
+                Some(vcd::ScopeItem::Scope(s))
+            }
+            vcd::ScopeItem::Var(v) => {
+                if is_internal(&v.reference) {
+                    dropped.insert(v.code);
+                    None
+                } else {
+                    Some(vcd::ScopeItem::Var(v))
+                }
+            }
+        })
+        .collect()
+}
+
+/// Rewrite a trace produced by SymbiYosys to use the naming of the
+/// traces produced by [Simulation](crate::simulate::Simulation).
+pub fn remap_trace<R: std::io::BufRead, W: Write>(r: R, w: W) -> std::io::Result<()> {
+    let mut parser = vcd::Parser::new(r);
+    let mut header = parser.parse_header()?;
+    for item in &mut header.items {
+        if let vcd::ScopeItem::Scope(s) = item {
+            if s.identifier == "top" {
+                s.identifier = "uut".into();
+            }
+        }
+    }
+    let mut dropped = HashSet::new();
+    header.items = remap_scope(header.items, &mut dropped);
+    let mut writer = vcd::Writer::new(w);
+    writer.header(&header)?;
+    for command in parser {
+        let command = command?;
+        let skip = match &command {
+            vcd::Command::ChangeScalar(id, _)
+            | vcd::Command::ChangeVector(id, _)
+            | vcd::Command::ChangeReal(id, _)
+            | vcd::Command::ChangeString(id, _) => dropped.contains(id),
+            _ => false,
+        };
+        if !skip {
+            writer.command(&command)?;
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_failed_property_is_found() {
+    let log = "SBY 12:00:00 [top] summary: Assert failed in top: no_overflow\n\
+               SBY 12:00:00 [top] DONE (FAIL, rc=2)";
+    assert_eq!(failed_property(log), Some("no_overflow".into()));
+    assert_eq!(failed_property("DONE (PASS, rc=0)"), None);
+}
@@ -0,0 +1,518 @@
+use crate::atom::Atom;
+use crate::block::Block;
+use crate::probe::Probe;
+use crate::synth::VCDValue;
+use crate::type_descriptor::{enum_width, TypeDescriptor, TypeKind};
+use crate::vcd_probe::TraceWriter;
+use flate2::write::{GzEncoder, ZlibEncoder};
+use flate2::Compression;
+use std::collections::HashMap;
+use std::io::{Result, Seek, SeekFrom, Write};
+
+// Block types, scope and variable codes from the FST format
+const FST_BL_HDR: u8 = 0;
+const FST_BL_VCDATA: u8 = 1;
+const FST_BL_GEOM: u8 = 3;
+const FST_BL_HIER: u8 = 4;
+const FST_ST_VCD_MODULE: u8 = 0;
+const FST_ST_VCD_SCOPE: u8 = 254;
+const FST_ST_VCD_UPSCOPE: u8 = 255;
+const FST_VT_VCD_WIRE: u8 = 16;
+const FST_VD_IMPLICIT: u8 = 0;
+const FST_HDR_SIM_VERSION_SIZE: usize = 128;
+const FST_HDR_DATE_SIZE: usize = 119;
+const FST_DOUBLE_ENDTEST: f64 = std::f64::consts::E;
+
+// Value changes are written out in blocks of about this many bytes
+const BLOCK_SIZE: usize = 32 * 1024 * 1024;
+
+#[derive(Clone, Debug)]
+enum FstIdCode {
+    Singleton(usize),
+    // The short name of each label, and the value that encodes it
+    Enum(usize, Vec<(String, u128)>),
+    Composite(Vec<FstIdCode>),
+}
+
+/// Writes simulation traces in the FST format used by GTKWave (and other
+/// waveform viewers).  FST files are binary and compressed, and so are much
+/// smaller than the equivalent VCD file.  Because the header of the file is
+/// rewritten once the simulation is done, the writer must be seekable.
+/// `LogicState` enums are written as the bits of their encoded value (see
+/// the `#[encoding]` attribute of `LogicState`).
+pub struct FstProbe<W: Write + Seek> {
+    out: W,
+    id_map: HashMap<usize, FstIdCode>,
+    hierarchy: Vec<u8>,
+    scope_count: u64,
+    widths: Vec<usize>,
+    // The current value of each signal, as a string of `0`, `1`, `x` and `z`
+    values: Vec<Vec<u8>>,
+    // The value changes of the current block
+    frame: Vec<u8>,
+    times: Vec<u64>,
+    chains: Vec<Vec<u8>>,
+    last_index: Vec<usize>,
+    buffered: usize,
+    start_time: Option<u64>,
+    end_time: u64,
+    blocks: u64,
+}
+
+fn write_varint(buf: &mut Vec<u8>, mut x: u64) {
+    loop {
+        let byte = (x & 0x7F) as u8;
+        x >>= 7;
+        if x == 0 {
+            buf.push(byte);
+            return;
+        }
+        buf.push(byte | 0x80);
+    }
+}
+
+fn zlib(data: &[u8]) -> Result<Vec<u8>> {
+    let mut encoder = ZlibEncoder::new(vec![], Compression::default());
+    encoder.write_all(data)?;
+    encoder.finish()
+}
+
+// Compressed sections are stored uncompressed if that is smaller, which
+// readers detect by the compressed and uncompressed lengths being equal
+fn zlib_or_raw(data: &[u8]) -> Result<Vec<u8>> {
+    let packed = zlib(data)?;
+    Ok(if packed.len() < data.len() {
+        packed
+    } else {
+        data.to_vec()
+    })
+}
+
+impl<W: Write + Seek> FstProbe<W> {
+    fn new(out: W) -> FstProbe<W> {
+        Self {
+            out,
+            id_map: HashMap::default(),
+            hierarchy: vec![],
+            scope_count: 0,
+            widths: vec![],
+            values: vec![],
+            frame: vec![],
+            times: vec![],
+            chains: vec![],
+            last_index: vec![],
+            buffered: 0,
+            start_time: None,
+            end_time: 0,
+            blocks: 0,
+        }
+    }
+
+    fn add_scope(&mut self, name: &str) {
+        self.hierarchy.push(FST_ST_VCD_SCOPE);
+        self.hierarchy.push(FST_ST_VCD_MODULE);
+        self.hierarchy.extend(name.as_bytes());
+        // The name is followed by an (empty) component name
+        self.hierarchy.extend([0, 0]);
+        self.scope_count += 1;
+    }
+
+    fn upscope(&mut self) {
+        self.hierarchy.push(FST_ST_VCD_UPSCOPE);
+    }
+
+    fn add_var(&mut self, name: &str, width: usize) -> usize {
+        self.hierarchy.push(FST_VT_VCD_WIRE);
+        self.hierarchy.push(FST_VD_IMPLICIT);
+        self.hierarchy.extend(name.as_bytes());
+        self.hierarchy.push(0);
+        write_varint(&mut self.hierarchy, width as u64);
+        // An alias of zero means that this is a new signal
+        write_varint(&mut self.hierarchy, 0);
+        self.widths.push(width);
+        self.values.push(vec![b'x'; width]);
+        self.frame.extend(vec![b'x'; width]);
+        self.chains.push(vec![]);
+        self.last_index.push(0);
+        self.widths.len() - 1
+    }
+
+    fn register_signal(&mut self, name: &str, descriptor: &TypeDescriptor) -> FstIdCode {
+        match &descriptor.kind {
+            TypeKind::Bits(width) | TypeKind::Signed(width) => {
+                FstIdCode::Singleton(self.add_var(name, *width))
+            }
+            TypeKind::Enum(_) | TypeKind::EncodedEnum(_) => {
+                let labels = descriptor.kind.enum_labels().unwrap_or_default();
+                let width = enum_width(&labels).max(1);
+                let labels = labels
+                    .iter()
+                    .map(|x| {
+                        let name = x.name.rsplit("::").next().unwrap_or_default();
+                        (name.to_owned(), x.value)
+                    })
+                    .collect();
+                FstIdCode::Enum(self.add_var(name, width), labels)
+            }
+            TypeKind::Composite(k) => FstIdCode::Composite(
+                k.iter()
+                    .map(|field| {
+                        let sub_name = format!("{}${}", name, field.fieldname);
+                        self.register_signal(&sub_name, &field.kind)
+                    })
+                    .collect(),
+            ),
+        }
+    }
+
+    fn write_header(&mut self) -> Result<()> {
+        let mut block = vec![FST_BL_HDR];
+        block.extend(329_u64.to_be_bytes());
+        block.extend(self.start_time.unwrap_or_default().to_be_bytes());
+        block.extend(self.end_time.to_be_bytes());
+        block.extend(FST_DOUBLE_ENDTEST.to_le_bytes());
+        // Memory used by the writer
+        block.extend(0_u64.to_be_bytes());
+        block.extend(self.scope_count.to_be_bytes());
+        // The number of variables and the number of signals are the same,
+        // since aliases are not used
+        block.extend((self.widths.len() as u64).to_be_bytes());
+        block.extend((self.widths.len() as u64).to_be_bytes());
+        block.extend(self.blocks.to_be_bytes());
+        // Time is in picoseconds
+        block.push(-12_i8 as u8);
+        let mut version = b"rust-hdl".to_vec();
+        version.resize(FST_HDR_SIM_VERSION_SIZE, 0);
+        block.extend(version);
+        block.extend([0; FST_HDR_DATE_SIZE]);
+        // File type of Verilog
+        block.push(0);
+        // Time zero
+        block.extend(0_u64.to_be_bytes());
+        self.out.write_all(&block)
+    }
+
+    fn change(&mut self, handle: usize, value: Vec<u8>) {
+        if self.values[handle] == value {
+            return;
+        }
+        let index = self.times.len().saturating_sub(1);
+        let delta = (index - self.last_index[handle]) as u64;
+        self.last_index[handle] = index;
+        let chain = &mut self.chains[handle];
+        let start = chain.len();
+        if value.len() == 1 {
+            match value[0] {
+                b'0' => write_varint(chain, delta << 2),
+                b'1' => write_varint(chain, (delta << 2) | 2),
+                b'z' => write_varint(chain, (delta << 4) | 3),
+                _ => write_varint(chain, (delta << 4) | 1),
+            }
+        } else if value.iter().all(|x| *x == b'0' || *x == b'1') {
+            // Two state values are packed 8 bits to a byte, most significant first
+            write_varint(chain, delta << 1);
+            let mut packed = vec![0_u8; value.len().div_ceil(8)];
+            for (ndx, bit) in value.iter().enumerate() {
+                if *bit == b'1' {
+                    packed[ndx / 8] |= 0x80 >> (ndx % 8);
+                }
+            }
+            chain.extend(packed);
+        } else {
+            write_varint(chain, (delta << 1) | 1);
+            chain.extend(&value);
+        }
+        self.buffered += chain.len() - start;
+        self.values[handle] = value;
+    }
+
+    fn record(&mut self, idc: &FstIdCode, val: &VCDValue) {
+        let to_ascii = |x: &vcd::Value| match x {
+            vcd::Value::V0 => b'0',
+            vcd::Value::V1 => b'1',
+            vcd::Value::X => b'x',
+            vcd::Value::Z => b'z',
+        };
+        match (idc, val) {
+            (FstIdCode::Singleton(handle), VCDValue::Single(x)) => {
+                self.change(*handle, vec![to_ascii(x)])
+            }
+            (FstIdCode::Singleton(handle), VCDValue::Vector(x)) => {
+                self.change(*handle, x.iter().map(to_ascii).collect())
+            }
+            (FstIdCode::Enum(handle, labels), VCDValue::String(x)) => {
+                let width = self.widths[*handle];
+                let value = match labels.iter().find(|label| label.0 == *x) {
+                    Some((_, value)) => (0..width)
+                        .rev()
+                        .map(|bit| if value & (1 << bit) != 0 { b'1' } else { b'0' })
+                        .collect(),
+                    None => vec![b'x'; width],
+                };
+                self.change(*handle, value)
+            }
+            (FstIdCode::Composite(idcs), VCDValue::Composite(vals)) => {
+                assert_eq!(
+                    idcs.len(),
+                    vals.len(),
+                    "Mismatch in values versus type information"
+                );
+                for (idc, val) in idcs.iter().zip(vals) {
+                    self.record(idc, val);
+                }
+            }
+            (FstIdCode::Composite(_), _) => panic!("Scalar data received for composite type"),
+            _ => panic!("Composite data received for singleton type"),
+        }
+    }
+
+    fn write_block(&mut self) -> Result<()> {
+        if self.times.is_empty() {
+            return Ok(());
+        }
+        let mut block = vec![];
+        block.extend(self.times[0].to_be_bytes());
+        block.extend(self.times[self.times.len() - 1].to_be_bytes());
+        // The memory needed by a reader to unpack the value changes
+        let memory = self.frame.len() + self.chains.iter().map(|x| x.len()).sum::<usize>();
+        block.extend((memory as u64).to_be_bytes());
+        let frame = zlib_or_raw(&self.frame)?;
+        write_varint(&mut block, self.frame.len() as u64);
+        write_varint(&mut block, frame.len() as u64);
+        write_varint(&mut block, self.widths.len() as u64);
+        block.extend(frame);
+        write_varint(&mut block, self.widths.len() as u64);
+        // The chains of value changes are located relative to the pack type
+        let chain_start = block.len();
+        block.push(b'Z');
+        let mut offsets = vec![];
+        for chain in &self.chains {
+            if chain.is_empty() {
+                offsets.push(None);
+                continue;
+            }
+            offsets.push(Some((block.len() - chain_start) as u64));
+            let packed = zlib(chain)?;
+            if packed.len() < chain.len() {
+                write_varint(&mut block, chain.len() as u64);
+                block.extend(packed);
+            } else {
+                // An uncompressed length of zero marks a chain that is not compressed
+                write_varint(&mut block, 0);
+                block.extend(chain);
+            }
+        }
+        // The position table holds the (delta encoded) offsets of the chains,
+        // with runs of signals that did not change stored as a count
+        let mut table = vec![];
+        let mut previous = 0;
+        let mut unchanged = 0;
+        for offset in offsets {
+            match offset {
+                Some(offset) => {
+                    if unchanged != 0 {
+                        write_varint(&mut table, unchanged << 1);
+                        unchanged = 0;
+                    }
+                    write_varint(&mut table, ((offset - previous) << 1) | 1);
+                    previous = offset;
+                }
+                None => unchanged += 1,
+            }
+        }
+        if unchanged != 0 {
+            write_varint(&mut table, unchanged << 1);
+        }
+        block.extend(&table);
+        block.extend((table.len() as u64).to_be_bytes());
+        let mut times = vec![];
+        let mut previous = 0;
+        for time in &self.times {
+            write_varint(&mut times, time - previous);
+            previous = *time;
+        }
+        let packed = zlib_or_raw(&times)?;
+        block.extend(&packed);
+        block.extend((times.len() as u64).to_be_bytes());
+        block.extend((packed.len() as u64).to_be_bytes());
+        block.extend((self.times.len() as u64).to_be_bytes());
+        self.out.write_all(&[FST_BL_VCDATA])?;
+        self.out
+            .write_all(&(block.len() as u64 + 8).to_be_bytes())?;
+        self.out.write_all(&block)?;
+        self.blocks += 1;
+        // The next block starts from the current values
+        self.frame = self.values.concat();
+        self.times.clear();
+        self.chains.iter_mut().for_each(|x| x.clear());
+        self.last_index.iter_mut().for_each(|x| *x = 0);
+        self.buffered = 0;
+        Ok(())
+    }
+
+    /// Start the value changes at time `ts`.
+    pub fn timestamp(&mut self, ts: u64) -> Result<()> {
+        if self.times.last() == Some(&ts) {
+            return Ok(());
+        }
+        if self.buffered > BLOCK_SIZE {
+            self.write_block()?;
+        }
+        self.start_time.get_or_insert(ts);
+        self.end_time = ts;
+        self.times.push(ts);
+        Ok(())
+    }
+
+    /// Write out the remaining value changes, the description of the
+    /// signals, and the final header.
+    pub fn finish(&mut self) -> Result<()> {
+        self.write_block()?;
+        let mut geometry = vec![];
+        for width in &self.widths {
+            write_varint(&mut geometry, *width as u64);
+        }
+        let packed = zlib_or_raw(&geometry)?;
+        self.out.write_all(&[FST_BL_GEOM])?;
+        self.out
+            .write_all(&(packed.len() as u64 + 24).to_be_bytes())?;
+        self.out.write_all(&(geometry.len() as u64).to_be_bytes())?;
+        self.out
+            .write_all(&(self.widths.len() as u64).to_be_bytes())?;
+        self.out.write_all(&packed)?;
+        let mut encoder = GzEncoder::new(vec![], Compression::default());
+        encoder.write_all(&self.hierarchy)?;
+        let packed = encoder.finish()?;
+        self.out.write_all(&[FST_BL_HIER])?;
+        self.out
+            .write_all(&(packed.len() as u64 + 16).to_be_bytes())?;
+        self.out
+            .write_all(&(self.hierarchy.len() as u64).to_be_bytes())?;
+        self.out.write_all(&packed)?;
+        self.out.seek(SeekFrom::Start(0))?;
+        self.write_header()?;
+        self.out.seek(SeekFrom::End(0))?;
+        self.out.flush()
+    }
+}
+
+struct FstHeader<'a, W: Write + Seek> {
+    probe: FstProbe<W>,
+    select: &'a dyn Fn(&str) -> bool,
+    // The open scopes, and whether they have been written yet
+    scope: Vec<(String, bool)>,
+}
+
+impl<W: Write + Seek> FstHeader<'_, W> {
+    fn end_scope(&mut self) {
+        if let Some((_, true)) = self.scope.pop() {
+            self.probe.upscope();
+        }
+    }
+}
+
+impl<W: Write + Seek> Probe for FstHeader<'_, W> {
+    fn visit_start_scope(&mut self, name: &str, _node: &dyn Block) {
+        self.scope.push((name.into(), false));
+    }
+
+    fn visit_start_namespace(&mut self, name: &str, _node: &dyn Block) {
+        self.scope.push((name.into(), false));
+    }
+
+    fn visit_atom(&mut self, name: &str, signal: &dyn Atom) {
+        let path = self
+            .scope
+            .iter()
+            .map(|(x, _)| x.as_str())
+            .chain(std::iter::once(name))
+            .collect::<Vec<_>>()
+            .join(".");
+        if !(self.select)(&path) {
+            return;
+        }
+        for (scope, written) in self.scope.iter_mut().filter(|(_, written)| !written) {
+            self.probe.add_scope(scope);
+            *written = true;
+        }
+        let idc = self.probe.register_signal(name, &signal.descriptor());
+        self.probe.id_map.insert(signal.id(), idc);
+    }
+
+    fn visit_end_namespace(&mut self, _name: &str, _node: &dyn Block) {
+        self.end_scope();
+    }
+
+    fn visit_end_scope(&mut self, _name: &str, _node: &dyn Block) {
+        self.end_scope();
+    }
+}
+
+/// Start an FST file with the signals of `uut` whose (`.` separated)
+/// paths are accepted by `select`.  The rest of the file is written as
+/// the simulation progresses, and by [FstProbe::finish].
+pub fn write_fst_header<W: Write + Seek>(
+    writer: W,
+    uut: &dyn Block,
+    select: &dyn Fn(&str) -> bool,
+) -> Result<FstProbe<W>> {
+    let mut visitor = FstHeader {
+        probe: FstProbe::new(writer),
+        select,
+        scope: vec![],
+    };
+    uut.accept("uut", &mut visitor);
+    // Reserve space for the header, which is written once the simulation is done
+    visitor.probe.write_header()?;
+    Ok(visitor.probe)
+}
+
+struct FstChange<'a, W: Write + Seek> {
+    probe: &'a mut FstProbe<W>,
+    id_map: &'a HashMap<usize, FstIdCode>,
+}
+
+impl<W: Write + Seek> Probe for FstChange<'_, W> {
+    fn visit_atom(&mut self, _name: &str, signal: &dyn Atom) {
+        if let Some(idc) = self.id_map.get(&signal.id()) {
+            self.probe.record(idc, &signal.vcd());
+        }
+    }
+}
+
+/// Record the changes to the signals of `uut` at the current time.
+pub fn write_fst_change<W: Write + Seek>(fst: &mut FstProbe<W>, uut: &dyn Block) {
+    let id_map = std::mem::take(&mut fst.id_map);
+    uut.accept(
+        "uut",
+        &mut FstChange {
+            probe: fst,
+            id_map: &id_map,
+        },
+    );
+    fst.id_map = id_map;
+}
+
+impl<W: Write + Seek> TraceWriter for FstProbe<W> {
+    fn timestamp(&mut self, ts: u64) {
+        FstProbe::timestamp(self, ts).unwrap();
+    }
+
+    fn change(&mut self, uut: &dyn Block) {
+        write_fst_change(self, uut);
+    }
+
+    // Signals that have not changed since they were last written do not
+    // need to be written again.  A trace that starts at time zero is dumped
+    // without a timestamp (as in a VCD file), but FST needs one.
+    fn dump(&mut self, uut: &dyn Block) {
+        if self.times.is_empty() {
+            FstProbe::timestamp(self, 0).unwrap();
+        }
+        write_fst_change(self, uut);
+    }
+
+    fn finish(&mut self) {
+        FstProbe::finish(self).unwrap();
+    }
+}
@@ -0,0 +1,38 @@
+use crate::yosys::SynthError;
+use std::env::temp_dir;
+use std::fs::{create_dir_all, remove_dir_all, File};
+use std::io::Write;
+use std::process::Command;
+
+/// Returns `true` if `ghdl` can be run on this machine.
+pub fn ghdl_available() -> bool {
+    Command::new("ghdl")
+        .arg("--version")
+        .output()
+        .map(|x| x.status.success())
+        .unwrap_or(false)
+}
+
+/// Analyse and elaborate the output of [generate_vhdl](crate::vhdl_defines::generate_vhdl)
+/// with `ghdl`, using the entity `top` as the top level.  The design must not
+/// instantiate any Verilog components, since `ghdl` cannot elaborate those.
+pub fn ghdl_validate(prefix: &str, translation: &str) -> Result<(), SynthError> {
+    let dir = temp_dir().as_path().join(prefix);
+    let _ = remove_dir_all(&dir);
+    create_dir_all(&dir)?;
+    let mut v_file = File::create(dir.join("top.vhd"))?;
+    write!(v_file, "{}", translation)?;
+    for args in [&["-a", "--std=08", "top.vhd"], &["-e", "--std=08", "top"]] {
+        let output = Command::new("ghdl")
+            .current_dir(dir.clone())
+            .args(args)
+            .output()?;
+        if !output.status.success() {
+            return Err(SynthError::SynthesisFailed {
+                stdout: String::from_utf8_lossy(&output.stdout).to_string(),
+                stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+            });
+        }
+    }
+    Ok(())
+}
@@ -8,16 +8,25 @@ pub mod bitvec;
 pub mod block;
 pub mod check_connected;
 pub mod check_error;
+pub mod check_function_names;
 pub mod check_logic_loops;
 pub mod check_timing;
 pub mod check_write_inputs;
+pub mod checkpoint;
 pub mod clock;
 pub mod code_writer;
+pub mod compiled_sim;
 pub mod constant;
 pub mod constraint;
+pub mod coverage;
 pub mod direction;
+pub mod formal;
+pub mod fst_probe;
+pub mod ghdl;
+pub mod lint;
 pub mod logic;
 pub mod module_defines;
+pub(crate) mod module_details;
 pub mod named_path;
 pub mod path_tools;
 pub mod prelude;
@@ -27,11 +36,17 @@ pub mod short_bit_vec;
 pub mod signal;
 pub mod signed;
 pub mod simulate;
+pub mod sv_defines;
 pub mod synth;
 pub mod timing;
 pub mod top_wrap;
+pub mod trace_filter;
 pub mod type_descriptor;
 pub mod vcd_probe;
+pub mod verilator;
 pub mod verilog_gen;
 pub mod verilog_visitor;
+pub mod vhdl_defines;
+pub mod vhdl_gen;
+pub mod waveform;
 pub mod yosys;
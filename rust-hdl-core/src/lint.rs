@@ -0,0 +1,384 @@
+use crate::ast::{
+    Verilog, VerilogConditional, VerilogExpression, VerilogLink, VerilogLiteral, VerilogLoop,
+    VerilogMatch,
+};
+use crate::atom::{Atom, AtomKind};
+use crate::block::Block;
+use crate::check_error::PathedName;
+use crate::named_path::NamedPath;
+use crate::probe::Probe;
+use crate::type_descriptor::{enum_width, EnumLabel};
+use crate::verilog_gen::{ident_fixup, LoopVariable};
+use crate::verilog_visitor::{walk_block, walk_cast, VerilogVisitor};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fmt::{Display, Formatter};
+
+/// The kinds of problems reported by [lint].
+#[derive(Clone, Debug, PartialEq)]
+pub enum LintKind {
+    /// A local signal is not assigned on every path through the block, so
+    /// synthesis will infer a latch to hold its value
+    Latch,
+    /// A local signal is assigned, but its value is never used
+    Unused,
+    /// An output of the block is never driven
+    UndrivenOutput,
+    /// A cast of a constant to a narrower type drops bits that are not zero
+    Truncation {
+        /// The constant that is truncated (as a Verilog literal)
+        value: String,
+        /// The width it is cast to
+        width: usize,
+    },
+    /// A match arm can never be taken, because every value it covers
+    /// is handled by an earlier arm.  A default arm after all of the
+    /// variants is only reported if the variants use every encoding.
+    UnreachableArm,
+}
+
+/// A problem found by [lint].  The `location` is the module (`path`) and
+/// the signal (`name`) involved.  For an [LintKind::UnreachableArm] the
+/// name is the pattern of the arm, and for a [LintKind::Truncation] it is
+/// the signal holding the constant (or empty, for a literal).
+#[derive(Clone, Debug, PartialEq)]
+pub struct Lint {
+    pub location: PathedName,
+    pub kind: LintKind,
+}
+
+impl Display for Lint {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match &self.kind {
+            LintKind::Latch => write!(
+                f,
+                "{} is not assigned on every path, and will infer a latch",
+                self.location
+            ),
+            LintKind::Unused => write!(f, "{} is assigned but never used", self.location),
+            LintKind::UndrivenOutput => write!(f, "{} is never driven", self.location),
+            LintKind::Truncation { value, width } => write!(
+                f,
+                "{} casts {} to {} bits, which drops bits that are set",
+                self.location, value, width
+            ),
+            LintKind::UnreachableArm => {
+                write!(f, "{} is an unreachable match arm", self.location)
+            }
+        }
+    }
+}
+
+fn drops_set_bits(value: &VerilogLiteral, width: usize) -> bool {
+    let bits = value.to_binary_string();
+    bits.len() > width && bits[..bits.len() - width].contains('1')
+}
+
+// Lints the combinational HDL of a single block
+struct BlockLint<'a> {
+    path: String,
+    enums: &'a HashMap<String, Vec<EnumLabel>>,
+    constants: &'a HashMap<String, VerilogLiteral>,
+    loops: Vec<LoopVariable>,
+    reads: HashSet<String>,
+    writes: HashSet<String>,
+    // The signals that are assigned on every path to this point
+    assigned: HashSet<String>,
+    lints: Vec<Lint>,
+}
+
+impl BlockLint<'_> {
+    fn name(&self, c: &str) -> String {
+        ident_fixup(c, &self.loops)
+    }
+    fn report(&mut self, name: &str, kind: LintKind) {
+        self.lints.push(Lint {
+            location: PathedName {
+                path: self.path.clone(),
+                name: name.into(),
+            },
+            kind,
+        })
+    }
+    // Record the signal written by the left hand side of an assignment
+    fn write(&mut self, e: &VerilogExpression) {
+        match e {
+            VerilogExpression::Signal(x) => {
+                let name = self.name(x);
+                self.writes.insert(name.clone());
+                self.assigned.insert(name);
+            }
+            VerilogExpression::Index(base, ndx) | VerilogExpression::Slice(base, _, ndx) => {
+                self.visit_expression(ndx);
+                if let VerilogExpression::Signal(x) = base.as_ref() {
+                    let name = self.name(x);
+                    self.writes.insert(name);
+                } else {
+                    self.write(base);
+                }
+            }
+            _ => self.visit_expression(e),
+        }
+    }
+    fn enum_labels(&self, test: &VerilogExpression) -> Option<&Vec<EnumLabel>> {
+        match test {
+            VerilogExpression::Signal(x) => {
+                self.enums.get(&format!("{}${}", self.path, self.name(x)))
+            }
+            VerilogExpression::Paren(x) => self.enum_labels(x),
+            _ => None,
+        }
+    }
+}
+
+impl VerilogVisitor for BlockLint<'_> {
+    fn visit_loop(&mut self, a: &VerilogLoop) {
+        for i in a.from.as_usize()..a.to.as_usize() {
+            self.loops.push(LoopVariable {
+                variable: a.index.clone(),
+                value: i,
+            });
+            walk_block(self, &a.block);
+            self.loops.pop();
+        }
+    }
+
+    fn visit_slice_assignment(
+        &mut self,
+        base: &VerilogExpression,
+        _width: &usize,
+        offset: &VerilogExpression,
+        replacement: &VerilogExpression,
+    ) {
+        self.visit_expression(offset);
+        self.visit_expression(replacement);
+        if let VerilogExpression::Signal(x) = base {
+            let name = self.name(x);
+            self.writes.insert(name);
+        }
+    }
+
+    fn visit_conditional(&mut self, c: &VerilogConditional) {
+        self.visit_expression(&c.test);
+        let before = self.assigned.clone();
+        self.visit_block(&c.then);
+        let then = std::mem::replace(&mut self.assigned, before);
+        self.visit_block_or_conditional(&c.otherwise);
+        self.assigned.retain(|x| then.contains(x));
+    }
+
+    fn visit_match(&mut self, m: &VerilogMatch) {
+        self.visit_expression(&m.test);
+        let labels = self
+            .enum_labels(&m.test)
+            .map(|x| {
+                x.iter()
+                    .map(|x| x.name.replace("::", "$"))
+                    .collect::<BTreeSet<_>>()
+            })
+            .unwrap_or_default();
+        let fills_encoding = self
+            .enum_labels(&m.test)
+            .is_some_and(|x| 1_usize.checked_shl(enum_width(x) as u32) == Some(x.len()));
+        let mut covered = BTreeSet::new();
+        let mut has_default = false;
+        for case in &m.cases {
+            let reachable = if has_default {
+                false
+            } else if case.condition == "default" {
+                // Unless the enum fills its encoding, the default arm catches
+                // the unused encodings in hardware
+                has_default = true;
+                labels.is_empty() || !labels.is_subset(&covered) || !fills_encoding
+            } else {
+                covered.insert(case.condition.clone())
+            };
+            if !reachable && !labels.is_empty() {
+                self.report(&case.condition, LintKind::UnreachableArm);
+            }
+        }
+        let complete = has_default || (!labels.is_empty() && labels.is_subset(&covered));
+        let before = self.assigned.clone();
+        let mut after: Option<HashSet<String>> = None;
+        for case in &m.cases {
+            self.assigned = before.clone();
+            self.visit_case(case);
+            after = Some(match after {
+                None => std::mem::take(&mut self.assigned),
+                Some(x) => x.intersection(&self.assigned).cloned().collect(),
+            });
+        }
+        self.assigned = match after {
+            Some(after) if complete => after,
+            _ => before,
+        };
+    }
+
+    fn visit_signal(&mut self, c: &str) {
+        let name = self.name(c);
+        self.reads.insert(name);
+    }
+
+    fn visit_link(&mut self, c: &[VerilogLink]) {
+        // Linked signals are both driven and used by the link
+        for link in c {
+            let x = match link {
+                VerilogLink::Forward(x)
+                | VerilogLink::Backward(x)
+                | VerilogLink::Bidirectional(x) => x,
+            };
+            for owner in [&x.owner_name, &x.other_name] {
+                let name = format!("{}${}", self.name(owner), x.my_name);
+                self.reads.insert(name.clone());
+                self.writes.insert(name.clone());
+                self.assigned.insert(name);
+            }
+        }
+    }
+
+    fn visit_assignment(&mut self, l: &VerilogExpression, r: &VerilogExpression) {
+        self.visit_expression(r);
+        self.write(l);
+    }
+
+    fn visit_cast(&mut self, a: &VerilogExpression, b: &usize) {
+        let mut inner = a;
+        while let VerilogExpression::Paren(x) = inner {
+            inner = x.as_ref();
+        }
+        let constant = match inner {
+            VerilogExpression::Literal(x) => Some((String::new(), x.clone())),
+            VerilogExpression::Signal(x) => {
+                let name = self.name(x);
+                self.constants
+                    .get(&format!("{}${}", self.path, name))
+                    .map(|value| (name, value.clone()))
+            }
+            _ => None,
+        };
+        if let Some((name, value)) = constant {
+            if drops_set_bits(&value, *b) {
+                self.report(
+                    &name,
+                    LintKind::Truncation {
+                        value: value.to_string(),
+                        width: *b,
+                    },
+                );
+            }
+        }
+        walk_cast(self, a, b);
+    }
+}
+
+#[derive(Default)]
+struct Linter {
+    path: NamedPath,
+    namespaces: Vec<NamedPath>,
+    // The local signals and outputs of each open scope
+    atoms: Vec<Vec<(String, AtomKind)>>,
+    enums: HashMap<String, Vec<EnumLabel>>,
+    constants: HashMap<String, VerilogLiteral>,
+    lints: Vec<Lint>,
+}
+
+impl Probe for Linter {
+    fn visit_start_scope(&mut self, name: &str, _node: &dyn Block) {
+        self.path.push(name);
+        self.namespaces.push(NamedPath::default());
+        self.atoms.push(vec![]);
+    }
+
+    fn visit_start_namespace(&mut self, name: &str, _node: &dyn Block) {
+        if let Some(namespace) = self.namespaces.last_mut() {
+            namespace.push(name);
+        }
+    }
+
+    fn visit_atom(&mut self, name: &str, signal: &dyn Atom) {
+        let namespace = self
+            .namespaces
+            .last()
+            .map(|x| x.flat("$"))
+            .unwrap_or_default();
+        let name = if namespace.is_empty() {
+            name.to_owned()
+        } else {
+            format!("{}${}", namespace, name)
+        };
+        let full_name = format!("{}${}", self.path.to_string(), name);
+        if let Some(labels) = signal.descriptor().kind.enum_labels() {
+            self.enums.insert(full_name.clone(), labels);
+        }
+        match signal.kind() {
+            AtomKind::Constant => {
+                self.constants.insert(full_name, signal.verilog());
+            }
+            AtomKind::LocalSignal | AtomKind::OutputParameter => {
+                if let Some(atoms) = self.atoms.last_mut() {
+                    atoms.push((name, signal.kind()));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn visit_end_namespace(&mut self, _name: &str, _node: &dyn Block) {
+        if let Some(namespace) = self.namespaces.last_mut() {
+            namespace.pop();
+        }
+    }
+
+    fn visit_end_scope(&mut self, _name: &str, node: &dyn Block) {
+        let atoms = self.atoms.pop().unwrap_or_default();
+        self.namespaces.pop();
+        if let Verilog::Combinatorial(code) = &node.hdl() {
+            let mut block = BlockLint {
+                path: self.path.to_string(),
+                enums: &self.enums,
+                constants: &self.constants,
+                loops: vec![],
+                reads: Default::default(),
+                writes: Default::default(),
+                assigned: Default::default(),
+                lints: vec![],
+            };
+            block.visit_block(code);
+            for (name, kind) in &atoms {
+                match kind {
+                    AtomKind::LocalSignal => {
+                        if block.writes.contains(name) && !block.assigned.contains(name) {
+                            block.report(name, LintKind::Latch);
+                        }
+                        if block.writes.contains(name) && !block.reads.contains(name) {
+                            block.report(name, LintKind::Unused);
+                        }
+                    }
+                    AtomKind::OutputParameter if !block.writes.contains(name) => {
+                        block.report(name, LintKind::UndrivenOutput);
+                    }
+                    _ => {}
+                }
+            }
+            self.lints.extend(block.lints);
+        }
+        self.path.pop();
+    }
+}
+
+/// Lint the HDL of a circuit.  This goes beyond [check_all](crate::check_error::check_all),
+/// and looks at the combinational logic (`#[hdl_gen]`) of every block for
+/// constructs that are legal, but probably not what was intended:
+///
+/// * local signals that are not assigned on every path through the block,
+///   which make the synthesis tools infer a latch,
+/// * local signals that are assigned, but never used,
+/// * outputs that are never driven,
+/// * casts (e.g., `bit_cast`) of constant values that drop bits that are set,
+/// * `match` arms on `LogicState` enums that can never be taken.
+///
+/// Blocks with custom Verilog are not linted.
+pub fn lint<U: Block>(uut: &U) -> Vec<Lint> {
+    let mut linter = Linter::default();
+    uut.accept("uut", &mut linter);
+    linter.lints
+}
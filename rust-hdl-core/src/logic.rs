@@ -1,5 +1,12 @@
-use crate::ast::{Verilog, VerilogLink};
+use crate::ast::{Verilog, VerilogExpression, VerilogLink};
+use crate::bits::clog2;
+use crate::checkpoint::CircuitState;
+use crate::compiled_sim::Primitive;
+use crate::coverage::BranchSite;
+use crate::formal::Property;
+use crate::synth::Synth;
 use crate::timing::TimingInfo;
+use crate::type_descriptor::TypeKind;
 
 pub trait Logic {
     fn update(&mut self);
@@ -10,6 +17,42 @@ pub trait Logic {
     fn timing(&self) -> Vec<TimingInfo> {
         vec![]
     }
+    /// The architecture body (declarations, `begin` and statements) to use
+    /// when translating a block with custom HDL to VHDL.  Blocks that return
+    /// `None` here and do not use `#[hdl_gen]` are instantiated as external
+    /// components by [generate_vhdl](crate::vhdl_defines::generate_vhdl).
+    fn vhdl(&self) -> Option<String> {
+        None
+    }
+    /// The model of this block to use in a
+    /// [CompiledSimulation](crate::compiled_sim::CompiledSimulation).  Only
+    /// needed for blocks with custom HDL, since `#[hdl_gen]` kernels are
+    /// compiled directly.
+    fn primitive(&self) -> Option<Primitive> {
+        None
+    }
+    /// Save or restore simulation state that is not held in signals (such
+    /// as the contents of a memory model) when a simulation checkpoint is
+    /// taken or restored.
+    fn checkpoint(&mut self, _state: &mut CircuitState) {}
+    /// Returns `true` for blocks that are built to carry signals safely from
+    /// one clock domain to another.  Registers inside such a block are not
+    /// reported by [check_cdc](crate::check_timing::check_cdc).
+    fn is_synchronizer(&self) -> bool {
+        false
+    }
+    /// The `if` and `match` statements of the `update` function, used to
+    /// report branch coverage in [Simulation::run_covered](crate::simulate::Simulation::run_covered).
+    /// Generated by `#[hdl_gen(coverage)]`.
+    fn branch_sites(&self) -> Vec<BranchSite> {
+        vec![]
+    }
+    /// Assertions, assumptions and cover properties of the block, which
+    /// are emitted into the generated Verilog for use by formal tools.  See
+    /// [formal_verify](crate::formal::formal_verify).
+    fn properties(&self) -> Vec<Property> {
+        vec![]
+    }
 }
 
 pub fn logic_connect_fn<L: Logic>(x: &mut L) {
@@ -66,3 +109,37 @@ pub fn logic_connect_join_fn<L: LogicJoin, K: LogicJoin>(source: &mut L, dest: &
     source.join_connect();
     dest.join_connect();
 }
+
+/// Stands in for a `let` binding of an HDL kernel when its Verilog is
+/// generated, so that the type of the binding is inferred by the compiler.
+/// The initializer is never evaluated.
+#[doc(hidden)]
+pub fn hdl_local<T: Synth, F: FnOnce() -> T>(_init: F) -> T {
+    T::default()
+}
+
+/// The layout of the fields of the variants of a `LogicState` enum with
+/// payloads, which an HDL kernel needs to build or take apart its values.
+#[doc(hidden)]
+pub trait LogicPayload {
+    fn get_my_width(&self, variant: &str, field: &str) -> usize;
+    fn get_my_offset(&self, variant: &str, field: &str) -> usize;
+}
+
+/// The part of the value of a `match` in an HDL kernel that is compared
+/// with the patterns.  The variants of a `LogicState` enum with payloads are
+/// told apart by the tag in its low bits, and the rest is the payload.
+#[doc(hidden)]
+pub fn hdl_match_test<T: Synth>(test: VerilogExpression, _value: &T) -> VerilogExpression {
+    match T::descriptor().kind {
+        TypeKind::Composite(fields) => match &fields[0].kind.kind {
+            TypeKind::Enum(labels) => VerilogExpression::Slice(
+                Box::new(test),
+                clog2(labels.len()),
+                Box::new(VerilogExpression::Literal(0_u32.into())),
+            ),
+            _ => test,
+        },
+        _ => test,
+    }
+}
@@ -1,45 +1,22 @@
-use crate::ast::{Verilog, VerilogLink, VerilogLiteral};
+use crate::ast::{Verilog, VerilogLink};
 use crate::atom::AtomKind::{StubInputSignal, StubOutputSignal};
 use crate::atom::{is_atom_signed, Atom, AtomKind};
 use crate::block::Block;
-use crate::check_error::check_all;
+use crate::check_error::{check_all, GenerateError};
 use crate::code_writer::CodeWriter;
+use crate::formal::{write_properties, Property};
+use crate::module_details::{
+    get_link_equivalence, link_assignment, AtomDetails, EnumDefinition, ModuleDetails,
+    SubModuleInvocation,
+};
 use crate::named_path::NamedPath;
 use crate::probe::Probe;
-use crate::type_descriptor::{TypeDescriptor, TypeKind};
-use crate::verilog_gen::{verilog_combinatorial, verilog_link_extraction};
-use std::collections::BTreeMap;
-
-#[derive(Clone, Debug, Default)]
-struct SubModuleInvocation {
-    kind: String,
-    name: String,
-}
-
-#[derive(Clone, Debug, Default)]
-struct ModuleDetails {
-    atoms: Vec<AtomDetails>,
-    sub_modules: Vec<SubModuleInvocation>,
-    enums: Vec<EnumDefinition>,
-    code: Verilog,
-    links: Vec<VerilogLink>,
-}
-
-#[derive(Clone, Debug, PartialEq)]
-struct EnumDefinition {
-    pub type_name: String,
-    pub discriminant: String,
-    pub value: usize,
-}
-
-#[derive(Clone, Debug)]
-struct AtomDetails {
-    name: String,
-    kind: AtomKind,
-    width: usize,
-    const_val: VerilogLiteral,
-    signed: bool,
-}
+use crate::type_descriptor::{enum_width, TypeDescriptor, TypeKind};
+use crate::verilog_gen::{
+    verilog_combinatorial, verilog_function, verilog_functions, verilog_has_fixed_widths,
+    verilog_link_extraction, verilog_locals,
+};
+use std::collections::{BTreeMap, HashMap};
 
 fn verilog_atom_name(x: &AtomKind) -> &str {
     match x {
@@ -54,7 +31,7 @@ fn verilog_atom_name(x: &AtomKind) -> &str {
     }
 }
 
-fn decl(x: &AtomDetails) -> String {
+pub(crate) fn decl(x: &AtomDetails) -> String {
     let signed = if x.signed { "signed" } else { "" };
     if x.kind == AtomKind::Constant {
         format!(
@@ -79,14 +56,79 @@ fn decl(x: &AtomDetails) -> String {
     }
 }
 
+#[derive(Clone, Debug)]
+struct ParameterDetails {
+    name: String,
+    value: String,
+    signed: bool,
+}
+
+fn width_parameter(x: &AtomDetails) -> String {
+    format!("{}$WIDTH", x.name)
+}
+
+fn is_width_generic(x: &AtomDetails) -> bool {
+    x.kind.is_parameter() || x.kind == AtomKind::LocalSignal
+}
+
+fn parameterized_decl(x: &AtomDetails) -> String {
+    let signed = if x.signed { "signed" } else { "" };
+    if is_width_generic(x) {
+        format!(
+            "{} {} [{}-1:0] {};",
+            verilog_atom_name(&x.kind),
+            signed,
+            width_parameter(x),
+            x.name
+        )
+    } else {
+        decl(x)
+    }
+}
+
+// The widths of the signals can only become parameters if the code of the
+// module does not depend on them (through a cast or a slice, for example).
+// Otherwise, overriding a width would leave the code at the old width.
+fn widths_are_parameters(module_details: &ModuleDetails) -> bool {
+    match &module_details.code {
+        Verilog::Combinatorial(code) => !verilog_has_fixed_widths(code),
+        _ => true,
+    }
+}
+
+// Replace the values of everything that will become a parameter,
+// so that modules which differ only in their parameters compare equal.
+fn strip_parameter_values(module_details: &ModuleDetails) -> ModuleDetails {
+    let widths = widths_are_parameters(module_details);
+    let mut details = module_details.clone();
+    for atom in &mut details.atoms {
+        if atom.kind == AtomKind::Constant {
+            atom.const_val = false.into();
+        } else if widths && is_width_generic(atom) {
+            atom.width = 0;
+        }
+    }
+    details
+}
+
 #[derive(Default)]
 pub struct ModuleDefines {
     path: NamedPath,
     namespace: NamedPath,
-    details: BTreeMap<String, ModuleDetails>,
+    pub(crate) details: BTreeMap<String, ModuleDetails>,
+    parameterized: bool,
 }
 
 impl ModuleDefines {
+    /// Create a [ModuleDefines] that emits `parameter`s for the constants
+    /// and the signal widths of each module, instead of baking them into
+    /// the module definition.
+    pub fn parameterized() -> Self {
+        Self {
+            parameterized: true,
+            ..Default::default()
+        }
+    }
     fn add_atom(&mut self, module: &str, atom: AtomDetails) {
         let entry = self.details.entry(module.into()).or_default();
         entry.atoms.push(atom)
@@ -102,12 +144,14 @@ impl ModuleDefines {
         let entry = self.details.entry(module.into()).or_default();
         let enum_name = descriptor.name.clone();
         match &descriptor.kind {
-            TypeKind::Enum(x) => {
-                for (ndx, label) in x.iter().enumerate() {
+            TypeKind::Enum(_) | TypeKind::EncodedEnum(_) => {
+                let labels = descriptor.kind.enum_labels().unwrap_or_default();
+                for label in &labels {
                     let def = EnumDefinition {
                         type_name: enum_name.clone(),
-                        discriminant: label.into(),
-                        value: ndx,
+                        discriminant: label.name.clone(),
+                        value: label.value,
+                        width: enum_width(&labels),
                     };
                     if !entry.enums.contains(&def) {
                         entry.enums.push(def);
@@ -132,6 +176,43 @@ impl ModuleDefines {
         };
         entry.code = code;
     }
+    // The `let` bindings in the code are declared as local signals
+    fn add_locals(&mut self, module: &str, code: &Verilog) {
+        if let Verilog::Combinatorial(code) = code {
+            for local in verilog_locals(code) {
+                self.add_enums(module, &local.descriptor);
+                self.add_atom(
+                    module,
+                    AtomDetails {
+                        name: local.name,
+                        kind: AtomKind::LocalSignal,
+                        width: local.width,
+                        const_val: false.into(),
+                        signed: local.signed,
+                        descriptor: local.descriptor,
+                    },
+                );
+            }
+            for function in verilog_functions(code) {
+                for local in function
+                    .args
+                    .iter()
+                    .chain(verilog_locals(&function.body).iter())
+                    .chain(std::iter::once(&function.result))
+                {
+                    self.add_enums(module, &local.descriptor);
+                }
+            }
+        }
+    }
+    fn add_vhdl(&mut self, module: &str, vhdl: Option<String>) {
+        let entry = self.details.entry(module.into()).or_default();
+        entry.vhdl = vhdl;
+    }
+    fn add_properties(&mut self, module: &str, properties: Vec<Property>) {
+        let entry = self.details.entry(module.into()).or_default();
+        entry.properties = properties;
+    }
 }
 
 impl Probe for ModuleDefines {
@@ -141,6 +222,8 @@ impl Probe for ModuleDefines {
         self.namespace.reset();
         self.add_submodule(&top_level, name, &self.path.to_string());
         self.add_code(&self.path.to_string(), node.hdl());
+        self.add_vhdl(&self.path.to_string(), node.vhdl());
+        self.add_properties(&self.path.to_string(), node.properties());
     }
 
     fn visit_start_namespace(&mut self, name: &str, _node: &dyn Block) {
@@ -162,6 +245,7 @@ impl Probe for ModuleDefines {
             width: signal.bits(),
             const_val: signal.verilog(),
             signed: is_atom_signed(signal),
+            descriptor: signal.descriptor(),
         };
         if param.kind.is_parameter() {
             let kind = if param.kind == AtomKind::InputParameter {
@@ -175,6 +259,7 @@ impl Probe for ModuleDefines {
                 width: signal.bits(),
                 const_val: signal.verilog(),
                 signed: is_atom_signed(signal),
+                descriptor: signal.descriptor(),
             };
             let parent_name = self.path.parent();
             self.add_atom(&parent_name, parent_param);
@@ -188,45 +273,63 @@ impl Probe for ModuleDefines {
         self.namespace.pop();
     }
 
-    fn visit_end_scope(&mut self, _name: &str, _node: &dyn Block) {
+    fn visit_end_scope(&mut self, _name: &str, node: &dyn Block) {
+        self.add_locals(&self.path.to_string(), &node.hdl());
         self.path.pop();
     }
 }
 
-fn get_link_equivalence(link: &VerilogLink) -> (String, String) {
-    match link {
-        VerilogLink::Forward(link) => (
-            format!("{}${}", link.other_name, link.my_name),
-            format!("{}${}", link.owner_name, link.my_name),
-        ),
-        VerilogLink::Backward(link) => (
-            format!("{}${}", link.owner_name, link.my_name),
-            format!("{}${}", link.other_name, link.my_name),
-        ),
-        VerilogLink::Bidirectional(link) => {
-            if link.my_name.is_empty() {
-                (link.owner_name.clone(), link.other_name.clone())
-            } else {
-                (
-                    format!("{}${}", link.other_name, link.my_name),
-                    format!("{}${}", link.owner_name, link.my_name),
-                )
-            }
+impl ModuleDefines {
+    fn is_parameterized(&self, module_details: &ModuleDetails) -> bool {
+        // Custom and wrapped Verilog may bake in widths that we cannot see
+        self.parameterized
+            && matches!(
+                module_details.code,
+                Verilog::Combinatorial(_) | Verilog::Empty
+            )
+    }
+    fn parameters(&self, module_details: &ModuleDetails) -> Vec<ParameterDetails> {
+        if !self.is_parameterized(module_details) {
+            return vec![];
+        }
+        let widths = module_details
+            .atoms
+            .iter()
+            .filter(|x| widths_are_parameters(module_details) && is_width_generic(x))
+            .map(|x| ParameterDetails {
+                name: width_parameter(x),
+                value: x.width.to_string(),
+                signed: false,
+            });
+        let consts = module_details
+            .atoms
+            .iter()
+            .filter(|x| x.kind == AtomKind::Constant)
+            .map(|x| ParameterDetails {
+                name: x.name.clone(),
+                value: x.const_val.to_string(),
+                signed: x.signed,
+            });
+        widths.chain(consts).collect()
+    }
+    fn declare(&self, module_details: &ModuleDetails, x: &AtomDetails) -> String {
+        if self.is_parameterized(module_details) && widths_are_parameters(module_details) {
+            parameterized_decl(x)
+        } else {
+            decl(x)
         }
     }
-}
-
-impl ModuleDefines {
     fn sub_module_invocation(
         &self,
         module_details: &ModuleDetails,
         child: &SubModuleInvocation,
+        names: &BTreeMap<String, String>,
         io: &mut CodeWriter,
     ) {
         let entry = self.details.get(&child.kind).unwrap();
         let submodule_kind = match &entry.code {
             Verilog::Blackbox(b) => &b.name,
-            _ => &child.kind,
+            _ => names.get(&child.kind).unwrap_or(&child.kind),
         };
         let child_args = entry
             .atoms
@@ -234,9 +337,8 @@ impl ModuleDefines {
             .filter(|x| x.kind.is_parameter())
             .map(|x| {
                 let arg_name = format!("{}${}", child.name, x.name);
-                let arg_name = if self.stub_is_linked_to_module_argument(module_details, &arg_name)
-                {
-                    self.get_linked_argument_name(module_details, &arg_name)
+                let arg_name = if module_details.stub_is_linked_to_module_argument(&arg_name) {
+                    module_details.get_linked_argument_name(&arg_name)
                 } else {
                     arg_name
                 };
@@ -244,13 +346,27 @@ impl ModuleDefines {
             })
             .collect::<Vec<_>>()
             .join(",\n");
-        io.add(format!("{} {}(\n", submodule_kind, child.name));
+        let overrides = self
+            .parameters(entry)
+            .iter()
+            .map(|x| format!(".{}({})", x.name, x.value))
+            .collect::<Vec<_>>();
+        if overrides.is_empty() {
+            io.add(format!("{} {}(\n", submodule_kind, child.name));
+        } else {
+            io.add(format!(
+                "{} #({}) {}(\n",
+                submodule_kind,
+                overrides.join(", "),
+                child.name
+            ));
+        }
         io.push();
         io.add(child_args);
         io.pop();
         io.add(");\n");
     }
-    fn module_argument_is_passed_through_to_submodule(
+    pub(crate) fn module_argument_is_passed_through_to_submodule(
         &self,
         module_details: &ModuleDetails,
         module_arg_name: &str,
@@ -259,62 +375,18 @@ impl ModuleDefines {
             let entry = self.details.get(&child.kind).unwrap();
             for child_arg in &entry.atoms {
                 let arg_name = format!("{}${}", child.name, child_arg.name);
-                if self.get_linked_argument_name(module_details, &arg_name) == module_arg_name {
-                    return true;
-                }
-            }
-        }
-        false
-    }
-    fn get_linked_argument_name(&self, module_details: &ModuleDetails, arg_name: &str) -> String {
-        for link in &module_details.links {
-            let equiv = get_link_equivalence(link);
-            if arg_name == equiv.0 {
-                return equiv.1.clone();
-            }
-            if arg_name == equiv.1 {
-                return equiv.0.clone();
-            }
-        }
-        arg_name.to_string()
-    }
-    fn signal_name_is_module_argument(
-        &self,
-        module_details: &ModuleDetails,
-        signal_name: &str,
-    ) -> bool {
-        // We now know the stub is linked to something... but is that a module argument?
-        for atom in &module_details.atoms {
-            if atom.kind.is_parameter() && atom.name == signal_name {
-                return true;
-            }
-        }
-        false
-    }
-    fn stub_is_linked_to_module_argument(
-        &self,
-        module_details: &ModuleDetails,
-        atom_name: &str,
-    ) -> bool {
-        for link in &module_details.links {
-            let equiv = get_link_equivalence(link);
-            if atom_name == equiv.0 || atom_name == equiv.1 {
-                let linked_name = if atom_name == equiv.0 {
-                    equiv.1
-                } else {
-                    equiv.0
-                };
-                if self.signal_name_is_module_argument(module_details, &linked_name) {
+                if module_details.get_linked_argument_name(&arg_name) == module_arg_name {
                     return true;
                 }
             }
         }
         false
     }
-    fn process_module(
+    pub(crate) fn process_module(
         &self,
         module_name: &str,
         module_details: &ModuleDetails,
+        names: &BTreeMap<String, String>,
         io: &mut CodeWriter,
     ) {
         // Remap the output parameters to pass through (net type) in case we have a wrapper
@@ -363,57 +435,78 @@ impl ModuleDefines {
             .join(",");
         io.add(format!("\n\nmodule {}({});", module_name, module_args));
         io.push();
+        let parameters = self.parameters(module_details);
+        if !parameters.is_empty() {
+            io.add("\n// Parameters");
+            parameters.iter().for_each(|x| {
+                let signed = if x.signed { "signed " } else { "" };
+                io.add(format!("parameter {}{} = {};", signed, x.name, x.value))
+            });
+        }
         if !args.is_empty() {
             io.add("\n// Module arguments");
             args.iter().for_each(|x| {
                 if !self.module_argument_is_passed_through_to_submodule(module_details, &x.name)
                     || x.kind != AtomKind::OutputParameter
                 {
-                    io.add(decl(x))
+                    io.add(self.declare(module_details, x))
                 } else {
                     // For some synthesis engines, you cannot pass a module argument
                     // to a child module if it is of reg type
                     let mut x = (*x).clone();
                     x.kind = AtomKind::OutputPassthrough;
-                    io.add(decl(&x))
+                    io.add(self.declare(module_details, &x))
                 }
             });
         }
         let submodules = &module_details.sub_modules;
-        if !consts.is_empty() {
+        if !consts.is_empty() & !self.is_parameterized(module_details) {
             io.add("\n// Constant declarations");
             consts.iter().for_each(|x| io.add(decl(x)));
         }
         if !module_details.enums.is_empty() & !wrapper_mode {
             io.add("\n// Enums");
             module_details.enums.iter().for_each(|x| {
+                // An unsized localparam is only 32 bits wide
+                let value = if x.value >> 31 == 0 {
+                    x.value.to_string()
+                } else {
+                    format!("{}'d{}", x.width, x.value)
+                };
                 io.add(format!(
                     "localparam {} = {};",
                     x.discriminant.replace("::", "$"),
-                    x.value
+                    value
                 ))
             });
         }
         if !stubs.is_empty() & !wrapper_mode {
             io.add("\n// Stub signals");
             stubs.iter().for_each(|x| {
-                if !self.stub_is_linked_to_module_argument(module_details, &x.name) {
+                if !module_details.stub_is_linked_to_module_argument(&x.name) {
                     io.add(decl(x))
                 }
             });
         }
         if !locals.is_empty() & !wrapper_mode {
             io.add("\n// Local signals");
-            locals.iter().for_each(|x| io.add(decl(x)));
+            locals
+                .iter()
+                .for_each(|x| io.add(self.declare(module_details, x)));
         }
         if !submodules.is_empty() & !wrapper_mode {
             io.add("\n// Sub module instances");
             for child in submodules {
-                self.sub_module_invocation(module_details, child, io);
+                self.sub_module_invocation(module_details, child, names, io);
             }
         }
         match &module_details.code {
             Verilog::Combinatorial(code) => {
+                let functions = verilog_functions(code);
+                if !functions.is_empty() {
+                    io.add("\n// Functions");
+                    functions.iter().for_each(|x| io.add(verilog_function(x)));
+                }
                 io.add("\n// Update code");
                 io.add(verilog_combinatorial(code));
             }
@@ -430,71 +523,141 @@ impl ModuleDefines {
         }
         for x in &module_details.links {
             let equiv = get_link_equivalence(x);
-            if !self.signal_name_is_module_argument(module_details, &equiv.0)
-                & !self.signal_name_is_module_argument(module_details, &equiv.1)
+            if !module_details.signal_name_is_module_argument(&equiv.0)
+                & !module_details.signal_name_is_module_argument(&equiv.1)
             {
+                let (target, source) = link_assignment(x);
                 let txt = match x {
-                    VerilogLink::Forward(x) => {
-                        format!(
-                            "always @(*) {}${} = {}${};",
-                            x.other_name.replace("[", "$").replace("]", ""),
-                            x.my_name,
-                            x.owner_name.replace("[", "$").replace("]", ""),
-                            x.my_name
-                        )
-                    }
-                    VerilogLink::Backward(x) => {
-                        format!(
-                            "always @(*) {}${} = {}${};",
-                            x.owner_name.replace("[", "$").replace("]", ""),
-                            x.my_name,
-                            x.other_name.replace("[", "$").replace("]", ""),
-                            x.my_name
-                        )
-                    }
-                    VerilogLink::Bidirectional(x) => {
-                        if x.my_name.is_empty() {
-                            format!("assign {} = {};", x.owner_name, x.other_name)
-                        } else {
-                            format!(
-                                "assign {}${} = {}${};",
-                                x.owner_name, x.my_name, x.other_name, x.my_name
-                            )
-                        }
-                    }
+                    VerilogLink::Bidirectional(_) => format!("assign {} = {};", target, source),
+                    _ => format!("always @(*) {} = {};", target, source),
                 };
                 io.add_line(txt);
             }
         }
+        write_properties(&module_details.properties, io);
         io.pop();
         io.add(format!("endmodule // {}", module_name));
     }
 
+    // Two modules are structurally identical if they render to the same
+    // Verilog (up to the module name), which covers their atoms, widths,
+    // constants, code and the kinds of their submodules.  Children are
+    // resolved first, so that their canonical names are used in the
+    // rendering of the parent.
+    fn resolve_module_name(
+        &self,
+        module_name: &str,
+        names: &mut BTreeMap<String, String>,
+        signatures: &mut HashMap<String, String>,
+    ) -> String {
+        if let Some(name) = names.get(module_name) {
+            return name.clone();
+        }
+        let module_details = self.details.get(module_name).unwrap();
+        for child in &module_details.sub_modules {
+            self.resolve_module_name(&child.kind, names, signatures);
+        }
+        let canonical = match &module_details.code {
+            Verilog::Blackbox(_) => module_name.to_string(),
+            _ => {
+                let mut io = CodeWriter::default();
+                if self.is_parameterized(module_details) {
+                    let stripped = strip_parameter_values(module_details);
+                    self.process_module("", &stripped, names, &mut io);
+                } else {
+                    self.process_module("", module_details, names, &mut io);
+                }
+                if let Verilog::Wrapper(w) = &module_details.code {
+                    io.add(&w.cores);
+                }
+                signatures
+                    .entry(io.to_string())
+                    .or_insert_with(|| module_name.to_string())
+                    .clone()
+            }
+        };
+        names.insert(module_name.to_string(), canonical.clone());
+        canonical
+    }
+
+    pub(crate) fn module_names(&self) -> BTreeMap<String, String> {
+        let mut names = BTreeMap::new();
+        let mut signatures = HashMap::new();
+        for module_name in self.details.keys().filter(|x| !x.is_empty()) {
+            self.resolve_module_name(module_name, &mut names, &mut signatures);
+        }
+        names
+    }
+
     pub fn defines(&self) -> String {
+        let names = self.module_names();
         let mut io = CodeWriter::default();
         self.details
             .iter()
             .filter(|x| x.0.len() != 0)
             .filter(|x| !matches!(x.1.code, Verilog::Blackbox(_)))
+            .filter(|x| names.get(x.0) == Some(x.0))
             .for_each(|k| {
                 let module_name = k.0;
                 let module_details = k.1;
-                self.process_module(module_name, module_details, &mut io);
+                self.process_module(module_name, module_details, &names, &mut io);
             });
-        self.details.iter().for_each(|x| match &x.1.code {
-            Verilog::Blackbox(b) => io.add(&b.code),
-            Verilog::Wrapper(w) => io.add(&w.cores),
-            _ => {}
-        });
+        self.add_cores(&mut io);
         io.to_string()
     }
+
+    // Blackbox and wrapper cores are emitted once each, after the modules
+    pub(crate) fn add_cores(&self, io: &mut CodeWriter) {
+        let mut cores: Vec<&String> = vec![];
+        self.details.iter().for_each(|x| {
+            let core = match &x.1.code {
+                Verilog::Blackbox(b) => &b.code,
+                Verilog::Wrapper(w) => &w.cores,
+                _ => return,
+            };
+            if !cores.contains(&core) {
+                io.add(core);
+                cores.push(core);
+            }
+        });
+    }
 }
 
+/// Generate Verilog for a circuit.  Panics if the circuit fails [check_all].
+/// Use [try_generate_verilog] to get the error instead.
 pub fn generate_verilog<U: Block>(uut: &U) -> String {
+    try_generate_verilog(uut).unwrap_or_else(|e| panic!("{}", e))
+}
+
+/// Generate Verilog for a circuit, or return a [GenerateError] if the circuit
+/// fails [check_all] (because of open signals, logic loops or writes to inputs).
+pub fn try_generate_verilog<U: Block>(uut: &U) -> Result<String, GenerateError> {
+    check_all(uut)?;
     let mut defines = ModuleDefines::default();
-    check_all(uut).unwrap(); // TODO - make this not panic...
     uut.accept("top", &mut defines);
-    defines.defines()
+    Ok(defines.defines())
+}
+
+/// Generate Verilog in which the constants and signal widths of each module
+/// are exposed as `parameter`s.  Modules that differ only in those values are
+/// emitted once, and each instance overrides the parameters it needs.  Modules
+/// with custom or wrapped Verilog are emitted as in [generate_verilog].  The
+/// widths of a module whose code depends on them (through a cast, a slice, a
+/// `let` binding or an `#[hdl_function]`) are kept fixed, since overriding
+/// them would not resize that code.
+/// Panics if the circuit fails [check_all].  Use [try_generate_verilog_parameterized]
+/// to get the error instead.
+pub fn generate_verilog_parameterized<U: Block>(uut: &U) -> String {
+    try_generate_verilog_parameterized(uut).unwrap_or_else(|e| panic!("{}", e))
+}
+
+/// Generate parameterized Verilog (as in [generate_verilog_parameterized]), or
+/// return a [GenerateError] if the circuit fails [check_all].
+pub fn try_generate_verilog_parameterized<U: Block>(uut: &U) -> Result<String, GenerateError> {
+    check_all(uut)?;
+    let mut defines = ModuleDefines::parameterized();
+    uut.accept("top", &mut defines);
+    Ok(defines.defines())
 }
 
 pub fn generate_verilog_unchecked<U: Block>(uut: &U) -> String {
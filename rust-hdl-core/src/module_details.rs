@@ -0,0 +1,144 @@
+// The per-module details collected by [ModuleDefines](crate::module_defines::ModuleDefines),
+// shared by the Verilog, SystemVerilog and VHDL backends.
+use crate::ast::{Verilog, VerilogLink, VerilogLiteral};
+use crate::atom::AtomKind;
+use crate::formal::Property;
+use crate::type_descriptor::TypeDescriptor;
+
+#[derive(Clone, Debug, Default)]
+pub(crate) struct SubModuleInvocation {
+    pub(crate) kind: String,
+    pub(crate) name: String,
+}
+
+#[derive(Clone, Debug, Default)]
+pub(crate) struct ModuleDetails {
+    pub(crate) atoms: Vec<AtomDetails>,
+    pub(crate) sub_modules: Vec<SubModuleInvocation>,
+    pub(crate) enums: Vec<EnumDefinition>,
+    pub(crate) code: Verilog,
+    pub(crate) links: Vec<VerilogLink>,
+    pub(crate) vhdl: Option<String>,
+    pub(crate) properties: Vec<Property>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct EnumDefinition {
+    pub type_name: String,
+    pub discriminant: String,
+    pub value: u128,
+    pub width: usize,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct AtomDetails {
+    pub(crate) name: String,
+    pub(crate) kind: AtomKind,
+    pub(crate) width: usize,
+    pub(crate) const_val: VerilogLiteral,
+    pub(crate) signed: bool,
+    pub(crate) descriptor: TypeDescriptor,
+}
+
+impl ModuleDetails {
+    pub(crate) fn get_linked_argument_name(&self, arg_name: &str) -> String {
+        for link in &self.links {
+            let equiv = get_link_equivalence(link);
+            if arg_name == equiv.0 {
+                return equiv.1.clone();
+            }
+            if arg_name == equiv.1 {
+                return equiv.0.clone();
+            }
+        }
+        arg_name.to_string()
+    }
+    pub(crate) fn signal_name_is_module_argument(&self, signal_name: &str) -> bool {
+        // We now know the stub is linked to something... but is that a module argument?
+        for atom in &self.atoms {
+            if atom.kind.is_parameter() && atom.name == signal_name {
+                return true;
+            }
+        }
+        false
+    }
+    pub(crate) fn stub_is_linked_to_module_argument(&self, atom_name: &str) -> bool {
+        for link in &self.links {
+            let equiv = get_link_equivalence(link);
+            if atom_name == equiv.0 || atom_name == equiv.1 {
+                let linked_name = if atom_name == equiv.0 {
+                    equiv.1
+                } else {
+                    equiv.0
+                };
+                if self.signal_name_is_module_argument(&linked_name) {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+}
+
+pub(crate) fn get_link_equivalence(link: &VerilogLink) -> (String, String) {
+    match link {
+        VerilogLink::Forward(link) => (
+            format!("{}${}", link.other_name, link.my_name),
+            format!("{}${}", link.owner_name, link.my_name),
+        ),
+        VerilogLink::Backward(link) => (
+            format!("{}${}", link.owner_name, link.my_name),
+            format!("{}${}", link.other_name, link.my_name),
+        ),
+        VerilogLink::Bidirectional(link) => {
+            if link.my_name.is_empty() {
+                (link.owner_name.clone(), link.other_name.clone())
+            } else {
+                (
+                    format!("{}${}", link.other_name, link.my_name),
+                    format!("{}${}", link.owner_name, link.my_name),
+                )
+            }
+        }
+    }
+}
+
+// The (target, source) pair of the assignment that implements a link.
+pub(crate) fn link_assignment(link: &VerilogLink) -> (String, String) {
+    match link {
+        VerilogLink::Forward(x) => (
+            format!(
+                "{}${}",
+                x.other_name.replace("[", "$").replace("]", ""),
+                x.my_name
+            ),
+            format!(
+                "{}${}",
+                x.owner_name.replace("[", "$").replace("]", ""),
+                x.my_name
+            ),
+        ),
+        VerilogLink::Backward(x) => (
+            format!(
+                "{}${}",
+                x.owner_name.replace("[", "$").replace("]", ""),
+                x.my_name
+            ),
+            format!(
+                "{}${}",
+                x.other_name.replace("[", "$").replace("]", ""),
+                x.my_name
+            ),
+        ),
+        VerilogLink::Bidirectional(x) => {
+            if x.my_name.is_empty() {
+                (x.owner_name.clone(), x.other_name.clone())
+            } else {
+                (
+                    format!("{}${}", x.owner_name, x.my_name),
+                    format!("{}${}", x.other_name, x.my_name),
+                )
+            }
+        }
+    }
+}
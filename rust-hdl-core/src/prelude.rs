@@ -14,21 +14,39 @@ pub use crate::block;
 pub use crate::block::Block;
 pub use crate::check_connected::check_connected;
 pub use crate::check_error::check_all;
-pub use crate::check_timing::check_timing;
+pub use crate::check_error::{CheckError, GenerateError, PathedName};
+pub use crate::check_timing::{check_cdc, check_timing, CdcReport, ClockDomainCrossing};
+pub use crate::checkpoint;
+pub use crate::checkpoint::{Checkpoint, CheckpointError, CircuitState};
 pub use crate::clock;
 pub use crate::clock::freq_hz_to_period_femto;
 pub use crate::clock::Clock;
 pub use crate::clock::NANOS_PER_FEMTO;
+pub use crate::compiled_sim::{
+    CompileError, CompiledSimulation, CompiledTrace, Primitive, SignalId,
+};
 pub use crate::constant::Constant;
 pub use crate::constraint::Timing::*;
 pub use crate::constraint::*;
+pub use crate::coverage;
+pub use crate::coverage::{BranchCoverage, Coverage, CoverageSummary, FsmCoverage, ToggleCoverage};
 pub use crate::direction::{Direction, In, InOut, Local, Out};
+pub use crate::formal::{
+    formal_export, formal_verify, generate_sby, remap_trace, symbiyosys_available, FormalError,
+    FormalMode, FormalOptions, FormalResult, Property, PropertyKind,
+};
+pub use crate::fst_probe::{write_fst_change, write_fst_header, FstProbe};
+pub use crate::ghdl::{ghdl_available, ghdl_validate};
+pub use crate::lint::{lint, Lint, LintKind};
 pub use crate::logic;
 pub use crate::logic::Logic;
 pub use crate::logic::LogicJoin;
 pub use crate::logic::LogicLink;
 pub use crate::module_defines::ModuleDefines;
-pub use crate::module_defines::{generate_verilog, generate_verilog_unchecked};
+pub use crate::module_defines::{
+    generate_verilog, generate_verilog_parameterized, generate_verilog_unchecked,
+    try_generate_verilog, try_generate_verilog_parameterized,
+};
 pub use crate::named_path::NamedPath;
 pub use crate::probe;
 pub use crate::probe::Probe;
@@ -42,23 +60,34 @@ pub use crate::sim_assert_eq;
 pub use crate::simple_sim;
 pub use crate::simulate::sim_time;
 pub use crate::simulate::simulate;
+pub use crate::simulate::SIMULATION_SEED_VAR;
 pub use crate::simulate::SIMULATION_TIME_ONE_SECOND;
-pub use crate::simulate::{Sim, SimError, Simulation};
+pub use crate::simulate::{Sim, SimError, SimHalt, Simulation};
+pub use crate::sv_defines::{generate_system_verilog, try_generate_system_verilog};
 pub use crate::synth;
 pub use crate::synth::Synth;
 pub use crate::synth::VCDValue;
 pub use crate::target_path;
 pub use crate::timing::TimingInfo;
 pub use crate::top_wrap::TopWrap;
+pub use crate::trace_filter::TraceFilter;
 pub use crate::type_descriptor;
-pub use crate::type_descriptor::{TypeDescriptor, TypeField, TypeKind};
+pub use crate::type_descriptor::{EnumLabel, TypeDescriptor, TypeField, TypeKind};
 pub use crate::vcd_path;
-pub use crate::vcd_probe::{write_vcd_change, write_vcd_dump, write_vcd_header};
+pub use crate::vcd_probe::{
+    write_vcd_change, write_vcd_dump, write_vcd_header, write_vcd_header_selected,
+};
+pub use crate::verilator::{verilator_available, verilator_cosim, CosimError};
 pub use crate::verilog_gen::filter_blackbox_directives;
 pub use crate::verilog_visitor::VerilogVisitor;
+pub use crate::vhdl_defines::{generate_vhdl, generate_vhdl_unchecked, try_generate_vhdl};
+pub use crate::vhdl_gen::{vhdl_ident, vhdl_type, vhdl_value};
 pub use crate::wait_clock_cycle;
 pub use crate::wait_clock_cycles;
 pub use crate::wait_clock_false;
 pub use crate::wait_clock_true;
+pub use crate::waveform::{WaveValue, Waveform, WaveformRecorder};
 pub use crate::yosys::*;
-pub use rust_hdl_macros::{hdl_gen, LogicBlock, LogicInterface, LogicState, LogicStruct};
+pub use rust_hdl_macros::{
+    hdl_function, hdl_gen, LogicBlock, LogicInterface, LogicState, LogicStruct,
+};
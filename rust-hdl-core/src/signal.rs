@@ -5,6 +5,7 @@ use crate::ast::{VerilogLink, VerilogLinkDetails, VerilogLiteral};
 use crate::atom::{Atom, AtomKind};
 use crate::bits::Bit;
 use crate::block::Block;
+use crate::checkpoint::CircuitState;
 use crate::clock::Clock;
 use crate::constraint::{Constraint, PinConstraint, SignalType};
 use crate::direction::{Direction, In, InOut, Local, Out};
@@ -28,7 +29,10 @@ pub struct Signal<D: Direction, T: Synth> {
     claimed: bool,
     id: usize,
     tristate_is_output: bool,
+    tristate_changed: bool,
     signal_is_undriven: bool,
+    signal_is_contended: bool,
+    unknown: u128,
     constraints: Vec<PinConstraint>,
     dir: std::marker::PhantomData<D>,
 }
@@ -123,6 +127,9 @@ impl<T: Synth> LogicLink for Signal<InOut, T> {
         // So if the inner scope is driven, we take it's value
         // and mark ourselves as driven.  If the inner scope is
         // not driven, we are not driven and we push our value
+        if self.tristate_is_output != other.tristate_is_output {
+            self.tristate_changed = true;
+        }
         self.tristate_is_output = other.tristate_is_output;
         if other.tristate_is_output {
             self.next = other.val();
@@ -130,6 +137,9 @@ impl<T: Synth> LogicLink for Signal<InOut, T> {
             other.next = self.val();
         }
         self.signal_is_undriven = other.signal_is_undriven;
+        // Contention is detected where the tristates are connected, which
+        // is always in an outer scope
+        other.signal_is_contended = self.signal_is_contended;
     }
     fn link_hdl(my_name: &str, owner_name: &str, other_name: &str) -> Vec<VerilogLink> {
         let details = VerilogLinkDetails {
@@ -166,6 +176,18 @@ impl<D: Direction, T: Synth> Signal<D, T> {
             constraint: Constraint::Kind(signal),
         });
     }
+
+    /// Returns `true` if any bit of the signal is unknown (`X`).  Signals
+    /// are only ever unknown in a simulation with four-state tracking (see
+    /// [Simulation::set_four_state](crate::simulate::Simulation::set_four_state)).
+    pub fn is_unknown(&self) -> bool {
+        self.unknown != 0
+    }
+    /// The bits of the signal that are unknown (`X`), with bit 0 being the
+    /// least significant bit of the value.
+    pub fn unknown_bits(&self) -> u128 {
+        self.unknown
+    }
 }
 
 impl<D: Direction, T: Synth> Atom for Signal<D, T> {
@@ -190,10 +212,24 @@ impl<D: Direction, T: Synth> Atom for Signal<D, T> {
     }
 
     fn vcd(&self) -> VCDValue {
-        if !self.signal_is_undriven {
-            self.val.vcd()
-        } else {
+        if self.signal_is_contended {
+            VCDValue::Vector(vec![vcd::Value::X; T::BITS])
+        } else if self.signal_is_undriven {
             VCDValue::Vector(vec![vcd::Value::Z; T::BITS])
+        } else if self.unknown != 0 {
+            let bits = self.val.verilog().to_binary_string();
+            VCDValue::Vector(
+                bits.chars()
+                    .zip((0..T::BITS).rev())
+                    .map(|(c, ndx)| match c {
+                        _ if ndx < 128 && (self.unknown >> ndx) & 1 != 0 => vcd::Value::X,
+                        '1' => vcd::Value::V1,
+                        _ => vcd::Value::V0,
+                    })
+                    .collect(),
+            )
+        } else {
+            self.val.vcd()
         }
     }
 
@@ -221,8 +257,11 @@ impl<D: Direction, T: Synth> Block for Signal<D, T> {
     fn connect_all(&mut self) {}
 
     fn update_all(&mut self) {
-        self.changed = self.val != self.next;
-        if self.changed {
+        // A tristate that starts or stops driving counts as a change, even
+        // if the value stays the same, so that the other end sees it
+        self.changed = self.val != self.next || self.tristate_changed;
+        self.tristate_changed = false;
+        if self.val != self.next {
             self.prev = self.val;
             self.val = self.next;
         }
@@ -235,6 +274,24 @@ impl<D: Direction, T: Synth> Block for Signal<D, T> {
     fn accept(&self, name: &str, probe: &mut dyn Probe) {
         probe.visit_atom(name, self);
     }
+
+    fn checkpoint_all(&mut self, state: &mut CircuitState) {
+        state.value(&mut self.next);
+        state.value(&mut self.val);
+        state.value(&mut self.prev);
+    }
+
+    fn port_changed(&self) -> bool {
+        self.changed && D::KIND != AtomKind::LocalSignal
+    }
+
+    fn exposed_changed(&self) -> bool {
+        self.changed
+    }
+
+    fn mark_unknown_all(&mut self, unknown: &dyn Fn(usize) -> u128) {
+        self.unknown = unknown(self.id);
+    }
 }
 
 impl Signal<In, Clock> {
@@ -258,7 +315,10 @@ impl<T: Synth> Signal<Out, T> {
             claimed: false,
             id: get_signal_id(),
             tristate_is_output: false,
+            tristate_changed: false,
             signal_is_undriven: false,
+            signal_is_contended: false,
+            unknown: 0,
             constraints: vec![],
             dir: PhantomData,
         }
@@ -284,7 +344,10 @@ impl<D: Direction, T: Synth> Default for Signal<D, T> {
             claimed: false,
             id: get_signal_id(),
             tristate_is_output: false,
+            tristate_changed: false,
             signal_is_undriven: false,
+            signal_is_contended: false,
+            unknown: 0,
             constraints: vec![],
             dir: PhantomData,
         }
@@ -294,7 +357,7 @@ impl<D: Direction, T: Synth> Default for Signal<D, T> {
 impl<T: Synth> Signal<InOut, T> {
     pub fn set_tristate_is_output(&mut self, flag: bool) {
         if self.tristate_is_output != flag {
-            self.changed = true;
+            self.tristate_changed = true;
         }
         self.tristate_is_output = flag;
         self.signal_is_undriven = !flag;
@@ -302,9 +365,20 @@ impl<T: Synth> Signal<InOut, T> {
     pub fn is_driving_tristate(&self) -> bool {
         self.tristate_is_output
     }
+    /// Returns `true` if both ends of a connected tristate are driving it.
+    /// The value of the signal is then unknown, and it is recorded as `x`
+    /// in the VCD trace.
+    pub fn is_contended(&self) -> bool {
+        self.signal_is_contended
+    }
     pub fn simulate_connected_tristate(&mut self, other: &mut Self) {
-        //        assert!(!(self.is_driving_tristate() & other.is_driving_tristate()));
-        if self.is_driving_tristate() {
+        let contended = self.is_driving_tristate() && other.is_driving_tristate();
+        self.signal_is_contended = contended;
+        other.signal_is_contended = contended;
+        if contended {
+            self.signal_is_undriven = false;
+            other.signal_is_undriven = false;
+        } else if self.is_driving_tristate() {
             other.next = self.val();
             self.signal_is_undriven = false;
             other.signal_is_undriven = false;
@@ -92,6 +92,9 @@ impl<const N: usize> Signed<N> {
     pub fn inner(&self) -> Bits<N> {
         self.0
     }
+    pub(crate) fn from_inner(x: Bits<N>) -> Self {
+        Signed(x)
+    }
 }
 
 impl<const N: usize> From<BigInt> for Signed<N> {
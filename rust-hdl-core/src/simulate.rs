@@ -3,8 +3,18 @@ use crossbeam::channel::{RecvError, SendError};
 
 use crate::block::Block;
 use crate::check_error::{check_all, CheckError};
-use crate::vcd_probe::{write_vcd_change, write_vcd_dump, write_vcd_header};
-use std::io::Write;
+use crate::checkpoint::{Checkpoint, CheckpointError};
+use crate::compiled_sim::{CompileError, CompiledSimulation};
+use crate::coverage::{record_branches, Coverage, CoverageRecorder};
+use crate::fst_probe::write_fst_header;
+use crate::trace_filter::TraceFilter;
+use crate::vcd_probe::{write_vcd_header_selected, TraceWriter};
+use crate::waveform::{Waveform, WaveformRecorder};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::cell::Cell;
+use std::fmt::{Display, Formatter};
+use std::io::{Seek, Write};
 use std::thread::JoinHandle;
 
 /// Update changes to a circuit until it stabilizes
@@ -34,13 +44,70 @@ pub enum SimError {
     /// The simulation reached the maximum allowed time for the simulation
     MaxTimeReached,
     /// The simulation halted - usually this means an assertion failed
-    SimHalted,
+    SimHalted(SimHalt),
     /// The circuit failed to converge.  This means the logic has some issue (like an oscillation).
     FailedToConverge,
     /// Something went wrong with the circuit check (either a missing connection or other issue, like a latching write).
     Check(CheckError),
     /// The simulation panicked.  This usually means `.unwrap` was called on a result in the testbench.
     SimPanic,
+    /// The trace file could not be written.  The message is that of the underlying IO error.
+    IOError(String),
+}
+
+/// The details of why a testbench halted the simulation.  These are filled
+/// in by [sim_assert!] and [sim_assert_eq!], and returned from the simulation
+/// in [SimError::SimHalted].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SimHalt {
+    /// The expression that failed (e.g., `x.count.val() == 4`)
+    pub expression: String,
+    /// The value of the left hand side, for [sim_assert_eq!]
+    pub lhs: Option<String>,
+    /// The value of the right hand side, for [sim_assert_eq!]
+    pub rhs: Option<String>,
+    /// The simulation time at which the testbench halted
+    pub time: u64,
+    /// The index of the testbench that halted.  Clocks and testbenches are
+    /// numbered in the order they were added to the [Simulation].
+    pub testbench: usize,
+    /// The source file of the assertion
+    pub file: &'static str,
+    /// The line number of the assertion
+    pub line: u32,
+}
+
+impl SimHalt {
+    pub fn new(expression: &str, file: &'static str, line: u32) -> Self {
+        Self {
+            expression: expression.into(),
+            file,
+            line,
+            ..Default::default()
+        }
+    }
+    /// Record the values of the two sides of a failed comparison
+    pub fn with_values(self, lhs: String, rhs: String) -> Self {
+        Self {
+            lhs: Some(lhs),
+            rhs: Some(rhs),
+            ..self
+        }
+    }
+}
+
+impl Display for SimHalt {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "testbench {} halted at time {} ({}:{}): {}",
+            self.testbench, self.time, self.file, self.line, self.expression
+        )?;
+        if let (Some(lhs), Some(rhs)) = (&self.lhs, &self.rhs) {
+            write!(f, ", left: {}, right: {}", lhs, rhs)?;
+        }
+        Ok(())
+    }
 }
 
 impl From<CheckError> for SimError {
@@ -49,6 +116,12 @@ impl From<CheckError> for SimError {
     }
 }
 
+impl From<std::io::Error> for SimError {
+    fn from(x: std::io::Error) -> Self {
+        SimError::IOError(x.to_string())
+    }
+}
+
 impl From<RecvError> for SimError {
     fn from(_x: RecvError) -> Self {
         SimError::SimTerminated
@@ -69,7 +142,7 @@ enum TriggerType<T> {
     Time(u64),
     Function(Box<dyn Fn(&T) -> bool + Send>),
     Clock(u64),
-    Halt,
+    Halt(SimHalt),
 }
 
 struct Message<T> {
@@ -93,8 +166,20 @@ struct Worker<T> {
 /// are otherwise difficult or impossible to model.
 pub type CustomLogicFn<T> = Box<dyn Fn(&mut T) -> ()>;
 
+/// The environment variable that sets the seed of the random number
+/// generators of a [Simulation] built with [Simulation::new].
+pub const SIMULATION_SEED_VAR: &str = "RUST_HDL_SEED";
+
 /// This type represents a simulation over a circuit `T`.   To simulate
 /// a circuit, you will need to construct one of these structs.
+///
+/// The simulation is two-state: the values of [Bits](crate::bits::Bits) and
+/// [Signal](crate::signal::Signal) are always `0` or `1`, and registers start
+/// out at their default values.  By default, the only unknown (`X`) values it
+/// produces are on contended tristates (see [Signal::is_contended](crate::signal::Signal::is_contended)),
+/// which are recorded as `x` in the VCD trace.  To propagate unknown values
+/// through the logic (to find registers that are never reset, for example),
+/// enable four-state tracking with [Simulation::set_four_state].
 pub struct Simulation<T> {
     workers: Vec<Worker<T>>,
     recv: Receiver<MessageOrPanic<T>>,
@@ -102,22 +187,31 @@ pub struct Simulation<T> {
     time: u64,
     testbenches: Vec<JoinHandle<Result<()>>>,
     custom_logic: Vec<CustomLogicFn<T>>,
+    event_driven: bool,
+    branch_coverage: bool,
+    four_state: Option<CompiledSimulation>,
+    seed: u64,
+    rng: StdRng,
 }
 
 /// The `Sim` struct is used to communicate with a simulation.  Every testbench
 /// will be provided with a copy of this struct, and will use it to communicate
 /// with the core simulation.
 pub struct Sim<T> {
-    time: u64,
+    id: usize,
+    // A simulation restored from a checkpoint does not start at time zero, and
+    // the endpoint only learns the start time in `init`, which takes `&self`.
+    time: Cell<u64>,
     to_sim: Sender<MessageOrPanic<T>>,
     from_sim: Receiver<Message<T>>,
+    rng: StdRng,
 }
 
 struct NextTime {
     time: u64,
     idx: usize,
     clocks_only: bool,
-    halted: bool,
+    halted: Option<SimHalt>,
 }
 
 impl<T: Send + 'static + Block> Default for Simulation<T> {
@@ -127,8 +221,23 @@ impl<T: Send + 'static + Block> Default for Simulation<T> {
 }
 
 impl<T: Send + 'static + Block> Simulation<T> {
-    /// Construct a simulation struct
+    /// Construct a simulation struct.  The random number generators of the
+    /// simulation are seeded from the `RUST_HDL_SEED` environment variable
+    /// if it is set, and with a random seed otherwise.  The seed is printed
+    /// if the simulation fails, so that the failure can be reproduced.
     pub fn new() -> Simulation<T> {
+        let seed = std::env::var(SIMULATION_SEED_VAR)
+            .ok()
+            .and_then(|x| x.trim().parse().ok())
+            .unwrap_or_else(rand::random);
+        Self::with_seed(seed)
+    }
+    /// Construct a simulation struct with the given seed for its random
+    /// number generators.  Each testbench gets its own generator (see
+    /// [Sim::rng]), derived from the seed and the order in which the
+    /// testbenches were added, so the random values seen by a testbench
+    /// do not depend on the scheduling of the testbench threads.
+    pub fn with_seed(seed: u64) -> Simulation<T> {
         let (send, recv) = bounded(0);
         Self {
             workers: vec![],
@@ -137,8 +246,51 @@ impl<T: Send + 'static + Block> Simulation<T> {
             time: 0,
             testbenches: vec![],
             custom_logic: vec![],
+            event_driven: false,
+            branch_coverage: false,
+            four_state: None,
+            seed,
+            rng: StdRng::seed_from_u64(seed),
         }
     }
+    /// The seed of the random number generators of the simulation.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+    /// A random number generator seeded from the seed of the simulation,
+    /// for generating test data before the simulation is run.
+    pub fn rng(&mut self) -> &mut StdRng {
+        &mut self.rng
+    }
+    /// Select the event driven kernel.  When enabled, each pass of the
+    /// simulation only calls `update` on blocks for which one of the signals
+    /// they read changed in the previous pass, instead of updating the whole
+    /// circuit.  This can be substantially faster for large designs where
+    /// most of the circuit is idle at any given time.  The circuit still gets
+    /// a full update when the testbenches are initialized.
+    pub fn set_event_driven(&mut self, enable: bool) {
+        self.event_driven = enable;
+    }
+    /// Track unknown (`X`) values in the simulation.  The generated HDL of
+    /// `uut` is compiled for a four-state simulation (as in
+    /// [CompiledSimulation::new_four_state]), which runs in step with the
+    /// circuit and follows the values of its top level inputs (which are
+    /// driven by the testbenches, and so are always known).  Every time the
+    /// circuit settles, the bits that are unknown in the four-state simulation
+    /// are recorded in the signals of the circuit, where testbenches can check
+    /// them with [Signal::is_unknown](crate::signal::Signal::is_unknown), and
+    /// they are written as `x` to the trace.  Registers start out unknown and
+    /// stay that way until they are clocked with a known value, so a register
+    /// that is never reset shows up as `X`.  The values of the signals are
+    /// still those of the native simulation.
+    ///
+    /// `uut` must be the circuit that is passed to `run` (or a clone of it).
+    /// Returns an error if the design cannot be compiled, e.g., because it
+    /// has custom HDL.
+    pub fn set_four_state(&mut self, uut: &T) -> std::result::Result<(), CompileError> {
+        self.four_state = Some(CompiledSimulation::new_four_state(uut)?);
+        Ok(())
+    }
     /// Add a clock function to the simulation
     ///
     /// # Arguments
@@ -267,12 +419,17 @@ impl<T: Send + 'static + Block> Simulation<T> {
         };
         self.workers.push(worker);
         Sim {
+            id,
             to_sim: self.channel_to_sim.clone(),
             from_sim: recv_from_sim_to_worker,
-            time: 0,
+            time: Cell::new(0),
+            rng: StdRng::seed_from_u64(self.seed.wrapping_add(id as u64 + 1)),
         }
     }
     fn dispatch(&mut self, idx: usize, x: Box<T>) -> Result<Box<T>> {
+        self.dispatch_with(idx, x, self.event_driven)
+    }
+    fn dispatch_with(&mut self, idx: usize, x: Box<T>, event_driven: bool) -> Result<Box<T>> {
         let worker = &mut self.workers[idx];
         worker.channel_to_worker.send(Message {
             kind: TriggerType::Time(self.time),
@@ -292,17 +449,30 @@ impl<T: Send + 'static + Block> Simulation<T> {
             for l in &self.custom_logic {
                 l(&mut x.circuit);
             }
-            x.circuit.update_all();
+            if event_driven {
+                x.circuit.update_changed();
+            } else {
+                x.circuit.update_all();
+            }
             if !x.circuit.has_changed() {
                 converged = true;
                 break;
             }
         }
         if !converged {
-            Err(SimError::FailedToConverge)
-        } else {
-            Ok(x.circuit)
+            return Err(SimError::FailedToConverge);
+        }
+        if let Some(four_state) = &mut self.four_state {
+            four_state.drive_inputs(x.circuit.as_ref());
+            four_state.settle()?;
+            four_state.mark_unknown(x.circuit.as_mut());
         }
+        if self.branch_coverage {
+            // Updating a settled circuit changes nothing, but shows which
+            // branches it takes
+            record_branches(|| x.circuit.update_all());
+        }
+        Ok(x.circuit)
     }
     fn scan_workers(&self, x: &T) -> NextTime {
         let mut min_time = !0_u64;
@@ -310,9 +480,9 @@ impl<T: Send + 'static + Block> Simulation<T> {
         let mut only_clock_waiters = true;
         for worker in self.workers.iter() {
             match &worker.kind {
-                TriggerType::Halt => {
+                TriggerType::Halt(halt) => {
                     return NextTime {
-                        halted: true,
+                        halted: Some(halt.clone()),
                         time: !0,
                         idx: !0,
                         clocks_only: false,
@@ -346,76 +516,242 @@ impl<T: Send + 'static + Block> Simulation<T> {
             time: min_time,
             idx: min_idx,
             clocks_only: only_clock_waiters,
-            halted: false,
+            halted: None,
         }
     }
+    fn report_seed<S>(&self, result: Result<S>) -> Result<S> {
+        if result.is_err() {
+            eprintln!(
+                "Simulation failed with seed {} (set {}={} to reproduce)",
+                self.seed, SIMULATION_SEED_VAR, self.seed
+            );
+        }
+        result
+    }
     fn terminate(&mut self) {
         self.workers.clear();
         for handle in std::mem::take(&mut self.testbenches) {
             let _ = handle.join().unwrap();
         }
     }
-    pub fn run(&mut self, mut x: Box<T>, max_time: u64) -> Result<()> {
+    pub fn run(&mut self, x: Box<T>, max_time: u64) -> Result<()> {
+        self.run_observed(x, max_time, |_, _| {})
+    }
+    /// Run the simulation, and call `observer` with the time and the state
+    /// of the circuit once the workers are initialized, and then after
+    /// every change to the circuit has settled.
+    pub fn run_observed<F>(&mut self, x: Box<T>, max_time: u64, observer: F) -> Result<()>
+    where
+        F: FnMut(u64, &T),
+    {
+        let result = self.run_to_end(x, max_time, observer);
+        self.report_seed(result).map(|_| ())
+    }
+    /// Run the simulation, and return a [Checkpoint] of the circuit and the
+    /// simulation time once all of the testbenches are done.  The checkpoint
+    /// can be saved to disk, and restored (with [Simulation::restore]) into a
+    /// new simulation of the same circuit with different testbenches.  This
+    /// lets a long initialization sequence be simulated once and then reused.
+    pub fn run_to_checkpoint(&mut self, x: Box<T>, max_time: u64) -> Result<Checkpoint> {
+        let result = self.run_to_end(x, max_time, |_, _| {});
+        let mut x = self.report_seed(result)?;
+        Ok(Checkpoint::capture(self.time, x.as_mut()))
+    }
+    /// Restore the state of the circuit and the simulation time from a
+    /// [Checkpoint].  Call this before running the simulation - the
+    /// testbenches then start at the time of the checkpoint.  Clocks added with
+    /// [Simulation::add_clock] restart from that time, so the checkpoint
+    /// should be taken on a multiple of the clock interval to keep the phase
+    /// of the clock.
+    pub fn restore(
+        &mut self,
+        x: &mut T,
+        checkpoint: &Checkpoint,
+    ) -> std::result::Result<(), CheckpointError> {
+        checkpoint.restore(x)?;
+        self.time = checkpoint.time;
+        Ok(())
+    }
+    fn run_to_end<F>(&mut self, mut x: Box<T>, max_time: u64, mut observer: F) -> Result<Box<T>>
+    where
+        F: FnMut(u64, &T),
+    {
         x.as_mut().connect_all();
         check_all(x.as_mut())?;
         // First initialize the workers.
         for id in 0..self.workers.len() {
-            x = self.dispatch(id, x)?;
+            x = self.dispatch_with(id, x, false)?;
         }
+        observer(self.time, x.as_ref());
         // Next run until we have no one else waiting
-        let mut halted = false;
+        let mut halted = None;
         while self.time < max_time {
             let next = self.scan_workers(&x);
-            if next.time == !0 || next.clocks_only || next.halted {
+            if next.time == !0 || next.clocks_only || next.halted.is_some() {
                 halted = next.halted;
                 break;
             }
             self.time = next.time;
             x = self.dispatch(next.idx, x)?;
+            observer(self.time, x.as_ref());
         }
         self.terminate();
         if self.time >= max_time {
             return Err(SimError::MaxTimeReached);
         }
-        if halted {
-            return Err(SimError::SimHalted);
+        if let Some(halt) = halted {
+            return Err(SimError::SimHalted(halt));
         }
-        Ok(())
+        Ok(x)
+    }
+    /// Run the simulation, and record the signals of the circuit into
+    /// `waveform`.  The waveform is filled in even if the simulation fails,
+    /// so that it can be used to find out what went wrong.
+    pub fn run_recorded(
+        &mut self,
+        x: Box<T>,
+        max_time: u64,
+        waveform: &mut Waveform,
+    ) -> Result<()> {
+        let mut recorder: Option<WaveformRecorder> = None;
+        let result = self.run_observed(x, max_time, |time, x| {
+            recorder
+                .get_or_insert_with(|| WaveformRecorder::new(x))
+                .sample(time, x)
+        });
+        if let Some(recorder) = recorder {
+            *waveform = recorder.finish(self.time);
+        }
+        result
+    }
+    /// Run the simulation, and add the coverage of the circuit by the
+    /// testbenches to `coverage`.  Use the same [Coverage] for several
+    /// simulations to get their combined coverage.  The circuit is sampled
+    /// every time it settles, so toggles and state transitions that happen
+    /// while the circuit settles are not counted.  Branch coverage is only
+    /// collected for kernels marked with `#[hdl_gen(coverage)]`.
+    pub fn run_covered(&mut self, x: Box<T>, max_time: u64, coverage: &mut Coverage) -> Result<()> {
+        let mut recorder: Option<CoverageRecorder> = None;
+        self.branch_coverage = true;
+        let result = self.run_observed(x, max_time, |_, x| {
+            recorder
+                .get_or_insert_with(|| CoverageRecorder::new(x))
+                .sample(x)
+        });
+        self.branch_coverage = false;
+        if let Some(recorder) = recorder {
+            coverage.merge(&recorder.finish());
+        }
+        result
     }
+    /// Run the simulation, and write a trace to the file `name`.  The trace
+    /// is written in the FST format if the name ends in `.fst`, and as a VCD
+    /// file otherwise.
     pub fn run_to_file(&mut self, x: Box<T>, max_time: u64, name: &str) -> Result<()> {
+        if name.ends_with(".fst") {
+            let fst = std::io::BufWriter::new(std::fs::File::create(name)?);
+            return self.run_traced_fst(x, max_time, fst, TraceFilter::default());
+        }
         let mut vcd = vec![];
         let result = self.run_traced(x, max_time, &mut vcd);
-        std::fs::write(name, vcd).unwrap();
+        std::fs::write(name, vcd)?;
         result
     }
-    pub fn run_traced<W: Write>(&mut self, mut x: Box<T>, max_time: u64, trace: W) -> Result<()> {
+    pub fn run_traced<W: Write>(&mut self, x: Box<T>, max_time: u64, trace: W) -> Result<()> {
+        self.run_traced_filtered(x, max_time, trace, TraceFilter::default())
+    }
+    /// Run the simulation, and write a VCD trace of the signals (and the
+    /// stretches of time) selected by the [TraceFilter].  Whenever the filter
+    /// becomes active, the values of all of the selected signals are written,
+    /// so that the trace is complete even if they changed while it was not.
+    pub fn run_traced_filtered<W: Write>(
+        &mut self,
+        mut x: Box<T>,
+        max_time: u64,
+        trace: W,
+        filter: TraceFilter<T>,
+    ) -> Result<()> {
+        x.as_mut().connect_all();
+        check_all(x.as_mut())?;
+        let vcd = write_vcd_header_selected(trace, x.as_ref(), &|path| filter.selects(path));
+        self.run_with_trace_writer(x, max_time, vcd, filter)
+    }
+    /// Run the simulation, and write an FST trace of the signals selected
+    /// by the [TraceFilter].  FST files are much smaller than VCD files,
+    /// and can be opened with GTKWave.
+    pub fn run_traced_fst<W: Write + Seek>(
+        &mut self,
+        mut x: Box<T>,
+        max_time: u64,
+        trace: W,
+        filter: TraceFilter<T>,
+    ) -> Result<()> {
         x.as_mut().connect_all();
         check_all(x.as_mut())?;
-        let mut vcd = write_vcd_header(trace, x.as_ref());
+        let fst = write_fst_header(trace, x.as_ref(), &|path| filter.selects(path))?;
+        self.run_with_trace_writer(x, max_time, fst, filter)
+    }
+    fn run_with_trace_writer<P: TraceWriter>(
+        &mut self,
+        x: Box<T>,
+        max_time: u64,
+        mut trace: P,
+        mut filter: TraceFilter<T>,
+    ) -> Result<()> {
+        let result = self.run_trace_loop(x, max_time, &mut trace, &mut filter);
+        trace.finish();
+        self.report_seed(result)
+    }
+    fn run_trace_loop<P: TraceWriter>(
+        &mut self,
+        mut x: Box<T>,
+        max_time: u64,
+        trace: &mut P,
+        filter: &mut TraceFilter<T>,
+    ) -> Result<()> {
         // First initialize the workers.
         for id in 0..self.workers.len() {
-            x = self.dispatch(id, x)?;
+            x = self.dispatch_with(id, x, false)?;
         }
-        vcd = write_vcd_dump(vcd, x.as_ref());
-        let mut halted = false;
+        let mut active = filter.is_active(self.time, x.as_ref());
+        if active {
+            // The trace starts at time zero, unless the simulation was
+            // restored from a checkpoint
+            if self.time != 0 {
+                trace.timestamp(self.time);
+            }
+            trace.dump(x.as_ref());
+        }
+        let mut halted = None;
         // Next run until we have no one else waiting
         while self.time < max_time {
             let next = self.scan_workers(x.as_ref());
-            if next.time == !0 || next.clocks_only || next.halted {
+            if next.time == !0 || next.clocks_only || next.halted.is_some() {
                 halted = next.halted;
                 break;
             }
             self.time = next.time;
             x = self.dispatch(next.idx, x)?;
-            vcd.timestamp(next.time).unwrap();
-            vcd = write_vcd_change(vcd, x.as_ref());
+            if filter.is_active(next.time, x.as_ref()) {
+                trace.timestamp(next.time);
+                // Signals may have changed while the filter was not active,
+                // so the trace picks up again with all of their values
+                if active {
+                    trace.change(x.as_ref());
+                } else {
+                    trace.dump(x.as_ref());
+                }
+                active = true;
+            } else {
+                active = false;
+            }
         }
         self.terminate();
         if self.time >= max_time {
             return Err(SimError::MaxTimeReached);
         }
-        if halted {
-            return Err(SimError::SimHalted);
+        if let Some(halt) = halted {
+            return Err(SimError::SimHalted(halt));
         }
         Ok(())
     }
@@ -431,7 +767,12 @@ pub mod sim_time {
 
 impl<T> Sim<T> {
     pub fn init(&self) -> Result<Box<T>> {
-        Ok(self.from_sim.recv()?.circuit)
+        let t = self.from_sim.recv()?;
+        // The simulation may not start at time zero if it was restored from a checkpoint
+        if let TriggerType::Time(t0) = t.kind {
+            self.time.set(t0);
+        }
+        Ok(t.circuit)
     }
     pub fn watch<S>(&mut self, check: S, x: Box<T>) -> Result<Box<T>>
     where
@@ -443,29 +784,29 @@ impl<T> Sim<T> {
         }))?;
         let t = self.from_sim.recv()?;
         if let TriggerType::Time(t0) = t.kind {
-            self.time = t0;
+            self.time.set(t0);
         }
         Ok(t.circuit)
     }
     pub fn clock(&mut self, delta: u64, x: Box<T>) -> Result<Box<T>> {
         self.to_sim.send(MessageOrPanic::Message(Message {
-            kind: TriggerType::Clock(delta + self.time),
+            kind: TriggerType::Clock(delta + self.time.get()),
             circuit: x,
         }))?;
         let t = self.from_sim.recv()?;
         if let TriggerType::Time(t0) = t.kind {
-            self.time = t0;
+            self.time.set(t0);
         }
         Ok(t.circuit)
     }
     pub fn wait(&mut self, delta: u64, x: Box<T>) -> Result<Box<T>> {
         self.to_sim.send(MessageOrPanic::Message(Message {
-            kind: TriggerType::Time(delta + self.time),
+            kind: TriggerType::Time(delta + self.time.get()),
             circuit: x,
         }))?;
         let t = self.from_sim.recv()?;
         if let TriggerType::Time(t0) = t.kind {
-            self.time = t0;
+            self.time.set(t0);
         }
         Ok(t.circuit)
     }
@@ -477,14 +818,28 @@ impl<T> Sim<T> {
         Ok(())
     }
     pub fn halt(&self, x: Box<T>) -> Result<()> {
+        self.halt_with(SimHalt::default(), x)
+    }
+    /// Halt the simulation with the details of what went wrong.  The time
+    /// and the testbench index are filled in from this endpoint.
+    pub fn halt_with(&self, mut halt: SimHalt, x: Box<T>) -> Result<()> {
+        halt.time = self.time.get();
+        halt.testbench = self.id;
         self.to_sim.send(MessageOrPanic::Message(Message {
-            kind: TriggerType::Halt,
+            kind: TriggerType::Halt(halt.clone()),
             circuit: x,
         }))?;
-        Err(SimError::SimHalted)
+        Err(SimError::SimHalted(halt))
     }
     pub fn time(&self) -> u64 {
-        self.time
+        self.time.get()
+    }
+    /// The random number generator of this testbench.  Draw random values
+    /// from here (rather than from `rand::thread_rng`) so that the
+    /// testbench can be rerun with the same values from the seed of the
+    /// [Simulation].
+    pub fn rng(&mut self) -> &mut StdRng {
+        &mut self.rng
     }
 }
 
@@ -533,8 +888,10 @@ macro_rules! wait_clock_cycles {
 macro_rules! sim_assert {
     ($sim: ident, $test: expr, $circuit: ident) => {
         if !($test) {
-            println!("HALT {}", stringify!($test));
-            return $sim.halt($circuit);
+            return $sim.halt_with(
+                $crate::simulate::SimHalt::new(stringify!($test), file!(), line!()),
+                $circuit,
+            );
         }
     };
 }
@@ -543,14 +900,13 @@ macro_rules! sim_assert {
 macro_rules! sim_assert_eq {
     ($sim: ident, $lhs: expr, $rhs: expr, $circuit: ident) => {
         if !($lhs == $rhs) {
-            println!(
-                "HALT {} != {},  {:?} != {:?}",
-                stringify!($lhs),
-                stringify!($rhs),
-                $lhs,
-                $rhs
-            );
-            return $sim.halt($circuit);
+            let halt = $crate::simulate::SimHalt::new(
+                concat!(stringify!($lhs), " == ", stringify!($rhs)),
+                file!(),
+                line!(),
+            )
+            .with_values(format!("{:?}", $lhs), format!("{:?}", $rhs));
+            return $sim.halt_with(halt, $circuit);
         }
     };
 }
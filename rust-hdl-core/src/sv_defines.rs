@@ -0,0 +1,691 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::ast::{Verilog, VerilogLink};
+use crate::atom::AtomKind;
+use crate::block::Block;
+use crate::check_error::{check_all, GenerateError};
+use crate::code_writer::CodeWriter;
+use crate::formal::write_properties;
+use crate::module_defines::{decl, ModuleDefines};
+use crate::module_details::{
+    get_link_equivalence, link_assignment, AtomDetails, ModuleDetails, SubModuleInvocation,
+};
+use crate::type_descriptor::{enum_width, TypeDescriptor, TypeKind};
+use crate::verilog_gen::{system_verilog_combinatorial, verilog_function, verilog_functions};
+
+const TYPES_PACKAGE: &str = "rust_hdl_types";
+
+fn flat_type(width: usize, signed: bool) -> String {
+    let signed = if signed { " signed" } else { "" };
+    if width == 1 {
+        format!("logic{}", signed)
+    } else {
+        format!("logic{} [{}:0]", signed, width - 1)
+    }
+}
+
+// The typedefs for the `LogicState` and `LogicStruct` types used in a design.
+// Generic structs share the name in their descriptor, so a second layout
+// under the same name gets a numeric suffix.
+#[derive(Default)]
+struct SvTypes {
+    typedefs: Vec<(String, String, String)>,
+}
+
+impl SvTypes {
+    fn resolve(&mut self, descriptor: &TypeDescriptor) -> Option<String> {
+        let body = match &descriptor.kind {
+            TypeKind::Enum(_) | TypeKind::EncodedEnum(_) => {
+                let labels = descriptor.kind.enum_labels().unwrap_or_default();
+                let width = enum_width(&labels).max(1);
+                let labels = labels
+                    .iter()
+                    .map(|x| format!("{} = {}'d{}", x.name.replace("::", "$"), width, x.value))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                format!("enum logic [{}:0] {{{}}}", width - 1, labels)
+            }
+            TypeKind::Composite(fields) => {
+                // LogicStruct packs the first field into the LSBs, while a packed
+                // struct puts its first member in the MSBs.
+                let members = fields
+                    .iter()
+                    .rev()
+                    .map(|x| format!("    {} {};", self.field_type(&x.kind), x.fieldname))
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                format!("struct packed {{\n{}\n}}", members)
+            }
+            _ => return None,
+        };
+        if let Some(x) = self
+            .typedefs
+            .iter()
+            .find(|x| x.0 == descriptor.name && x.2 == body)
+        {
+            return Some(x.1.clone());
+        }
+        let count = self
+            .typedefs
+            .iter()
+            .filter(|x| x.0 == descriptor.name)
+            .count();
+        let name = if count == 0 {
+            descriptor.name.clone()
+        } else {
+            format!("{}${}", descriptor.name, count)
+        };
+        self.typedefs
+            .push((descriptor.name.clone(), name.clone(), body));
+        Some(name)
+    }
+
+    fn field_type(&mut self, descriptor: &TypeDescriptor) -> String {
+        if let Some(name) = self.resolve(descriptor) {
+            return name;
+        }
+        match &descriptor.kind {
+            TypeKind::Bits(n) => flat_type(*n, false),
+            TypeKind::Signed(n) => flat_type(*n, true),
+            _ => unreachable!(),
+        }
+    }
+
+    fn enum_type(&mut self, descriptor: &TypeDescriptor) -> Option<String> {
+        match &descriptor.kind {
+            TypeKind::Enum(_) | TypeKind::EncodedEnum(_) => self.resolve(descriptor),
+            _ => None,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.typedefs.is_empty()
+    }
+
+    fn package(&self) -> String {
+        let mut io = CodeWriter::default();
+        io.add(format!("package {};", TYPES_PACKAGE));
+        io.push();
+        for (_, name, body) in &self.typedefs {
+            io.add(format!("typedef {} {};", body, name));
+        }
+        io.pop();
+        io.add("endpackage");
+        io.to_string()
+    }
+}
+
+struct SvInterface {
+    name: String,
+    members: Vec<String>,
+    modports: Vec<(String, String)>,
+}
+
+impl SvInterface {
+    fn definition(&self, import: bool) -> String {
+        let mut io = CodeWriter::default();
+        io.add(format!("\n\ninterface {};", self.name));
+        io.push();
+        if import {
+            io.add(format!("import {}::*;", TYPES_PACKAGE));
+        }
+        self.members.iter().for_each(|x| io.add(x));
+        for (name, ports) in &self.modports {
+            io.add(format!("modport {} ({});", name, ports));
+        }
+        io.pop();
+        io.add(format!("endinterface // {}", self.name));
+        io.to_string()
+    }
+}
+
+#[derive(Default)]
+struct SvContext {
+    types: SvTypes,
+    interfaces: Vec<SvInterface>,
+    // (module, namespace) -> (interface, modport)
+    ports: BTreeMap<(String, String), (String, String)>,
+    typed: BTreeSet<String>,
+}
+
+// The namespace of a port that belongs to a `LogicInterface`
+fn port_namespace(x: &AtomDetails) -> Option<&str> {
+    if x.kind.is_parameter() {
+        x.name.split_once('$').map(|x| x.0)
+    } else {
+        None
+    }
+}
+
+// Namespaced ports become interfaces, unless they carry an `InOut` signal,
+// which cannot be connected through an interface port with a procedural
+// assignment.
+fn interface_namespaces(module_details: &ModuleDetails) -> Vec<String> {
+    let mut ret: Vec<String> = vec![];
+    let mut excluded: Vec<String> = vec![];
+    for atom in &module_details.atoms {
+        if let Some(ns) = port_namespace(atom) {
+            if atom.kind == AtomKind::InOutParameter {
+                excluded.push(ns.into());
+            } else if !ret.iter().any(|x| x == ns) {
+                ret.push(ns.into());
+            }
+        }
+    }
+    ret.retain(|x| !excluded.contains(x));
+    ret
+}
+
+impl SvContext {
+    fn declaration_type(&mut self, x: &AtomDetails) -> String {
+        self.types
+            .resolve(&x.descriptor)
+            .unwrap_or_else(|| flat_type(x.width, x.signed))
+    }
+
+    fn add_interface(&mut self, module_name: &str, namespace: &str, details: &ModuleDetails) {
+        let prefix = format!("{}$", namespace);
+        let atoms = details
+            .atoms
+            .iter()
+            .filter(|x| x.kind.is_parameter() && x.name.starts_with(&prefix))
+            .collect::<Vec<_>>();
+        let members = atoms
+            .iter()
+            .map(|x| format!("{} {};", self.declaration_type(x), &x.name[prefix.len()..]))
+            .collect::<Vec<_>>();
+        let directions = atoms
+            .iter()
+            .map(|x| {
+                let dir = if x.kind == AtomKind::InputParameter {
+                    "input"
+                } else {
+                    "output"
+                };
+                format!("{} {}", dir, &x.name[prefix.len()..])
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        let ndx = match self.interfaces.iter().position(|x| x.members == members) {
+            Some(ndx) => ndx,
+            None => {
+                self.interfaces.push(SvInterface {
+                    name: format!("{}${}$if", module_name, namespace),
+                    members,
+                    modports: vec![],
+                });
+                self.interfaces.len() - 1
+            }
+        };
+        let interface = &mut self.interfaces[ndx];
+        let modport = match interface.modports.iter().find(|x| x.1 == directions) {
+            Some(x) => x.0.clone(),
+            None => {
+                interface
+                    .modports
+                    .push((module_name.to_string(), directions));
+                module_name.to_string()
+            }
+        };
+        self.ports.insert(
+            (module_name.to_string(), namespace.to_string()),
+            (interface.name.clone(), modport),
+        );
+    }
+
+    fn interface_of(&self, module_name: &str, namespace: &str) -> Option<&(String, String)> {
+        self.ports
+            .get(&(module_name.to_string(), namespace.to_string()))
+    }
+}
+
+// Rename whole identifiers, leaving alone those that follow a `.` (which are
+// named port connections or member accesses).
+fn rename_identifiers(text: &str, renames: &BTreeMap<String, String>) -> String {
+    fn renamed<'a>(token: &'a str, before: char, renames: &'a BTreeMap<String, String>) -> &'a str {
+        if before == '.' || before == '\'' {
+            return token;
+        }
+        renames.get(token).map(|x| x.as_str()).unwrap_or(token)
+    }
+    let mut ret = String::with_capacity(text.len());
+    let mut token = String::new();
+    let mut before = ' ';
+    for c in text.chars() {
+        if c.is_ascii_alphanumeric() || c == '_' || c == '$' {
+            token.push(c);
+            continue;
+        }
+        ret += renamed(&token, before, renames);
+        token.clear();
+        ret.push(c);
+        before = c;
+    }
+    ret += renamed(&token, before, renames);
+    ret
+}
+
+fn is_typeable(module_details: &ModuleDetails) -> bool {
+    matches!(
+        module_details.code,
+        Verilog::Combinatorial(_) | Verilog::Empty
+    )
+}
+
+impl ModuleDefines {
+    fn canonical_name(&self, kind: &str, names: &BTreeMap<String, String>) -> String {
+        names.get(kind).cloned().unwrap_or_else(|| kind.to_string())
+    }
+
+    // The submodule that a stub signal belongs to, and the name of the stub
+    // in that submodule
+    fn stub_owner<'a>(
+        &self,
+        module_details: &'a ModuleDetails,
+        stub_name: &str,
+    ) -> Option<(&'a SubModuleInvocation, String)> {
+        module_details
+            .sub_modules
+            .iter()
+            .filter(|x| stub_name.starts_with(&format!("{}$", x.name)))
+            .max_by_key(|x| x.name.len())
+            .map(|x| (x, stub_name[x.name.len() + 1..].to_string()))
+    }
+
+    // The interface instance and member for a stub that connects to an
+    // interface port of a submodule
+    fn interface_stub(
+        &self,
+        module_details: &ModuleDetails,
+        stub_name: &str,
+        names: &BTreeMap<String, String>,
+        ctx: &SvContext,
+    ) -> Option<(String, String, String)> {
+        let (child, arg) = self.stub_owner(module_details, stub_name)?;
+        let (ns, member) = arg.split_once('$')?;
+        let (interface, _) = ctx.interface_of(&self.canonical_name(&child.kind, names), ns)?;
+        Some((
+            interface.clone(),
+            format!("{}${}", child.name, ns),
+            member.to_string(),
+        ))
+    }
+
+    fn stub_is_typed(
+        &self,
+        module_details: &ModuleDetails,
+        stub_name: &str,
+        names: &BTreeMap<String, String>,
+        ctx: &SvContext,
+    ) -> bool {
+        self.stub_owner(module_details, stub_name)
+            .map(|(child, _)| ctx.typed.contains(&self.canonical_name(&child.kind, names)))
+            .unwrap_or(false)
+    }
+
+    // As `module_argument_is_passed_through_to_submodule`, but ignoring
+    // interface ports, which are connected with explicit assignments
+    fn sv_argument_is_passed_through(
+        &self,
+        module_details: &ModuleDetails,
+        module_arg_name: &str,
+        names: &BTreeMap<String, String>,
+        ctx: &SvContext,
+    ) -> bool {
+        for child in &module_details.sub_modules {
+            let entry = self.details.get(&child.kind).unwrap();
+            let kind = self.canonical_name(&child.kind, names);
+            for child_arg in &entry.atoms {
+                if port_namespace(child_arg)
+                    .and_then(|ns| ctx.interface_of(&kind, ns))
+                    .is_some()
+                {
+                    continue;
+                }
+                let arg_name = format!("{}${}", child.name, child_arg.name);
+                if module_details.get_linked_argument_name(&arg_name) == module_arg_name {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    fn atom_is_typed(
+        &self,
+        module_details: &ModuleDetails,
+        x: &AtomDetails,
+        names: &BTreeMap<String, String>,
+        ctx: &SvContext,
+    ) -> bool {
+        match x.kind {
+            AtomKind::InputParameter | AtomKind::LocalSignal => true,
+            AtomKind::OutputParameter => {
+                // Outputs driven directly by a submodule port keep a plain vector
+                // type, since that port may not be typed
+                port_namespace(x).is_some()
+                    || !self.sv_argument_is_passed_through(module_details, &x.name, names, ctx)
+            }
+            AtomKind::StubInputSignal | AtomKind::StubOutputSignal => {
+                self.stub_is_typed(module_details, &x.name, names, ctx)
+            }
+            _ => false,
+        }
+    }
+
+    fn sv_declare(
+        &self,
+        module_details: &ModuleDetails,
+        x: &AtomDetails,
+        names: &BTreeMap<String, String>,
+        ctx: &mut SvContext,
+    ) -> String {
+        if x.kind == AtomKind::OutputParameter
+            && self.sv_argument_is_passed_through(module_details, &x.name, names, ctx)
+        {
+            let mut x = x.clone();
+            x.kind = AtomKind::OutputPassthrough;
+            return decl(&x);
+        }
+        if !self.atom_is_typed(module_details, x, names, ctx) {
+            return decl(x);
+        }
+        match ctx.types.resolve(&x.descriptor) {
+            Some(kind) => match x.kind {
+                AtomKind::InputParameter => format!("input {} {};", kind, x.name),
+                AtomKind::OutputParameter => format!("output {} {};", kind, x.name),
+                _ => format!("{} {};", kind, x.name),
+            },
+            None => decl(x),
+        }
+    }
+
+    fn sv_sub_module_invocation(
+        &self,
+        module_details: &ModuleDetails,
+        child: &SubModuleInvocation,
+        names: &BTreeMap<String, String>,
+        ctx: &SvContext,
+        io: &mut CodeWriter,
+    ) {
+        let entry = self.details.get(&child.kind).unwrap();
+        let kind = self.canonical_name(&child.kind, names);
+        let submodule_kind = match &entry.code {
+            Verilog::Blackbox(b) => b.name.clone(),
+            _ => kind.clone(),
+        };
+        let mut child_args: Vec<String> = vec![];
+        for x in entry.atoms.iter().filter(|x| x.kind.is_parameter()) {
+            if let Some(ns) = port_namespace(x).filter(|ns| ctx.interface_of(&kind, ns).is_some()) {
+                let arg = format!(".{}({}${})", ns, child.name, ns);
+                if !child_args.contains(&arg) {
+                    child_args.push(arg);
+                }
+                continue;
+            }
+            let arg_name = format!("{}${}", child.name, x.name);
+            let arg_name = if module_details.stub_is_linked_to_module_argument(&arg_name) {
+                module_details.get_linked_argument_name(&arg_name)
+            } else {
+                arg_name
+            };
+            child_args.push(format!(".{}({})", x.name, arg_name));
+        }
+        io.add(format!("{} {}(\n", submodule_kind, child.name));
+        io.push();
+        io.add(child_args.join(",\n"));
+        io.pop();
+        io.add(");\n");
+    }
+
+    fn process_sv_module(
+        &self,
+        module_name: &str,
+        module_details: &ModuleDetails,
+        names: &BTreeMap<String, String>,
+        ctx: &mut SvContext,
+        io: &mut CodeWriter,
+    ) {
+        if !ctx.typed.contains(module_name) {
+            self.process_module(module_name, module_details, names, io);
+            return;
+        }
+        let own_interfaces = interface_namespaces(module_details);
+        let own_interface = |x: &AtomDetails| -> Option<String> {
+            port_namespace(x)
+                .filter(|ns| own_interfaces.iter().any(|y| y == ns))
+                .map(|ns| ns.to_string())
+        };
+        let atoms = &module_details.atoms;
+        let args = atoms
+            .iter()
+            .filter(|x| x.kind.is_parameter())
+            .collect::<Vec<_>>();
+        let mut module_args: Vec<String> = vec![];
+        for x in &args {
+            let name = own_interface(x).unwrap_or_else(|| x.name.clone());
+            if !module_args.contains(&name) {
+                module_args.push(name);
+            }
+        }
+        let mut renames = BTreeMap::new();
+        let mut casts = BTreeMap::new();
+        for x in atoms {
+            if let Some(ns) = own_interface(x) {
+                renames.insert(
+                    x.name.clone(),
+                    format!("{}.{}", ns, &x.name[ns.len() + 1..]),
+                );
+            }
+            if x.kind.is_stub() {
+                if let Some((_, instance, member)) =
+                    self.interface_stub(module_details, &x.name, names, ctx)
+                {
+                    renames.insert(x.name.clone(), format!("{}.{}", instance, member));
+                }
+            }
+            if self.atom_is_typed(module_details, x, names, ctx) {
+                if let Some(kind) = ctx.types.enum_type(&x.descriptor) {
+                    casts.insert(x.name.clone(), kind);
+                }
+            }
+        }
+        let mut body = CodeWriter::default();
+        body.add(format!(
+            "\n\nmodule {}({});",
+            module_name,
+            module_args.join(",")
+        ));
+        body.push();
+        if !ctx.types.is_empty() {
+            body.add(format!("import {}::*;", TYPES_PACKAGE));
+        }
+        if !args.is_empty() {
+            body.add("\n// Module arguments");
+            let mut declared: Vec<String> = vec![];
+            for x in &args {
+                if let Some(ns) = own_interface(x) {
+                    if !declared.contains(&ns) {
+                        let (interface, modport) = ctx.interface_of(module_name, &ns).unwrap();
+                        body.add(format!("{}.{} {};", interface, modport, ns));
+                        declared.push(ns);
+                    }
+                } else {
+                    body.add(self.sv_declare(module_details, x, names, ctx));
+                }
+            }
+        }
+        let consts = atoms
+            .iter()
+            .filter(|x| x.kind == AtomKind::Constant)
+            .collect::<Vec<_>>();
+        if !consts.is_empty() {
+            body.add("\n// Constant declarations");
+            consts.iter().for_each(|x| body.add(decl(x)));
+        }
+        let stubs = atoms
+            .iter()
+            .filter(|x| x.kind.is_stub())
+            .collect::<Vec<_>>();
+        if !stubs.is_empty() {
+            body.add("\n// Stub signals");
+            let mut instances: Vec<String> = vec![];
+            for x in &stubs {
+                if let Some((interface, instance, _)) =
+                    self.interface_stub(module_details, &x.name, names, ctx)
+                {
+                    if !instances.contains(&instance) {
+                        body.add(format!("{} {}();", interface, instance));
+                        instances.push(instance);
+                    }
+                } else if !module_details.stub_is_linked_to_module_argument(&x.name) {
+                    body.add(self.sv_declare(module_details, x, names, ctx));
+                }
+            }
+        }
+        let locals = atoms
+            .iter()
+            .filter(|x| x.kind == AtomKind::LocalSignal)
+            .collect::<Vec<_>>();
+        if !locals.is_empty() {
+            body.add("\n// Local signals");
+            for x in &locals {
+                body.add(self.sv_declare(module_details, x, names, ctx));
+            }
+        }
+        if !module_details.sub_modules.is_empty() {
+            body.add("\n// Sub module instances");
+            for child in &module_details.sub_modules {
+                self.sv_sub_module_invocation(module_details, child, names, ctx, &mut body);
+            }
+        }
+        if let Verilog::Combinatorial(code) = &module_details.code {
+            let functions = verilog_functions(code);
+            if !functions.is_empty() {
+                body.add("\n// Functions");
+                functions.iter().for_each(|x| body.add(verilog_function(x)));
+            }
+            body.add("\n// Update code");
+            body.add(system_verilog_combinatorial(code, &casts));
+        }
+        for x in &module_details.links {
+            let equiv = get_link_equivalence(x);
+            let via_interface = self
+                .interface_stub(module_details, &equiv.0, names, ctx)
+                .is_some()
+                || self
+                    .interface_stub(module_details, &equiv.1, names, ctx)
+                    .is_some();
+            if via_interface
+                || (!module_details.signal_name_is_module_argument(&equiv.0)
+                    & !module_details.signal_name_is_module_argument(&equiv.1))
+            {
+                let (target, source) = link_assignment(x);
+                let txt = match x {
+                    VerilogLink::Bidirectional(_) => format!("assign {} = {};", target, source),
+                    _ => match casts.get(&target) {
+                        Some(kind) => format!("always_comb {} = {}'({});", target, kind, source),
+                        None => format!("always_comb {} = {};", target, source),
+                    },
+                };
+                body.add_line(txt);
+            }
+        }
+        write_properties(&module_details.properties, &mut body);
+        body.pop();
+        body.add(format!("endmodule // {}", module_name));
+        io.add(rename_identifiers(&body.to_string(), &renames));
+    }
+
+    /// Emit the design as SystemVerilog.  See [generate_system_verilog].
+    pub fn system_verilog_defines(&self) -> String {
+        let names = self.module_names();
+        let modules = self
+            .details
+            .iter()
+            .filter(|x| !x.0.is_empty())
+            .filter(|x| !matches!(x.1.code, Verilog::Blackbox(_)))
+            .filter(|x| names.get(x.0) == Some(x.0))
+            .collect::<Vec<_>>();
+        let mut ctx = SvContext::default();
+        // Modules with custom Verilog instantiate their children with the
+        // plain Verilog port lists, so those children cannot be typed either.
+        let mut untyped: BTreeSet<String> = BTreeSet::new();
+        for (_, details) in &modules {
+            if !is_typeable(details) {
+                for child in &details.sub_modules {
+                    untyped.insert(self.canonical_name(&child.kind, &names));
+                }
+            }
+        }
+        for (module_name, details) in &modules {
+            if is_typeable(details) && !untyped.contains(*module_name) {
+                ctx.typed.insert(module_name.to_string());
+            }
+        }
+        for (module_name, details) in &modules {
+            if !ctx.typed.contains(*module_name) {
+                continue;
+            }
+            for atom in &details.atoms {
+                ctx.types.resolve(&atom.descriptor);
+            }
+            for ns in interface_namespaces(details) {
+                ctx.add_interface(module_name, &ns, details);
+            }
+        }
+        let mut body = CodeWriter::default();
+        for (module_name, details) in &modules {
+            self.process_sv_module(module_name, details, &names, &mut ctx, &mut body);
+        }
+        let mut io = CodeWriter::default();
+        if !ctx.types.is_empty() {
+            io.add(ctx.types.package());
+        }
+        for interface in &ctx.interfaces {
+            io.add(interface.definition(!ctx.types.is_empty()));
+        }
+        io.add(body.to_string());
+        self.add_cores(&mut io);
+        io.to_string()
+    }
+}
+
+/// Generate SystemVerilog for a circuit.  Unlike [generate_verilog](crate::module_defines::generate_verilog),
+/// the output keeps the types of the design:
+///  - `LogicState` enums become `typedef enum`s (so that `State$Idle` names an
+///    enum member, rather than a `localparam`)
+///  - `LogicStruct` types become `typedef struct packed`s
+///  - The ports of a `LogicInterface` become a single `interface` port,
+///    with a `modport` for each direction in which it is used.
+///
+/// The typedefs are placed in a `rust_hdl_types` package, which is imported by each module
+/// and interface.  Modules with custom or wrapped Verilog (and their children) keep the
+/// plain Verilog port lists.  Panics if the circuit fails [check_all].  Use
+/// [try_generate_system_verilog] to get the error instead.
+pub fn generate_system_verilog<U: Block>(uut: &U) -> String {
+    try_generate_system_verilog(uut).unwrap_or_else(|e| panic!("{}", e))
+}
+
+/// Generate SystemVerilog for a circuit (as in [generate_system_verilog]), or
+/// return a [GenerateError] if the circuit fails [check_all].
+pub fn try_generate_system_verilog<U: Block>(uut: &U) -> Result<String, GenerateError> {
+    check_all(uut)?;
+    let mut defines = ModuleDefines::default();
+    uut.accept("top", &mut defines);
+    Ok(defines.system_verilog_defines())
+}
+
+#[test]
+fn test_rename_identifiers() {
+    let mut renames = BTreeMap::new();
+    renames.insert("bus$addr".to_string(), "bus.addr".to_string());
+    assert_eq!(
+        rename_identifiers("always_comb bus$addr = x$bus$addr + bus$addr_hi;", &renames),
+        "always_comb bus.addr = x$bus$addr + bus$addr_hi;"
+    );
+    assert_eq!(
+        rename_identifiers(".bus$addr(bus$addr)", &renames),
+        ".bus$addr(bus.addr)"
+    );
+}
@@ -32,6 +32,27 @@ pub trait Synth: Default + Copy + PartialEq + Debug {
     fn bits(self) -> usize {
         Self::BITS
     }
+    /// Rebuild a value from its bits, least significant bit first (i.e., the
+    /// reverse of `verilog`).  Returns `None` if the bits do not describe a
+    /// value of the type.  This is needed to restore simulation checkpoints,
+    /// and the default (which always returns `None`) makes restoring a
+    /// checkpoint into a circuit that uses the type fail with an error.
+    fn from_bits(_bits: &[bool]) -> Option<Self> {
+        None
+    }
+}
+
+fn bits_from_slice<const N: usize>(bits: &[bool]) -> Option<Bits<N>> {
+    if bits.len() != N {
+        return None;
+    }
+    Some(
+        bits.iter()
+            .enumerate()
+            .fold(Bits::<N>::default(), |acc, (ndx, bit)| {
+                acc.replace_bit(ndx, *bit)
+            }),
+    )
 }
 
 impl<const N: usize> Synth for Bits<N> {
@@ -51,6 +72,10 @@ impl<const N: usize> Synth for Bits<N> {
     fn verilog(self) -> VerilogLiteral {
         self.into()
     }
+
+    fn from_bits(bits: &[bool]) -> Option<Self> {
+        bits_from_slice(bits)
+    }
 }
 
 impl Synth for Bit {
@@ -74,6 +99,13 @@ impl Synth for Bit {
     fn verilog(self) -> VerilogLiteral {
         self.into()
     }
+
+    fn from_bits(bits: &[bool]) -> Option<Self> {
+        match bits {
+            [bit] => Some(*bit),
+            _ => None,
+        }
+    }
 }
 
 impl Synth for Clock {
@@ -93,6 +125,10 @@ impl Synth for Clock {
     fn verilog(self) -> VerilogLiteral {
         self.clk.into()
     }
+
+    fn from_bits(bits: &[bool]) -> Option<Self> {
+        bool::from_bits(bits).map(|clk| Clock { clk })
+    }
 }
 
 impl<const N: usize> Synth for Signed<N> {
@@ -109,4 +145,7 @@ impl<const N: usize> Synth for Signed<N> {
     fn verilog(self) -> VerilogLiteral {
         self.inner().into()
     }
+    fn from_bits(bits: &[bool]) -> Option<Self> {
+        bits_from_slice(bits).map(Signed::from_inner)
+    }
 }
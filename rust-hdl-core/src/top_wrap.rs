@@ -1,4 +1,7 @@
-use crate::{ast::Verilog, block::Block, logic::Logic, probe::Probe, timing::TimingInfo};
+use crate::{
+    ast::Verilog, block::Block, checkpoint::CircuitState, logic::Logic, probe::Probe,
+    timing::TimingInfo,
+};
 
 pub struct TopWrap<U: Block> {
     pub uut: U,
@@ -31,4 +34,11 @@ impl<U: Block> Block for TopWrap<U> {
         self.uut.accept("uut", probe);
         probe.visit_end_scope(name, self);
     }
+    fn checkpoint_all(&mut self, state: &mut CircuitState) {
+        self.checkpoint(state);
+        self.uut.checkpoint_all(state);
+    }
+    fn mark_unknown_all(&mut self, unknown: &dyn Fn(usize) -> u128) {
+        self.uut.mark_unknown_all(unknown);
+    }
 }
@@ -0,0 +1,124 @@
+use regex::Regex;
+
+/// The condition that starts tracing (see [TraceFilter::trigger]).
+pub type TraceTrigger<T> = Box<dyn FnMut(&T) -> bool>;
+
+/// Selects which signals, and which stretch of time, are written by
+/// [Simulation::run_traced_filtered](crate::simulate::Simulation::run_traced_filtered).
+/// Signals are selected by their hierarchical path, in the `.` separated form used
+/// in the VCD file (e.g., `uut.sdram.bank.address`).  The default filter traces
+/// everything.
+///
+/// ```
+/// # use rust_hdl_core::prelude::*;
+/// # #[derive(LogicBlock, Default)]
+/// # struct Top { pub clock: Signal<In, Clock>, pub done: Signal<Out, Bit> }
+/// # impl Logic for Top { fn update(&mut self) {} }
+/// let filter = TraceFilter::<Top>::default()
+///     .include("uut.*")
+///     .include("uut.sdram.**")
+///     .exclude("uut.sdram.**.clock")
+///     .max_depth(3)
+///     .window(1_000_000, 2_000_000)
+///     .trigger(|x: &Top| x.done.val());
+/// assert!(filter.selects("uut.sdram.bank.address"));
+/// assert!(!filter.selects("uut.sdram.bank.clock"));
+/// assert!(!filter.selects("uut.sdram.bank.timer.count"));
+/// ```
+pub struct TraceFilter<T> {
+    include: Vec<Regex>,
+    exclude: Vec<Regex>,
+    max_depth: Option<usize>,
+    windows: Vec<(u64, u64)>,
+    trigger: Option<TraceTrigger<T>>,
+    triggered: bool,
+}
+
+impl<T> Default for TraceFilter<T> {
+    fn default() -> Self {
+        Self {
+            include: vec![],
+            exclude: vec![],
+            max_depth: None,
+            windows: vec![],
+            trigger: None,
+            triggered: false,
+        }
+    }
+}
+
+// In a glob, `*` matches within one level of the hierarchy, `**` matches
+// any number of levels, and `?` matches a single character
+fn glob_to_regex(glob: &str) -> Regex {
+    let pattern = regex::escape(glob)
+        .replace(r"\*\*", ".*")
+        .replace(r"\*", "[^.]*")
+        .replace(r"\?", "[^.]");
+    Regex::new(&format!("^{}$", pattern)).unwrap()
+}
+
+impl<T> TraceFilter<T> {
+    /// Trace the signals whose paths match the glob.  In the glob, `*`
+    /// matches a single level of the hierarchy and `**` matches any
+    /// number of levels.  If no signals are included, all signals are
+    /// traced.
+    pub fn include(mut self, glob: &str) -> Self {
+        self.include.push(glob_to_regex(glob));
+        self
+    }
+    /// Trace the signals whose paths match the regular expression.
+    /// Panics if the expression is invalid.
+    pub fn include_regex(mut self, regex: &str) -> Self {
+        self.include
+            .push(Regex::new(regex).expect("invalid trace filter regex"));
+        self
+    }
+    /// Do not trace the signals whose paths match the glob, even if they
+    /// are included.
+    pub fn exclude(mut self, glob: &str) -> Self {
+        self.exclude.push(glob_to_regex(glob));
+        self
+    }
+    /// Only trace signals that are at most `depth` levels below the top
+    /// of the circuit, so that a depth of 1 traces the signals of the top
+    /// level circuit, and nothing inside of it.
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = Some(depth);
+        self
+    }
+    /// Only write changes that happen at or after `start` and before `stop`.
+    /// Call this more than once to trace several stretches of time.
+    pub fn window(mut self, start: u64, stop: u64) -> Self {
+        self.windows.push((start, stop));
+        self
+    }
+    /// Only start writing changes once `trigger` returns true.  Once
+    /// triggered, tracing continues (subject to the windows) until the
+    /// end of the simulation.
+    pub fn trigger<F: FnMut(&T) -> bool + 'static>(mut self, trigger: F) -> Self {
+        self.trigger = Some(Box::new(trigger));
+        self
+    }
+    /// Returns `true` if the signal with the given path is traced.
+    pub fn selects(&self, path: &str) -> bool {
+        let depth = path.matches('.').count();
+        !matches!(self.max_depth, Some(x) if depth > x)
+            && (self.include.is_empty() || self.include.iter().any(|x| x.is_match(path)))
+            && !self.exclude.iter().any(|x| x.is_match(path))
+    }
+    /// Returns `true` if changes to the circuit at `time` should be written.
+    pub fn is_active(&mut self, time: u64, x: &T) -> bool {
+        if !self.triggered {
+            self.triggered = match &mut self.trigger {
+                Some(trigger) => trigger(x),
+                None => true,
+            };
+        }
+        self.triggered
+            && (self.windows.is_empty()
+                || self
+                    .windows
+                    .iter()
+                    .any(|(start, stop)| time >= *start && time < *stop))
+    }
+}
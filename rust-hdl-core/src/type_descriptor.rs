@@ -10,10 +10,50 @@ pub struct TypeField {
     pub kind: TypeDescriptor,
 }
 
+/// A variant of a `LogicState` enum, and the value that encodes it
+#[derive(Clone, Debug, PartialEq)]
+pub struct EnumLabel {
+    pub name: String,
+    pub value: u128,
+}
+
 #[derive(Clone, Debug)]
 pub enum TypeKind {
     Bits(usize),
     Signed(usize),
     Enum(Vec<String>),
+    /// An enum whose variants are not numbered in order (because of its
+    /// `#[encoding]` or discriminants), with the value that encodes each label
+    EncodedEnum(Vec<EnumLabel>),
     Composite(Vec<Box<TypeField>>),
 }
+
+impl TypeKind {
+    /// The labels of an enum and the values that encode them, or `None` if
+    /// this is not an enum.  The labels of a [TypeKind::Enum] are numbered in order.
+    pub fn enum_labels(&self) -> Option<Vec<EnumLabel>> {
+        match self {
+            TypeKind::Enum(x) => Some(
+                x.iter()
+                    .zip(0..)
+                    .map(|(name, value)| EnumLabel {
+                        name: name.clone(),
+                        value,
+                    })
+                    .collect(),
+            ),
+            TypeKind::EncodedEnum(x) => Some(x.clone()),
+            _ => None,
+        }
+    }
+}
+
+/// The number of bits needed to hold the encoding of every label of an enum.
+/// For the default (sequential) encoding, this is `clog2` of the number of labels.
+pub fn enum_width(labels: &[EnumLabel]) -> usize {
+    labels
+        .iter()
+        .map(|x| (u128::BITS - x.value.leading_zeros()) as usize)
+        .max()
+        .unwrap_or_default()
+}
@@ -33,7 +33,13 @@ impl<W: Write> VCDProbe<W> {
     }
 }
 
-struct VCDHeader<W: Write>(VCDProbe<W>);
+struct VCDHeader<'a, W: Write> {
+    probe: VCDProbe<W>,
+    select: &'a dyn Fn(&str) -> bool,
+    // The open scopes, and whether they have been written yet.  Scopes are
+    // only written once a signal in them is selected.
+    scope: Vec<(String, bool)>,
+}
 
 fn register_signal<W: Write>(
     name: &str,
@@ -44,7 +50,9 @@ fn register_signal<W: Write>(
         TypeKind::Bits(width) | TypeKind::Signed(width) => {
             VCDIDCode::Singleton(vcd.add_wire(*width as u32, name).unwrap())
         }
-        TypeKind::Enum(_) => VCDIDCode::Singleton(vcd.add_wire(0, name).unwrap()),
+        TypeKind::Enum(_) | TypeKind::EncodedEnum(_) => {
+            VCDIDCode::Singleton(vcd.add_wire(0, name).unwrap())
+        }
         TypeKind::Composite(k) => {
             let mut ret = vec![];
             for field in k {
@@ -57,42 +65,82 @@ fn register_signal<W: Write>(
     }
 }
 
-impl<W: Write> Probe for VCDHeader<W> {
+impl<W: Write> VCDHeader<'_, W> {
+    fn end_scope(&mut self) {
+        if let Some((_, true)) = self.scope.pop() {
+            self.probe.vcd.upscope().unwrap();
+        }
+    }
+}
+
+impl<W: Write> Probe for VCDHeader<'_, W> {
     fn visit_start_scope(&mut self, name: &str, _node: &dyn Block) {
-        self.0.vcd.add_module(name).unwrap();
+        self.scope.push((name.into(), false));
     }
 
     fn visit_start_namespace(&mut self, name: &str, _node: &dyn Block) {
-        self.0.vcd.add_module(name).unwrap();
+        self.scope.push((name.into(), false));
     }
 
     fn visit_atom(&mut self, name: &str, signal: &dyn Atom) {
-        self.0.id_map.insert(
+        let path = self
+            .scope
+            .iter()
+            .map(|(x, _)| x.as_str())
+            .chain(std::iter::once(name))
+            .collect::<Vec<_>>()
+            .join(".");
+        if !(self.select)(&path) {
+            return;
+        }
+        for (scope, written) in self.scope.iter_mut().filter(|(_, written)| !written) {
+            self.probe.vcd.add_module(scope).unwrap();
+            *written = true;
+        }
+        self.probe.id_map.insert(
             signal.id(),
-            register_signal(name, &signal.descriptor(), &mut self.0.vcd),
+            register_signal(name, &signal.descriptor(), &mut self.probe.vcd),
         );
     }
 
     fn visit_end_namespace(&mut self, _name: &str, _node: &dyn Block) {
-        self.0.vcd.upscope().unwrap();
+        self.end_scope();
     }
 
     fn visit_end_scope(&mut self, _name: &str, _node: &dyn Block) {
-        self.0.vcd.upscope().unwrap();
+        self.end_scope();
     }
 }
 
 pub fn write_vcd_header<W: Write>(writer: W, uut: &dyn Block) -> VCDProbe<W> {
-    let mut visitor = VCDHeader(VCDProbe::new(writer));
-    visitor.0.vcd.timescale(1, vcd::TimescaleUnit::PS).unwrap();
+    write_vcd_header_selected(writer, uut, &|_| true)
+}
+
+/// Write the header of a VCD file that only includes the signals whose
+/// (`.` separated) paths are accepted by `select`.
+pub fn write_vcd_header_selected<W: Write>(
+    writer: W,
+    uut: &dyn Block,
+    select: &dyn Fn(&str) -> bool,
+) -> VCDProbe<W> {
+    let mut visitor = VCDHeader {
+        probe: VCDProbe::new(writer),
+        select,
+        scope: vec![],
+    };
+    visitor
+        .probe
+        .vcd
+        .timescale(1, vcd::TimescaleUnit::PS)
+        .unwrap();
     uut.accept("uut", &mut visitor);
-    visitor.0.vcd.enddefinitions().unwrap();
-    visitor.0
+    visitor.probe.vcd.enddefinitions().unwrap();
+    visitor.probe
 }
 
-struct VCDChange<W: Write>(VCDProbe<W>);
+struct VCDChange<'a, W: Write>(&'a mut VCDProbe<W>);
 
-impl<W: Write> Probe for VCDChange<W> {
+impl<W: Write> Probe for VCDChange<'_, W> {
     fn visit_atom(&mut self, _name: &str, signal: &dyn Atom) {
         if let Some(idc) = self.0.id_map.get(&signal.id()) {
             do_vcd_change(
@@ -106,15 +154,14 @@ impl<W: Write> Probe for VCDChange<W> {
     }
 }
 
-pub fn write_vcd_change<W: Write>(vcd: VCDProbe<W>, uut: &dyn Block) -> VCDProbe<W> {
-    let mut visitor = VCDChange(vcd);
-    uut.accept("uut", &mut visitor);
-    visitor.0
+pub fn write_vcd_change<W: Write>(mut vcd: VCDProbe<W>, uut: &dyn Block) -> VCDProbe<W> {
+    uut.accept("uut", &mut VCDChange(&mut vcd));
+    vcd
 }
 
-struct VCDDump<W: Write>(VCDProbe<W>);
+struct VCDDump<'a, W: Write>(&'a mut VCDProbe<W>);
 
-impl<W: Write> Probe for VCDDump<W> {
+impl<W: Write> Probe for VCDDump<'_, W> {
     fn visit_atom(&mut self, _name: &str, signal: &dyn Atom) {
         if let Some(idc) = &self.0.id_map.get(&signal.id()) {
             do_vcd_change(
@@ -182,14 +229,36 @@ fn do_vcd_change<W: Write>(
     }
 }
 
-pub fn write_vcd_dump<W: Write>(vcd: VCDProbe<W>, uut: &dyn Block) -> VCDProbe<W> {
-    let mut visitor = VCDDump(vcd);
-    visitor
-        .0
-        .vcd
-        .begin(vcd::SimulationCommand::Dumpvars)
-        .unwrap();
-    uut.accept("uut", &mut visitor);
-    visitor.0.vcd.end().unwrap();
-    visitor.0
+pub fn write_vcd_dump<W: Write>(mut vcd: VCDProbe<W>, uut: &dyn Block) -> VCDProbe<W> {
+    vcd.dump(uut);
+    vcd
+}
+
+/// The operations needed to write a trace of a simulation.  This is
+/// implemented by the [VCDProbe], and by the [FstProbe](crate::fst_probe::FstProbe).
+pub(crate) trait TraceWriter {
+    /// Start the changes at time `ts`
+    fn timestamp(&mut self, ts: u64);
+    /// Write the signals that have changed
+    fn change(&mut self, uut: &dyn Block);
+    /// Write the values of all of the signals
+    fn dump(&mut self, uut: &dyn Block);
+    /// Complete the trace
+    fn finish(&mut self) {}
+}
+
+impl<W: Write> TraceWriter for VCDProbe<W> {
+    fn timestamp(&mut self, ts: u64) {
+        self.vcd.timestamp(ts).unwrap();
+    }
+
+    fn change(&mut self, uut: &dyn Block) {
+        uut.accept("uut", &mut VCDChange(self));
+    }
+
+    fn dump(&mut self, uut: &dyn Block) {
+        self.vcd.begin(vcd::SimulationCommand::Dumpvars).unwrap();
+        uut.accept("uut", &mut VCDDump(self));
+        self.vcd.end().unwrap();
+    }
 }
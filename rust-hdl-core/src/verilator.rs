@@ -0,0 +1,489 @@
+use crate::ast::Verilog;
+use crate::atom::{Atom, AtomKind};
+use crate::block::Block;
+use crate::check_error::{GenerateError, PathedName};
+use crate::code_writer::CodeWriter;
+use crate::module_defines::try_generate_verilog;
+use crate::named_path::NamedPath;
+use crate::probe::Probe;
+use crate::simulate::{SimError, Simulation};
+use std::env::temp_dir;
+use std::fmt::{Display, Formatter};
+use std::fs::{create_dir_all, remove_dir_all, File};
+use std::io::Write;
+use std::process::Command;
+
+#[derive(Debug)]
+pub enum CosimError {
+    /// The `verilator` executable could not be found
+    VerilatorNotFound,
+    /// The design could not be translated to Verilog
+    Generate(GenerateError),
+    /// Verilator failed to build the design
+    BuildFailed {
+        stdout: String,
+        stderr: String,
+    },
+    /// The Rust simulation failed
+    Simulation(SimError),
+    /// The compiled Verilog model did not run to completion
+    ModelFailed {
+        stderr: String,
+    },
+    /// An output of the Verilog model differs from the Rust simulation
+    Mismatch {
+        time: u64,
+        signal: PathedName,
+        expected: String,
+        actual: String,
+    },
+    IOError(std::io::Error),
+}
+
+impl From<std::io::Error> for CosimError {
+    fn from(x: std::io::Error) -> Self {
+        CosimError::IOError(x)
+    }
+}
+
+impl From<GenerateError> for CosimError {
+    fn from(x: GenerateError) -> Self {
+        CosimError::Generate(x)
+    }
+}
+
+impl From<SimError> for CosimError {
+    fn from(x: SimError) -> Self {
+        CosimError::Simulation(x)
+    }
+}
+
+impl Display for CosimError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CosimError::VerilatorNotFound => write!(f, "verilator is not installed"),
+            CosimError::Generate(x) => write!(f, "{}", x),
+            CosimError::BuildFailed { stdout, stderr } => {
+                write!(f, "verilator build failed:\n{}\n{}", stdout, stderr)
+            }
+            CosimError::Simulation(x) => write!(f, "simulation failed: {:?}", x),
+            CosimError::ModelFailed { stderr } => {
+                write!(f, "verilator model failed:\n{}", stderr)
+            }
+            CosimError::Mismatch {
+                time,
+                signal,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "mismatch on {} at time {}: expected {}, verilator produced {}",
+                signal, time, expected, actual
+            ),
+            CosimError::IOError(x) => write!(f, "{}", x),
+        }
+    }
+}
+
+impl std::error::Error for CosimError {}
+
+// A signal of the design that is compared between the Rust simulation and
+// the Verilator model.  The ports of the top module are the ports of the
+// model, and every other signal is read out through a port of a wrapper
+// module (using a hierarchical reference).
+struct PortDetails {
+    // The position of the signal in the order in which the atoms are visited
+    index: usize,
+    path: String,
+    name: String,
+    port: String,
+    reference: String,
+    width: usize,
+    input: bool,
+}
+
+// Collects the signals to compare.  These are the ports of every block
+// that is present in the generated Verilog, and the local signals of
+// blocks with `#[hdl_gen]` kernels.  The internals of wrapped and black box
+// blocks are not visible.
+#[derive(Copy, Clone)]
+struct Visibility {
+    ports: bool,
+    locals: bool,
+    children: bool,
+}
+
+#[derive(Default)]
+struct CosimLayout {
+    count: usize,
+    scopes: Vec<String>,
+    namespace: NamedPath,
+    saved: Vec<NamedPath>,
+    visible: Vec<Visibility>,
+    ports: Vec<PortDetails>,
+}
+
+impl Probe for CosimLayout {
+    fn visit_start_scope(&mut self, name: &str, node: &dyn Block) {
+        let inside = self.visible.last().map(|x| x.children).unwrap_or(true);
+        let code = node.hdl();
+        self.visible.push(Visibility {
+            ports: inside,
+            locals: inside && matches!(code, Verilog::Combinatorial(_)),
+            children: inside && !matches!(code, Verilog::Wrapper(_) | Verilog::Blackbox(_)),
+        });
+        self.scopes.push(name.to_owned());
+        self.saved.push(std::mem::take(&mut self.namespace));
+    }
+
+    fn visit_start_namespace(&mut self, name: &str, _node: &dyn Block) {
+        self.namespace.push(name);
+    }
+
+    fn visit_atom(&mut self, name: &str, signal: &dyn Atom) {
+        let index = self.count;
+        self.count += 1;
+        let visible = match self.visible.last() {
+            Some(x) => *x,
+            None => return,
+        };
+        let include = match signal.kind() {
+            AtomKind::InputParameter | AtomKind::OutputParameter => visible.ports,
+            AtomKind::LocalSignal => visible.locals,
+            _ => false,
+        };
+        if !include {
+            return;
+        }
+        let name = if self.namespace.is_empty() {
+            name.to_owned()
+        } else {
+            format!("{}${}", self.namespace.flat("$"), name)
+        };
+        let top_level = self.scopes.len() == 1 && signal.kind() != AtomKind::LocalSignal;
+        let input = top_level && signal.kind() == AtomKind::InputParameter;
+        let port = if top_level {
+            name.clone()
+        } else {
+            format!("probe${}", self.ports.len())
+        };
+        let mut reference = vec!["dut".to_string()];
+        reference.extend(self.scopes.iter().skip(1).cloned());
+        reference.push(name.clone());
+        self.ports.push(PortDetails {
+            index,
+            path: self.scopes.join("$"),
+            name,
+            port,
+            reference: reference.join("."),
+            width: signal.bits(),
+            input,
+        });
+    }
+
+    fn visit_end_namespace(&mut self, _name: &str, _node: &dyn Block) {
+        self.namespace.pop();
+    }
+
+    fn visit_end_scope(&mut self, _name: &str, _node: &dyn Block) {
+        self.namespace = self.saved.pop().unwrap_or_default();
+        self.scopes.pop();
+        self.visible.pop();
+    }
+}
+
+// The values of all of the atoms of the design (as little endian 32 bit
+// words, which is how Verilator stores wide signals), in the order in
+// which they are visited.
+#[derive(Default)]
+struct AtomValues {
+    values: Vec<Vec<u32>>,
+}
+
+impl Probe for AtomValues {
+    fn visit_atom(&mut self, _name: &str, signal: &dyn Atom) {
+        self.values
+            .push(binary_to_words(&signal.verilog().to_binary_string()));
+    }
+}
+
+fn atom_values<T: Block>(uut: &T) -> Vec<Vec<u32>> {
+    let mut values = AtomValues::default();
+    uut.accept("top", &mut values);
+    values.values
+}
+
+// The wrapper instantiates the design as `dut`, and brings the signals
+// inside of it out to ports of its own.
+fn wrapper(ports: &[PortDetails]) -> String {
+    let mut io = CodeWriter::default();
+    let names = ports.iter().map(|x| x.port.clone()).collect::<Vec<_>>();
+    io.add(format!("module cosim({});", names.join(",")));
+    io.push();
+    for port in ports {
+        let direction = if port.input { "input" } else { "output" };
+        io.add(format!(
+            "{} wire [{}:0] {};",
+            direction,
+            port.width - 1,
+            port.port
+        ));
+    }
+    let connections = ports
+        .iter()
+        .filter(|x| x.port == x.name)
+        .map(|x| format!(".{}({})", x.name, x.name))
+        .collect::<Vec<_>>();
+    io.add(format!("top dut({});", connections.join(", ")));
+    for port in ports.iter().filter(|x| x.port != x.name) {
+        io.add(format!("assign {} = {};", port.port, port.reference));
+    }
+    io.pop();
+    io.add("endmodule");
+    io.to_string()
+}
+
+fn binary_to_words(x: &str) -> Vec<u32> {
+    let bits = x.as_bytes();
+    bits.rchunks(32)
+        .map(|chunk| {
+            chunk
+                .iter()
+                .fold(0_u32, |acc, bit| (acc << 1) | u32::from(*bit == b'1'))
+        })
+        .collect()
+}
+
+fn words_to_hex(x: &[u32]) -> String {
+    x.iter()
+        .rev()
+        .map(|w| format!("{:08x}", w))
+        .collect::<Vec<_>>()
+        .join("_")
+}
+
+fn num_words(width: usize) -> usize {
+    width.div_ceil(32)
+}
+
+// Verilator escapes the `$` in identifiers when it generates C++.
+fn verilator_name(x: &str) -> String {
+    x.replace('$', "__024")
+}
+
+// The harness reads the input values (one line per step of the Rust
+// simulation), evaluates the model, and prints the output values.
+fn harness(ports: &[PortDetails]) -> String {
+    let max_words = ports.iter().map(|x| num_words(x.width)).max().unwrap_or(1);
+    let mut io = CodeWriter::default();
+    io.add("#include \"Vcosim.h\"");
+    io.add("#include \"verilated.h\"");
+    io.add("#include <cstdint>");
+    io.add("#include <cstdio>");
+    io.add_line("");
+    io.add("static bool read_words(FILE *in, uint32_t *w, int count) {");
+    io.push();
+    io.add("for (int i = 0; i < count; i++) {");
+    io.push();
+    io.add("if (fscanf(in, \"%x\", &w[i]) != 1) return false;");
+    io.pop();
+    io.add("}");
+    io.add("return true;");
+    io.pop();
+    io.add("}");
+    io.add_line("");
+    io.add("int main(int argc, char **argv) {");
+    io.push();
+    io.add("Verilated::commandArgs(argc, argv);");
+    io.add("Vcosim *top = new Vcosim;");
+    io.add("FILE *in = fopen(argv[1], \"r\");");
+    io.add(format!("uint32_t w[{}];", max_words));
+    io.add("while (true) {");
+    io.push();
+    for port in ports.iter().filter(|x| x.input) {
+        let name = verilator_name(&port.port);
+        let words = num_words(port.width);
+        io.add(format!("if (!read_words(in, w, {})) break;", words));
+        match words {
+            1 => io.add(format!("top->{} = w[0];", name)),
+            2 => io.add(format!(
+                "top->{} = ((uint64_t) w[1] << 32) | (uint64_t) w[0];",
+                name
+            )),
+            _ => io.add(format!(
+                "for (int i = 0; i < {}; i++) top->{}[i] = w[i];",
+                words, name
+            )),
+        }
+    }
+    io.add("top->eval();");
+    for port in ports.iter().filter(|x| !x.input) {
+        let name = verilator_name(&port.port);
+        let words = num_words(port.width);
+        match words {
+            1 => io.add(format!("printf(\" %08x\", (uint32_t) top->{});", name)),
+            2 => io.add(format!(
+                "printf(\" %08x %08x\", (uint32_t) top->{0}, (uint32_t) (top->{0} >> 32));",
+                name
+            )),
+            _ => io.add(format!(
+                "for (int i = 0; i < {}; i++) printf(\" %08x\", (uint32_t) top->{}[i]);",
+                words, name
+            )),
+        }
+    }
+    io.add("printf(\"\\n\");");
+    io.pop();
+    io.add("}");
+    io.add("top->final();");
+    io.add("delete top;");
+    io.add("return 0;");
+    io.pop();
+    io.add("}");
+    io.to_string()
+}
+
+/// Returns `true` if `verilator` can be run on this machine.
+pub fn verilator_available() -> bool {
+    Command::new("verilator")
+        .arg("--version")
+        .output()
+        .map(|x| x.status.success())
+        .unwrap_or(false)
+}
+
+/// Check the Verilog generated for a design against the Rust simulation
+/// of the same design.
+///
+/// The simulation is run as usual (with whatever testbenches and clocks
+/// have been added to `sim`), and the values of the signals of the design
+/// are recorded every time the circuit settles.  The recorded inputs of the
+/// top module are then replayed into a Verilator model built from
+/// [generate_verilog](crate::module_defines::generate_verilog), and the rest
+/// of the signals are compared step by step.  Besides the outputs of the top
+/// module, these are the ports of every block in the generated Verilog, and
+/// the local signals of blocks with `#[hdl_gen]` kernels (the internals of
+/// wrapped and black box blocks cannot be seen).  The first signal that
+/// differs is reported as a [CosimError::Mismatch].
+///
+/// The working files are kept in a directory named `prefix` under the
+/// temporary directory, for debugging.  Returns [CosimError::VerilatorNotFound]
+/// if Verilator is not installed.
+pub fn verilator_cosim<T: Send + 'static + Block>(
+    sim: &mut Simulation<T>,
+    mut uut: Box<T>,
+    max_time: u64,
+    prefix: &str,
+) -> Result<(), CosimError> {
+    if !verilator_available() {
+        return Err(CosimError::VerilatorNotFound);
+    }
+    uut.connect_all();
+    let verilog = try_generate_verilog(uut.as_ref())?;
+    let mut layout = CosimLayout::default();
+    uut.accept("top", &mut layout);
+    let ports = layout.ports;
+    let dir = temp_dir().as_path().join(prefix);
+    let _ = remove_dir_all(&dir);
+    create_dir_all(&dir)?;
+    write!(File::create(dir.join("top.v"))?, "{}", verilog)?;
+    write!(File::create(dir.join("cosim.v"))?, "{}", wrapper(&ports))?;
+    write!(
+        File::create(dir.join("harness.cpp"))?,
+        "{}",
+        harness(&ports)
+    )?;
+    let build = Command::new("verilator")
+        .current_dir(&dir)
+        .args([
+            "--cc",
+            "cosim.v",
+            "top.v",
+            "--exe",
+            "harness.cpp",
+            "--top-module",
+            "cosim",
+            "--build",
+            "-Wno-fatal",
+            "-Wno-lint",
+            "-Wno-style",
+            "-o",
+            "cosim",
+        ])
+        .output()?;
+    if !build.status.success() {
+        return Err(CosimError::BuildFailed {
+            stdout: String::from_utf8_lossy(&build.stdout).into(),
+            stderr: String::from_utf8_lossy(&build.stderr).into(),
+        });
+    }
+    let mut steps = vec![];
+    sim.run_observed(uut, max_time, |time, x| {
+        let values = atom_values(x);
+        steps.push((
+            time,
+            ports
+                .iter()
+                .map(|port| values[port.index].clone())
+                .collect::<Vec<_>>(),
+        ));
+    })?;
+    let mut stimulus = String::new();
+    for (_, values) in &steps {
+        let line = ports
+            .iter()
+            .zip(values)
+            .filter(|(port, _)| port.input)
+            .flat_map(|(_, words)| words.iter().map(|w| format!("{:x}", w)))
+            .collect::<Vec<_>>()
+            .join(" ");
+        stimulus += &line;
+        stimulus += "\n";
+    }
+    write!(File::create(dir.join("stimulus.txt"))?, "{}", stimulus)?;
+    let model = Command::new(dir.join("obj_dir").join("cosim"))
+        .current_dir(&dir)
+        .arg("stimulus.txt")
+        .output()?;
+    let stderr = String::from_utf8_lossy(&model.stderr).to_string();
+    if !model.status.success() {
+        return Err(CosimError::ModelFailed { stderr });
+    }
+    let response = String::from_utf8_lossy(&model.stdout).to_string();
+    let mut lines = response.lines();
+    for (time, values) in &steps {
+        let line = lines.next().ok_or_else(|| CosimError::ModelFailed {
+            stderr: stderr.clone(),
+        })?;
+        let mut words = line
+            .split_whitespace()
+            .map(|x| u32::from_str_radix(x, 16).unwrap_or_default());
+        for (port, expected) in ports.iter().zip(values).filter(|(port, _)| !port.input) {
+            let actual = words
+                .by_ref()
+                .take(num_words(port.width))
+                .collect::<Vec<_>>();
+            if &actual != expected {
+                return Err(CosimError::Mismatch {
+                    time: *time,
+                    signal: PathedName {
+                        path: port.path.clone(),
+                        name: port.name.clone(),
+                    },
+                    expected: words_to_hex(expected),
+                    actual: words_to_hex(&actual),
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_binary_to_words() {
+    assert_eq!(binary_to_words("101"), vec![5]);
+    let wide = format!("11{}", "0".repeat(32));
+    assert_eq!(binary_to_words(&wide), vec![0, 3]);
+    assert_eq!(words_to_hex(&[0, 3]), "00000003_00000000");
+    assert_eq!(verilator_name("bus$addr"), "bus__024addr");
+}
@@ -1,18 +1,19 @@
 use evalexpr::ContextWithMutableVariables;
 use num_bigint::BigUint;
 use regex::Regex;
+use std::collections::BTreeMap;
 
 use crate::ast::{
     VerilogBlock, VerilogBlockOrConditional, VerilogCase, VerilogConditional, VerilogExpression,
-    VerilogLink, VerilogLinkDetails, VerilogLiteral, VerilogLoop, VerilogMatch, VerilogOp,
-    VerilogOpUnary,
+    VerilogFunction, VerilogLink, VerilogLinkDetails, VerilogLiteral, VerilogLocal, VerilogLoop,
+    VerilogMatch, VerilogOp, VerilogOpUnary, VerilogStatement,
 };
 use crate::code_writer::CodeWriter;
-use crate::verilog_visitor::{walk_block, VerilogVisitor};
+use crate::verilog_visitor::{walk_block, walk_call, VerilogVisitor};
 
-struct LoopVariable {
-    variable: String,
-    value: usize,
+pub(crate) struct LoopVariable {
+    pub(crate) variable: String,
+    pub(crate) value: usize,
 }
 
 #[derive(Default)]
@@ -20,26 +21,50 @@ struct VerilogCodeGenerator {
     io: CodeWriter,
     loops: Vec<LoopVariable>,
     links: Vec<VerilogLink>,
+    casts: BTreeMap<String, String>,
 }
 
-impl VerilogCodeGenerator {
-    fn array_index_simplification(&self, a: &str) -> String {
-        let re = Regex::new(r"\[([^\]]*)\]").unwrap();
-        let mut context = evalexpr::HashMapContext::new();
-        for lvar in &self.loops {
-            let _ = context.set_value(lvar.variable.clone(), (lvar.value as i64).into());
-        }
-        if let Some(x) = re.captures(a) {
-            if x.len() == 2 {
-                if let Some(txt) = x.get(1) {
-                    let arg = evalexpr::eval_with_context(txt.as_str(), &context).unwrap();
-                    return re.replace(a, format!("$${}", arg)).to_string();
-                }
+fn array_index_simplification(a: &str, loops: &[LoopVariable]) -> String {
+    let re = Regex::new(r"\[([^\]]*)\]").unwrap();
+    let mut context = evalexpr::HashMapContext::new();
+    for lvar in loops {
+        let _ = context.set_value(lvar.variable.clone(), (lvar.value as i64).into());
+    }
+    if let Some(x) = re.captures(a) {
+        if x.len() == 2 {
+            if let Some(txt) = x.get(1) {
+                let arg = evalexpr::eval_with_context(txt.as_str(), &context).unwrap();
+                return re.replace(a, format!("$${}", arg)).to_string();
             }
         }
-        a.to_string()
     }
+    a.to_string()
+}
 
+/// Map a Rust path (as captured by `hdl_gen`) to the flattened signal name
+/// used in the generated HDL, substituting the values of any loop variables.
+pub(crate) fn ident_fixup(a: &str, loops: &[LoopVariable]) -> String {
+    let mut x = a.to_owned();
+    for index in loops {
+        if x == index.variable {
+            x = format!("{}", index.value);
+        }
+    }
+    if x.starts_with(".") {
+        x.remove(0);
+    }
+    x = x
+        .replace(".", "$")
+        .replace("::", "$")
+        .trim_end_matches("$next")
+        .to_owned();
+    if x.contains('[') {
+        x = array_index_simplification(&x, loops);
+    }
+    x
+}
+
+impl VerilogCodeGenerator {
     fn link_fixup(&self, x: &VerilogLinkDetails) -> VerilogLinkDetails {
         VerilogLinkDetails {
             my_name: self.ident_fixup(&x.my_name),
@@ -49,24 +74,7 @@ impl VerilogCodeGenerator {
     }
 
     fn ident_fixup(&self, a: &str) -> String {
-        let mut x = a.to_owned();
-        for index in &self.loops {
-            if x == index.variable {
-                x = format!("{}", index.value);
-            }
-        }
-        if x.starts_with(".") {
-            x.remove(0);
-        }
-        x = x
-            .replace(".", "$")
-            .replace("::", "$")
-            .trim_end_matches("$next")
-            .to_owned();
-        if x.contains('[') {
-            x = self.array_index_simplification(&x);
-        }
-        x
+        ident_fixup(a, &self.loops)
     }
 }
 
@@ -82,12 +90,163 @@ pub fn verilog_link_extraction(code: &VerilogBlock) -> Vec<VerilogLink> {
     gen.links
 }
 
+#[derive(Default)]
+struct LocalCollector {
+    locals: Vec<VerilogLocal>,
+}
+
+impl VerilogVisitor for LocalCollector {
+    fn visit_local(&mut self, l: &VerilogLocal) {
+        if !self.locals.iter().any(|x| x.name == l.name) {
+            self.locals.push(l.clone());
+        }
+    }
+}
+
+/// The signals declared by `let` bindings in the code of a module.
+pub(crate) fn verilog_locals(code: &VerilogBlock) -> Vec<VerilogLocal> {
+    let mut collector = LocalCollector::default();
+    collector.visit_block(code);
+    collector.locals
+}
+
+#[derive(Default)]
+struct FixedWidthFinder {
+    found: bool,
+}
+
+impl VerilogVisitor for FixedWidthFinder {
+    fn visit_slice_assignment(
+        &mut self,
+        _base: &VerilogExpression,
+        _width: &usize,
+        _offset: &VerilogExpression,
+        _replacement: &VerilogExpression,
+    ) {
+        self.found = true;
+    }
+    fn visit_local(&mut self, _l: &VerilogLocal) {
+        self.found = true;
+    }
+    fn visit_cast(&mut self, _a: &VerilogExpression, _b: &usize) {
+        self.found = true;
+    }
+    fn visit_slice(&mut self, _a: &VerilogExpression, _b: &usize, _c: &VerilogExpression) {
+        self.found = true;
+    }
+    fn visit_slice_replace(
+        &mut self,
+        _a: &VerilogExpression,
+        _b: &usize,
+        _c: &VerilogExpression,
+        _d: &VerilogExpression,
+    ) {
+        self.found = true;
+    }
+    fn visit_call(&mut self, _f: &VerilogFunction, _args: &[VerilogExpression]) {
+        self.found = true;
+    }
+}
+
+/// Returns true if the code of a module bakes in the widths of the Rust
+/// types it was generated from (in casts, slices, `let` bindings or function
+/// calls), so that it cannot follow a change to the widths of its signals.
+pub(crate) fn verilog_has_fixed_widths(code: &VerilogBlock) -> bool {
+    let mut finder = FixedWidthFinder::default();
+    finder.visit_block(code);
+    finder.found
+}
+
+#[derive(Default)]
+struct FunctionCollector {
+    functions: Vec<VerilogFunction>,
+}
+
+impl VerilogVisitor for FunctionCollector {
+    fn visit_call(&mut self, f: &VerilogFunction, args: &[VerilogExpression]) {
+        if !self.functions.iter().any(|x| x.name == f.name) {
+            // Functions called by this one have to be declared first
+            self.visit_block(&f.body);
+            self.functions.push(f.clone());
+        }
+        walk_call(self, f, args);
+    }
+}
+
+/// The `#[hdl_function]`s called (directly or indirectly) by the code of a
+/// module, ordered so that each function comes after the ones it calls.
+pub(crate) fn verilog_functions(code: &VerilogBlock) -> Vec<VerilogFunction> {
+    let mut collector = FunctionCollector::default();
+    collector.visit_block(code);
+    collector.functions
+}
+
+fn function_decl(kind: &str, name: &str, x: &VerilogLocal) -> String {
+    let signed = if x.signed { "signed" } else { "" };
+    if x.width == 1 {
+        format!("{} {} {};", kind, signed, name)
+    } else {
+        format!("{} {} [{}:0] {};", kind, signed, x.width - 1, name)
+    }
+}
+
+/// The declaration of an `#[hdl_function]` as a Verilog `function`.
+pub(crate) fn verilog_function(f: &VerilogFunction) -> String {
+    let mut io = CodeWriter::default();
+    io.add(function_decl("function", &f.name, &f.result));
+    io.push();
+    f.args
+        .iter()
+        .for_each(|x| io.add(function_decl("input", &x.name, x)));
+    verilog_locals(&f.body)
+        .iter()
+        .for_each(|x| io.add(function_decl("reg", &x.name, x)));
+    let mut gen = VerilogCodeGenerator::default();
+    gen.visit_block(&f.body);
+    io.add(gen.to_string());
+    io.pop();
+    io.add("endfunction");
+    io.to_string()
+}
+
 pub fn verilog_combinatorial(code: &VerilogBlock) -> String {
     let mut gen = VerilogCodeGenerator::default();
     gen.visit_block(code);
     format!("always @(*) {}\n", gen.to_string())
 }
 
+/// As [verilog_combinatorial], but as an `always_comb` block.  Assignments to
+/// the signals in `casts` (which hold `typedef enum` values) are cast to the
+/// given type, since SystemVerilog does not convert vectors to enums implicitly.
+pub(crate) fn system_verilog_combinatorial(
+    code: &VerilogBlock,
+    casts: &BTreeMap<String, String>,
+) -> String {
+    let mut gen = VerilogCodeGenerator {
+        casts: casts.clone(),
+        ..Default::default()
+    };
+    gen.visit_block(code);
+    format!("always_comb {}\n", gen.to_string())
+}
+
+/// Render a single statement as Verilog (for use in diagnostics), with the
+/// given loop variables substituted into any array indices.
+pub(crate) fn verilog_statement(s: &VerilogStatement, loops: &[LoopVariable]) -> String {
+    let mut gen = VerilogCodeGenerator {
+        loops: loops
+            .iter()
+            .map(|x| LoopVariable {
+                variable: x.variable.clone(),
+                value: x.value,
+            })
+            .collect(),
+        ..Default::default()
+    };
+    gen.visit_statement(s);
+    gen.to_string().trim().to_string()
+}
+
 impl VerilogVisitor for VerilogCodeGenerator {
     fn visit_block(&mut self, b: &VerilogBlock) {
         self.io.writeln("begin");
@@ -226,7 +385,17 @@ impl VerilogVisitor for VerilogCodeGenerator {
     fn visit_assignment(&mut self, l: &VerilogExpression, r: &VerilogExpression) {
         self.visit_expression(l);
         self.io.write(" = ");
-        self.visit_expression(r);
+        let cast = match l {
+            VerilogExpression::Signal(x) => self.casts.get(&self.ident_fixup(x)).cloned(),
+            _ => None,
+        };
+        if let Some(kind) = cast {
+            self.io.write(format!("{}'(", kind));
+            self.visit_expression(r);
+            self.io.write(")");
+        } else {
+            self.visit_expression(r);
+        }
         self.io.writeln(";");
     }
 
@@ -285,6 +454,17 @@ impl VerilogVisitor for VerilogCodeGenerator {
         self.visit_expression(ndx);
         self.io.write(")))");
     }
+
+    fn visit_call(&mut self, f: &VerilogFunction, args: &[VerilogExpression]) {
+        self.io.write(format!("{}(", f.name));
+        for (ndx, arg) in args.iter().enumerate() {
+            if ndx > 0 {
+                self.io.write(", ");
+            }
+            self.visit_expression(arg);
+        }
+        self.io.write(")");
+    }
 }
 
 #[test]
@@ -1,7 +1,7 @@
 use crate::ast::{
     VerilogBlock, VerilogBlockOrConditional, VerilogCase, VerilogConditional, VerilogExpression,
-    VerilogIndexAssignment, VerilogLink, VerilogLiteral, VerilogLoop, VerilogMatch, VerilogOp,
-    VerilogOpUnary, VerilogStatement,
+    VerilogFunction, VerilogIndexAssignment, VerilogLink, VerilogLiteral, VerilogLocal,
+    VerilogLoop, VerilogMatch, VerilogOp, VerilogOpUnary, VerilogStatement,
 };
 
 pub trait VerilogVisitor {
@@ -59,6 +59,10 @@ pub trait VerilogVisitor {
         // Terminal
     }
 
+    fn visit_local(&mut self, _l: &VerilogLocal) {
+        // Terminal
+    }
+
     fn visit_case(&mut self, c: &VerilogCase) {
         walk_case(self, c);
     }
@@ -125,6 +129,10 @@ pub trait VerilogVisitor {
     ) {
         walk_index_replacement(self, a, b, c);
     }
+
+    fn visit_call(&mut self, f: &VerilogFunction, args: &[VerilogExpression]) {
+        walk_call(self, f, args);
+    }
 }
 
 pub fn walk_index_replacement<V: VerilogVisitor + ?Sized>(
@@ -138,6 +146,16 @@ pub fn walk_index_replacement<V: VerilogVisitor + ?Sized>(
     visitor.visit_expression(c);
 }
 
+pub fn walk_call<V: VerilogVisitor + ?Sized>(
+    visitor: &mut V,
+    _f: &VerilogFunction,
+    args: &[VerilogExpression],
+) {
+    for arg in args {
+        visitor.visit_expression(arg);
+    }
+}
+
 pub fn walk_slice<V: VerilogVisitor + ?Sized>(
     visitor: &mut V,
     a: &VerilogExpression,
@@ -251,6 +269,9 @@ pub fn walk_statement<V: VerilogVisitor + ?Sized>(visitor: &mut V, s: &VerilogSt
                 visitor.visit_statement(statement);
             }
         }
+        VerilogStatement::Local(l) => {
+            visitor.visit_local(l);
+        }
     }
 }
 
@@ -354,5 +375,8 @@ pub fn walk_expression<V: VerilogVisitor + ?Sized>(visitor: &mut V, e: &VerilogE
         VerilogExpression::Unsigned(a) => {
             visitor.visit_unsigned(a);
         }
+        VerilogExpression::Call(f, args) => {
+            visitor.visit_call(f, args);
+        }
     }
 }
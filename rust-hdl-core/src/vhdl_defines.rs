@@ -0,0 +1,381 @@
+use std::collections::BTreeMap;
+
+use crate::ast::Verilog;
+use crate::atom::AtomKind;
+use crate::block::Block;
+use crate::check_error::{check_all, GenerateError};
+use crate::code_writer::CodeWriter;
+use crate::module_defines::ModuleDefines;
+use crate::module_details::{
+    get_link_equivalence, link_assignment, AtomDetails, EnumDefinition, ModuleDetails,
+};
+use crate::type_descriptor::{enum_width, EnumLabel};
+use crate::verilog_gen::verilog_functions;
+use crate::vhdl_gen::{
+    is_sequential, vhdl_combinatorial, vhdl_function, vhdl_ident, vhdl_package, vhdl_value,
+    VhdlType,
+};
+
+// The labels of each enum, ordered by their encoding
+fn enum_labels<'a>(
+    defs: impl Iterator<Item = &'a EnumDefinition>,
+) -> BTreeMap<String, Vec<EnumLabel>> {
+    let mut enums: BTreeMap<String, Vec<EnumLabel>> = BTreeMap::new();
+    for def in defs {
+        let labels = enums.entry(def.type_name.clone()).or_default();
+        let label = EnumLabel {
+            name: def.discriminant.clone(),
+            value: def.value,
+        };
+        if !labels.contains(&label) {
+            labels.push(label);
+        }
+    }
+    enums
+        .values_mut()
+        .for_each(|x| x.sort_by_key(|label| label.value));
+    enums
+}
+
+fn port_mode(x: &AtomKind) -> &str {
+    match x {
+        AtomKind::InputParameter => "in",
+        AtomKind::InOutParameter => "inout",
+        _ => "out",
+    }
+}
+
+fn port_declarations(module_details: &ModuleDetails) -> Vec<String> {
+    module_details
+        .atoms
+        .iter()
+        .filter(|x| x.kind.is_parameter())
+        .map(|x| {
+            format!(
+                "{} : {} {}",
+                vhdl_ident(&x.name),
+                port_mode(&x.kind),
+                VhdlType::from_descriptor(&x.descriptor).declaration()
+            )
+        })
+        .collect()
+}
+
+fn signal_decl(x: &AtomDetails) -> String {
+    format!(
+        "signal {} : {};",
+        vhdl_ident(&x.name),
+        VhdlType::from_descriptor(&x.descriptor).declaration()
+    )
+}
+
+fn constant_decl(x: &AtomDetails) -> String {
+    format!(
+        "constant {} : {} := {};",
+        vhdl_ident(&x.name),
+        VhdlType::from_descriptor(&x.descriptor).declaration(),
+        vhdl_value(&x.descriptor, &x.const_val)
+    )
+}
+
+impl ModuleDefines {
+    // Modules without VHDL (blackboxes, and custom or wrapped Verilog that
+    // does not provide a `vhdl` body) are instantiated as components.
+    fn is_vhdl_component(module_details: &ModuleDetails) -> bool {
+        match &module_details.code {
+            Verilog::Blackbox(_) => true,
+            Verilog::Custom(_) | Verilog::Wrapper(_) => module_details.vhdl.is_none(),
+            _ => false,
+        }
+    }
+
+    fn vhdl_component_name(&self, module_name: &str, names: &BTreeMap<String, String>) -> String {
+        let entry = self.details.get(module_name).unwrap();
+        match &entry.code {
+            Verilog::Blackbox(b) => b.name.clone(),
+            _ => names
+                .get(module_name)
+                .cloned()
+                .unwrap_or_else(|| module_name.to_string()),
+        }
+    }
+
+    fn vhdl_enum_types(&self, module_details: &ModuleDetails) -> BTreeMap<String, VhdlType> {
+        let mut types = BTreeMap::new();
+        for (name, labels) in enum_labels(module_details.enums.iter()) {
+            // Enums with an encoding of their own are vectors, and their
+            // labels are vector constants
+            let kind = if is_sequential(&labels) {
+                VhdlType::Enum(name)
+            } else {
+                VhdlType::Vector(enum_width(&labels))
+            };
+            for label in labels {
+                types.insert(label.name.replace("::", "$"), kind.clone());
+            }
+        }
+        types
+    }
+
+    fn vhdl_types(&self, module_details: &ModuleDetails) -> BTreeMap<String, VhdlType> {
+        let mut types = self.vhdl_enum_types(module_details);
+        for atom in &module_details.atoms {
+            types.insert(
+                atom.name.clone(),
+                VhdlType::from_descriptor(&atom.descriptor),
+            );
+        }
+        types
+    }
+
+    fn vhdl_entity(&self, module_name: &str, module_details: &ModuleDetails, io: &mut CodeWriter) {
+        io.add("");
+        io.add("library ieee;");
+        io.add("use ieee.std_logic_1164.all;");
+        io.add("use ieee.numeric_std.all;");
+        io.add("use work.rust_hdl_pkg.all;");
+        io.add("");
+        io.add(format!("entity {} is", vhdl_ident(module_name)));
+        let ports = port_declarations(module_details);
+        if !ports.is_empty() {
+            io.push();
+            io.add("port (");
+            io.push();
+            io.add(ports.join(";\n"));
+            io.pop();
+            io.add(");");
+            io.pop();
+        }
+        io.add("end entity;");
+    }
+
+    fn vhdl_component(&self, module_name: &str, names: &BTreeMap<String, String>) -> String {
+        let entry = self.details.get(module_name).unwrap();
+        let mut io = CodeWriter::default();
+        io.add(format!(
+            "component {} is",
+            vhdl_ident(&self.vhdl_component_name(module_name, names))
+        ));
+        let ports = port_declarations(entry);
+        if !ports.is_empty() {
+            io.push();
+            io.add("port (");
+            io.push();
+            io.add(ports.join(";\n"));
+            io.pop();
+            io.add(");");
+            io.pop();
+        }
+        io.add("end component;");
+        io.to_string()
+    }
+
+    fn vhdl_instance(
+        &self,
+        module_details: &ModuleDetails,
+        child_kind: &str,
+        child_name: &str,
+        names: &BTreeMap<String, String>,
+        io: &mut CodeWriter,
+    ) {
+        let entry = self.details.get(child_kind).unwrap();
+        let port_map = entry
+            .atoms
+            .iter()
+            .filter(|x| x.kind.is_parameter())
+            .map(|x| {
+                let arg_name = format!("{}${}", child_name, x.name);
+                let arg_name = if module_details.stub_is_linked_to_module_argument(&arg_name) {
+                    module_details.get_linked_argument_name(&arg_name)
+                } else {
+                    arg_name
+                };
+                format!("{} => {}", vhdl_ident(&x.name), vhdl_ident(&arg_name))
+            })
+            .collect::<Vec<_>>();
+        let unit = if Self::is_vhdl_component(entry) {
+            vhdl_ident(&self.vhdl_component_name(child_kind, names))
+        } else {
+            format!(
+                "entity work.{}",
+                vhdl_ident(&self.vhdl_component_name(child_kind, names))
+            )
+        };
+        if port_map.is_empty() {
+            io.add(format!("{} : {};", vhdl_ident(child_name), unit));
+        } else {
+            io.add(format!("{} : {}", vhdl_ident(child_name), unit));
+            io.push();
+            io.add("port map (");
+            io.push();
+            io.add(port_map.join(",\n"));
+            io.pop();
+            io.add(");");
+            io.pop();
+        }
+    }
+
+    fn vhdl_links(&self, module_details: &ModuleDetails, io: &mut CodeWriter) {
+        for x in &module_details.links {
+            let equiv = get_link_equivalence(x);
+            if module_details.signal_name_is_module_argument(&equiv.0)
+                || module_details.signal_name_is_module_argument(&equiv.1)
+            {
+                continue;
+            }
+            let (target, source) = link_assignment(x);
+            io.add(format!(
+                "{} <= {};",
+                vhdl_ident(&target),
+                vhdl_ident(&source)
+            ));
+        }
+    }
+
+    fn vhdl_architecture(
+        &self,
+        module_name: &str,
+        module_details: &ModuleDetails,
+        names: &BTreeMap<String, String>,
+        io: &mut CodeWriter,
+    ) {
+        io.add("");
+        io.add(format!(
+            "architecture rtl of {} is",
+            vhdl_ident(module_name)
+        ));
+        if let Some(body) = &module_details.vhdl {
+            io.push();
+            io.add(body);
+            io.pop();
+            io.add("end architecture;");
+            return;
+        }
+        io.push();
+        let consts = module_details
+            .atoms
+            .iter()
+            .filter(|x| x.kind == AtomKind::Constant)
+            .collect::<Vec<_>>();
+        if !consts.is_empty() {
+            io.add("-- Constant declarations");
+            consts.iter().for_each(|x| io.add(constant_decl(x)));
+        }
+        let stubs = module_details
+            .atoms
+            .iter()
+            .filter(|x| x.kind.is_stub())
+            .filter(|x| !module_details.stub_is_linked_to_module_argument(&x.name))
+            .collect::<Vec<_>>();
+        if !stubs.is_empty() {
+            io.add("-- Stub signals");
+            stubs.iter().for_each(|x| io.add(signal_decl(x)));
+        }
+        let locals = module_details
+            .atoms
+            .iter()
+            .filter(|x| x.kind == AtomKind::LocalSignal)
+            .collect::<Vec<_>>();
+        if !locals.is_empty() {
+            io.add("-- Local signals");
+            locals.iter().for_each(|x| io.add(signal_decl(x)));
+        }
+        let mut components = vec![];
+        for child in &module_details.sub_modules {
+            let entry = self.details.get(&child.kind).unwrap();
+            if Self::is_vhdl_component(entry) {
+                let component = self.vhdl_component(&child.kind, names);
+                if !components.contains(&component) {
+                    components.push(component);
+                }
+            }
+        }
+        if !components.is_empty() {
+            io.add("-- Components");
+            components.iter().for_each(|x| io.add(x));
+        }
+        if let Verilog::Combinatorial(code) = &module_details.code {
+            let functions = verilog_functions(code);
+            if !functions.is_empty() {
+                let enums = self.vhdl_enum_types(module_details);
+                io.add("-- Functions");
+                functions
+                    .iter()
+                    .for_each(|x| io.add(vhdl_function(x, &enums)));
+            }
+        }
+        io.pop();
+        io.add("begin");
+        io.push();
+        if !module_details.sub_modules.is_empty() {
+            io.add("-- Sub module instances");
+            for child in &module_details.sub_modules {
+                self.vhdl_instance(module_details, &child.kind, &child.name, names, io);
+            }
+        }
+        if let Verilog::Combinatorial(code) = &module_details.code {
+            io.add("-- Update code");
+            io.add(vhdl_combinatorial(code, &self.vhdl_types(module_details)));
+        }
+        self.vhdl_links(module_details, io);
+        io.pop();
+        io.add("end architecture;");
+    }
+
+    /// Emit the design as a VHDL package (holding the enums of the design),
+    /// followed by an entity/architecture pair for each unique module.
+    pub fn vhdl_defines(&self) -> String {
+        let names = self.module_names();
+        let enums = enum_labels(self.details.values().flat_map(|x| &x.enums));
+        let mut io = CodeWriter::default();
+        io.add(vhdl_package(&enums));
+        let mut foreign = vec![];
+        self.details
+            .iter()
+            .filter(|x| !x.0.is_empty())
+            .filter(|x| names.get(x.0) == Some(x.0))
+            .for_each(|(module_name, module_details)| {
+                if Self::is_vhdl_component(module_details) {
+                    foreign.push(self.vhdl_component_name(module_name, &names));
+                } else {
+                    self.vhdl_entity(module_name, module_details, &mut io);
+                    self.vhdl_architecture(module_name, module_details, &names, &mut io);
+                }
+            });
+        if !foreign.is_empty() {
+            io.add("");
+            io.add("-- The following modules have no VHDL translation, and must be");
+            io.add("-- supplied from the Verilog generated by generate_verilog:");
+            foreign.iter().for_each(|x| io.add(format!("--   {}", x)));
+        }
+        io.to_string()
+    }
+}
+
+/// Generate VHDL for a circuit.  The output contains a `rust_hdl_pkg`
+/// package with the `LogicState` enums of the design (as enumerated types),
+/// and an entity/architecture pair for each unique module.  Blocks that use
+/// custom or wrapped Verilog (like `DFF`) are translated only if they provide
+/// a [Logic::vhdl](crate::logic::Logic::vhdl) body.  Otherwise, they (and any
+/// [BlackBox](crate::ast::BlackBox) cores) are instantiated as components,
+/// and the corresponding Verilog must be added to a mixed-language project.
+/// Panics if the circuit fails [check_all].  Use [try_generate_vhdl] to get
+/// the error instead.
+pub fn generate_vhdl<U: Block>(uut: &U) -> String {
+    try_generate_vhdl(uut).unwrap_or_else(|e| panic!("{}", e))
+}
+
+/// Generate VHDL for a circuit (as in [generate_vhdl]), or return a
+/// [GenerateError] if the circuit fails [check_all].
+pub fn try_generate_vhdl<U: Block>(uut: &U) -> Result<String, GenerateError> {
+    check_all(uut)?;
+    let mut defines = ModuleDefines::default();
+    uut.accept("top", &mut defines);
+    Ok(defines.vhdl_defines())
+}
+
+pub fn generate_vhdl_unchecked<U: Block>(uut: &U) -> String {
+    let mut defines = ModuleDefines::default();
+    uut.accept("top", &mut defines);
+    defines.vhdl_defines()
+}
@@ -0,0 +1,965 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{Display, Formatter};
+
+use crate::ast::{
+    VerilogBlock, VerilogBlockOrConditional, VerilogConditional, VerilogExpression,
+    VerilogFunction, VerilogLiteral, VerilogLoop, VerilogMatch, VerilogOp, VerilogOpUnary,
+    VerilogStatement,
+};
+use crate::bits::clog2;
+use crate::code_writer::CodeWriter;
+use crate::type_descriptor::{enum_width, EnumLabel, TypeDescriptor, TypeKind};
+use crate::verilog_gen::{ident_fixup, verilog_locals, LoopVariable};
+use crate::verilog_visitor::{walk_block, VerilogVisitor};
+
+const VHDL_RESERVED: &[&str] = &[
+    "abs",
+    "access",
+    "after",
+    "alias",
+    "all",
+    "and",
+    "architecture",
+    "array",
+    "assert",
+    "assume",
+    "attribute",
+    "begin",
+    "block",
+    "body",
+    "buffer",
+    "bus",
+    "case",
+    "component",
+    "configuration",
+    "constant",
+    "context",
+    "cover",
+    "default",
+    "disconnect",
+    "downto",
+    "else",
+    "elsif",
+    "end",
+    "entity",
+    "exit",
+    "fairness",
+    "file",
+    "for",
+    "force",
+    "function",
+    "generate",
+    "generic",
+    "group",
+    "guarded",
+    "if",
+    "impure",
+    "in",
+    "inertial",
+    "inout",
+    "is",
+    "label",
+    "library",
+    "linkage",
+    "literal",
+    "loop",
+    "map",
+    "mod",
+    "nand",
+    "new",
+    "next",
+    "nor",
+    "not",
+    "null",
+    "of",
+    "on",
+    "open",
+    "or",
+    "others",
+    "out",
+    "package",
+    "parameter",
+    "port",
+    "postponed",
+    "procedure",
+    "process",
+    "property",
+    "protected",
+    "pure",
+    "range",
+    "record",
+    "register",
+    "reject",
+    "release",
+    "rem",
+    "report",
+    "restrict",
+    "return",
+    "rol",
+    "ror",
+    "select",
+    "sequence",
+    "severity",
+    "shared",
+    "signal",
+    "sla",
+    "sll",
+    "sra",
+    "srl",
+    "strong",
+    "subtype",
+    "then",
+    "to",
+    "transport",
+    "type",
+    "unaffected",
+    "units",
+    "until",
+    "use",
+    "variable",
+    "vmode",
+    "vprop",
+    "vunit",
+    "wait",
+    "when",
+    "while",
+    "with",
+    "xnor",
+    "xor",
+];
+
+/// Map a flattened signal or module name (like `top$knot_1`) to a legal VHDL
+/// identifier.  Names that are not valid basic identifiers are escaped as
+/// VHDL-93 extended identifiers (i.e., `\top$knot_1\`).
+pub fn vhdl_ident(name: &str) -> String {
+    let basic = name
+        .chars()
+        .next()
+        .map(|c| c.is_ascii_alphabetic())
+        .unwrap_or(false)
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !name.contains("__")
+        && !name.ends_with('_')
+        && !VHDL_RESERVED.contains(&name.to_lowercase().as_str());
+    if basic {
+        name.to_string()
+    } else {
+        format!("\\{}\\", name.replace('\\', "\\\\"))
+    }
+}
+
+/// Enum type names are always escaped, since extended identifiers cannot
+/// collide with the (case insensitive) basic identifiers used for signals.
+pub(crate) fn vhdl_type_name(name: &str) -> String {
+    format!("\\{}\\", name.replace('\\', "\\\\"))
+}
+
+/// Enums with the default encoding (where the labels are numbered in order)
+/// are VHDL enumerated types.  Any other encoding is kept as a bit vector,
+/// since the representation of an enumerated type is up to the synthesizer,
+/// and its labels are `std_logic_vector` constants.
+pub(crate) fn is_sequential(labels: &[EnumLabel]) -> bool {
+    labels
+        .iter()
+        .enumerate()
+        .all(|(ndx, label)| label.value == ndx as u128)
+}
+
+fn descriptor_width(descriptor: &TypeDescriptor) -> usize {
+    match &descriptor.kind {
+        TypeKind::Bits(n) => *n,
+        TypeKind::Signed(n) => *n,
+        TypeKind::Enum(labels) => clog2(labels.len()),
+        TypeKind::EncodedEnum(labels) => enum_width(labels),
+        TypeKind::Composite(fields) => fields.iter().map(|x| descriptor_width(&x.kind)).sum(),
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum VhdlType {
+    Unsigned(usize),
+    Signed(usize),
+    Enum(String),
+    Integer,
+    // The labels of an enum with its own encoding
+    Vector(usize),
+}
+
+impl VhdlType {
+    pub(crate) fn from_descriptor(descriptor: &TypeDescriptor) -> VhdlType {
+        match &descriptor.kind {
+            TypeKind::Signed(n) => VhdlType::Signed(*n),
+            TypeKind::Enum(_) => VhdlType::Enum(descriptor.name.clone()),
+            _ => VhdlType::Unsigned(descriptor_width(descriptor)),
+        }
+    }
+
+    pub(crate) fn declaration(&self) -> String {
+        match self {
+            VhdlType::Unsigned(n) => format!("unsigned({} downto 0)", (*n).max(1) - 1),
+            VhdlType::Signed(n) => format!("signed({} downto 0)", (*n).max(1) - 1),
+            VhdlType::Enum(name) => vhdl_type_name(name),
+            VhdlType::Integer => "integer".into(),
+            VhdlType::Vector(n) => format!("std_logic_vector({} downto 0)", (*n).max(1) - 1),
+        }
+    }
+
+    // Function results cannot be constrained, so they are declared by type mark
+    fn type_mark(&self) -> String {
+        match self {
+            VhdlType::Unsigned(_) => "unsigned".into(),
+            VhdlType::Signed(_) => "signed".into(),
+            VhdlType::Enum(name) => vhdl_type_name(name),
+            VhdlType::Integer => "integer".into(),
+            VhdlType::Vector(_) => "std_logic_vector".into(),
+        }
+    }
+
+    fn width(&self) -> usize {
+        match self {
+            VhdlType::Unsigned(n) | VhdlType::Signed(n) | VhdlType::Vector(n) => *n,
+            _ => 0,
+        }
+    }
+}
+
+/// The VHDL type used to represent a [Synth](crate::synth::Synth) type with
+/// the given descriptor.  Bit vectors (including single bits and clocks) map
+/// to `unsigned`, [Signed](crate::signed::Signed) maps to `signed`, and
+/// `LogicState` enums map to the enumerated types declared in the generated
+/// `rust_hdl_pkg` package.
+pub fn vhdl_type(descriptor: &TypeDescriptor) -> String {
+    VhdlType::from_descriptor(descriptor).declaration()
+}
+
+/// A VHDL literal for the given value of a type with the given descriptor.
+pub fn vhdl_value(descriptor: &TypeDescriptor, value: &VerilogLiteral) -> String {
+    match &descriptor.kind {
+        TypeKind::Enum(labels) => vhdl_ident(&labels[value.as_usize()].replace("::", "$")),
+        TypeKind::EncodedEnum(labels) => format!(
+            "unsigned'(\"{:0width$b}\")",
+            value.as_u128(),
+            width = enum_width(labels)
+        ),
+        TypeKind::Signed(_) => format!("signed'(\"{}\")", value.to_binary_string()),
+        _ => format!("unsigned'(\"{}\")", value.to_binary_string()),
+    }
+}
+
+fn convert(expr: String, from: &VhdlType, to: &VhdlType) -> String {
+    match (from, to) {
+        (a, b) if a == b => expr,
+        (VhdlType::Integer, VhdlType::Unsigned(n)) => format!("to_unsigned({}, {})", expr, n),
+        (VhdlType::Integer, VhdlType::Signed(n)) => format!("to_signed({}, {})", expr, n),
+        (VhdlType::Unsigned(_), VhdlType::Unsigned(n))
+        | (VhdlType::Signed(_), VhdlType::Signed(n)) => format!("resize({}, {})", expr, n),
+        (VhdlType::Unsigned(m), VhdlType::Signed(n)) => {
+            if m == n {
+                format!("signed({})", expr)
+            } else {
+                format!("signed(resize({}, {}))", expr, n)
+            }
+        }
+        (VhdlType::Signed(m), VhdlType::Unsigned(n)) => {
+            if m == n {
+                format!("unsigned({})", expr)
+            } else {
+                format!("unsigned(resize({}, {}))", expr, n)
+            }
+        }
+        (VhdlType::Unsigned(_), VhdlType::Integer) | (VhdlType::Signed(_), VhdlType::Integer) => {
+            format!("to_integer({})", expr)
+        }
+        (VhdlType::Enum(t), VhdlType::Unsigned(n)) => {
+            format!("to_unsigned({}'pos({}), {})", vhdl_type_name(t), expr, n)
+        }
+        (VhdlType::Unsigned(_), VhdlType::Enum(t)) => {
+            format!("{}'val(to_integer({}))", vhdl_type_name(t), expr)
+        }
+        (VhdlType::Integer, VhdlType::Enum(t)) => format!("{}'val({})", vhdl_type_name(t), expr),
+        _ => expr,
+    }
+}
+
+fn as_unsigned(expr: String, kind: &VhdlType) -> String {
+    match kind {
+        VhdlType::Signed(_) => format!("unsigned({})", expr),
+        VhdlType::Integer => format!("to_unsigned({}, 32)", expr),
+        VhdlType::Enum(_) => convert(expr, kind, &VhdlType::Unsigned(32)),
+        VhdlType::Vector(_) => format!("unsigned({})", expr),
+        VhdlType::Unsigned(_) => expr,
+    }
+}
+
+fn as_integer(expr: String, kind: &VhdlType) -> String {
+    match kind {
+        VhdlType::Integer => expr,
+        VhdlType::Enum(t) => format!("{}'pos({})", vhdl_type_name(t), expr),
+        _ => format!("to_integer({})", expr),
+    }
+}
+
+fn common_type(l: &VhdlType, r: &VhdlType) -> VhdlType {
+    match (l, r) {
+        (VhdlType::Integer, x) | (x, VhdlType::Integer) => x.clone(),
+        (VhdlType::Enum(_), _) => l.clone(),
+        (_, VhdlType::Enum(_)) => r.clone(),
+        (VhdlType::Signed(_), _) | (_, VhdlType::Signed(_)) => {
+            VhdlType::Signed(l.width().max(r.width()))
+        }
+        _ => VhdlType::Unsigned(l.width().max(r.width())),
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct VhdlVariable {
+    pub(crate) signal: String,
+    pub(crate) kind: VhdlType,
+}
+
+/// Translates the `Verilog::Combinatorial` AST of a module into the body of a
+/// VHDL combinational process.  Signals written by the process are shadowed
+/// by variables, so that reads after writes see the new value, as they do
+/// with blocking assignments in the `always @(*)` block of the Verilog output.
+pub(crate) struct VHDLCodeGenerator<'a> {
+    io: CodeWriter,
+    loops: Vec<LoopVariable>,
+    types: &'a BTreeMap<String, VhdlType>,
+    written: BTreeSet<String>,
+}
+
+impl<'a> VHDLCodeGenerator<'a> {
+    pub(crate) fn new(types: &'a BTreeMap<String, VhdlType>) -> Self {
+        Self {
+            io: CodeWriter::default(),
+            loops: vec![],
+            types,
+            written: Default::default(),
+        }
+    }
+
+    fn variable_name(name: &str) -> String {
+        vhdl_ident(&format!("{}$next", name))
+    }
+
+    fn kind_of(&self, name: &str) -> VhdlType {
+        self.types.get(name).cloned().unwrap_or(VhdlType::Integer)
+    }
+
+    fn signal(&self, sig: &str) -> (String, VhdlType) {
+        let name = ident_fixup(sig, &self.loops);
+        if name.parse::<i64>().is_ok() {
+            return (name, VhdlType::Integer);
+        }
+        let kind = self.kind_of(&name);
+        if let VhdlType::Vector(n) = kind {
+            return (
+                format!("unsigned({})", vhdl_ident(&name)),
+                VhdlType::Unsigned(n),
+            );
+        }
+        if self.written.contains(&name) {
+            (Self::variable_name(&name), kind)
+        } else {
+            (vhdl_ident(&name), kind)
+        }
+    }
+
+    fn expression(&self, e: &VerilogExpression) -> (String, VhdlType) {
+        match e {
+            VerilogExpression::Signal(s) => self.signal(s),
+            VerilogExpression::Literal(l) => (
+                format!("unsigned'(\"{}\")", l.to_binary_string()),
+                VhdlType::Unsigned(l.bits()),
+            ),
+            VerilogExpression::Cast(a, bits) => {
+                let (a, kind) = self.expression(a);
+                (
+                    format!("resize({}, {})", as_unsigned(a, &kind), bits),
+                    VhdlType::Unsigned(*bits),
+                )
+            }
+            VerilogExpression::Signed(a) => {
+                let (a, kind) = self.expression(a);
+                match kind {
+                    VhdlType::Unsigned(n) => (format!("signed({})", a), VhdlType::Signed(n)),
+                    _ => (a, kind),
+                }
+            }
+            VerilogExpression::Unsigned(a) => {
+                let (a, kind) = self.expression(a);
+                match kind {
+                    VhdlType::Signed(n) => (format!("unsigned({})", a), VhdlType::Unsigned(n)),
+                    _ => (a, kind),
+                }
+            }
+            VerilogExpression::Paren(a) => {
+                let (a, kind) = self.expression(a);
+                (format!("({})", a), kind)
+            }
+            VerilogExpression::Binary(l, op, r) => self.binop(l, op, r),
+            VerilogExpression::Unary(op, a) => self.unop(op, a),
+            VerilogExpression::Index(a, b) => {
+                let (a, akind) = self.expression(a);
+                let (b, bkind) = self.expression(b);
+                (
+                    format!(
+                        "rhdl_index({}, {})",
+                        as_unsigned(a, &akind),
+                        as_integer(b, &bkind)
+                    ),
+                    VhdlType::Unsigned(1),
+                )
+            }
+            VerilogExpression::Slice(a, width, offset) => {
+                let (a, akind) = self.expression(a);
+                let (offset, okind) = self.expression(offset);
+                (
+                    format!(
+                        "resize(shift_right({}, {}), {})",
+                        as_unsigned(a, &akind),
+                        as_integer(offset, &okind),
+                        width
+                    ),
+                    VhdlType::Unsigned(*width),
+                )
+            }
+            VerilogExpression::IndexReplace(a, b, c) => {
+                let (a, akind) = self.expression(a);
+                let (b, bkind) = self.expression(b);
+                let (c, ckind) = self.expression(c);
+                let replaced = format!(
+                    "rhdl_replace({}, {}, {})",
+                    as_unsigned(a, &akind),
+                    as_integer(b, &bkind),
+                    convert(c, &ckind, &VhdlType::Unsigned(1))
+                );
+                match akind {
+                    VhdlType::Signed(n) => (format!("signed({})", replaced), VhdlType::Signed(n)),
+                    _ => (replaced, VhdlType::Unsigned(akind.width())),
+                }
+            }
+            VerilogExpression::Call(f, args) => {
+                let args = f
+                    .args
+                    .iter()
+                    .zip(args)
+                    .map(|(param, arg)| {
+                        let (arg, kind) = self.expression(arg);
+                        convert(arg, &kind, &VhdlType::from_descriptor(&param.descriptor))
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                (
+                    format!("{}({})", vhdl_ident(&f.name), args),
+                    VhdlType::from_descriptor(&f.result.descriptor),
+                )
+            }
+        }
+    }
+
+    fn binop(
+        &self,
+        l: &VerilogExpression,
+        op: &VerilogOp,
+        r: &VerilogExpression,
+    ) -> (String, VhdlType) {
+        let (ls, lt) = self.expression(l);
+        let (rs, rt) = self.expression(r);
+        let arithmetic = |sym: &str| {
+            let target = common_type(&lt, &rt);
+            (
+                format!(
+                    "({} {} {})",
+                    convert(ls.clone(), &lt, &target),
+                    sym,
+                    convert(rs.clone(), &rt, &target)
+                ),
+                target,
+            )
+        };
+        let compare = |sym: &str| {
+            let (ls, rs) = match (&lt, &rt) {
+                (VhdlType::Enum(_), VhdlType::Unsigned(_)) => {
+                    (ls.clone(), convert(rs.clone(), &rt, &lt))
+                }
+                (VhdlType::Unsigned(_), VhdlType::Enum(_)) => {
+                    (convert(ls.clone(), &lt, &rt), rs.clone())
+                }
+                (VhdlType::Unsigned(_), VhdlType::Signed(_)) => {
+                    (convert(ls.clone(), &lt, &rt), rs.clone())
+                }
+                (VhdlType::Signed(_), VhdlType::Unsigned(_)) => {
+                    (ls.clone(), convert(rs.clone(), &rt, &lt))
+                }
+                _ => (ls.clone(), rs.clone()),
+            };
+            (
+                format!("rhdl_vec({} {} {})", ls, sym, rs),
+                VhdlType::Unsigned(1),
+            )
+        };
+        let logical = |sym: &str| {
+            (
+                format!(
+                    "rhdl_vec({} {} {})",
+                    condition(ls.clone(), &lt),
+                    sym,
+                    condition(rs.clone(), &rt)
+                ),
+                VhdlType::Unsigned(1),
+            )
+        };
+        let shift = |func: &str| {
+            (
+                format!("{}({}, {})", func, ls.clone(), as_integer(rs.clone(), &rt)),
+                lt.clone(),
+            )
+        };
+        match op {
+            VerilogOp::Add => arithmetic("+"),
+            VerilogOp::Sub => arithmetic("-"),
+            VerilogOp::BitXor => arithmetic("xor"),
+            VerilogOp::BitAnd => arithmetic("and"),
+            VerilogOp::BitOr => arithmetic("or"),
+            VerilogOp::Mul => {
+                let target = common_type(&lt, &rt);
+                let product = match &target {
+                    VhdlType::Signed(n) => VhdlType::Signed(2 * n),
+                    VhdlType::Unsigned(n) => VhdlType::Unsigned(2 * n),
+                    _ => target.clone(),
+                };
+                (
+                    format!(
+                        "({} * {})",
+                        convert(ls.clone(), &lt, &target),
+                        convert(rs.clone(), &rt, &target)
+                    ),
+                    product,
+                )
+            }
+            VerilogOp::LogicalAnd => logical("and"),
+            VerilogOp::LogicalOr => logical("or"),
+            VerilogOp::Shl => shift("shift_left"),
+            VerilogOp::Shr => shift("shift_right"),
+            VerilogOp::Eq => compare("="),
+            VerilogOp::Lt => compare("<"),
+            VerilogOp::Le => compare("<="),
+            VerilogOp::Ne => compare("/="),
+            VerilogOp::Ge => compare(">="),
+            VerilogOp::Gt => compare(">"),
+        }
+    }
+
+    fn unop(&self, op: &VerilogOpUnary, a: &VerilogExpression) -> (String, VhdlType) {
+        let (a, kind) = self.expression(a);
+        match op {
+            VerilogOpUnary::Not => (format!("(not {})", a), kind),
+            VerilogOpUnary::Neg => match kind {
+                VhdlType::Unsigned(_) => (format!("(0 - {})", a), kind),
+                _ => (format!("(-{})", a), kind),
+            },
+            VerilogOpUnary::All => (
+                format!("rhdl_all({})", as_unsigned(a, &kind)),
+                VhdlType::Unsigned(1),
+            ),
+            VerilogOpUnary::Any => (
+                format!("rhdl_any({})", as_unsigned(a, &kind)),
+                VhdlType::Unsigned(1),
+            ),
+            VerilogOpUnary::Xor => (
+                format!("rhdl_xor({})", as_unsigned(a, &kind)),
+                VhdlType::Unsigned(1),
+            ),
+        }
+    }
+
+    fn target(&mut self, e: &VerilogExpression) -> Option<(String, VhdlType)> {
+        if let VerilogExpression::Signal(s) = e {
+            let name = ident_fixup(s, &self.loops);
+            let kind = self.kind_of(&name);
+            self.written.insert(name.clone());
+            Some((Self::variable_name(&name), kind))
+        } else {
+            None
+        }
+    }
+
+    fn conditional(&mut self, c: &VerilogConditional, keyword: &str) {
+        let (test, kind) = self.expression(&c.test);
+        self.io
+            .add(format!("{} {} then", keyword, condition(test, &kind)));
+        self.io.push();
+        self.visit_block(&c.then);
+        self.io.pop();
+        match &c.otherwise {
+            VerilogBlockOrConditional::Block(b) => {
+                self.io.add("else");
+                self.io.push();
+                self.visit_block(b);
+                self.io.pop();
+            }
+            VerilogBlockOrConditional::Conditional(s) => {
+                if let VerilogStatement::If(c) = s.as_ref() {
+                    self.conditional(c, "elsif");
+                    return;
+                }
+                self.io.add("else");
+                self.io.push();
+                self.visit_statement(s);
+                self.io.pop();
+            }
+            VerilogBlockOrConditional::None => {}
+        }
+        self.io.add("end if;");
+    }
+
+    /// The signals written by the process, which need variables declared
+    /// for them, and a final assignment back to the signal.
+    pub(crate) fn variables(&self) -> Vec<VhdlVariable> {
+        self.written
+            .iter()
+            .map(|x| VhdlVariable {
+                signal: x.clone(),
+                kind: self.kind_of(x),
+            })
+            .collect()
+    }
+}
+
+fn condition(expr: String, kind: &VhdlType) -> String {
+    match kind {
+        VhdlType::Integer => format!("({} /= 0)", expr),
+        VhdlType::Enum(t) => format!("({}'pos({}) /= 0)", vhdl_type_name(t), expr),
+        _ => format!("rhdl_bool({})", expr),
+    }
+}
+
+impl<'a> VerilogVisitor for VHDLCodeGenerator<'a> {
+    fn visit_block(&mut self, b: &VerilogBlock) {
+        walk_block(self, b);
+    }
+
+    fn visit_loop(&mut self, a: &VerilogLoop) {
+        let start = a.from.as_usize();
+        let end = a.to.as_usize();
+        for i in start..end {
+            self.loops.push(LoopVariable {
+                variable: a.index.clone(),
+                value: i,
+            });
+            walk_block(self, &a.block);
+            self.loops.pop();
+        }
+    }
+
+    fn visit_slice_assignment(
+        &mut self,
+        base: &VerilogExpression,
+        width: &usize,
+        offset: &VerilogExpression,
+        replacement: &VerilogExpression,
+    ) {
+        let (offset, okind) = self.expression(offset);
+        let (replacement, rkind) = self.expression(replacement);
+        let (current, _) = self.expression(base);
+        if let Some((target, kind)) = self.target(base) {
+            let replaced = format!(
+                "rhdl_replace_slice({}, {}, {}, {})",
+                as_unsigned(current, &kind),
+                as_integer(offset, &okind),
+                width,
+                convert(replacement, &rkind, &VhdlType::Unsigned(*width))
+            );
+            let replaced = convert(replaced, &VhdlType::Unsigned(kind.width()), &kind);
+            self.io.add(format!("{} := {};", target, replaced));
+        }
+    }
+
+    fn visit_conditional(&mut self, c: &VerilogConditional) {
+        self.conditional(c, "if");
+    }
+
+    fn visit_match(&mut self, m: &VerilogMatch) {
+        let (test, kind) = self.expression(&m.test);
+        let is_enum = matches!(kind, VhdlType::Enum(_));
+        // The labels of an enum with its own encoding are vectors, so the
+        // test is compared as one
+        let is_vector = m.cases.iter().any(|x| {
+            let name = ident_fixup(&x.condition, &self.loops);
+            matches!(self.types.get(&name), Some(VhdlType::Vector(_)))
+        });
+        if is_enum {
+            self.io.add(format!("case {} is", test));
+        } else if is_vector {
+            self.io.add(format!(
+                "case std_logic_vector({}) is",
+                as_unsigned(test, &kind)
+            ));
+        } else {
+            self.io.add(format!("case {} is", as_integer(test, &kind)));
+        }
+        self.io.push();
+        let mut has_default = false;
+        for case in &m.cases {
+            let choice = if case.condition == "default" {
+                has_default = true;
+                "others".to_string()
+            } else {
+                let name = ident_fixup(&case.condition, &self.loops);
+                match self.types.get(&name) {
+                    Some(VhdlType::Enum(t)) if !is_enum => {
+                        format!("{}'pos({})", vhdl_type_name(t), vhdl_ident(&name))
+                    }
+                    _ if is_enum => vhdl_ident(&name),
+                    Some(VhdlType::Vector(_)) => vhdl_ident(&name),
+                    _ => name,
+                }
+            };
+            self.io.add(format!("when {} =>", choice));
+            self.io.push();
+            self.visit_block(&case.block);
+            self.io.pop();
+        }
+        if !has_default {
+            self.io.add("when others =>");
+            self.io.push();
+            self.io.add("null;");
+            self.io.pop();
+        }
+        self.io.pop();
+        self.io.add("end case;");
+    }
+
+    fn visit_comment(&mut self, x: &str) {
+        self.io.add(format!("-- {}", x));
+    }
+
+    fn visit_assignment(&mut self, l: &VerilogExpression, r: &VerilogExpression) {
+        let (value, vkind) = self.expression(r);
+        if let Some((target, kind)) = self.target(l) {
+            self.io
+                .add(format!("{} := {};", target, convert(value, &vkind, &kind)));
+        }
+    }
+}
+
+impl<'a> Display for VHDLCodeGenerator<'a> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.io.to_string())
+    }
+}
+
+/// The helper package shared by all the entities generated by
+/// [generate_vhdl](crate::vhdl_defines::generate_vhdl).  It declares the
+/// enumerated types (or the label constants of enums with their own encoding)
+/// and the functions used to bridge Verilog's loose typing into VHDL.
+pub(crate) fn vhdl_package(enums: &BTreeMap<String, Vec<EnumLabel>>) -> String {
+    let mut io = CodeWriter::default();
+    io.add("library ieee;");
+    io.add("use ieee.std_logic_1164.all;");
+    io.add("use ieee.numeric_std.all;");
+    io.add("");
+    io.add("package rust_hdl_pkg is");
+    io.push();
+    for (name, labels) in enums {
+        if !is_sequential(labels) {
+            let width = enum_width(labels).max(1);
+            for label in labels {
+                io.add(format!(
+                    "constant {} : std_logic_vector({} downto 0) := \"{:0width$b}\";",
+                    vhdl_ident(&label.name.replace("::", "$")),
+                    width - 1,
+                    label.value,
+                    width = width
+                ));
+            }
+            continue;
+        }
+        let labels = labels
+            .iter()
+            .map(|x| vhdl_ident(&x.name.replace("::", "$")))
+            .collect::<Vec<_>>()
+            .join(", ");
+        io.add(format!("type {} is ({});", vhdl_type_name(name), labels));
+    }
+    io.add(
+        r#"function rhdl_bool(x : unsigned) return boolean;
+function rhdl_bool(x : signed) return boolean;
+function rhdl_vec(x : boolean) return unsigned;
+function rhdl_all(x : unsigned) return unsigned;
+function rhdl_any(x : unsigned) return unsigned;
+function rhdl_xor(x : unsigned) return unsigned;
+function rhdl_index(x : unsigned; i : natural) return unsigned;
+function rhdl_replace(x : unsigned; i : natural; v : unsigned) return unsigned;
+function rhdl_replace_slice(x : unsigned; offset : natural; width : natural; v : unsigned) return unsigned;"#,
+    );
+    io.pop();
+    io.add("end package;");
+    io.add("");
+    io.add(
+        r#"package body rust_hdl_pkg is
+    function rhdl_bool(x : unsigned) return boolean is
+    begin
+        return x /= 0;
+    end function;
+
+    function rhdl_bool(x : signed) return boolean is
+    begin
+        return x /= 0;
+    end function;
+
+    function rhdl_vec(x : boolean) return unsigned is
+        variable r : unsigned(0 downto 0) := "0";
+    begin
+        if x then
+            r := "1";
+        end if;
+        return r;
+    end function;
+
+    function rhdl_all(x : unsigned) return unsigned is
+        variable r : boolean := true;
+    begin
+        for i in x'range loop
+            r := r and (x(i) = '1');
+        end loop;
+        return rhdl_vec(r);
+    end function;
+
+    function rhdl_any(x : unsigned) return unsigned is
+        variable r : boolean := false;
+    begin
+        for i in x'range loop
+            r := r or (x(i) = '1');
+        end loop;
+        return rhdl_vec(r);
+    end function;
+
+    function rhdl_xor(x : unsigned) return unsigned is
+        variable r : std_logic := '0';
+    begin
+        for i in x'range loop
+            r := r xor x(i);
+        end loop;
+        return rhdl_vec(r = '1');
+    end function;
+
+    function rhdl_index(x : unsigned; i : natural) return unsigned is
+        variable xv : unsigned(x'length - 1 downto 0) := x;
+        variable r : unsigned(0 downto 0);
+    begin
+        r(0) := xv(i);
+        return r;
+    end function;
+
+    function rhdl_replace(x : unsigned; i : natural; v : unsigned) return unsigned is
+        variable r : unsigned(x'length - 1 downto 0) := x;
+    begin
+        r(i) := v(v'low);
+        return r;
+    end function;
+
+    function rhdl_replace_slice(x : unsigned; offset : natural; width : natural; v : unsigned) return unsigned is
+        variable r : unsigned(x'length - 1 downto 0) := x;
+    begin
+        r(offset + width - 1 downto offset) := resize(v, width);
+        return r;
+    end function;
+end package body;"#,
+    );
+    io.to_string()
+}
+
+/// Translate a combinational block into a VHDL process.
+pub(crate) fn vhdl_combinatorial(
+    code: &VerilogBlock,
+    types: &BTreeMap<String, VhdlType>,
+) -> String {
+    let mut gen = VHDLCodeGenerator::new(types);
+    gen.visit_block(code);
+    let mut io = CodeWriter::default();
+    io.add("process(all)");
+    io.push();
+    for var in gen.variables() {
+        io.add(format!(
+            "variable {} : {};",
+            VHDLCodeGenerator::variable_name(&var.signal),
+            var.kind.declaration()
+        ));
+    }
+    io.pop();
+    io.add("begin");
+    io.push();
+    io.add(gen.to_string());
+    for var in gen.variables() {
+        io.add(format!(
+            "{} <= {};",
+            vhdl_ident(&var.signal),
+            VHDLCodeGenerator::variable_name(&var.signal)
+        ));
+    }
+    io.pop();
+    io.add("end process;");
+    io.to_string()
+}
+
+/// Translate an `#[hdl_function]` into a VHDL function.  The `enums` map the
+/// discriminants of the enums in the design to their types.
+pub(crate) fn vhdl_function(f: &VerilogFunction, enums: &BTreeMap<String, VhdlType>) -> String {
+    let mut types = enums.clone();
+    for local in f
+        .args
+        .iter()
+        .chain(verilog_locals(&f.body).iter())
+        .chain(std::iter::once(&f.result))
+    {
+        types.insert(
+            local.name.clone(),
+            VhdlType::from_descriptor(&local.descriptor),
+        );
+    }
+    let mut gen = VHDLCodeGenerator::new(&types);
+    gen.visit_block(&f.body);
+    let params = f
+        .args
+        .iter()
+        .map(|x| format!("{} : {}", vhdl_ident(&x.name), types[&x.name].declaration()))
+        .collect::<Vec<_>>()
+        .join("; ");
+    let mut io = CodeWriter::default();
+    io.add(format!(
+        "function {}({}) return {} is",
+        vhdl_ident(&f.name),
+        params,
+        types[&f.result.name].type_mark()
+    ));
+    io.push();
+    for var in gen.variables() {
+        io.add(format!(
+            "variable {} : {};",
+            VHDLCodeGenerator::variable_name(&var.signal),
+            var.kind.declaration()
+        ));
+    }
+    io.pop();
+    io.add("begin");
+    io.push();
+    io.add(gen.to_string());
+    io.add(format!(
+        "return {};",
+        VHDLCodeGenerator::variable_name(&f.result.name)
+    ));
+    io.pop();
+    io.add("end function;");
+    io.to_string()
+}
+
+#[test]
+fn test_vhdl_identifiers() {
+    assert_eq!(vhdl_ident("sig_in"), "sig_in");
+    assert_eq!(vhdl_ident("top$knot_1"), "\\top$knot_1\\");
+    assert_eq!(vhdl_ident("in"), "\\in\\");
+    assert_eq!(vhdl_ident("a__b"), "\\a__b\\");
+    assert_eq!(vhdl_ident("_a"), "\\_a\\");
+}
@@ -0,0 +1,530 @@
+use crate::atom::Atom;
+use crate::block::Block;
+use crate::probe::Probe;
+use crate::synth::VCDValue;
+use crate::type_descriptor::{TypeDescriptor, TypeKind};
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::{BufWriter, Error, ErrorKind, Read, Result, Write};
+use std::path::Path;
+
+const MAGIC: &[u8] = b"rust-hdl waveform 1\n";
+
+/// The value of a signal in a [Waveform].
+#[derive(Clone, Debug, PartialEq)]
+pub enum WaveValue {
+    /// The bits of the signal, most significant bit first (as in a VCD file)
+    Bits(Vec<vcd::Value>),
+    /// A string value (used for the state of a `LogicState` enum)
+    String(String),
+}
+
+impl WaveValue {
+    /// The value of a single bit signal, or `None` if the signal is
+    /// wider than one bit, or is not a `0` or `1`.
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            WaveValue::Bits(x) if x.len() == 1 => match x[0] {
+                vcd::Value::V0 => Some(false),
+                vcd::Value::V1 => Some(true),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+    /// The value of the signal as an unsigned integer, or `None` if the
+    /// signal is a string, is wider than 128 bits, or has `X` or `Z` bits.
+    pub fn as_u128(&self) -> Option<u128> {
+        match self {
+            WaveValue::Bits(x) if x.len() <= 128 => x.iter().try_fold(0_u128, |acc, b| match b {
+                vcd::Value::V0 => Some(acc << 1),
+                vcd::Value::V1 => Some((acc << 1) | 1),
+                _ => None,
+            }),
+            _ => None,
+        }
+    }
+}
+
+impl Display for WaveValue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WaveValue::Bits(x) => {
+                for b in x {
+                    write!(f, "{}", b)?;
+                }
+                Ok(())
+            }
+            WaveValue::String(x) => write!(f, "{}", x),
+        }
+    }
+}
+
+impl From<bool> for WaveValue {
+    fn from(x: bool) -> Self {
+        WaveValue::Bits(vec![x.into()])
+    }
+}
+
+/// A change in the value of a signal.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Change {
+    pub time: u64,
+    pub value: WaveValue,
+}
+
+/// The recorded history of a single signal.  The changes are in time order,
+/// and each one differs from the one before it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Trace {
+    /// The hierarchical path of the signal (e.g., `uut.counter.q`)
+    pub path: String,
+    /// The width of the signal in bits (0 for string valued signals)
+    pub width: usize,
+    changes: Vec<Change>,
+}
+
+impl Trace {
+    /// All of the changes to the signal.
+    pub fn changes(&self) -> &[Change] {
+        &self.changes
+    }
+    /// The value of the signal at `time`, or `None` if the signal had
+    /// not been recorded yet.
+    pub fn value_at(&self, time: u64) -> Option<&WaveValue> {
+        let ndx = self.changes.partition_point(|x| x.time <= time);
+        ndx.checked_sub(1).map(|ndx| &self.changes[ndx].value)
+    }
+    /// The changes that happen at or after `start` and before `end`.
+    pub fn changes_in(&self, start: u64, end: u64) -> &[Change] {
+        let first = self.changes.partition_point(|x| x.time < start);
+        let last = self.changes.partition_point(|x| x.time < end);
+        &self.changes[first..last.max(first)]
+    }
+    fn edges(&self, start: u64, end: u64, rising: bool) -> Vec<u64> {
+        let first = self.changes.partition_point(|x| x.time < start);
+        self.changes_in(start, end)
+            .iter()
+            .enumerate()
+            .filter(|(ndx, change)| {
+                let before = (first + ndx)
+                    .checked_sub(1)
+                    .and_then(|x| self.changes[x].value.as_bool());
+                before == Some(!rising) && change.value.as_bool() == Some(rising)
+            })
+            .map(|(_, change)| change.time)
+            .collect()
+    }
+    /// The times of the `0` to `1` transitions of a single bit signal at or
+    /// after `start` and before `end`.
+    pub fn rising_edges(&self, start: u64, end: u64) -> Vec<u64> {
+        self.edges(start, end, true)
+    }
+    /// The times of the `1` to `0` transitions of a single bit signal at or
+    /// after `start` and before `end`.
+    pub fn falling_edges(&self, start: u64, end: u64) -> Vec<u64> {
+        self.edges(start, end, false)
+    }
+    /// The first time at or after `start` that the value of the signal
+    /// satisfies `predicate`.
+    pub fn first_time<F>(&self, start: u64, predicate: F) -> Option<u64>
+    where
+        F: Fn(&WaveValue) -> bool,
+    {
+        match self.value_at(start) {
+            Some(x) if predicate(x) => Some(start),
+            _ => {
+                let first = self.changes.partition_point(|x| x.time <= start);
+                self.changes[first..]
+                    .iter()
+                    .find(|x| predicate(&x.value))
+                    .map(|x| x.time)
+            }
+        }
+    }
+    fn record(&mut self, time: u64, value: WaveValue) {
+        match self.changes.last_mut() {
+            Some(last) if last.value == value => {}
+            // Several changes at the same time settle to the last one
+            Some(last) if last.time == time => {
+                last.value = value;
+                let len = self.changes.len();
+                if len > 1 && self.changes[len - 2].value == self.changes[len - 1].value {
+                    self.changes.pop();
+                }
+            }
+            _ => self.changes.push(Change { time, value }),
+        }
+    }
+}
+
+/// An in-memory store of the signals of a simulation, keyed by their
+/// hierarchical path.  The paths use the same `.` separated form as the
+/// VCD files written by [Simulation::run_to_file](crate::simulate::Simulation::run_to_file),
+/// so that `uut.clock` is the clock input of the top level circuit.
+/// Record one with [Simulation::run_recorded](crate::simulate::Simulation::run_recorded),
+/// and then query it once the simulation is done, save it in a compact binary
+/// form with [Waveform::save], or export it with [Waveform::write_vcd].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Waveform {
+    traces: Vec<Trace>,
+    index: HashMap<String, usize>,
+    end_time: u64,
+}
+
+impl Waveform {
+    /// The traces, in the order the signals appear in the circuit.
+    pub fn traces(&self) -> &[Trace] {
+        &self.traces
+    }
+    /// The trace of the signal with the given path.
+    pub fn trace(&self, path: &str) -> Option<&Trace> {
+        self.index.get(path).map(|x| &self.traces[*x])
+    }
+    /// The value of the signal with the given path at `time`.
+    pub fn value_at(&self, path: &str, time: u64) -> Option<&WaveValue> {
+        self.trace(path).and_then(|x| x.value_at(time))
+    }
+    /// The time at which the recording ended.
+    pub fn end_time(&self) -> u64 {
+        self.end_time
+    }
+    fn add_trace(&mut self, path: String, width: usize) -> usize {
+        self.index.insert(path.clone(), self.traces.len());
+        self.traces.push(Trace {
+            path,
+            width,
+            changes: vec![],
+        });
+        self.traces.len() - 1
+    }
+    /// Write the waveform as a VCD file.
+    pub fn write_vcd<W: Write>(&self, w: W) -> Result<()> {
+        let mut vcd = vcd::Writer::new(w);
+        vcd.timescale(1, vcd::TimescaleUnit::PS)?;
+        let mut scope: Vec<&str> = vec![];
+        let mut ids = vec![];
+        for trace in &self.traces {
+            let mut parts = trace.path.split('.').collect::<Vec<_>>();
+            let name = parts.pop().unwrap_or_default();
+            let common = scope.iter().zip(&parts).take_while(|(a, b)| a == b).count();
+            while scope.len() > common {
+                vcd.upscope()?;
+                scope.pop();
+            }
+            for part in &parts[common..] {
+                vcd.add_module(part)?;
+                scope.push(*part);
+            }
+            ids.push(vcd.add_wire(trace.width as u32, name)?);
+        }
+        for _ in &scope {
+            vcd.upscope()?;
+        }
+        vcd.enddefinitions()?;
+        let mut changes = self
+            .traces
+            .iter()
+            .enumerate()
+            .flat_map(|(ndx, trace)| trace.changes.iter().map(move |x| (ndx, x)))
+            .collect::<Vec<_>>();
+        changes.sort_by_key(|(_, x)| x.time);
+        let mut now = None;
+        for (ndx, change) in changes {
+            if now != Some(change.time) {
+                vcd.timestamp(change.time)?;
+                now = Some(change.time);
+            }
+            match &change.value {
+                WaveValue::Bits(x) if x.len() == 1 => vcd.change_scalar(ids[ndx], x[0])?,
+                WaveValue::Bits(x) => vcd.change_vector(ids[ndx], x)?,
+                WaveValue::String(x) => vcd.change_string(ids[ndx], x)?,
+            }
+        }
+        Ok(())
+    }
+    /// Save the waveform to a file in a compact binary form.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let mut w = BufWriter::new(File::create(path)?);
+        w.write_all(MAGIC)?;
+        write_number(&mut w, self.end_time)?;
+        write_number(&mut w, self.traces.len() as u64)?;
+        for trace in &self.traces {
+            write_string(&mut w, &trace.path)?;
+            write_number(&mut w, trace.width as u64)?;
+            write_number(&mut w, trace.changes.len() as u64)?;
+            let mut time = 0;
+            for change in &trace.changes {
+                write_number(&mut w, change.time - time)?;
+                time = change.time;
+                match &change.value {
+                    WaveValue::Bits(x) => {
+                        w.write_all(&[0])?;
+                        write_number(&mut w, x.len() as u64)?;
+                        for chunk in x.chunks(4) {
+                            let packed = chunk
+                                .iter()
+                                .enumerate()
+                                .fold(0_u8, |acc, (ndx, b)| acc | (value_code(*b) << (ndx * 2)));
+                            w.write_all(&[packed])?;
+                        }
+                    }
+                    WaveValue::String(x) => {
+                        w.write_all(&[1])?;
+                        write_string(&mut w, x)?;
+                    }
+                }
+            }
+        }
+        w.flush()
+    }
+    /// Load a waveform saved with [Waveform::save].
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Waveform> {
+        let data = std::fs::read(path)?;
+        // Lengths are checked against what is left of the file before
+        // anything is allocated, so a corrupt file cannot exhaust memory
+        let mut r = data
+            .strip_prefix(MAGIC)
+            .ok_or_else(|| malformed("not a waveform file"))?;
+        let mut waveform = Waveform {
+            end_time: read_number(&mut r)?,
+            ..Default::default()
+        };
+        for _ in 0..read_number(&mut r)? {
+            let path = read_string(&mut r)?;
+            let width = read_number(&mut r)? as usize;
+            let ndx = waveform.add_trace(path, width);
+            let mut time = 0;
+            for _ in 0..read_number(&mut r)? {
+                time += read_number(&mut r)?;
+                let value = match read_byte(&mut r)? {
+                    0 => {
+                        let len = read_number(&mut r)?;
+                        if len.div_ceil(4) > r.len() as u64 {
+                            return Err(malformed("value is longer than the file"));
+                        }
+                        let len = len as usize;
+                        let mut bits = Vec::with_capacity(len);
+                        while bits.len() < len {
+                            let packed = read_byte(&mut r)?;
+                            for ndx in 0..(len - bits.len()).min(4) {
+                                bits.push(code_value(packed >> (ndx * 2)));
+                            }
+                        }
+                        WaveValue::Bits(bits)
+                    }
+                    1 => WaveValue::String(read_string(&mut r)?),
+                    _ => return Err(malformed("unknown value type")),
+                };
+                waveform.traces[ndx].changes.push(Change { time, value });
+            }
+        }
+        Ok(waveform)
+    }
+}
+
+fn malformed(msg: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, msg)
+}
+
+fn value_code(x: vcd::Value) -> u8 {
+    match x {
+        vcd::Value::V0 => 0,
+        vcd::Value::V1 => 1,
+        vcd::Value::X => 2,
+        vcd::Value::Z => 3,
+    }
+}
+
+fn code_value(x: u8) -> vcd::Value {
+    match x & 3 {
+        0 => vcd::Value::V0,
+        1 => vcd::Value::V1,
+        2 => vcd::Value::X,
+        _ => vcd::Value::Z,
+    }
+}
+
+// Numbers are stored 7 bits at a time, least significant first, with the top
+// bit of each byte set if more bytes follow
+fn write_number<W: Write>(w: &mut W, mut x: u64) -> Result<()> {
+    loop {
+        let byte = (x & 0x7F) as u8;
+        x >>= 7;
+        if x == 0 {
+            return w.write_all(&[byte]);
+        }
+        w.write_all(&[byte | 0x80])?;
+    }
+}
+
+fn read_byte<R: Read>(r: &mut R) -> Result<u8> {
+    let mut byte = [0_u8];
+    r.read_exact(&mut byte)?;
+    Ok(byte[0])
+}
+
+fn read_number<R: Read>(r: &mut R) -> Result<u64> {
+    let mut x = 0_u64;
+    for shift in (0..64).step_by(7) {
+        let byte = read_byte(r)?;
+        x |= ((byte & 0x7F) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(x);
+        }
+    }
+    Err(malformed("number is too long"))
+}
+
+fn write_string<W: Write>(w: &mut W, x: &str) -> Result<()> {
+    write_number(w, x.len() as u64)?;
+    w.write_all(x.as_bytes())
+}
+
+fn read_string(r: &mut &[u8]) -> Result<String> {
+    let len = read_number(r)?;
+    if len > r.len() as u64 {
+        return Err(malformed("string is longer than the file"));
+    }
+    let (buf, rest) = r.split_at(len as usize);
+    *r = rest;
+    String::from_utf8(buf.to_vec()).map_err(|_| malformed("string is not UTF-8"))
+}
+
+#[derive(Clone, Debug)]
+enum Slot {
+    Singleton(usize),
+    Composite(Vec<Slot>),
+}
+
+/// Records the signals of a circuit into a [Waveform].  Create it once the
+/// circuit is connected, and then call [WaveformRecorder::sample] whenever
+/// the circuit has settled.
+pub struct WaveformRecorder {
+    waveform: Waveform,
+    slots: HashMap<usize, Slot>,
+}
+
+struct WaveformHeader<'a> {
+    recorder: &'a mut WaveformRecorder,
+    scope: Vec<String>,
+}
+
+fn register_signal(name: &str, descriptor: &TypeDescriptor, waveform: &mut Waveform) -> Slot {
+    match &descriptor.kind {
+        TypeKind::Bits(width) | TypeKind::Signed(width) => {
+            Slot::Singleton(waveform.add_trace(name.into(), *width))
+        }
+        TypeKind::Enum(_) | TypeKind::EncodedEnum(_) => {
+            Slot::Singleton(waveform.add_trace(name.into(), 0))
+        }
+        TypeKind::Composite(k) => Slot::Composite(
+            k.iter()
+                .map(|field| {
+                    let sub_name = format!("{}${}", name, field.fieldname);
+                    register_signal(&sub_name, &field.kind, waveform)
+                })
+                .collect(),
+        ),
+    }
+}
+
+impl Probe for WaveformHeader<'_> {
+    fn visit_start_scope(&mut self, name: &str, _node: &dyn Block) {
+        self.scope.push(name.into());
+    }
+
+    fn visit_start_namespace(&mut self, name: &str, _node: &dyn Block) {
+        self.scope.push(name.into());
+    }
+
+    fn visit_atom(&mut self, name: &str, signal: &dyn Atom) {
+        let path = format!("{}.{}", self.scope.join("."), name);
+        let slot = register_signal(&path, &signal.descriptor(), &mut self.recorder.waveform);
+        self.recorder.slots.insert(signal.id(), slot);
+    }
+
+    fn visit_end_namespace(&mut self, _name: &str, _node: &dyn Block) {
+        self.scope.pop();
+    }
+
+    fn visit_end_scope(&mut self, _name: &str, _node: &dyn Block) {
+        self.scope.pop();
+    }
+}
+
+struct WaveformSample<'a> {
+    recorder: &'a mut WaveformRecorder,
+    time: u64,
+}
+
+fn record_value(waveform: &mut Waveform, slot: &Slot, time: u64, value: VCDValue) {
+    match (slot, value) {
+        (Slot::Singleton(ndx), VCDValue::Single(x)) => {
+            waveform.traces[*ndx].record(time, WaveValue::Bits(vec![x]))
+        }
+        (Slot::Singleton(ndx), VCDValue::Vector(x)) => {
+            waveform.traces[*ndx].record(time, WaveValue::Bits(x))
+        }
+        (Slot::Singleton(ndx), VCDValue::String(x)) => {
+            waveform.traces[*ndx].record(time, WaveValue::String(x))
+        }
+        (Slot::Composite(slots), VCDValue::Composite(vals)) => {
+            assert_eq!(
+                slots.len(),
+                vals.len(),
+                "Mismatch in values versus type information"
+            );
+            for (slot, val) in slots.iter().zip(vals) {
+                record_value(waveform, slot, time, *val);
+            }
+        }
+        (Slot::Singleton(_), _) => panic!("Composite data received for singleton type"),
+        (Slot::Composite(_), _) => panic!("Scalar data received for composite type"),
+    }
+}
+
+impl Probe for WaveformSample<'_> {
+    fn visit_atom(&mut self, _name: &str, signal: &dyn Atom) {
+        let recorder = &mut *self.recorder;
+        if let Some(slot) = recorder.slots.get(&signal.id()) {
+            record_value(&mut recorder.waveform, slot, self.time, signal.vcd());
+        }
+    }
+}
+
+impl WaveformRecorder {
+    /// Create a recorder for the signals of `uut`.
+    pub fn new(uut: &dyn Block) -> WaveformRecorder {
+        let mut recorder = WaveformRecorder {
+            waveform: Default::default(),
+            slots: Default::default(),
+        };
+        uut.accept(
+            "uut",
+            &mut WaveformHeader {
+                recorder: &mut recorder,
+                scope: vec![],
+            },
+        );
+        recorder
+    }
+    /// Record the values of the signals of `uut` at `time`.  Samples must
+    /// be taken in time order.
+    pub fn sample(&mut self, time: u64, uut: &dyn Block) {
+        uut.accept(
+            "uut",
+            &mut WaveformSample {
+                recorder: self,
+                time,
+            },
+        );
+        self.waveform.end_time = time;
+    }
+    /// Finish the recording at `time`, and return the [Waveform].
+    pub fn finish(mut self, time: u64) -> Waveform {
+        self.waveform.end_time = self.waveform.end_time.max(time);
+        self.waveform
+    }
+}
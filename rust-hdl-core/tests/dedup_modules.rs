@@ -0,0 +1,156 @@
+use rust_hdl_core::prelude::*;
+
+#[derive(LogicBlock)]
+struct Offset {
+    pub i1: Signal<In, Bits<8>>,
+    pub o1: Signal<Out, Bits<8>>,
+    c1: Constant<Bits<8>>,
+}
+
+impl Offset {
+    fn new(offset: LiteralType) -> Self {
+        Self {
+            i1: Default::default(),
+            o1: Default::default(),
+            c1: Constant::new(offset.into()),
+        }
+    }
+}
+
+impl Logic for Offset {
+    #[hdl_gen]
+    fn update(&mut self) {
+        self.o1.next = self.i1.val() + self.c1.val();
+    }
+}
+
+#[derive(LogicBlock)]
+struct Chain {
+    pub i1: Signal<In, Bits<8>>,
+    pub o1: Signal<Out, Bits<8>>,
+    a: Offset,
+    b: Offset,
+    c: Offset,
+}
+
+impl Logic for Chain {
+    #[hdl_gen]
+    fn update(&mut self) {
+        self.a.i1.next = self.i1.val();
+        self.b.i1.next = self.a.o1.val();
+        self.c.i1.next = self.b.o1.val();
+        self.o1.next = self.c.o1.val();
+    }
+}
+
+#[test]
+fn test_identical_modules_are_emitted_once() {
+    let mut uut = Chain {
+        i1: Default::default(),
+        o1: Default::default(),
+        a: Offset::new(3),
+        b: Offset::new(3),
+        c: Offset::new(5),
+    };
+    uut.i1.connect();
+    uut.connect_all();
+    let vlog = generate_verilog(&uut);
+    println!("{}", vlog);
+    assert_eq!(vlog.matches("\nmodule ").count(), 3);
+    assert!(vlog.contains("module top$a("));
+    assert!(!vlog.contains("module top$b("));
+    assert!(vlog.contains("module top$c("));
+    assert!(vlog.contains("top$a a("));
+    assert!(vlog.contains("top$a b("));
+    assert!(vlog.contains("top$c c("));
+}
+
+#[test]
+fn test_parameterized_modules_share_definition() {
+    let mut uut = Chain {
+        i1: Default::default(),
+        o1: Default::default(),
+        a: Offset::new(3),
+        b: Offset::new(3),
+        c: Offset::new(5),
+    };
+    uut.i1.connect();
+    uut.connect_all();
+    let vlog = generate_verilog_parameterized(&uut);
+    println!("{}", vlog);
+    assert_eq!(vlog.matches("\nmodule ").count(), 2);
+    assert!(vlog.contains("parameter c1 = 8'h3;"));
+    assert!(vlog.contains("input wire  [i1$WIDTH-1:0] i1;"));
+    assert!(vlog.contains("top$a #(.i1$WIDTH(8), .o1$WIDTH(8), .c1(8'h3)) a("));
+    assert!(vlog.contains("top$a #(.i1$WIDTH(8), .o1$WIDTH(8), .c1(8'h5)) c("));
+}
+
+#[derive(LogicBlock, Default)]
+struct Pass<const N: usize> {
+    pub i1: Signal<In, Bits<N>>,
+    pub o1: Signal<Out, Bits<N>>,
+}
+
+impl<const N: usize> Logic for Pass<N> {
+    #[hdl_gen]
+    fn update(&mut self) {
+        self.o1.next = self.i1.val();
+    }
+}
+
+#[derive(LogicBlock, Default)]
+struct LowNibble<const N: usize> {
+    pub i1: Signal<In, Bits<N>>,
+    pub o1: Signal<Out, Bits<4>>,
+}
+
+impl<const N: usize> Logic for LowNibble<N> {
+    #[hdl_gen]
+    fn update(&mut self) {
+        self.o1.next = self.i1.val().get_bits::<4>(0);
+    }
+}
+
+#[derive(LogicBlock, Default)]
+struct Widths {
+    pub i1: Signal<In, Bits<8>>,
+    pub o1: Signal<Out, Bits<16>>,
+    pub o2: Signal<Out, Bits<4>>,
+    pub o3: Signal<Out, Bits<4>>,
+    narrow: Pass<8>,
+    wide: Pass<16>,
+    nibble_narrow: LowNibble<8>,
+    nibble_wide: LowNibble<16>,
+}
+
+impl Logic for Widths {
+    #[hdl_gen]
+    fn update(&mut self) {
+        self.narrow.i1.next = self.i1.val();
+        self.wide.i1.next = bit_cast::<16, 8>(self.narrow.o1.val());
+        self.o1.next = self.wide.o1.val();
+        self.nibble_narrow.i1.next = self.i1.val();
+        self.nibble_wide.i1.next = self.wide.o1.val();
+        self.o2.next = self.nibble_narrow.o1.val();
+        self.o3.next = self.nibble_wide.o1.val();
+    }
+}
+
+#[test]
+fn test_parameterized_widths_are_overridden() {
+    let mut uut = Widths::default();
+    uut.i1.connect();
+    uut.connect_all();
+    let vlog = generate_verilog_parameterized(&uut);
+    println!("{}", vlog);
+    // The pass through does not depend on its width, so one definition serves both
+    assert!(vlog.contains("module top$narrow("));
+    assert!(!vlog.contains("module top$wide("));
+    assert!(vlog.contains("top$narrow #(.i1$WIDTH(8), .o1$WIDTH(8)) narrow("));
+    assert!(vlog.contains("top$narrow #(.i1$WIDTH(16), .o1$WIDTH(16)) wide("));
+    // The slice bakes in the width of the input, so the widths stay fixed
+    assert!(vlog.contains("module top$nibble_narrow("));
+    assert!(vlog.contains("module top$nibble_wide("));
+    assert!(vlog.contains("input wire  [15:0] i1;"));
+    assert!(vlog.contains("top$nibble_wide nibble_wide("));
+}
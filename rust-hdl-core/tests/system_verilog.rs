@@ -0,0 +1,108 @@
+use rust_hdl_core::prelude::*;
+
+#[derive(Copy, Clone, Debug, PartialEq, LogicState)]
+enum Command {
+    Idle,
+    Read,
+    Write,
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, LogicStruct)]
+struct Request {
+    pub cmd: Command,
+    pub addr: Bits<6>,
+}
+
+#[derive(LogicInterface, Default)]
+#[join = "Responder"]
+struct Controller {
+    pub request: Signal<Out, Request>,
+    pub ready: Signal<In, Bit>,
+}
+
+#[derive(LogicInterface, Default)]
+#[join = "Controller"]
+struct Responder {
+    pub request: Signal<In, Request>,
+    pub ready: Signal<Out, Bit>,
+}
+
+#[derive(LogicBlock, Default)]
+struct Source {
+    pub req: Signal<In, Request>,
+    pub bus: Controller,
+}
+
+impl Logic for Source {
+    #[hdl_gen]
+    fn update(&mut self) {
+        self.bus.request.next = self.req.val();
+    }
+}
+
+#[derive(LogicBlock, Default)]
+struct Sink {
+    pub bus: Responder,
+    pub cmd: Signal<Out, Command>,
+}
+
+impl Logic for Sink {
+    #[hdl_gen]
+    fn update(&mut self) {
+        self.bus.ready.next = true;
+        self.cmd.next = self.bus.request.val().cmd;
+    }
+}
+
+#[derive(LogicBlock, Default)]
+struct Top {
+    pub req: Signal<In, Request>,
+    pub cmd: Signal<Out, Command>,
+    source: Source,
+    sink: Sink,
+}
+
+impl Logic for Top {
+    #[hdl_gen]
+    fn update(&mut self) {
+        self.source.req.next = self.req.val();
+        Controller::join(&mut self.source.bus, &mut self.sink.bus);
+        self.cmd.next = self.sink.cmd.val();
+    }
+}
+
+#[test]
+fn test_system_verilog_keeps_types() {
+    let mut uut = Top::default();
+    uut.req.connect();
+    uut.connect_all();
+    let vlog = generate_system_verilog(&uut);
+    println!("{}", vlog);
+    assert!(vlog.contains("package rust_hdl_types;"));
+    assert!(vlog.contains(
+        "typedef enum logic [1:0] {Command$Idle = 2'd0, Command$Read = 2'd1, Command$Write = 2'd2} Command;"
+    ));
+    assert!(vlog.contains("typedef struct packed {"));
+    assert!(vlog.contains("} Request;"));
+    assert!(!vlog.contains("localparam Command$Idle"));
+    assert!(vlog.contains("input Request req;"));
+    assert!(vlog.contains("output Command cmd;"));
+}
+
+#[test]
+fn test_system_verilog_interfaces() {
+    let mut uut = Top::default();
+    uut.req.connect();
+    uut.connect_all();
+    let vlog = generate_system_verilog(&uut);
+    println!("{}", vlog);
+    assert!(vlog.contains("interface top$sink$bus$if;"));
+    assert!(vlog.contains("modport top$sink (input request, output ready);"));
+    assert!(vlog.contains("modport top$source (output request, input ready);"));
+    assert!(vlog.contains("module top$sink(bus,cmd);"));
+    assert!(vlog.contains("top$sink$bus$if.top$sink bus;"));
+    assert!(vlog.contains("top$sink$bus$if sink$bus();"));
+    assert!(vlog.contains(".bus(sink$bus)"));
+    assert!(vlog.contains("always_comb sink$bus.request = source$bus.request;"));
+    assert!(vlog.contains("always_comb source$bus.ready = sink$bus.ready;"));
+}
@@ -0,0 +1,65 @@
+use rust_hdl_core::prelude::*;
+
+#[derive(Copy, Clone, Debug, PartialEq, LogicState)]
+enum Mode {
+    Pass,
+    Invert,
+}
+
+#[derive(LogicBlock, Default)]
+struct Inverter {
+    pub mode: Signal<In, Mode>,
+    pub i1: Signal<In, Bits<8>>,
+    pub o1: Signal<Out, Bits<8>>,
+    pub s_in: Signal<In, Signed<8>>,
+    pub s_out: Signal<Out, Signed<8>>,
+}
+
+impl Logic for Inverter {
+    #[hdl_gen]
+    fn update(&mut self) {
+        self.s_out.next = self.s_in.val();
+        match self.mode.val() {
+            Mode::Pass => {
+                self.o1.next = self.i1.val();
+            }
+            Mode::Invert => {
+                self.o1.next = !self.i1.val();
+            }
+        }
+    }
+}
+
+#[test]
+fn test_vhdl_entity_and_architecture() {
+    let mut uut = Inverter::default();
+    uut.mode.connect();
+    uut.i1.connect();
+    uut.s_in.connect();
+    uut.connect_all();
+    let vhdl = generate_vhdl(&uut);
+    println!("{}", vhdl);
+    assert!(vhdl.contains("package rust_hdl_pkg is"));
+    assert!(vhdl.contains("type \\Mode\\ is (\\Mode$Pass\\, \\Mode$Invert\\);"));
+    assert!(vhdl.contains("entity top is"));
+    assert!(vhdl.contains("mode : in \\Mode\\"));
+    assert!(vhdl.contains("i1 : in unsigned(7 downto 0)"));
+    assert!(vhdl.contains("s_out : out signed(7 downto 0)"));
+    assert!(vhdl.contains("architecture rtl of top is"));
+    assert!(vhdl.contains("case mode is"));
+    assert!(vhdl.contains("when \\Mode$Invert\\ =>"));
+    assert!(vhdl.contains("o1 <= \\o1$next\\;"));
+}
+
+// Run with `cargo test -- --ignored` on a machine with ghdl installed
+#[test]
+#[ignore]
+fn test_vhdl_elaborates_with_ghdl() {
+    let mut uut = Inverter::default();
+    uut.mode.connect();
+    uut.i1.connect();
+    uut.s_in.connect();
+    uut.connect_all();
+    assert!(ghdl_available(), "ghdl is not installed");
+    ghdl_validate("vhdl_inverter", &generate_vhdl(&uut)).unwrap();
+}
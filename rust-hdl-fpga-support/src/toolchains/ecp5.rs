@@ -2,7 +2,7 @@
 use rust_hdl_core::prelude::*;
 use std::collections::HashMap;
 
-use super::map_signal_type_to_lattice_string;
+use super::{try_map_signal_type_to_lattice_string, unsupported_constraint};
 
 #[derive(Default)]
 struct LPFGenerator {
@@ -10,6 +10,7 @@ struct LPFGenerator {
     namespace: NamedPath,
     lpf: Vec<String>,
     names: HashMap<usize, String>,
+    error: Option<GenerateError>,
 }
 
 impl Probe for LPFGenerator {
@@ -40,11 +41,16 @@ impl Probe for LPFGenerator {
                     self.lpf
                         .push(format!("LOCATE COMP \"{}\" SITE \"{}\"", prefix, l));
                 }
-                Constraint::Kind(k) => {
-                    let name = map_signal_type_to_lattice_string(k);
-                    self.lpf
-                        .push(format!("IOBUF PORT \"{}\" IO_TYPE={}", prefix, name))
-                }
+                Constraint::Kind(k) => match try_map_signal_type_to_lattice_string(k) {
+                    Some(kind) => self
+                        .lpf
+                        .push(format!("IOBUF PORT \"{}\" IO_TYPE={}", prefix, kind)),
+                    None => {
+                        self.error.get_or_insert_with(|| {
+                            unsupported_constraint(&self.path, &name, &pin.constraint, "ECP5")
+                        });
+                    }
+                },
                 Constraint::Timing(t) => {
                     let timing = match t {
                         Timing::Periodic(p) => {
@@ -56,7 +62,12 @@ impl Probe for LPFGenerator {
                             )
                         }
                         Timing::Custom(c) => c.to_string(),
-                        _ => unimplemented!("Unknown timing constraint for ECP5 generation"),
+                        _ => {
+                            self.error.get_or_insert_with(|| {
+                                unsupported_constraint(&self.path, &name, &pin.constraint, "ECP5")
+                            });
+                            continue;
+                        }
                     };
                     if !timing.is_empty() {
                         self.lpf.push(timing);
@@ -83,8 +94,17 @@ impl Probe for LPFGenerator {
 }
 
 pub fn generate_lpf<U: Block>(uut: &U) -> String {
+    try_generate_lpf(uut).unwrap_or_else(|e| panic!("{}", e))
+}
+
+/// Generate the LPF file for a circuit, or return the first constraint
+/// that cannot be expressed for the ECP5.
+pub fn try_generate_lpf<U: Block>(uut: &U) -> Result<String, GenerateError> {
     let mut lpf = LPFGenerator::default();
     uut.accept("top", &mut lpf);
+    if let Some(e) = lpf.error {
+        return Err(e);
+    }
     let mut lpf_uniq = vec![];
     lpf_uniq.push("BLOCK RESETPATHS".to_string());
     lpf_uniq.push("BLOCK ASYNCPATHS".to_string());
@@ -93,5 +113,5 @@ pub fn generate_lpf<U: Block>(uut: &U) -> String {
             lpf_uniq.push(line);
         }
     }
-    lpf_uniq.join(";\n") + ";\n"
+    Ok(lpf_uniq.join(";\n") + ";\n")
 }
@@ -1,3 +1,4 @@
+use crate::toolchains::unsupported_constraint;
 use rust_hdl_core::prelude::*;
 
 #[derive(Default)]
@@ -5,6 +6,7 @@ struct PCFGenerator {
     path: NamedPath,
     namespace: NamedPath,
     pcf: Vec<String>,
+    error: Option<GenerateError>,
 }
 
 impl Probe for PCFGenerator {
@@ -36,7 +38,9 @@ impl Probe for PCFGenerator {
                     }
                     Constraint::Custom(s) => self.pcf.push(s.clone()),
                     _ => {
-                        panic!("Pin constraint type {:?} is unsupported!", pin.constraint)
+                        self.error.get_or_insert_with(|| {
+                            unsupported_constraint(&self.path, &name, &pin.constraint, "icestorm")
+                        });
                     }
                 }
             }
@@ -51,7 +55,16 @@ impl Probe for PCFGenerator {
 }
 
 pub fn generate_pcf<U: Block>(uut: &U) -> String {
+    try_generate_pcf(uut).unwrap_or_else(|e| panic!("{}", e))
+}
+
+/// Generate the PCF file for a circuit, or return the first pin constraint
+/// that cannot be expressed in a PCF file.
+pub fn try_generate_pcf<U: Block>(uut: &U) -> Result<String, GenerateError> {
     let mut pcf = PCFGenerator::default();
     uut.accept("top", &mut pcf);
-    pcf.pcf.join("\n") + "\n"
+    match pcf.error {
+        Some(e) => Err(e),
+        None => Ok(pcf.pcf.join("\n") + "\n"),
+    }
 }
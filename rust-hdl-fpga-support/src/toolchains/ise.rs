@@ -1,6 +1,6 @@
 use std::collections::HashMap;
 
-use crate::toolchains::map_signal_type_to_xilinx_string;
+use crate::toolchains::{map_signal_type_to_xilinx_string, unsupported_constraint};
 use rust_hdl_core::prelude::*;
 
 #[derive(Default)]
@@ -9,6 +9,7 @@ struct UCFGenerator {
     namespace: NamedPath,
     ucf: Vec<String>,
     names: HashMap<usize, String>,
+    error: Option<GenerateError>,
 }
 
 pub fn collect_xrefs(txt: &[String]) -> Vec<(String, String)> {
@@ -121,7 +122,12 @@ impl Probe for UCFGenerator {
                         }
                         Timing::Custom(c) => c.to_string(),
                         Timing::VivadoFalsePath(_) => "".to_string(),
-                        _ => unimplemented!("Unknown timing constraint for ISE/UCF generation"),
+                        _ => {
+                            self.error.get_or_insert_with(|| {
+                                unsupported_constraint(&self.path, &name, &pin.constraint, "ISE")
+                            });
+                            continue;
+                        }
                     };
                     if !timing.is_empty() {
                         self.ucf.push(timing);
@@ -129,7 +135,9 @@ impl Probe for UCFGenerator {
                 }
                 Constraint::Custom(s) => self.ucf.push(s.clone()),
                 _ => {
-                    unimplemented!("Unsupported constraint type for UCF files")
+                    self.error.get_or_insert_with(|| {
+                        unsupported_constraint(&self.path, &name, &pin.constraint, "ISE")
+                    });
                 }
             }
         }
@@ -143,8 +151,17 @@ impl Probe for UCFGenerator {
 }
 
 pub fn generate_ucf<U: Block>(uut: &U) -> String {
+    try_generate_ucf(uut).unwrap_or_else(|e| panic!("{}", e))
+}
+
+/// Generate the UCF file for a circuit, or return the first constraint
+/// that cannot be expressed for ISE.
+pub fn try_generate_ucf<U: Block>(uut: &U) -> Result<String, GenerateError> {
     let mut ucf = UCFGenerator::default();
     uut.accept("top", &mut ucf);
+    if let Some(e) = ucf.error {
+        return Err(e);
+    }
     // Substitute the signal references
     let xrefs = collect_xrefs(&ucf.ucf);
     let ucf_lines = substitute_refs(&ucf.ucf, &xrefs, &ucf.names);
@@ -154,5 +171,5 @@ pub fn generate_ucf<U: Block>(uut: &U) -> String {
             ucf_uniq.push(line);
         }
     }
-    ucf_uniq.join(";\n")
+    Ok(ucf_uniq.join(";\n"))
 }
@@ -1,15 +1,40 @@
 use rust_hdl_core::prelude::*;
 
 pub fn map_signal_type_to_lattice_string(k: &SignalType) -> &str {
-    match k {
-        SignalType::LowVoltageCMOS_3v3 => "LVCMOS33",
-        _ => panic!(
+    match try_map_signal_type_to_lattice_string(k) {
+        Some(x) => x,
+        None => panic!(
             "Unsupported mapping for signal type {:?} in Lattice mapping",
             k
         ),
     }
 }
 
+pub fn try_map_signal_type_to_lattice_string(k: &SignalType) -> Option<&str> {
+    match k {
+        SignalType::LowVoltageCMOS_3v3 => Some("LVCMOS33"),
+        _ => None,
+    }
+}
+
+// Constraint generators record the first constraint they cannot express,
+// and the signal that carries it.
+pub(crate) fn unsupported_constraint(
+    path: &NamedPath,
+    name: &str,
+    constraint: &Constraint,
+    toolchain: &str,
+) -> GenerateError {
+    GenerateError::UnsupportedConstraint {
+        signal: PathedName {
+            path: path.to_string(),
+            name: name.to_string(),
+        },
+        constraint: format!("{:?}", constraint),
+        toolchain: toolchain.to_string(),
+    }
+}
+
 pub fn map_signal_type_to_xilinx_string(k: &SignalType) -> &str {
     match k {
         SignalType::LowVoltageCMOS_1v8 => "LVCMOS18",
@@ -1,4 +1,4 @@
-use crate::toolchains::map_signal_type_to_xilinx_string;
+use crate::toolchains::{map_signal_type_to_xilinx_string, unsupported_constraint};
 use rust_hdl_core::prelude::*;
 
 #[derive(Default)]
@@ -6,6 +6,7 @@ struct XDCGenerator {
     path: NamedPath,
     namespace: NamedPath,
     xdc: Vec<String>,
+    error: Option<GenerateError>,
 }
 
 impl Probe for XDCGenerator {
@@ -55,8 +56,17 @@ impl Probe for XDCGenerator {
                 Constraint::Timing(t) => {
                     let timing = match t {
                         Timing::Periodic(p) => {
+                            // Only 50 % duty cycle clocks are currently implemented
                             if p.duty_cycle != 50.0 {
-                                unimplemented!("Only 50 % duty cycle clocks currently implemented");
+                                self.error.get_or_insert_with(|| {
+                                    unsupported_constraint(
+                                        &self.path,
+                                        &name,
+                                        &pin.constraint,
+                                        "Vivado",
+                                    )
+                                });
+                                continue;
                             }
                             format!("create_clock -name {net} -period {period} [get_ports {{ {prefix} }}]",
                                     net=p.net,
@@ -97,7 +107,10 @@ set_multicycle_path -setup -from [get_ports {{ {prefix} }}] {cycles}",
                             )
                         }
                         _ => {
-                            unimplemented!("Constraint type {:?} is not implemented for Vivado", t)
+                            self.error.get_or_insert_with(|| {
+                                unsupported_constraint(&self.path, &name, &pin.constraint, "Vivado")
+                            });
+                            continue;
                         }
                     };
                     self.xdc.push(timing);
@@ -115,18 +128,27 @@ set_multicycle_path -setup -from [get_ports {{ {prefix} }}] {cycles}",
 }
 
 pub fn generate_xdc<U: Block>(uut: &U) -> String {
+    try_generate_xdc(uut).unwrap_or_else(|e| panic!("{}", e))
+}
+
+/// Generate the XDC file for a circuit, or return the first constraint
+/// that cannot be expressed for Vivado.
+pub fn try_generate_xdc<U: Block>(uut: &U) -> Result<String, GenerateError> {
     let mut xdc = XDCGenerator::default();
     uut.accept("top", &mut xdc);
+    if let Some(e) = xdc.error {
+        return Err(e);
+    }
     let mut xdc_uniq = vec![];
     for line in xdc.xdc {
         if !xdc_uniq.contains(&line) {
             xdc_uniq.push(line);
         }
     }
-    xdc_uniq.join("\n")
+    Ok(xdc_uniq.join("\n")
         + "
 set_property CFGBVS VCCO [current_design]
 set_property CONFIG_VOLTAGE 3.3 [current_design]
 set_property BITSTREAM.GENERAL.COMPRESS True [current_design]
-    "
+    ")
 }
@@ -237,16 +237,16 @@ impl<T: Synth, const N: usize> Logic for LazyFIFOReader<T, N> {
     }
 }
 
-pub fn bursty_rand() -> Bits<32> {
-    if rand::thread_rng().gen::<f64>() < 0.9 {
+pub fn bursty_rand<R: Rng>(rng: &mut R) -> Bits<32> {
+    if rng.gen::<f64>() < 0.9 {
         Bits::from(0)
     } else {
-        ((rand::thread_rng().gen::<f64>() * 40.0) as u32).to_bits()
+        ((rng.gen::<f64>() * 40.0) as u32).to_bits()
     }
 }
 
-pub fn bursty_vec(len: usize) -> Vec<Bits<32>> {
-    (0..len).map(|_| bursty_rand()).collect()
+pub fn bursty_vec<R: Rng>(rng: &mut R, len: usize) -> Vec<Bits<32>> {
+    (0..len).map(|_| bursty_rand(rng)).collect()
 }
 
 #[derive(LogicBlock)]
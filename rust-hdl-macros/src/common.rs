@@ -98,6 +98,23 @@ pub fn get_update_all(fields: Vec<TS>) -> syn::Result<TS> {
     })
 }
 
+pub fn get_checkpoint_all(fields: Vec<TS>) -> syn::Result<TS> {
+    Ok(quote! {
+        fn checkpoint_all(&mut self, state: &mut checkpoint::CircuitState) {
+            self.checkpoint(state);
+            #(self.#fields.checkpoint_all(state);)*
+        }
+    })
+}
+
+pub fn get_mark_unknown_all(fields: Vec<TS>) -> syn::Result<TS> {
+    Ok(quote! {
+        fn mark_unknown_all(&mut self, unknown: &dyn Fn(usize) -> u128) {
+            #(self.#fields.mark_unknown_all(unknown);)*
+        }
+    })
+}
+
 pub fn get_has_changed(fields: Vec<TS>) -> syn::Result<TS> {
     if fields.is_empty() {
         Ok(quote! {
@@ -114,6 +131,56 @@ pub fn get_has_changed(fields: Vec<TS>) -> syn::Result<TS> {
     }
 }
 
+fn any_field(fields: &[TS], method: TS) -> TS {
+    if fields.is_empty() {
+        quote!(false)
+    } else {
+        quote!(#(self.#fields.#method())||*)
+    }
+}
+
+// A block is not itself a port of the block that contains it.  What the
+// parent block can see of it are its own ports, which are the port signals
+// among its fields (but not the ports of its children).  The update function
+// of the block reads all of its fields, and needs to run if any of them
+// changed in a way visible to it.
+pub fn get_update_changed(fields: Vec<TS>) -> syn::Result<TS> {
+    let own_ports_changed = any_field(&fields, quote!(port_changed));
+    let inputs_changed = any_field(&fields, quote!(exposed_changed));
+    Ok(quote! {
+        fn port_changed(&self) -> bool {
+            false
+        }
+        fn exposed_changed(&self) -> bool {
+            #own_ports_changed
+        }
+        fn update_changed(&mut self) {
+            if #inputs_changed {
+                self.update();
+            }
+            #(self.#fields.update_changed();)*
+        }
+    })
+}
+
+// An interface is a bundle of ports of the block that contains it, so it
+// forwards both questions to its fields.
+pub fn get_update_changed_forwarded(fields: Vec<TS>) -> syn::Result<TS> {
+    let ports_changed = any_field(&fields, quote!(port_changed));
+    let exposed_changed = any_field(&fields, quote!(exposed_changed));
+    Ok(quote! {
+        fn port_changed(&self) -> bool {
+            #ports_changed
+        }
+        fn exposed_changed(&self) -> bool {
+            #exposed_changed
+        }
+        fn update_changed(&mut self) {
+            #(self.#fields.update_changed();)*
+        }
+    })
+}
+
 pub fn squash(x: &str) -> String {
     x.to_string().replace([' ', '\n'], "")
 }
@@ -131,6 +198,56 @@ pub fn fixup_ident(x: String) -> String {
     y
 }
 
+// An index into an array of signals (or blocks) is static if it can be
+// evaluated when the HDL is generated, which means it is built from literals
+// and loop indices.  `is_let` identifies the names bound by `let`, since
+// those hold values that are only known at run time.
+fn is_static_index(expr: &Expr, is_let: &dyn Fn(&str) -> bool) -> bool {
+    match expr {
+        Expr::Lit(_) => true,
+        Expr::Path(p) => match p.path.get_ident() {
+            Some(x) => !is_let(&x.to_string()),
+            None => true,
+        },
+        Expr::Paren(x) => is_static_index(&x.expr, is_let),
+        Expr::Unary(x) => is_static_index(&x.expr, is_let),
+        Expr::Binary(x) => is_static_index(&x.left, is_let) && is_static_index(&x.right, is_let),
+        _ => false,
+    }
+}
+
+/// Find the array in a path like `self.banks[ndx].data` that is indexed by
+/// a value only known at run time.
+pub fn dynamic_index<'a>(
+    expr: &'a Expr,
+    is_let: &dyn Fn(&str) -> bool,
+) -> Option<&'a syn::ExprIndex> {
+    match expr {
+        Expr::Field(x) => dynamic_index(&x.base, is_let),
+        Expr::Index(x) if !is_static_index(&x.index, is_let) => Some(x),
+        Expr::Index(x) => dynamic_index(&x.expr, is_let),
+        _ => None,
+    }
+}
+
+/// Replace the run time index found by [dynamic_index] with `index`.
+pub fn replace_dynamic_index(expr: &Expr, index: &Expr, is_let: &dyn Fn(&str) -> bool) -> Expr {
+    let mut ret = expr.clone();
+    let mut x = &mut ret;
+    loop {
+        match x {
+            Expr::Field(f) => x = f.base.as_mut(),
+            Expr::Index(i) if !is_static_index(&i.index, is_let) => {
+                *i.index = index.clone();
+                break;
+            }
+            Expr::Index(i) => x = i.expr.as_mut(),
+            _ => break,
+        }
+    }
+    ret
+}
+
 // The dff_setup macro uses clock, dfflist arguments
 #[derive(Debug)]
 pub struct DFFSetupArgs {
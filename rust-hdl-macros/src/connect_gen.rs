@@ -1,10 +1,22 @@
-use crate::common::{DFFSetupArgs, TS};
+use crate::common::{dynamic_index, replace_dynamic_index, DFFSetupArgs, TS};
 use quote::quote;
+use std::cell::RefCell;
 use std::ops::Index;
 use syn::spanned::Spanned;
 use syn::{Expr, Member, Result};
 
+// The names bound by `let` in the kernel.  An array indexed by one of them
+// is indexed at run time, so any of its elements can be driven.
+thread_local! {
+    static LETS: RefCell<Vec<String>> = const { RefCell::new(vec![]) };
+}
+
+fn is_let(name: &str) -> bool {
+    LETS.with(|x| x.borrow().iter().any(|x| x == name))
+}
+
 pub fn connect_gen(item: &syn::ItemFn) -> Result<TS> {
+    LETS.with(|x| x.borrow_mut().clear());
     let body = connect_block(&item.block)?;
     Ok(quote! {
         fn connect(&mut self) {
@@ -25,9 +37,19 @@ fn connect_statement(statement: &syn::Stmt) -> Result<TS> {
     match statement {
         syn::Stmt::Expr(e) => connect_inner_statement(e),
         syn::Stmt::Semi(e, _) => connect_inner_statement(e),
+        syn::Stmt::Local(local) => {
+            let pat = match &local.pat {
+                syn::Pat::Type(x) => x.pat.as_ref(),
+                x => x,
+            };
+            if let syn::Pat::Ident(x) = pat {
+                LETS.with(|lets| lets.borrow_mut().push(x.ident.to_string()));
+            }
+            Ok(TS::new())
+        }
         _ => Err(syn::Error::new(
             statement.span(),
-            "Local definitions and items are not allowed in HDL kernels",
+            "Items are not allowed in HDL kernels",
         )),
     }
 }
@@ -81,6 +103,13 @@ fn get_base_of_next(expr: &Expr) -> Result<TS> {
         if let Member::Named(nxt) = &field.member {
             if nxt.eq("next") {
                 let lhs = &field.base;
+                if let Some(index) = dynamic_index(lhs, &is_let) {
+                    let array = &index.expr;
+                    let element = replace_dynamic_index(lhs, &syn::parse_quote!(__index), &is_let);
+                    return Ok(quote!(for __index in 0..(#array).len() {
+                        logic::logic_connect_fn(&mut #element);
+                    }));
+                }
                 return Ok(quote!(logic::logic_connect_fn(&mut #lhs)));
             } else {
                 return get_base_of_next(&field.base);
@@ -0,0 +1,139 @@
+use crate::common::TS;
+use quote::{quote, quote_spanned};
+use syn::spanned::Spanned;
+use syn::{Expr, Result, Stmt};
+
+// Every `if` chain and `match` in the kernel is a branch site.  The
+// instrumented kernel calls `coverage::branch` (from the prelude) at the top
+// of each arm, and the sites (with the labels of their arms) are listed by
+// `branch_sites`, so that arms that are never taken can be reported.
+#[derive(Default)]
+struct Instrumenter {
+    sites: Vec<TS>,
+}
+
+pub fn coverage_gen(item: &syn::ItemFn) -> Result<(syn::ItemFn, TS)> {
+    let mut instrumenter = Instrumenter::default();
+    let mut item = item.clone();
+    instrumenter.block(&mut item.block)?;
+    let sites = instrumenter.sites;
+    Ok((
+        item,
+        quote! {
+            fn branch_sites(&self) -> Vec<coverage::BranchSite> {
+                vec![#(#sites),*]
+            }
+        },
+    ))
+}
+
+fn arm_label(pat: &syn::Pat) -> String {
+    quote!(#pat).to_string().replace(' ', "")
+}
+
+impl Instrumenter {
+    // Add a site, and return a function that makes the statement that
+    // records a hit on one of its arms.
+    fn site(&mut self, expr: &Expr, arms: Vec<String>) -> impl Fn(usize) -> Stmt {
+        let span = expr.span();
+        let file = quote_spanned!(span=> file!());
+        let line = quote_spanned!(span=> line!());
+        let column = quote_spanned!(span=> column!());
+        self.sites.push(quote! {
+            coverage::BranchSite {
+                file: #file,
+                line: #line,
+                column: #column,
+                arms: &[#(#arms),*],
+            }
+        });
+        move |arm: usize| {
+            syn::parse_quote! {
+                coverage::branch(#file, #line, #column, #arm);
+            }
+        }
+    }
+
+    fn block(&mut self, block: &mut syn::Block) -> Result<()> {
+        for statement in &mut block.stmts {
+            match statement {
+                Stmt::Expr(e) | Stmt::Semi(e, _) => self.expr(e)?,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn expr(&mut self, expr: &mut Expr) -> Result<()> {
+        match expr {
+            Expr::If(_) => self.conditional(expr),
+            Expr::Match(_) => self.matches(expr),
+            Expr::ForLoop(x) => self.block(&mut x.body),
+            Expr::Block(x) => self.block(&mut x.block),
+            _ => Ok(()),
+        }
+    }
+
+    // An `if`/`else if`/`else` chain is a single site, with one arm per
+    // condition, and a final arm for the `else`.  If the chain does not
+    // have an `else`, one is added that only records the hit.
+    fn conditional(&mut self, expr: &mut Expr) -> Result<()> {
+        let mut arms = vec![];
+        let mut node = &*expr;
+        while let Expr::If(x) = node {
+            arms.push(if arms.is_empty() { "if" } else { "else if" }.to_string());
+            match &x.else_branch {
+                Some((_, e)) => node = e.as_ref(),
+                None => break,
+            }
+        }
+        arms.push("else".to_string());
+        let count = arms.len();
+        let hit = self.site(expr, arms);
+        let mut node = expr;
+        for arm in 0..count - 1 {
+            let x = match node {
+                Expr::If(x) => x,
+                _ => unreachable!(),
+            };
+            x.then_branch.stmts.insert(0, hit(arm));
+            self.block(&mut x.then_branch)?;
+            if x.else_branch.is_none() {
+                let hit = hit(count - 1);
+                x.else_branch = Some((Default::default(), syn::parse_quote!({ #hit })));
+                return Ok(());
+            }
+            node = x.else_branch.as_mut().unwrap().1.as_mut();
+        }
+        match node {
+            Expr::Block(x) => {
+                x.block.stmts.insert(0, hit(count - 1));
+                self.block(&mut x.block)
+            }
+            _ => Err(syn::Error::new(
+                node.span(),
+                "Unsupported if/else structure",
+            )),
+        }
+    }
+
+    fn matches(&mut self, expr: &mut Expr) -> Result<()> {
+        let arms = match &*expr {
+            Expr::Match(m) => m.arms.iter().map(|arm| arm_label(&arm.pat)).collect(),
+            _ => unreachable!(),
+        };
+        let hit = self.site(expr, arms);
+        if let Expr::Match(m) = expr {
+            for (ndx, arm) in m.arms.iter_mut().enumerate() {
+                self.expr(&mut arm.body)?;
+                let body = &arm.body;
+                let hit = hit(ndx);
+                *arm.body = syn::parse_quote!({
+                    #hit
+                    #body
+                });
+            }
+        }
+        Ok(())
+    }
+}
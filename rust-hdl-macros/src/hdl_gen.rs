@@ -1,14 +1,134 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::ops::Index;
 
 use quote::format_ident;
 use quote::quote;
 use syn::spanned::Spanned;
-use syn::{BinOp, Expr, Pat, PathSegment, Result, Stmt, UnOp};
+use syn::{BinOp, Expr, FnArg, GenericParam, Pat, PathSegment, Result, ReturnType, Stmt, UnOp};
 
 use crate::common;
 use crate::common::{squash, DFFSetupArgs, TS};
 
+// The `let` bindings (and loop indices) in scope at the point being
+// translated, as pairs of the Rust name and the name of the signal that
+// holds the value.  Each binding gets its own signal, so that a binding
+// that shadows another (or reuses its name in another block) does not
+// change the earlier one.
+// A binding inside a branch of an `if` or `match` is only assigned when the
+// branch is taken, so its signal is given a default value (a zero of the
+// same width) at the top of the kernel.  Otherwise, the synthesis tools
+// would infer a latch to hold it.
+#[derive(Default)]
+struct Locals {
+    scope: Vec<(String, String)>,
+    count: HashMap<String, usize>,
+    branches: usize,
+    defaults: bool,
+}
+
+thread_local! {
+    static LOCALS: RefCell<Locals> = RefCell::new(Locals::default());
+}
+
+// Clears the bindings when a kernel starts, and again when it is done (even
+// if the translation fails part way), so nothing leaks into the next one.
+struct LocalsGuard;
+
+impl LocalsGuard {
+    fn new() -> Self {
+        LOCALS.with(|x| *x.borrow_mut() = Locals::default());
+        LocalsGuard
+    }
+}
+
+impl Drop for LocalsGuard {
+    fn drop(&mut self) {
+        LOCALS.with(|x| *x.borrow_mut() = Locals::default());
+    }
+}
+
+fn local_signal(name: &str) -> Option<String> {
+    LOCALS.with(|x| {
+        x.borrow()
+            .scope
+            .iter()
+            .rev()
+            .find(|(rust, _)| rust == name)
+            .map(|(_, signal)| signal.clone())
+    })
+}
+
+fn in_branch<T>(translate: impl FnOnce() -> Result<T>) -> Result<T> {
+    LOCALS.with(|x| x.borrow_mut().branches += 1);
+    let ret = translate();
+    LOCALS.with(|x| x.borrow_mut().branches -= 1);
+    ret
+}
+
+fn bind_local(name: &str, signal: &str) {
+    LOCALS.with(|x| x.borrow_mut().scope.push((name.into(), signal.into())))
+}
+
+fn new_local_signal(name: &str) -> String {
+    new_signal(&format!("{}$let", name))
+}
+
+fn new_signal(name: &str) -> String {
+    LOCALS.with(|x| {
+        let mut locals = x.borrow_mut();
+        let count = locals.count.entry(name.into()).or_default();
+        let signal = if *count == 0 {
+            name.to_string()
+        } else {
+            format!("{}${}", name, count)
+        };
+        *count += 1;
+        signal
+    })
+}
+
+// The statement that gives `signal` its default value, if it is set inside
+// a branch.  The `value` is only used for the width of the signal.
+fn local_default(signal: &str, value: TS) -> TS {
+    let in_branch = LOCALS.with(|x| {
+        let mut locals = x.borrow_mut();
+        locals.defaults |= locals.branches > 0;
+        locals.branches > 0
+    });
+    if !in_branch {
+        return quote!();
+    }
+    quote! {
+        hdl_defaults.push(ast::VerilogStatement::Assignment(
+            ast::VerilogExpression::Signal(#signal.to_string()),
+            ast::VerilogExpression::Literal(ast::VerilogLiteral::zero(ast::VerilogLocal::new(#signal, #value).width)),
+        ));
+    }
+}
+
+fn local_scope() -> usize {
+    LOCALS.with(|x| x.borrow().scope.len())
+}
+
+fn end_local_scope(len: usize) {
+    LOCALS.with(|x| x.borrow_mut().scope.truncate(len))
+}
+
+fn local_path_signal(expr: &syn::ExprPath) -> Option<String> {
+    expr.path
+        .get_ident()
+        .and_then(|x| local_signal(&x.to_string()))
+}
+
+// Loop indices are bound to themselves, and are known when the HDL is
+// generated.  Any other binding holds a run time value.
+fn is_let(name: &str) -> bool {
+    matches!(local_signal(name), Some(signal) if signal != name)
+}
+
 pub(crate) fn hdl_gen_process(item: syn::ItemFn) -> Result<TS> {
+    let _locals = LocalsGuard::new();
     let signature = &item.sig;
     if signature.inputs.len() != 1 {
         return Err(syn::Error::new(
@@ -17,36 +137,202 @@ pub(crate) fn hdl_gen_process(item: syn::ItemFn) -> Result<TS> {
         ));
     }
     let body = hdl_block(&item.block)?;
+    if !LOCALS.with(|x| x.borrow().defaults) {
+        return Ok(quote! {
+        fn hdl(&self) -> ast::Verilog {
+            ast::Verilog::Combinatorial(#body)
+        }
+        });
+    }
     Ok(quote! {
     fn hdl(&self) -> ast::Verilog {
-        ast::Verilog::Combinatorial(#body)
+        let mut hdl_defaults = vec![];
+        let body = #body;
+        hdl_defaults.extend(body);
+        ast::Verilog::Combinatorial(hdl_defaults)
+    }
+    })
+}
+
+// An `#[hdl_function]` is translated into a companion function (with an
+// `_hdl` suffix) that takes the same arguments and returns the AST of the
+// function.  The arguments are only used for their types.  The name in the
+// HDL is qualified with the module path, the value of any const generics and
+// the widths of the arguments and result, since functions with the same name
+// can live in different modules, and each instance of a generic function is a
+// different function in the HDL.
+pub(crate) fn hdl_function_process(item: &syn::ItemFn) -> Result<TS> {
+    let _locals = LocalsGuard::new();
+    let signature = &item.sig;
+    let mut consts = vec![];
+    for param in &signature.generics.params {
+        match param {
+            GenericParam::Const(x) => consts.push(&x.ident),
+            _ => {
+                return Err(syn::Error::new(
+                    param.span(),
+                    "HDL functions can only be generic over constants",
+                ))
+            }
+        }
+    }
+    let result = match &signature.output {
+        ReturnType::Type(_, ty) => ty.as_ref(),
+        ReturnType::Default => {
+            return Err(syn::Error::new(
+                signature.span(),
+                "HDL functions must return a value",
+            ))
+        }
+    };
+    let mut args = vec![];
+    for input in &signature.inputs {
+        match input {
+            FnArg::Typed(x) => match x.pat.as_ref() {
+                Pat::Ident(arg) if arg.by_ref.is_none() && arg.subpat.is_none() => {
+                    let name = arg.ident.to_string();
+                    bind_local(&name, &name);
+                    args.push(&arg.ident);
+                }
+                _ => {
+                    return Err(syn::Error::new(
+                        x.pat.span(),
+                        "HDL function arguments must be simple names",
+                    ))
+                }
+            },
+            FnArg::Receiver(x) => {
+                return Err(syn::Error::new(x.span(), "HDL functions cannot take self"))
+            }
+        }
+    }
+    let (tail, stmts) = match item.block.stmts.split_last() {
+        Some((Stmt::Expr(tail), stmts)) => (tail, stmts),
+        _ => {
+            return Err(syn::Error::new(
+                item.block.span(),
+                "HDL functions must end with an expression that gives the result",
+            ))
+        }
+    };
+    let mut body = vec![];
+    for statement in stmts {
+        body.push(hdl_statement(statement)?);
+    }
+    let value = hdl_compute(tail)?;
+    let vis = &item.vis;
+    let ident = &signature.ident;
+    let hdl_ident = format_ident!("{}_hdl", ident);
+    let ident = ident.to_string();
+    let generics = &signature.generics;
+    let where_clause = &generics.where_clause;
+    let inputs = &signature.inputs;
+    let arg_names = args.iter().map(|x| x.to_string());
+    Ok(quote! {
+    #vis fn #hdl_ident #generics (#inputs) -> ast::VerilogFunction #where_clause {
+        let name = [
+            module_path!().replace("::", "$"),
+            #ident.to_string()
+            #(, #consts.to_string())*
+            #(, ast::VerilogLocal::new("", &#args).width.to_string())*,
+            ast::VerilogLocal::new("", &<#result as Default>::default()).width.to_string(),
+        ]
+        .join("$");
+        let mut hdl_defaults: Vec<ast::VerilogStatement> = vec![];
+        let mut ret = vec![];
+        #(#body)*
+        let value = #value;
+        ret.push(ast::VerilogStatement::Assignment(ast::VerilogExpression::Signal(name.clone()), value));
+        hdl_defaults.extend(ret);
+        ast::VerilogFunction {
+            args: vec![#(ast::VerilogLocal::new(#arg_names, &#args)),*],
+            result: ast::VerilogLocal::new(&name, &<#result as Default>::default()),
+            body: hdl_defaults,
+            name,
+        }
     }
     })
 }
 
 fn hdl_block(block: &syn::Block) -> Result<TS> {
+    let scope = local_scope();
     let mut stmt = vec![];
     for statement in &block.stmts {
         stmt.push(hdl_statement(statement)?);
     }
+    end_local_scope(scope);
     Ok(quote! {
     {
         let mut ret = vec![];
-        #(ret.push(#stmt));*;
+        #(#stmt)*
         ret
     }
     })
 }
 
 fn hdl_statement(statement: &syn::Stmt) -> Result<TS> {
-    match statement {
-        Stmt::Expr(e) => hdl_inner_statement(e),
-        Stmt::Semi(e, _) => hdl_inner_statement(e),
-        _ => Err(syn::Error::new(
-            statement.span(),
-            "Local definitions and items are not allowed in HDL kernels",
-        )),
-    }
+    let statement = match statement {
+        Stmt::Expr(e) => hdl_inner_statement(e)?,
+        Stmt::Semi(e, _) => hdl_inner_statement(e)?,
+        Stmt::Local(local) => return hdl_local(local),
+        _ => {
+            return Err(syn::Error::new(
+                statement.span(),
+                "Items are not allowed in HDL kernels",
+            ))
+        }
+    };
+    // Reading from a dynamically indexed array adds statements to `ret`, so
+    // the statement is built before it is pushed
+    Ok(quote!({
+        let statement = #statement;
+        ret.push(statement);
+    }))
+}
+
+// A `let` binding becomes a local signal of the module, which is assigned
+// the value of the initializer.  The binding is also kept in the generated
+// code (with a placeholder value), so that the type of the signal is
+// inferred, and so that later expressions that need it (like the width of a
+// struct field) can refer to it.
+fn hdl_local(local: &syn::Local) -> Result<TS> {
+    let (pat, ty) = match &local.pat {
+        Pat::Type(x) => (x.pat.as_ref(), Some(x.ty.as_ref())),
+        x => (x, None),
+    };
+    let ident = match pat {
+        Pat::Ident(x) if x.by_ref.is_none() && x.subpat.is_none() => &x.ident,
+        _ => {
+            return Err(syn::Error::new(
+                local.pat.span(),
+                "let bindings in HDL kernels must bind a single name",
+            ))
+        }
+    };
+    let init = match &local.init {
+        Some((_, init)) => init.as_ref(),
+        None => {
+            return Err(syn::Error::new(
+                local.span(),
+                "let bindings in HDL kernels must be initialized",
+            ))
+        }
+    };
+    let value = hdl_compute(init)?;
+    let name = ident.to_string();
+    let signal = new_local_signal(&name);
+    bind_local(&name, &signal);
+    let ty = ty.map(|x| quote!(: #x));
+    let default = local_default(&signal, quote!(&#ident));
+    Ok(quote! {
+        let #ident #ty = logic::hdl_local(|| #init);
+        #default
+        ret.push(ast::VerilogStatement::Local(ast::VerilogLocal::new(#signal, &#ident)));
+        {
+            let value = #value;
+            ret.push(ast::VerilogStatement::Assignment(ast::VerilogExpression::Signal(#signal.to_string()), value));
+        }
+    })
 }
 
 fn hdl_for_loop(expr: &syn::ExprForLoop) -> Result<TS> {
@@ -54,15 +340,23 @@ fn hdl_for_loop(expr: &syn::ExprForLoop) -> Result<TS> {
         if let Expr::Range(range) = &expr.expr.as_ref() {
             if let Some(from) = range.from.as_ref() {
                 if let Some(to) = range.to.as_ref() {
-                    let block = hdl_block(&expr.body)?;
-                    let loop_index = quote!(#loop_index).to_string();
+                    let index = loop_index.ident.to_string();
+                    let scope = local_scope();
+                    bind_local(&index, &index);
+                    let block = hdl_block(&expr.body);
+                    end_local_scope(scope);
+                    let block = block?;
+                    let ident = &loop_index.ident;
                     return Ok(quote!(
                         ast::VerilogStatement::Loop(
                             ast::VerilogLoop {
-                            index: #loop_index.into(),
+                            index: #index.into(),
                             from: #from.into(),
                             to: #to.into(),
-                            block: #block,
+                            block: {
+                                let #ident = #from;
+                                #block
+                            },
                         }
                     )));
                 }
@@ -97,11 +391,78 @@ fn hdl_assignment(expr: &syn::ExprAssign) -> Result<TS> {
             expr.span(),
             "Indexed assignments do not translate",
         ))
+    } else if let Some(index) = common::dynamic_index(&expr.left, &is_let) {
+        // Assign to whichever element of the array is selected
+        let mut assign = expr.clone();
+        *assign.left = array_element(&expr.left)?;
+        let assign = hdl_non_indexed_assignment(&assign)?;
+        select_element(index, assign)
     } else {
         hdl_non_indexed_assignment(expr)
     }
 }
 
+// An array indexed by a value that is only known at run time is handled by
+// looping over the elements, and comparing the index to each in turn.  The
+// element is referred to with the loop index (`__index`), so that it maps to
+// one of the flattened signals (`name$0`, `name$1`, etc.) in the HDL.
+fn array_element(expr: &Expr) -> Result<Expr> {
+    let element = common::replace_dynamic_index(expr, &syn::parse_quote!(__index), &is_let);
+    if common::dynamic_index(&element, &is_let).is_some() {
+        return Err(syn::Error::new(
+            expr.span(),
+            "Only one array can be indexed at run time in an HDL expression",
+        ));
+    }
+    Ok(element)
+}
+
+fn select_element(index: &syn::ExprIndex, statement: TS) -> Result<TS> {
+    let array = &index.expr;
+    let select = hdl_compute(&index.index)?;
+    Ok(quote!({
+        ast::VerilogStatement::Loop(ast::VerilogLoop {
+            index: "__index".into(),
+            from: 0_usize.into(),
+            to: (#array).len().into(),
+            block: vec![ast::VerilogStatement::If(ast::VerilogConditional {
+                test: ast::VerilogExpression::Binary(
+                    Box::new(#select),
+                    ast::VerilogOp::Eq,
+                    Box::new(ast::VerilogExpression::Signal("__index".to_string())),
+                ),
+                then: vec![#statement],
+                otherwise: ast::VerilogBlockOrConditional::None,
+            })],
+        })
+    }))
+}
+
+// The selected element is copied into a local signal, which is what the
+// expression reads.  The local is cleared first, in case the index is out
+// of range, so that it does not become a latch.  An out of range index thus
+// reads as zero in the HDL, where the Rust kernel would panic.
+fn hdl_select(method: &syn::ExprMethodCall, index: &syn::ExprIndex) -> Result<TS> {
+    let receiver = method.receiver.as_ref();
+    let element = array_element(receiver)?;
+    let name = common::fixup_ident(quote!(#element).to_string()).replace("[__index]", "");
+    let signal = new_signal(&format!("{}$sel", name));
+    let default = local_default(&signal, quote!(&logic::hdl_local(|| #method)));
+    let value = hdl_compute(&element)?;
+    let assign = quote!(ast::VerilogStatement::Assignment(ast::VerilogExpression::Signal(#signal.to_string()), #value));
+    let select = select_element(index, assign)?;
+    Ok(quote!({
+        #default
+        let local = ast::VerilogLocal::new(#signal, &logic::hdl_local(|| #method));
+        let zero = ast::VerilogLiteral::zero(local.width);
+        ret.push(ast::VerilogStatement::Local(local));
+        ret.push(ast::VerilogStatement::Assignment(ast::VerilogExpression::Signal(#signal.to_string()), ast::VerilogExpression::Literal(zero)));
+        let statement = #select;
+        ret.push(statement);
+        ast::VerilogExpression::Signal(#signal.to_string())
+    }))
+}
+
 fn hdl_non_indexed_assignment(expr: &syn::ExprAssign) -> Result<TS> {
     let target;
     if let Expr::Field(p) = &*expr.left {
@@ -141,6 +502,11 @@ fn hdl_non_indexed_assignment(expr: &syn::ExprAssign) -> Result<TS> {
             };
         }
         target = hdl_map_field_assign(p)?;
+    } else if let Some(signal) = match &*expr.left {
+        Expr::Path(p) => local_path_signal(p),
+        _ => None,
+    } {
+        target = quote!(ast::VerilogExpression::Signal(#signal.to_string()));
     } else {
         return Err(syn::Error::new(
             expr.span(),
@@ -169,7 +535,11 @@ fn hdl_map_field_assign(expr: &syn::ExprField) -> Result<TS> {
 fn hdl_map_field(expr: &syn::ExprField) -> Result<TS> {
     // Check for .val().field - as this indicates a struct membership
     let base = &expr.base;
-    if common::fixup_ident(quote!(#base).to_string()).ends_with("val()") {
+    let base_is_local = match base.as_ref() {
+        Expr::Path(p) => local_path_signal(p).is_some(),
+        _ => false,
+    };
+    if base_is_local || common::fixup_ident(quote!(#base).to_string()).ends_with("val()") {
         return if let syn::Member::Named(x) = &expr.member {
             let field = x.to_string();
             let get_width_name = format_ident!("get_my_width_{}", field);
@@ -195,6 +565,9 @@ fn hdl_map_field(expr: &syn::ExprField) -> Result<TS> {
 }
 
 fn hdl_map_path(expr: &syn::ExprPath) -> Result<TS> {
+    if let Some(signal) = local_path_signal(expr) {
+        return Ok(quote!(ast::VerilogExpression::Signal(#signal.to_string())));
+    }
     let expr_expanded = common::fixup_ident(quote!(#expr).to_string());
     if expr_expanded.ends_with("$next") {
         return Err(syn::Error::new(
@@ -210,12 +583,12 @@ fn hdl_map_path(expr: &syn::ExprPath) -> Result<TS> {
 
 fn hdl_conditional(conditions: &syn::ExprIf) -> Result<TS> {
     let test_condition = hdl_compute(&conditions.cond)?;
-    let then_branch = hdl_block(&conditions.then_branch)?;
+    let then_branch = in_branch(|| hdl_block(&conditions.then_branch))?;
     let mut else_branch = quote!({ ast::VerilogBlockOrConditional::None });
     if let Some((_, e_branch)) = &conditions.else_branch {
         match e_branch.as_ref() {
             Expr::Block(block) => {
-                let else_branch_block = hdl_block(&block.block)?;
+                let else_branch_block = in_branch(|| hdl_block(&block.block))?;
                 else_branch = quote!({ast::VerilogBlockOrConditional::Block(#else_branch_block)});
             }
             Expr::If(cond) => {
@@ -236,13 +609,25 @@ fn hdl_conditional(conditions: &syn::ExprIf) -> Result<TS> {
 }
 
 fn hdl_match(m: &syn::ExprMatch) -> Result<TS> {
-    let test = hdl_compute(m.expr.as_ref())?;
+    let expr = m.expr.as_ref();
+    let value = hdl_compute(expr)?;
     let mut condition = vec![];
     let mut blocks = vec![];
     for arm in &m.arms {
         condition.push(hdl_pattern(&arm.pat)?);
-        blocks.push(hdl_body(&arm.body)?);
+        blocks.push(in_branch(|| hdl_arm(expr, &value, arm))?);
     }
+    // The variants of an enum with payloads are told apart by the tag
+    let is_enum = m.arms.iter().any(|arm| match &arm.pat {
+        Pat::Path(x) => x.path.segments.len() > 1,
+        Pat::Struct(_) | Pat::TupleStruct(_) => true,
+        _ => false,
+    });
+    let test = if is_enum {
+        quote!(logic::hdl_match_test(#value, &logic::hdl_local(|| #expr)))
+    } else {
+        value
+    };
     /*    if condition.len() == 0 || !condition.last().unwrap().eq("default") {
         return Err(syn::Error::new(
             m.span(),
@@ -258,9 +643,121 @@ fn hdl_match(m: &syn::ExprMatch) -> Result<TS> {
     }))
 }
 
+// The fields of the variant matched by an arm are bound to local signals,
+// which are sliced out of the payload.  Each binding is also declared in
+// the generated code (by matching on a placeholder value), so that its type
+// is inferred.
+fn hdl_arm(expr: &Expr, value: &TS, arm: &syn::Arm) -> Result<TS> {
+    let (path, fields) = match &arm.pat {
+        Pat::Struct(x) => (
+            &x.path,
+            x.fields
+                .iter()
+                .map(|f| (f.member.clone(), f.pat.as_ref()))
+                .collect::<Vec<_>>(),
+        ),
+        Pat::TupleStruct(x) => {
+            let mut fields = vec![];
+            for (ndx, pat) in x.pat.elems.iter().enumerate() {
+                match pat {
+                    Pat::Rest(_) if ndx + 1 == x.pat.elems.len() => {}
+                    _ => fields.push((syn::Member::Unnamed(ndx.into()), pat)),
+                }
+            }
+            (&x.path, fields)
+        }
+        _ => return hdl_body(&arm.body),
+    };
+    let variant = path.segments.last().unwrap().ident.to_string();
+    let scope = local_scope();
+    let mut bindings = vec![];
+    for (member, pat) in fields {
+        let ident = match pat {
+            Pat::Ident(x) if x.by_ref.is_none() && x.subpat.is_none() => &x.ident,
+            Pat::Wild(_) => continue,
+            _ => {
+                end_local_scope(scope);
+                return Err(syn::Error::new(
+                    pat.span(),
+                    "Fields of enum variants can only be bound to names in HDL",
+                ));
+            }
+        };
+        let name = ident.to_string();
+        let field = quote!(#member).to_string();
+        let signal = new_local_signal(&name);
+        bind_local(&name, &signal);
+        let default = local_default(&signal, quote!(&#ident));
+        bindings.push(quote! {
+            let #ident = logic::hdl_local(|| match #expr {
+                #path { #member: x, .. } => x,
+                #[allow(unreachable_patterns)]
+                _ => unreachable!(),
+            });
+            #default
+            ret.push(ast::VerilogStatement::Local(ast::VerilogLocal::new(#signal, &#ident)));
+            {
+                let value = ast::VerilogExpression::Slice(
+                    Box::new(#value),
+                    logic::LogicPayload::get_my_width(&logic::hdl_local(|| #expr), #variant, #field),
+                    Box::new(ast::VerilogExpression::Literal(logic::LogicPayload::get_my_offset(&logic::hdl_local(|| #expr), #variant, #field).into())),
+                );
+                ret.push(ast::VerilogStatement::Assignment(ast::VerilogExpression::Signal(#signal.to_string()), value));
+            }
+        });
+    }
+    let body = hdl_body(&arm.body);
+    end_local_scope(scope);
+    let body = body?;
+    Ok(quote!({
+        let mut ret = vec![];
+        #(#bindings)*
+        ret.extend(#body);
+        ret
+    }))
+}
+
+// A value of an enum with payloads starts out as the tag of the variant,
+// and the fields are then written into the payload.
+fn hdl_variant(expr: &Expr, path: &syn::Path, fields: Vec<(syn::Member, &Expr)>) -> Result<TS> {
+    let tag = common::fixup_ident(quote!(#path).to_string());
+    let variant = path.segments.last().unwrap().ident.to_string();
+    let signal = new_signal(&format!("{}$new", tag));
+    let default = local_default(&signal, quote!(&logic::hdl_local(|| #expr)));
+    let mut assignments = vec![];
+    for (member, value) in fields {
+        let field = quote!(#member).to_string();
+        let value = hdl_compute(value)?;
+        assignments.push(quote! {
+            {
+                let statement = ast::VerilogStatement::SliceAssignment {
+                    base: ast::VerilogExpression::Signal(#signal.to_string()),
+                    width: logic::LogicPayload::get_my_width(&logic::hdl_local(|| #expr), #variant, #field),
+                    offset: ast::VerilogExpression::Literal(logic::LogicPayload::get_my_offset(&logic::hdl_local(|| #expr), #variant, #field).into()),
+                    replacement: #value,
+                };
+                ret.push(statement);
+            }
+        });
+    }
+    Ok(quote!({
+        #default
+        ret.push(ast::VerilogStatement::Local(ast::VerilogLocal::new(#signal, &logic::hdl_local(|| #expr))));
+        ret.push(ast::VerilogStatement::Assignment(ast::VerilogExpression::Signal(#signal.to_string()), ast::VerilogExpression::Signal(#tag.to_string())));
+        #(#assignments)*
+        ast::VerilogExpression::Signal(#signal.to_string())
+    }))
+}
+
 fn hdl_compute(m: &syn::Expr) -> Result<TS> {
     //println!("Compute : {} {:?}", quote!(#m).to_string(), m);
     match m {
+        Expr::Index(_) | Expr::Field(_) if common::dynamic_index(m, &is_let).is_some() => {
+            Err(syn::Error::new(
+                m.span(),
+                "Arrays indexed at run time must be read with .val() in HDL",
+            ))
+        }
         Expr::Path(path) => hdl_map_path(path),
         Expr::Field(field) => hdl_map_field(field),
         Expr::Paren(paren) => {
@@ -272,6 +769,14 @@ fn hdl_compute(m: &syn::Expr) -> Result<TS> {
         Expr::Call(call) => hdl_call(call),
         Expr::MethodCall(method) => hdl_method(method),
         Expr::Lit(lit) => hdl_literal(lit),
+        Expr::Struct(x) if x.rest.is_none() => {
+            let fields = x
+                .fields
+                .iter()
+                .map(|f| (f.member.clone(), &f.expr))
+                .collect();
+            hdl_variant(m, &x.path, fields)
+        }
         Expr::Index(_ndx) => {
             let ndx_expanded = common::fixup_ident(quote!(#m).to_string());
             Ok(quote!(ast::VerilogExpression::Signal(#ndx_expanded.to_string())))
@@ -391,6 +896,36 @@ fn hdl_call(call: &syn::ExprCall) -> Result<TS> {
         hdl_join_or_link(call, "join")
     } else if squash(&funcname).contains("::link") {
         hdl_join_or_link(call, "link")
+    } else if let Some(p) = match call.func.as_ref() {
+        Expr::Path(p) if is_variant(&p.path) => Some(p),
+        _ => None,
+    } {
+        let fields = call
+            .args
+            .iter()
+            .enumerate()
+            .map(|(ndx, x)| (syn::Member::Unnamed(ndx.into()), x))
+            .collect();
+        hdl_variant(&Expr::Call(call.clone()), &p.path, fields)
+    } else if let Some(p) = match call.func.as_ref() {
+        Expr::Path(p) if p.qself.is_none() && is_free_function(&p.path) => Some(p),
+        _ => None,
+    } {
+        // A call to an `#[hdl_function]`, which provides the `_hdl` companion.
+        // The companion keeps the span of the call, so that calling any other
+        // function is reported there.
+        let mut call_path = p.path.clone();
+        if let Some(last) = call_path.segments.last_mut() {
+            last.ident = format_ident!("{}_hdl", last.ident, span = last.ident.span());
+        }
+        let args = call.args.iter();
+        let mut values = vec![];
+        for arg in &call.args {
+            values.push(hdl_compute(arg)?);
+        }
+        Ok(quote!({
+            ast::VerilogExpression::Call(Box::new(#call_path(#(logic::hdl_local(|| #args)),*)), vec![#(#values),*])
+        }))
     } else {
         Err(syn::Error::new(
             call.span(),
@@ -402,6 +937,33 @@ fn hdl_call(call: &syn::ExprCall) -> Result<TS> {
     }
 }
 
+// An `#[hdl_function]` is a free function, so calls to the standard library
+// or to associated functions (like `u8::from`) are not supported in HDL.
+fn is_free_function(path: &syn::Path) -> bool {
+    let std = path
+        .segments
+        .first()
+        .is_some_and(|x| ["std", "core", "alloc"].contains(&x.ident.to_string().as_str()));
+    let primitives = [
+        "bool", "u8", "u16", "u32", "u64", "u128", "usize", "i8", "i16", "i32", "i64", "i128",
+        "isize",
+    ];
+    let associated = path.segments.iter().rev().skip(1).any(|x| {
+        let name = x.ident.to_string();
+        name.starts_with(char::is_uppercase) || primitives.contains(&name.as_str())
+    });
+    !std && !associated
+}
+
+// A call like `Command::Read(addr)` builds a tuple variant of an enum
+fn is_variant(path: &syn::Path) -> bool {
+    path.segments.len() > 1
+        && path
+            .segments
+            .last()
+            .is_some_and(|x| x.ident.to_string().starts_with(char::is_uppercase))
+}
+
 fn hdl_method_set(method: &syn::ExprMethodCall) -> Result<TS> {
     let method_name = method.method.to_string();
     let field_set_match = regex::Regex::new(r"set_value_([a-zA-Z][a-zA-Z0-9_]*)").unwrap();
@@ -547,7 +1109,11 @@ fn hdl_method(method: &syn::ExprMethodCall) -> Result<TS> {
                 ast::VerilogExpression::Signed(Box::new(#target))
             }))
         }
-        "val" | "into" | "index" | "to_bits" => {
+        "val" => match common::dynamic_index(method.receiver.as_ref(), &is_let) {
+            Some(index) => hdl_select(method, index),
+            None => hdl_compute(method.receiver.as_ref()),
+        },
+        "into" | "index" | "to_bits" => {
             let receiver = method.receiver.as_ref();
             hdl_compute(receiver)
         }
@@ -563,7 +1129,12 @@ fn hdl_body(body: &syn::Expr) -> Result<TS> {
         hdl_block(&b.block)
     } else {
         let statement = hdl_inner_statement(body)?;
-        Ok(quote!({ vec![#statement] }))
+        Ok(quote!({
+            let mut ret = vec![];
+            let statement = #statement;
+            ret.push(statement);
+            ret
+        }))
     }
 }
 
@@ -572,6 +1143,14 @@ fn hdl_pattern(pat: &Pat) -> Result<String> {
         Pat::Ident(ident) => Ok(ident.ident.to_string()),
         Pat::Lit(lit) => Ok(quote!(#lit).to_string()),
         Pat::Path(pat) => Ok(common::fixup_ident(quote!(#pat).to_string())),
+        Pat::Struct(pat) => {
+            let path = &pat.path;
+            Ok(common::fixup_ident(quote!(#path).to_string()))
+        }
+        Pat::TupleStruct(pat) => {
+            let path = &pat.path;
+            Ok(common::fixup_ident(quote!(#path).to_string()))
+        }
         Pat::Wild(_pat) => Ok("default".to_string()),
         _ => Err(syn::Error::new(
             pat.span(),
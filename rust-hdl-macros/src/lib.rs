@@ -1,5 +1,6 @@
 mod common;
 mod connect_gen;
+mod coverage_gen;
 mod hdl_gen;
 mod logic_block;
 mod logic_interface;
@@ -11,7 +12,8 @@ use syn::DeriveInput;
 
 use crate::common::TS;
 use crate::connect_gen::connect_gen;
-use crate::hdl_gen::hdl_gen_process;
+use crate::coverage_gen::coverage_gen;
+use crate::hdl_gen::{hdl_function_process, hdl_gen_process};
 use crate::logic_block::get_impl_for_logic_block;
 use crate::logic_interface::get_impl_for_logic_interface;
 use crate::logic_state::get_logic_state_impls;
@@ -39,7 +41,7 @@ pub fn logic_interface(input: TokenStream) -> TokenStream {
     }
 }
 
-#[proc_macro_derive(LogicState)]
+#[proc_macro_derive(LogicState, attributes(encoding))]
 pub fn logic_state(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
 
@@ -60,13 +62,26 @@ pub fn logic_struct(input: TokenStream) -> TokenStream {
 }
 
 #[proc_macro_attribute]
-pub fn hdl_gen(_attr: TokenStream, item: TokenStream) -> TokenStream {
-    let orig = TS::from(item.clone());
+pub fn hdl_gen(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let attr = TS::from(attr);
     let parse = parse_macro_input!(item as syn::ItemFn);
     let connects = match connect_gen(&parse) {
         Err(e) => return e.to_compile_error().into(),
         Ok(t) => t,
     };
+    // Branch coverage instrumentation is only added with `#[hdl_gen(coverage)]`
+    let (orig, sites) = match attr.to_string().as_str() {
+        "" => (parse.clone(), TS::new()),
+        "coverage" => match coverage_gen(&parse) {
+            Err(e) => return e.to_compile_error().into(),
+            Ok(t) => t,
+        },
+        _ => {
+            return syn::Error::new_spanned(attr, "Expected `#[hdl_gen]` or `#[hdl_gen(coverage)]`")
+                .to_compile_error()
+                .into()
+        }
+    };
     match hdl_gen_process(parse) {
         Err(e) => e.to_compile_error().into(),
         Ok(hdl_code) => TokenStream::from(quote! {
@@ -78,7 +93,28 @@ pub fn hdl_gen(_attr: TokenStream, item: TokenStream) -> TokenStream {
         #[automatically_derived]
             #connects
 
+        #[automatically_derived]
+            #sites
+
+        #[allow(dead_code)]
+        #[allow(unused_variables)]
+        #[automatically_derived]
+            #hdl_code
+        }),
+    }
+}
+
+#[proc_macro_attribute]
+pub fn hdl_function(_attr: TokenStream, item: TokenStream) -> TokenStream {
+    let parse = parse_macro_input!(item as syn::ItemFn);
+    match hdl_function_process(&parse) {
+        Err(e) => e.to_compile_error().into(),
+        Ok(hdl_code) => TokenStream::from(quote! {
+            #parse
+
+        #[doc(hidden)]
         #[allow(dead_code)]
+        #[allow(unused_mut)]
         #[allow(unused_variables)]
         #[automatically_derived]
             #hdl_code
@@ -8,7 +8,10 @@ pub(crate) fn get_impl_for_logic_block(input: &syn::DeriveInput) -> Result<TS> {
     let fields = common::get_field_names(input)?;
     let update_all = common::get_update_all(fields.clone())?;
     let has_changed = common::get_has_changed(fields.clone())?;
+    let update_changed = common::get_update_changed(fields.clone())?;
     let connect_all = common::get_connect_all(fields.clone())?;
+    let checkpoint_all = common::get_checkpoint_all(fields.clone())?;
+    let mark_unknown_all = common::get_mark_unknown_all(fields.clone())?;
     let accept = get_accept(fields)?;
     let name = &input.ident;
     let (impl_generics, ty_generics, _where_clause) = &input.generics.split_for_impl();
@@ -18,6 +21,9 @@ pub(crate) fn get_impl_for_logic_block(input: &syn::DeriveInput) -> Result<TS> {
             #update_all
             #has_changed
             #accept
+            #update_changed
+            #checkpoint_all
+            #mark_unknown_all
         }
     })
 }
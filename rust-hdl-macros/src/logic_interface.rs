@@ -1,4 +1,7 @@
-use crate::common::{get_connect_all, get_has_changed, get_update_all, TS};
+use crate::common::{
+    get_checkpoint_all, get_connect_all, get_has_changed, get_mark_unknown_all, get_update_all,
+    get_update_changed_forwarded, TS,
+};
 use crate::common::{get_field_names, get_field_types};
 use quote::quote;
 use std::collections::HashMap;
@@ -12,7 +15,10 @@ pub(crate) fn get_impl_for_logic_interface(input: &syn::DeriveInput) -> Result<T
     let link_hdl = get_link_hdl(fields.clone(), field_types.clone())?;
     let update_all = get_update_all(fields.clone())?;
     let has_changed = get_has_changed(fields.clone())?;
+    let update_changed = get_update_changed_forwarded(fields.clone())?;
     let connect_all = get_connect_all(fields.clone())?;
+    let checkpoint_all = get_checkpoint_all(fields.clone())?;
+    let mark_unknown_all = get_mark_unknown_all(fields.clone())?;
     let join_connect = get_join_connect(fields.clone())?;
     let join_hdl = get_join_hdl(fields.clone(), field_types)?;
     let accept = get_accept(fields.clone())?;
@@ -40,6 +46,9 @@ pub(crate) fn get_impl_for_logic_interface(input: &syn::DeriveInput) -> Result<T
             #update_all
             #has_changed
             #accept
+            #update_changed
+            #checkpoint_all
+            #mark_unknown_all
         }
 
         impl #impl_generics logic::LogicLink for #name #ty_generics {
@@ -1,25 +1,14 @@
 use crate::common::*;
-use quote::quote;
+use quote::{format_ident, quote};
+use std::convert::TryFrom;
 use syn::spanned::Spanned;
-use syn::{Data, Result};
+use syn::{Data, Expr, ExprLit, Fields, Lit, Member, Result};
 
 fn get_variant_names(input: &syn::DeriveInput) -> Result<Vec<TS>> {
     let mut variants = vec![];
     match &input.data {
         Data::Enum(ed) => {
             for variant in &ed.variants {
-                if !variant.fields.is_empty() {
-                    return Err(syn::Error::new(
-                        variant.span(),
-                        "enum variants cannot have fields",
-                    ));
-                }
-                if variant.discriminant.is_some() {
-                    return Err(syn::Error::new(
-                        variant.span(),
-                        "enum variants cannot have discriminants",
-                    ));
-                }
                 let name = &variant.ident;
                 variants.push(quote!(#name));
             }
@@ -34,28 +23,138 @@ fn get_variant_names(input: &syn::DeriveInput) -> Result<Vec<TS>> {
     Ok(variants)
 }
 
+fn get_encoding_attribute(input: &syn::DeriveInput) -> Result<Option<syn::Ident>> {
+    let mut encoding = None;
+    for attr in &input.attrs {
+        if attr.path.is_ident("encoding") {
+            encoding = Some(attr.parse_args::<syn::Ident>()?);
+        }
+    }
+    Ok(encoding)
+}
+
+// The value that encodes each variant.  The variants are numbered in order,
+// unless the enum picks another encoding with `#[encoding(...)]`, or gives
+// its variants explicit discriminants (which follow the rules of Rust, so a
+// variant without one is one more than the variant before it).
+fn get_encoding(input: &syn::DeriveInput, ed: &syn::DataEnum) -> Result<Vec<u128>> {
+    let count = ed.variants.len() as u128;
+    let encoding = get_encoding_attribute(input)?;
+    let explicit = ed.variants.iter().any(|x| x.discriminant.is_some());
+    match encoding {
+        Some(x) if explicit => Err(syn::Error::new(
+            x.span(),
+            "An enum with explicit discriminants cannot also choose an encoding",
+        )),
+        Some(x) if x == "binary" => Ok((0..count).collect()),
+        Some(x) if x == "gray" => Ok((0..count).map(|x| x ^ (x >> 1)).collect()),
+        Some(x) if x == "one_hot" => {
+            if count > 128 {
+                return Err(syn::Error::new(
+                    x.span(),
+                    "One hot encoding is limited to 128 variants",
+                ));
+            }
+            Ok((0..count).map(|x| 1 << x).collect())
+        }
+        Some(x) => Err(syn::Error::new(
+            x.span(),
+            "Unknown encoding (expected binary, gray or one_hot)",
+        )),
+        None => {
+            let mut values: Vec<u128> = vec![];
+            for variant in &ed.variants {
+                let value = match &variant.discriminant {
+                    Some((
+                        _,
+                        Expr::Lit(ExprLit {
+                            lit: Lit::Int(x), ..
+                        }),
+                    )) => x.base10_parse::<u128>()?,
+                    Some((_, x)) => {
+                        return Err(syn::Error::new(
+                            x.span(),
+                            "Discriminants must be integer literals",
+                        ))
+                    }
+                    None => values.last().map(|x| x + 1).unwrap_or_default(),
+                };
+                if values.contains(&value) {
+                    return Err(syn::Error::new(
+                        variant.span(),
+                        "Two variants cannot have the same discriminant",
+                    ));
+                }
+                values.push(value);
+            }
+            Ok(values)
+        }
+    }
+}
+
 pub fn get_logic_state_impls(input: &syn::DeriveInput) -> Result<TS> {
     let variants = get_variant_names(input)?;
+    let ed = match &input.data {
+        Data::Enum(ed) => ed,
+        _ => unreachable!(),
+    };
+    if ed.variants.iter().any(|x| !x.fields.is_empty()) {
+        if let Some(x) = get_encoding_attribute(input)? {
+            return Err(syn::Error::new(
+                x.span(),
+                "Enums with payloads cannot choose an encoding",
+            ));
+        }
+        if let Some(x) = ed.variants.iter().find(|x| x.discriminant.is_some()) {
+            return Err(syn::Error::new(
+                x.span(),
+                "Enums with payloads cannot have discriminants",
+            ));
+        }
+        return get_payload_impls(input, ed);
+    }
     let first_variant = variants[0].clone();
-    let num_variants = variants.len();
-    let discriminants: Vec<usize> = (0_usize..variants.len()).collect();
+    let values = get_encoding(input, ed)?;
+    let width = values
+        .iter()
+        .map(|x| (u128::BITS - x.leading_zeros()) as usize)
+        .max()
+        .unwrap_or_default();
+    // Values that fit are kept as usize, so their Verilog literals are unchanged
+    let literals = values
+        .iter()
+        .map(|x| match usize::try_from(*x) {
+            Ok(x) => quote!(#x),
+            Err(_) => quote!(#x),
+        })
+        .collect::<Vec<_>>();
     let name = &input.ident;
     let name_as_string = name.to_string();
     let variants_as_strings = variants
         .iter()
         .map(|x| format!("{name_as_string}::{x}"))
         .collect::<Vec<String>>();
+    // Only an encoding other than numbering the variants in order is described
+    // with the value of each label
+    let kind = if values.iter().zip(0_u128..).all(|(x, ndx)| *x == ndx) {
+        quote!(TypeKind::Enum(vec![#(#variants_as_strings.to_string(),)*]))
+    } else {
+        quote!(TypeKind::EncodedEnum(vec![#(EnumLabel {
+            name: #variants_as_strings.to_string(),
+            value: #values,
+        },)*]))
+    };
     let variants_only_as_strings = variants
         .iter()
         .map(|x| x.to_string())
         .collect::<Vec<String>>();
     Ok(quote!(
         impl synth::Synth for #name {
-            const BITS: usize = clog2(#num_variants);
+            const BITS: usize = #width;
             fn descriptor() -> type_descriptor::TypeDescriptor {
                 TypeDescriptor {
                     name: #name_as_string.to_string(),
-                    kind: TypeKind::Enum(vec![#(#variants_as_strings.to_string(),)*])
+                    kind: #kind
                 }
             }
             fn vcd(self) -> VCDValue {
@@ -65,7 +164,20 @@ pub fn get_logic_state_impls(input: &syn::DeriveInput) -> Result<TS> {
             }
             fn verilog(self) -> VerilogLiteral {
                 match self {
-                    #(#name::#variants => #discriminants.into(),)*
+                    #(#name::#variants => #literals.into(),)*
+                }
+            }
+            fn from_bits(bits: &[bool]) -> Option<Self> {
+                if bits.len() != Self::BITS {
+                    return None;
+                }
+                let value = bits
+                    .iter()
+                    .rev()
+                    .fold(0_u128, |acc, bit| (acc << 1) | u128::from(*bit));
+                match value {
+                    #(#values => Some(#name::#variants),)*
+                    _ => None,
                 }
             }
         }
@@ -73,7 +185,7 @@ pub fn get_logic_state_impls(input: &syn::DeriveInput) -> Result<TS> {
         impl Into<Bits<{#name::BITS}>> for #name {
             fn into(self) -> Bits<{#name::BITS}> {
                 match self {
-                    #(#name::#variants => #discriminants.to_bits(),)*
+                    #(#name::#variants => #literals.to_bits(),)*
                 }
             }
         }
@@ -85,3 +197,176 @@ pub fn get_logic_state_impls(input: &syn::DeriveInput) -> Result<TS> {
         }
     ))
 }
+
+// The value of a variant (or a pattern that matches it), given an
+// expression (or pattern) for each of its fields.
+fn variant_shape(name: &syn::Ident, variant: &syn::Variant, values: &[TS]) -> TS {
+    let ident = &variant.ident;
+    match &variant.fields {
+        Fields::Named(f) => {
+            let members = f.named.iter().map(|x| &x.ident);
+            quote!(#name::#ident { #(#members: #values),* })
+        }
+        Fields::Unnamed(_) => quote!(#name::#ident(#(#values),*)),
+        Fields::Unit => quote!(#name::#ident),
+    }
+}
+
+// An enum with payloads is laid out as a tagged union.  The tag (the index
+// of the variant) is in the low bits, followed by the fields of the variant,
+// which are packed like the fields of a `LogicStruct`.  The payload is as
+// wide as the widest variant, and the unused bits are zero.
+fn get_payload_impls(input: &syn::DeriveInput, ed: &syn::DataEnum) -> Result<TS> {
+    let name = &input.ident;
+    let name_as_string = name.to_string();
+    let tag_name = format!("{}$tag", name_as_string);
+    let num_variants = ed.variants.len();
+    let tag_bits = quote!(clog2(#num_variants));
+    let mut labels = vec![];
+    let mut variant_names = vec![];
+    let mut widths = vec![];
+    let mut patterns = vec![];
+    let mut into_bits = vec![];
+    let mut from_bits = vec![];
+    let mut field_variants = vec![];
+    let mut field_names = vec![];
+    let mut field_offsets = vec![];
+    let mut field_types = vec![];
+    for (ndx, variant) in ed.variants.iter().enumerate() {
+        let variant_name = variant.ident.to_string();
+        let types = variant.fields.iter().map(|x| &x.ty).collect::<Vec<_>>();
+        let offsets = (0..types.len())
+            .map(|x| {
+                let previous = &types[0..x];
+                quote!((#tag_bits #(+ <#previous>::BITS)*))
+            })
+            .collect::<Vec<_>>();
+        let binds = (0..types.len())
+            .map(|x| {
+                let bind = format_ident!("x{}", x);
+                quote!(#bind)
+            })
+            .collect::<Vec<_>>();
+        for (field_ndx, field) in variant.fields.iter().enumerate() {
+            let member = match &field.ident {
+                Some(x) => Member::Named(x.clone()),
+                None => Member::Unnamed(field_ndx.into()),
+            };
+            field_variants.push(variant_name.clone());
+            field_names.push(quote!(#member).to_string());
+        }
+        field_offsets.extend(offsets.iter().cloned());
+        field_types.extend(types.iter().cloned());
+        let pattern = variant_shape(name, variant, &binds);
+        into_bits.push(quote!(
+            #pattern => #ndx.to_bits() #(| (bit_cast::<{#name::BITS}, {<#types>::BITS}>(#binds.into()) << (#offsets as LiteralType)))*
+        ));
+        let values = types
+            .iter()
+            .zip(&offsets)
+            .map(|(ty, offset)| quote!(<#ty>::from_bits(&bits[#offset..(#offset + <#ty>::BITS)])?))
+            .collect::<Vec<_>>();
+        let value = variant_shape(name, variant, &values);
+        from_bits.push(quote!(#ndx => Some(#value)));
+        let ident = &variant.ident;
+        patterns.push(quote!(#name::#ident { .. }));
+        labels.push(format!("{}::{}", name_as_string, variant_name));
+        variant_names.push(variant_name);
+        widths.push(quote!((0_usize #(+ <#types>::BITS)*)));
+    }
+    let first = &ed.variants[0];
+    let defaults = first
+        .fields
+        .iter()
+        .map(|_| quote!(Default::default()))
+        .collect::<Vec<_>>();
+    let default = variant_shape(name, first, &defaults);
+    Ok(quote!(
+        impl logic::LogicPayload for #name {
+            fn get_my_width(&self, variant: &str, field: &str) -> usize {
+                match (variant, field) {
+                    #((#field_variants, #field_names) => <#field_types>::BITS,)*
+                    _ => panic!("{} has no field {}", variant, field),
+                }
+            }
+
+            fn get_my_offset(&self, variant: &str, field: &str) -> usize {
+                match (variant, field) {
+                    #((#field_variants, #field_names) => #field_offsets,)*
+                    _ => panic!("{} has no field {}", variant, field),
+                }
+            }
+        }
+
+        impl synth::Synth for #name {
+            const BITS: usize = #tag_bits + {
+                let mut bits = 0;
+                #(if #widths > bits {
+                    bits = #widths;
+                })*
+                bits
+            };
+            fn descriptor() -> type_descriptor::TypeDescriptor {
+                let payload = Self::BITS - #tag_bits;
+                TypeDescriptor {
+                    name: #name_as_string.to_string(),
+                    kind: TypeKind::Composite(vec![
+                        Box::new(TypeField {
+                            fieldname: "tag".to_string(),
+                            kind: TypeDescriptor {
+                                name: #tag_name.to_string(),
+                                kind: TypeKind::Enum(vec![#(#labels.to_string(),)*]),
+                            },
+                        }),
+                        Box::new(TypeField {
+                            fieldname: "payload".to_string(),
+                            kind: TypeDescriptor {
+                                name: format!("Bits::<{}>", payload),
+                                kind: TypeKind::Bits(payload),
+                            },
+                        }),
+                    ])
+                }
+            }
+            fn vcd(self) -> VCDValue {
+                let bits: Bits<{#name::BITS}> = self.into();
+                let tag = match self {
+                    #(#patterns => VCDValue::String(#variant_names.into()),)*
+                };
+                let payload = bits.get_bits::<{#name::BITS - #tag_bits}>(#tag_bits);
+                VCDValue::Composite(vec![Box::new(tag), Box::new(payload.vcd())])
+            }
+            fn verilog(self) -> VerilogLiteral {
+                let t: Bits<{#name::BITS}> = self.into();
+                t.into()
+            }
+            fn from_bits(bits: &[bool]) -> Option<Self> {
+                if bits.len() != Self::BITS {
+                    return None;
+                }
+                let ndx = bits[0..#tag_bits]
+                    .iter()
+                    .rev()
+                    .fold(0_usize, |acc, bit| (acc << 1) | usize::from(*bit));
+                match ndx {
+                    #(#from_bits,)*
+                    _ => None,
+                }
+            }
+        }
+
+        impl Into<Bits<{#name::BITS}>> for #name {
+            fn into(self) -> Bits<{#name::BITS}> {
+                match self {
+                    #(#into_bits,)*
+                }
+            }
+        }
+
+        impl Default for #name {
+            fn default() -> #name {
+                #default
+            }
+        }
+    ))
+}
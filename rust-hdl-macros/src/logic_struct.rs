@@ -73,6 +73,17 @@ pub(crate) fn get_impl_for_logic_struct(input: &syn::DeriveInput) -> Result<TS>
                 let t: Bits<{Self::BITS}> = self.into();
                 t.into()
             }
+
+            fn from_bits(bits: &[bool]) -> Option<Self> {
+                if bits.len() != Self::BITS {
+                    return None;
+                }
+                Some(Self {
+                    #(#fields: <#field_types>::from_bits(
+                        &bits[(0_usize #prev_field)..(0_usize #prev_field + <#field_types>::BITS)]
+                    )?,)*
+                })
+            }
         }
     })
 }
@@ -40,6 +40,28 @@ end
             T::default().verilog()
         ))
     }
+    fn vhdl(&self) -> Option<String> {
+        Some(format!(
+            "\
+signal q_reg : {} := {};
+begin
+q <= q_reg;
+process(clock) begin
+   if rising_edge(clock(0)) then
+      q_reg <= d;
+   end if;
+end process;",
+            vhdl_type(&T::descriptor()),
+            vhdl_value(&T::descriptor(), &T::default().verilog())
+        ))
+    }
+    fn primitive(&self) -> Option<Primitive> {
+        Some(Primitive::Register {
+            clock: "clock".into(),
+            d: "d".into(),
+            q: "q".into(),
+        })
+    }
     fn timing(&self) -> Vec<TimingInfo> {
         vec![TimingInfo {
             name: "dff".into(),
@@ -39,6 +39,13 @@ always @(posedge clk) q <= d;",
             self.q.verilog()
         ))
     }
+    fn primitive(&self) -> Option<Primitive> {
+        Some(Primitive::Register {
+            clock: "clk".into(),
+            d: "d".into(),
+            q: "q".into(),
+        })
+    }
     fn timing(&self) -> Vec<TimingInfo> {
         vec![TimingInfo {
             name: "edge_ff".to_string(),
@@ -83,6 +83,9 @@ impl<D: Synth, const N: usize, const NP1: usize, const BLOCK_SIZE: u32> Logic
         self.write_fill.next = self.write_logic.fill_level.val();
         self.read_fill.next = self.read_logic.fill_level.val();
     }
+    fn is_synchronizer(&self) -> bool {
+        true
+    }
 }
 
 #[test]
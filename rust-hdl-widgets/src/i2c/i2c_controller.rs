@@ -282,16 +282,17 @@ fn test_i2c_controller_operation() {
         val_lsb: u8,
     }
 
+    let mut sim = Simulation::new();
     let test_cases = (0..12)
         .map(|ndx| TestCase {
-            address: if rand::thread_rng().gen::<bool>() {
+            address: if sim.rng().gen::<bool>() {
                 0x53_u8
             } else {
                 0x57_u8
             },
             reg_index: ndx,
-            val_msb: rand::thread_rng().gen::<u8>(),
-            val_lsb: rand::thread_rng().gen::<u8>(),
+            val_msb: sim.rng().gen::<u8>(),
+            val_lsb: sim.rng().gen::<u8>(),
         })
         .collect::<Vec<_>>();
     let mut uut = I2CControllerTest::default();
@@ -304,7 +305,6 @@ fn test_i2c_controller_operation() {
     //println!("{}", vlog);
     dbg!(I2CBusDriver::join_hdl("me", "foo", "bar"));
     yosys_validate("i2c_controller", &vlog).unwrap();
-    let mut sim = Simulation::new();
     sim.add_clock(500_000, |x: &mut Box<I2CControllerTest>| {
         x.clock.next = !x.clock.val()
     });
@@ -56,6 +56,10 @@ impl<D: Synth, const N: usize> Logic for RAM<D, N> {
         self.read_data.connect();
     }
 
+    fn checkpoint(&mut self, state: &mut CircuitState) {
+        state.map(self._sim.as_mut());
+    }
+
     fn hdl(&self) -> Verilog {
         let init = if self._sim.len() != 0 {
             format!(
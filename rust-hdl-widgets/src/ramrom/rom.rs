@@ -44,6 +44,10 @@ impl<D: Synth, const N: usize> Logic for ROM<D, N> {
         self.data.connect();
     }
 
+    fn checkpoint(&mut self, state: &mut CircuitState) {
+        state.map(self._sim.as_mut());
+    }
+
     fn hdl(&self) -> Verilog {
         let cases = self
             ._sim
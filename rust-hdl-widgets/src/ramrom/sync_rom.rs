@@ -39,6 +39,10 @@ impl<D: Synth, const N: usize> Logic for SyncROM<D, N> {
         self.data.connect();
     }
 
+    fn checkpoint(&mut self, state: &mut CircuitState) {
+        state.map(self._sim.as_mut());
+    }
+
     fn hdl(&self) -> Verilog {
         let init = self
             ._sim
@@ -25,6 +25,9 @@ impl Logic for BitSynchronizer {
         self.dff1.d.next = self.dff0.q.val();
         self.sig_out.next = self.dff1.q.val();
     }
+    fn is_synchronizer(&self) -> bool {
+        true
+    }
 }
 
 #[test]
@@ -239,6 +242,9 @@ impl<T: Synth> Logic for VectorSynchronizer<T> {
         self.recv.flag_in.next = self.sender.flag_out.val();
         self.sender.ack_in.next = self.recv.ack_out.val();
     }
+    fn is_synchronizer(&self) -> bool {
+        true
+    }
 }
 
 #[test]
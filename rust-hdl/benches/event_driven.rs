@@ -0,0 +1,17 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+
+#[path = "../tests/common/mod.rs"]
+mod common;
+
+use common::run_channel_bank;
+
+fn event_driven_benchmark(c: &mut Criterion) {
+    let mut group = c.benchmark_group("channel_bank");
+    group.sample_size(10);
+    group.bench_function("full_update", |b| b.iter(|| run_channel_bank(false, 2000)));
+    group.bench_function("event_driven", |b| b.iter(|| run_channel_bank(true, 2000)));
+    group.finish();
+}
+
+criterion_group!(benches, event_driven_benchmark);
+criterion_main!(benches);
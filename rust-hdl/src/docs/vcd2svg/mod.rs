@@ -1,6 +1,7 @@
 use crate::docs::vcd2svg::display_metrics::DisplayMetrics;
 use crate::docs::vcd2svg::trace_collection::TraceCollection;
 use crate::docs::vcd2svg::vcd_style::VCDStyle;
+use rust_hdl_core::waveform::Waveform;
 
 pub mod display_metrics;
 mod interval;
@@ -22,6 +23,27 @@ pub fn vcd_to_svg(
 ) -> anyhow::Result<()> {
     let vcd = std::fs::File::open(vcd_filename)?;
     let traces = TraceCollection::parse(signal_names, vcd)?;
+    traces_to_svg(&traces, svg_filename, min_time_in_ps, max_time_in_ps)
+}
+
+/// Render the given signals of a [Waveform] to an SVG file, like [vcd_to_svg].
+pub fn waveform_to_svg(
+    waveform: &Waveform,
+    svg_filename: &str,
+    signal_names: &[&str],
+    min_time_in_ps: u64,
+    max_time_in_ps: u64,
+) -> anyhow::Result<()> {
+    let traces = TraceCollection::from_waveform(signal_names, waveform)?;
+    traces_to_svg(&traces, svg_filename, min_time_in_ps, max_time_in_ps)
+}
+
+fn traces_to_svg(
+    traces: &TraceCollection,
+    svg_filename: &str,
+    min_time_in_ps: u64,
+    max_time_in_ps: u64,
+) -> anyhow::Result<()> {
     let mut metrics = DisplayMetrics::default();
     metrics.style = VCDStyle::gtkwave();
     metrics.min_time = min_time_in_ps;
@@ -43,6 +65,18 @@ pub fn vcd_to_txt(
     traces.as_string(min_time_in_ps, max_time_in_ps, max_columns as usize)
 }
 
+/// Render the given signals of a [Waveform] as text, like [vcd_to_txt].
+pub fn waveform_to_txt(
+    waveform: &Waveform,
+    signal_names: &[&str],
+    min_time_in_ps: u64,
+    max_time_in_ps: u64,
+    max_columns: u64,
+) -> anyhow::Result<String> {
+    let traces = TraceCollection::from_waveform(signal_names, waveform)?;
+    traces.as_string(min_time_in_ps, max_time_in_ps, max_columns as usize)
+}
+
 #[test]
 fn test_txt() {
     let msg = vcd_to_txt(
@@ -4,6 +4,7 @@ use crate::docs::vcd2svg::text_frame::TextFrame;
 use crate::docs::vcd2svg::timed_value::{changes, SignalType, TimedValue};
 use crate::docs::vcd2svg::utils::{time_label, value_to_bigint, value_to_bool};
 use num_bigint::BigInt;
+use rust_hdl_core::waveform::{WaveValue, Waveform};
 use std::clone::Clone;
 use std::collections::HashMap;
 use std::fmt::Debug;
@@ -183,6 +184,65 @@ impl TraceCollection {
         })
     }
 
+    /// Collect the traces of the given signals from a [Waveform] instead of a VCD file.
+    pub fn from_waveform(signals: &[&str], waveform: &Waveform) -> anyhow::Result<Self> {
+        let mut string_valued = HashMap::new();
+        let mut vector_valued = HashMap::new();
+        let mut scalar_valued = HashMap::new();
+        let mut signal_names = Vec::new();
+        for (ndx, signal) in signals.iter().enumerate() {
+            let trace = waveform
+                .trace(signal)
+                .ok_or_else(|| anyhow::Error::msg(format!("cannot resolve signal {}", signal)))?;
+            let code = IdCode::from(ndx as u64);
+            if trace.width == 0 {
+                let values = trace
+                    .changes()
+                    .iter()
+                    .map(|x| TimedValue {
+                        time: x.time,
+                        value: x.value.to_string(),
+                    })
+                    .collect();
+                string_valued.insert(code, values);
+            } else {
+                let mut scalars = BinaryTrace::new();
+                let mut vectors = VectorTrace::new();
+                for change in trace.changes() {
+                    let bits = match &change.value {
+                        WaveValue::Bits(x) => x,
+                        WaveValue::String(_) => {
+                            anyhow::bail!("string value found in bit signal {}", signal)
+                        }
+                    };
+                    if trace.width == 1 {
+                        scalars.push(TimedValue {
+                            time: change.time,
+                            value: value_to_bool(&bits[0])?,
+                        });
+                    } else {
+                        vectors.push(TimedValue {
+                            time: change.time,
+                            value: value_to_bigint(bits)?,
+                        });
+                    }
+                }
+                if trace.width == 1 {
+                    scalar_valued.insert(code, scalars);
+                } else {
+                    vector_valued.insert(code, vectors);
+                }
+            }
+            signal_names.push((code, signal.to_string()));
+        }
+        Ok(Self {
+            signal_names,
+            string_valued,
+            vector_valued,
+            scalar_valued,
+        })
+    }
+
     pub fn as_svg(&self, metrics: &DisplayMetrics) -> anyhow::Result<Document> {
         let document = Document::new()
             .set(
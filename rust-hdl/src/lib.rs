@@ -524,8 +524,8 @@
 //! ```
 //!
 //! - The body of the `update` function must be a single block, consisting of statements.
-//! Local definitions and items are not allowed in HDL kernels.  The following, for example, will
-//!fail.  This is an example of valid Rust that is not allowed in an HDL kernel.
+//!   Items (like nested functions) are not allowed in HDL kernels.  The following, for example, will
+//!   fail.  This is an example of valid Rust that is not allowed in an HDL kernel.
 //!
 //!```compile_fail
 //! # use rust_hdl::prelude::*;
@@ -536,7 +536,33 @@
 //!    #[hdl_gen]
 //!    fn update (&mut self) {
 //!      // Fails because local items are not allowed in HDL kernels.
-//!      let x = 32;
+//!      fn helper() {}
+//!    }
+//! }
+//!```
+//!
+//! - `let` bindings are allowed, as long as they bind a single name, are initialized,
+//!   and the value is a `Synth` type.  The type can be inferred, or given explicitly.  Each
+//!   binding becomes a local `reg` in the generated Verilog (named after the binding, e.g.
+//!   `sum$let`), so there is no need to add a `Signal<Local, _>` to the struct just to hold
+//!   an intermediate value.  A binding can be shadowed, and a `let mut` binding can be
+//!   reassigned.
+//!
+//!```rust
+//! # use rust_hdl::prelude::*;
+//!
+//! struct Foo {
+//!    pub sig1: Signal<In, Bits<8>>,
+//!    pub sig2: Signal<In, Bits<8>>,
+//!    pub sig3: Signal<Out, Bits<16>>,
+//! }
+//!
+//! impl Logic for Foo {
+//!    #[hdl_gen]
+//!    fn update(&mut self) {
+//!       let sum = self.sig1.val() + self.sig2.val();
+//!       let wide: Bits<16> = bit_cast::<16, 8>(sum);
+//!       self.sig3.next = wide;
 //!    }
 //! }
 //!```
@@ -623,6 +649,41 @@
 //!     - `bits`
 //!     - `Bits`
 //!     - `Type::join` and `Type::link` used to link and join logical interfaces...
+//!     - Free functions marked with `#[hdl_function]`.  These take and return `Synth` values,
+//!       and their bodies follow the same rules as a kernel, ending with an expression that
+//!       gives the result.  They can be generic over constants (but not types), and are emitted
+//!       as a Verilog `function` in each module that calls them, so that combinational logic
+//!       can be shared between blocks.  The name of the function in the HDL includes its
+//!       module path, the values of its const generics, and the widths of its arguments and
+//!       result.  Calls to the standard library or to associated functions are not supported,
+//!       and calling a free function that is not an `#[hdl_function]` fails to compile at the
+//!       call (since it has no `_hdl` companion).
+//! ```rust
+//! # use rust_hdl::prelude::*;
+//!
+//! #[hdl_function]
+//! fn saturating_add(a: Bits<8>, b: Bits<8>) -> Bits<8> {
+//!     let sum = bit_cast::<9, 8>(a) + bit_cast::<9, 8>(b);
+//!     let mut result = bit_cast::<8, 9>(sum);
+//!     if sum.get_bit(8) {
+//!         result = 0xFF.into();
+//!     }
+//!     result
+//! }
+//!
+//! struct Foo {
+//!     pub sig1: Signal<In, Bits<8>>,
+//!     pub sig2: Signal<In, Bits<8>>,
+//!     pub sig3: Signal<Out, Bits<8>>,
+//! }
+//!
+//! impl Logic for Foo {
+//!     #[hdl_gen]
+//!     fn update(&mut self) {
+//!         self.sig3.next = saturating_add(self.sig1.val(), self.sig2.val());
+//!     }
+//! }
+//! ```
 //! - Method calls - Kernels support the following limited set of method calls
 //!     - `get_bits` - extract a (fixed width) set of bits from a bit vector
 //!     - `get_bit` - extract a single bit from a bit vector
@@ -710,6 +771,16 @@
 //!     - `clock` - clock a set of components - this macro is also converted into the appropriate HDL
 //! - Loops - `for` loops are supported for code generation
 //!     - In software parlance, all `for` loops are unrolled at compile time, so they must be of the form `for <ident> in <const>..<const>`.
+//! - Arrays - arrays of signals (and of circuits) can be indexed by a value computed at run time
+//!     - Only arrays of signals or of blocks (that is, fields of the circuit) can be indexed this way,
+//!       since each element needs a name in the HDL.  Arrays of plain values cannot be.
+//!     - The element is read with `.val()`, e.g., `self.regs[self.addr.val().index()].q.val()`, and
+//!       assigned through `.next` as usual.  Only one index in each expression can be computed at run time.
+//!     - The generated HDL compares the index against each element in turn, so this is best kept to
+//!       small arrays like register files and crossbars.
+//!     - An index that is out of range panics in the Rust simulation (as any Rust index would), but
+//!       in the HDL it reads as zero, and writes nothing.  Size the index to the array (or check it)
+//!       so the two agree.
 //! A simple example to consider is a parameterizable mux.
 //!
 //! ```rust
@@ -741,6 +812,23 @@
 //!    }
 //! }
 //! ```
+//! The loop is not needed if the select signal is used to index the array directly.
+//!
+//! ```rust
+//! # use rust_hdl::prelude::*;
+//! struct Mux<D: Synth, const N: usize, const A: usize> {
+//!    pub input_lines: [Signal<In, D>; N],
+//!    pub select: Signal<In, Bits<A>>,
+//!    pub outsig: Signal<Out, D>,
+//! }
+//!
+//! impl<D: Synth, const N: usize, const A: usize> Logic for Mux<D, N, A> {
+//!   #[hdl_gen]
+//!   fn update(&mut self) {
+//!        self.outsig.next = self.input_lines[self.select.val().index()].val();
+//!    }
+//! }
+//! ```
 //! RustHDL is still pretty restrictive about arrays and loops.  You can still do great stuff though.
 //!
 //! Since an example is instructive, here is the HDL kernel for a nontrivial circuit (the `SPIMaster`),
@@ -915,7 +1003,7 @@
 //!
 //! ## Enums
 //!
-//! In keeping with Rust's strongly typed model, you can use enums (not sum types) in your HDL,
+//! In keeping with Rust's strongly typed model, you can use enums in your HDL,
 //! provided you derive the `LogicState` trait for them.  This makes your code much easier to
 //! read and debug, and `rustc` will make sure you don't do anything illegal with your
 //! enums.
@@ -976,6 +1064,35 @@
 //! ```
 //! RustHDL will _automatically_ choose a 3-bit representation.  
 //!
+//! By default, the variants are numbered in order.  For a state machine that needs to decode its
+//! state quickly, or to match register values given in a datasheet, you can choose the encoding.
+//! An `#[encoding(...)]` attribute selects `one_hot`, `gray` or `binary` (the default), and explicit
+//! discriminants set the values directly.  A variant without a discriminant is one more than the
+//! variant before it, just as in Rust.  The encoding is used both in simulation and in the generated
+//! HDL, and the width of the enum grows to hold its largest value.
+//!
+//! ```rust
+//! # use rust_hdl::prelude::*;
+//! #[derive(Copy, Clone, PartialEq, Debug, LogicState)]
+//! #[encoding(one_hot)]
+//! enum Phase {
+//!     Idle,     // <-- 4'b0001
+//!     Fetch,    // <-- 4'b0010
+//!     Decode,   // <-- 4'b0100
+//!     Execute,  // <-- 4'b1000
+//! }
+//!
+//! #[derive(Copy, Clone, PartialEq, Debug, LogicState)]
+//! enum Mode {
+//!     Off = 0x0,
+//!     Standby = 0x5,
+//!     Active,   // <-- 0x6
+//! }
+//!
+//! assert_eq!(Phase::BITS, 4);
+//! assert_eq!(Mode::BITS, 3);
+//! ```
+//!
 //! - RustHDL will ensure that assignments to `enum`-valued signals are valid at all times
 //!
 //! The strong type guarantees ensure you cannot assign arbitrary values to `enum` valued
@@ -1044,6 +1161,45 @@
 //! }
 //! ```
 //!
+//! The variants of an enum can also carry data.  Such an enum is stored as a tagged union, with
+//! the index of the variant (the tag) in the low bits, followed by the fields of the variant.
+//! The fields are packed like those of a `LogicStruct`, and the enum is as wide as the tag plus the
+//! largest variant.  In a kernel, the fields of a variant are bound by the arms of a `match`, and new
+//! values are built with the usual syntax.
+//!
+//! ```rust
+//! # use rust_hdl::prelude::*;
+//! #[derive(Copy, Clone, PartialEq, Debug, LogicState)]
+//! enum Command {
+//!     Idle,
+//!     Read(Bits<8>),
+//!     Write { addr: Bits<8>, data: Bits<16> },  // <-- Command is 2 + 24 bits wide
+//! }
+//!
+//! #[derive(LogicBlock, Default)]
+//! struct Foo {
+//!     pub cmd: Signal<In, Command>,
+//!     pub addr: Signal<Out, Bits<8>>,
+//!     pub echo: Signal<Out, Command>,
+//! }
+//!
+//! impl Logic for Foo {
+//!    #[hdl_gen]
+//!    fn update(&mut self) {
+//!       self.addr.next = 0.into();
+//!       self.echo.next = Command::Idle;
+//!       match self.cmd.val() {
+//!           Command::Idle => {}
+//!           Command::Read(addr) => self.addr.next = addr,
+//!           Command::Write { addr, data } => {
+//!               self.addr.next = addr;
+//!               self.echo.next = Command::Write { addr, data: !data };
+//!           }
+//!       }
+//!    }
+//! }
+//! ```
+//!
 //! ## Interfaces
 //!
 //! One area you will encouter as your circuits become more complex is that the interfaces
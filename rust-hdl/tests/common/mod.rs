@@ -0,0 +1,122 @@
+// Shared by the event driven kernel test and benchmark
+use rust_hdl::prelude::*;
+use std::sync::{Arc, Mutex};
+
+// A combinational stage that counts how often its `update` is called
+#[derive(LogicBlock, Default)]
+pub struct Channel {
+    pub raw: Signal<In, Bits<16>>,
+    pub gain: Signal<In, Bits<16>>,
+    pub scaled: Signal<Out, Bits<16>>,
+    _updates: usize,
+}
+
+impl Logic for Channel {
+    fn update(&mut self) {
+        self._updates += 1;
+        let mut x = self.raw.val();
+        for _ in 0..8 {
+            x = (x ^ (x << 3)) + self.gain.val();
+        }
+        self.scaled.next = x;
+    }
+
+    fn connect(&mut self) {
+        self.scaled.connect();
+    }
+}
+
+// A bank of channels of which only the front one sees new samples.  The rest
+// are fed from a setting that stays the same for the whole run, and the parent
+// writes them the same values on every pass, so they are idle.
+#[derive(LogicBlock)]
+pub struct ChannelBank {
+    pub clock: Signal<In, Clock>,
+    pub setting: Signal<In, Bits<16>>,
+    pub sample: Signal<In, Bits<16>>,
+    pub total: Signal<Out, Bits<16>>,
+    front: Channel,
+    channels: Vec<Channel>,
+    accum: DFF<Bits<16>>,
+}
+
+impl Default for ChannelBank {
+    fn default() -> Self {
+        Self {
+            clock: Default::default(),
+            setting: Default::default(),
+            sample: Default::default(),
+            total: Default::default(),
+            front: Default::default(),
+            channels: (0..32).map(|_| Channel::default()).collect(),
+            accum: Default::default(),
+        }
+    }
+}
+
+impl Logic for ChannelBank {
+    fn update(&mut self) {
+        self.accum.clock.next = self.clock.val();
+        self.front.raw.next = self.sample.val();
+        self.front.gain.next = self.setting.val();
+        let mut sum = self.front.scaled.val();
+        for channel in &mut self.channels {
+            channel.raw.next = self.setting.val();
+            channel.gain.next = self.setting.val();
+            sum = sum + channel.scaled.val();
+        }
+        self.accum.d.next = self.accum.q.val() + sum;
+        self.total.next = self.accum.q.val();
+    }
+
+    fn connect(&mut self) {
+        self.accum.clock.connect();
+        self.accum.d.connect();
+        self.front.raw.connect();
+        self.front.gain.connect();
+        for channel in &mut self.channels {
+            channel.raw.connect();
+            channel.gain.connect();
+        }
+        self.total.connect();
+    }
+}
+
+impl ChannelBank {
+    // The number of calls to `update` on the front channel, and on each of
+    // the idle ones
+    pub fn updates(&self) -> (usize, Vec<usize>) {
+        (
+            self.front._updates,
+            self.channels.iter().map(|x| x._updates).collect(),
+        )
+    }
+}
+
+// The totals after each clock cycle, and the update counts of the channels
+// at the end of the run
+pub type BankRun = (Vec<u64>, (usize, Vec<usize>));
+
+pub fn run_channel_bank(event_driven: bool, cycles: usize) -> BankRun {
+    let result = Arc::new(Mutex::new((vec![], (0, vec![]))));
+    let record = result.clone();
+    let mut sim = Simulation::new();
+    sim.set_event_driven(event_driven);
+    sim.add_clock(5, |x: &mut Box<ChannelBank>| x.clock.next = !x.clock.val());
+    sim.add_testbench(move |mut sim: Sim<ChannelBank>| {
+        let mut x = sim.init()?;
+        x.setting.next = 0x1234.into();
+        for cycle in 0..cycles {
+            x.sample.next = (cycle as u64).into();
+            wait_clock_cycle!(sim, clock, x);
+            record.lock().unwrap().0.push(x.total.val().to_u64());
+        }
+        record.lock().unwrap().1 = x.updates();
+        sim.done(x)
+    });
+    let mut uut = ChannelBank::default();
+    uut.connect_all();
+    sim.run(Box::new(uut), 1_000_000_000).unwrap();
+    let result = result.lock().unwrap();
+    result.clone()
+}
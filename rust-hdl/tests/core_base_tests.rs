@@ -1,4 +1,5 @@
 use rust_hdl::core::check_error::{CheckError, PathedName};
+use rust_hdl::core::check_logic_loops::{find_logic_loops, logic_loops_dot};
 use rust_hdl::core::prelude::*;
 
 #[allow(dead_code)]
@@ -416,16 +417,89 @@ fn test_local_logic_loop_detection() {
     uut.signal.connect();
     uut.connect_all();
     let e = check_all(&uut).expect_err("Loop should have been found");
-    if let CheckError::LogicLoops(m) = e {
+    assert!(e.to_string().contains("uut$foo -> uut$foo"));
+    if let CheckError::LogicLoops(m, loops) = e {
         assert!(m.contains(&PathedName {
             path: "uut".to_string(),
             name: "foo".to_string()
-        }))
+        }));
+        assert_eq!(loops[0].chain(), ["uut$foo", "uut$foo"]);
     } else {
         panic!("Error mismatch on loop detector")
     }
 }
 
+#[test]
+fn test_logic_loop_report_shows_cycle() {
+    #[derive(LogicBlock, Default)]
+    struct Inverter {
+        pub a: Signal<In, Bit>,
+        pub b: Signal<Out, Bit>,
+    }
+
+    impl Logic for Inverter {
+        #[hdl_gen]
+        fn update(&mut self) {
+            self.b.next = !self.a.val();
+        }
+    }
+
+    // A ring oscillator - the loop goes through the child block
+    #[derive(LogicBlock, Default)]
+    struct Ring {
+        pub out: Signal<Out, Bit>,
+        inv: Inverter,
+    }
+
+    impl Logic for Ring {
+        #[hdl_gen]
+        fn update(&mut self) {
+            self.inv.a.next = self.inv.b.val();
+            self.out.next = self.inv.b.val();
+        }
+    }
+
+    let mut uut = Ring::default();
+    uut.connect_all();
+    let loops = find_logic_loops(&uut);
+    assert_eq!(loops.len(), 1);
+    assert_eq!(loops[0].chain(), ["uut$inv$a", "uut$inv$b", "uut$inv$a"]);
+    assert_eq!(loops[0].edges[0].module, "uut$inv");
+    assert_eq!(loops[0].edges[1].module, "uut");
+    let dot = logic_loops_dot(&loops);
+    assert!(dot.contains("\"uut$inv$b\" -> \"uut$inv$a\""));
+}
+
+#[test]
+fn test_logic_loop_report_follows_local_values() {
+    #[derive(LogicBlock, Default)]
+    struct Accumulate {
+        pub bits: Signal<In, Bits<4>>,
+        pub count: Signal<Out, Bits<3>>,
+        pub foo: Signal<Local, Bits<3>>,
+    }
+
+    impl Logic for Accumulate {
+        #[hdl_gen]
+        fn update(&mut self) {
+            // Written before it is read, so this is not a loop
+            self.foo.next = 0.into();
+            if self.bits.val().get_bit(0) {
+                self.foo.next = self.foo.val() + 1;
+            }
+            if self.bits.val().get_bit(1) {
+                self.foo.next = self.foo.val() + 1;
+            }
+            self.count.next = self.foo.val();
+        }
+    }
+
+    let mut uut = Accumulate::default();
+    uut.bits.connect();
+    uut.connect_all();
+    assert!(find_logic_loops(&uut).is_empty());
+}
+
 #[test]
 fn not_example() {
     #[derive(LogicBlock)]
@@ -442,3 +516,93 @@ fn not_example() {
         }
     }
 }
+
+#[test]
+fn test_try_generate_verilog_reports_open_signals() {
+    #[derive(LogicBlock, Default)]
+    struct OutputUndrivenTest {
+        pub in1: Signal<In, Bit>,
+        pub out1: Signal<Out, Bit>,
+        pub out2: Signal<Out, Bit>,
+    }
+
+    impl Logic for OutputUndrivenTest {
+        #[hdl_gen]
+        fn update(&mut self) {
+            self.out1.next = self.in1.val();
+        }
+    }
+
+    let mut uut = OutputUndrivenTest::default();
+    uut.connect_all();
+    match try_generate_verilog(&uut) {
+        Err(GenerateError::Check(CheckError::OpenSignal(map))) => {
+            assert!(map.iter().any(|x| x.1.name == "out2"))
+        }
+        _ => panic!("Undriven signal undetected!"),
+    }
+    uut.out2.connect();
+    assert!(try_generate_verilog(&uut).is_ok());
+}
+
+#[test]
+fn test_try_generate_other_languages_report_logic_loops() {
+    #[derive(LogicBlock, Default)]
+    struct LoopTest {
+        pub in1: Signal<In, Bit>,
+        pub out1: Signal<Out, Bit>,
+        local: Signal<Local, Bit>,
+    }
+
+    impl Logic for LoopTest {
+        #[hdl_gen]
+        fn update(&mut self) {
+            self.local.next = self.in1.val() & self.out1.val();
+            self.out1.next = !self.local.val();
+        }
+    }
+
+    let mut uut = LoopTest::default();
+    uut.connect_all();
+    for result in [
+        try_generate_verilog_parameterized(&uut),
+        try_generate_vhdl(&uut),
+        try_generate_system_verilog(&uut),
+    ] {
+        assert!(matches!(
+            result,
+            Err(GenerateError::Check(CheckError::LogicLoops(..)))
+        ));
+    }
+}
+
+#[cfg(feature = "fpga")]
+#[test]
+fn test_try_generate_pcf_reports_unsupported_constraints() {
+    #[derive(LogicBlock, Default)]
+    struct Pins {
+        pub led: Signal<Out, Bit>,
+    }
+
+    impl Logic for Pins {
+        #[hdl_gen]
+        fn update(&mut self) {
+            self.led.next = true;
+        }
+    }
+
+    let mut uut = Pins::default();
+    uut.led.add_location(0, "J3");
+    uut.led.add_constraint(PinConstraint {
+        index: 0,
+        constraint: Constraint::Slew(SlewType::Fast),
+    });
+    uut.connect_all();
+    match rust_hdl::fpga::toolchains::icestorm::try_generate_pcf(&uut) {
+        Err(GenerateError::UnsupportedConstraint { signal, .. }) => {
+            assert_eq!(signal.path, "top");
+            assert_eq!(signal.name, "led");
+        }
+        _ => panic!("Unsupported constraint was not reported"),
+    }
+}
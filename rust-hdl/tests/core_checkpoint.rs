@@ -0,0 +1,117 @@
+use rust_hdl::prelude::*;
+
+#[derive(LogicBlock, Default)]
+struct Scratchpad {
+    pub clock: Signal<In, Clock>,
+    pub write_address: Signal<In, Bits<4>>,
+    pub write_data: Signal<In, Bits<8>>,
+    pub write_enable: Signal<In, Bit>,
+    pub read_address: Signal<In, Bits<4>>,
+    pub read_data: Signal<Out, Bits<8>>,
+    pub cycles: Signal<Out, Bits<16>>,
+    mem: RAM<Bits<8>, 4>,
+    counter: DFF<Bits<16>>,
+}
+
+impl Logic for Scratchpad {
+    #[hdl_gen]
+    fn update(&mut self) {
+        dff_setup!(self, clock, counter);
+        self.counter.d.next = self.counter.q.val() + 1;
+        self.cycles.next = self.counter.q.val();
+        self.mem.read_clock.next = self.clock.val();
+        self.mem.write_clock.next = self.clock.val();
+        self.mem.read_address.next = self.read_address.val();
+        self.mem.write_address.next = self.write_address.val();
+        self.mem.write_data.next = self.write_data.val();
+        self.mem.write_enable.next = self.write_enable.val();
+        self.read_data.next = self.mem.read_data.val();
+    }
+}
+
+fn boot() -> Checkpoint {
+    let mut sim = Simulation::new();
+    sim.add_clock(5, |x: &mut Box<Scratchpad>| x.clock.next = !x.clock.val());
+    sim.add_testbench(move |mut sim: Sim<Scratchpad>| {
+        let mut x = sim.init()?;
+        x.write_enable.next = true;
+        for i in 0..16_u64 {
+            x.write_address.next = i.into();
+            x.write_data.next = (i * 3 + 1).into();
+            wait_clock_cycle!(sim, clock, x);
+        }
+        x.write_enable.next = false;
+        wait_clock_cycle!(sim, clock, x);
+        sim.done(x)
+    });
+    sim.run_to_checkpoint(Box::new(Scratchpad::default()), 100_000)
+        .unwrap()
+}
+
+#[test]
+fn test_checkpoint_restore_resumes_simulation() {
+    let checkpoint = boot();
+    assert_ne!(checkpoint.time, 0);
+    let path = vcd_path!("scratchpad.ckpt");
+    checkpoint.save(&path).unwrap();
+    let checkpoint = Checkpoint::load(&path).unwrap();
+    let start = checkpoint.time;
+    let mut sim = Simulation::new();
+    sim.add_clock(5, |x: &mut Box<Scratchpad>| x.clock.next = !x.clock.val());
+    sim.add_testbench(move |mut sim: Sim<Scratchpad>| {
+        let mut x = sim.init()?;
+        sim_assert!(sim, sim.time() == start, x);
+        let start_cycles = x.cycles.val();
+        sim_assert!(sim, start_cycles != 0, x);
+        for i in 0..16_u64 {
+            x.read_address.next = i.into();
+            wait_clock_cycle!(sim, clock, x);
+            sim_assert!(sim, x.read_data.val() == i * 3 + 1, x);
+        }
+        sim_assert!(sim, x.cycles.val() == start_cycles + 16, x);
+        sim_assert!(sim, sim.time() > start, x);
+        sim.done(x)
+    });
+    let mut uut = Scratchpad::default();
+    sim.restore(&mut uut, &checkpoint).unwrap();
+    sim.run(Box::new(uut), start + 100_000).unwrap();
+}
+
+#[test]
+fn test_checkpoint_rejects_different_circuit() {
+    let checkpoint = boot();
+    let mut other = DFF::<Bits<8>>::default();
+    assert!(matches!(
+        checkpoint.restore(&mut other),
+        Err(CheckpointError::Mismatch(_))
+    ));
+}
+
+// A type that does not say how to rebuild itself from its bits
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+struct Celsius(u8);
+
+impl Synth for Celsius {
+    const BITS: usize = 8;
+    fn descriptor() -> TypeDescriptor {
+        Bits::<8>::descriptor()
+    }
+    fn vcd(self) -> VCDValue {
+        Bits::<8>::from(self.0 as u64).vcd()
+    }
+    fn verilog(self) -> VerilogLiteral {
+        Bits::<8>::from(self.0 as u64).verilog()
+    }
+}
+
+#[test]
+fn test_checkpoint_of_type_without_from_bits_is_not_restored() {
+    let mut sensor = Signal::<In, Celsius>::default();
+    sensor.next = Celsius(21);
+    let checkpoint = Checkpoint::capture(0, &mut sensor);
+    let mut other = Signal::<In, Celsius>::default();
+    assert!(matches!(
+        checkpoint.restore(&mut other),
+        Err(CheckpointError::Mismatch(_))
+    ));
+}
@@ -0,0 +1,194 @@
+use rust_hdl::prelude::*;
+use std::sync::{Arc, Mutex};
+
+#[derive(Copy, Clone, Debug, PartialEq, LogicState)]
+enum Phase {
+    Idle,
+    Run,
+    Drain,
+}
+
+#[derive(LogicBlock, Default)]
+struct Pipeline {
+    pub clock: Signal<In, Clock>,
+    pub start: Signal<In, Bit>,
+    pub data_in: Signal<In, Bits<8>>,
+    pub step: Signal<In, Signed<12>>,
+    pub data_out: Signal<Out, Bits<8>>,
+    pub busy: Signal<Out, Bit>,
+    pub total: Signal<Out, Signed<12>>,
+    delay: DelayLine<Bits<8>, 8, 3>,
+    state: DFF<Phase>,
+    count: DFF<Bits<4>>,
+    accum: DFF<Signed<12>>,
+}
+
+impl Logic for Pipeline {
+    #[hdl_gen]
+    fn update(&mut self) {
+        dff_setup!(self, clock, state, count, accum);
+        self.delay.clock.next = self.clock.val();
+        self.delay.data_in.next = self.data_in.val();
+        self.delay.delay.next = self.count.q.val().get_bits::<3>(0);
+        self.data_out.next = self.delay.data_out.val();
+        self.total.next = self.accum.q.val();
+        self.busy.next = false;
+        match self.state.q.val() {
+            Phase::Idle => {
+                if self.start.val() {
+                    self.state.d.next = Phase::Run;
+                    self.count.d.next = 0.into();
+                }
+            }
+            Phase::Run => {
+                self.busy.next = true;
+                self.count.d.next = self.count.q.val() + 1;
+                self.accum.d.next = self.accum.q.val() + self.step.val();
+                if self.count.q.val() == 11 {
+                    self.state.d.next = Phase::Drain;
+                }
+            }
+            Phase::Drain => {
+                if !self.start.val() {
+                    self.state.d.next = Phase::Idle;
+                }
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Outputs {
+    data_out: u64,
+    busy: bool,
+    total: String,
+}
+
+fn stimulus(cycle: usize) -> (bool, Bits<8>, Signed<12>) {
+    (
+        cycle % 23 < 3,
+        ((cycle * 37) as u64 & 0xFF).into(),
+        signed((cycle % 9) as i64 - 4),
+    )
+}
+
+fn run_native(cycles: usize) -> Vec<Outputs> {
+    let outputs = Arc::new(Mutex::new(vec![]));
+    let record = outputs.clone();
+    let mut sim = Simulation::new();
+    sim.add_clock(5, |x: &mut Box<Pipeline>| x.clock.next = !x.clock.val());
+    sim.add_testbench(move |mut sim: Sim<Pipeline>| {
+        let mut x = sim.init()?;
+        for cycle in 0..cycles {
+            let (start, data_in, step) = stimulus(cycle);
+            x.start.next = start;
+            x.data_in.next = data_in;
+            x.step.next = step;
+            wait_clock_cycle!(sim, clock, x);
+            record.lock().unwrap().push(Outputs {
+                data_out: x.data_out.val().to_u64(),
+                busy: x.busy.val(),
+                total: x.total.val().verilog().to_binary_string(),
+            });
+        }
+        sim.done(x)
+    });
+    let mut uut = Pipeline::default();
+    uut.connect_all();
+    sim.run(Box::new(uut), 1_000_000_000).unwrap();
+    let outputs = outputs.lock().unwrap();
+    outputs.clone()
+}
+
+fn run_compiled(cycles: usize) -> Vec<Outputs> {
+    let mut uut = Pipeline::default();
+    uut.connect_all();
+    let mut sim = CompiledSimulation::new(&uut).unwrap();
+    let clock = sim.signal("clock").unwrap();
+    let start = sim.signal("start").unwrap();
+    let data_in = sim.signal("data_in").unwrap();
+    let step = sim.signal("step").unwrap();
+    let data_out = sim.signal("data_out").unwrap();
+    let busy = sim.signal("busy").unwrap();
+    let total = sim.signal("total").unwrap();
+    let mut outputs = vec![];
+    sim.settle().unwrap();
+    for cycle in 0..cycles {
+        let stim = stimulus(cycle);
+        sim.write(start, stim.0);
+        sim.write(data_in, stim.1);
+        sim.write(step, stim.2);
+        sim.settle().unwrap();
+        sim.set(clock, 1);
+        sim.settle().unwrap();
+        sim.set(clock, 0);
+        sim.settle().unwrap();
+        outputs.push(Outputs {
+            data_out: sim.get(data_out) as u64,
+            busy: sim.get(busy) != 0,
+            total: format!("{:012b}", sim.get(total)),
+        });
+    }
+    outputs
+}
+
+#[test]
+fn test_compiled_simulation_matches_native() {
+    let native = run_native(300);
+    let compiled = run_compiled(300);
+    assert_eq!(native.len(), 300);
+    assert!(native.iter().any(|x| x.busy));
+    assert!(native.iter().any(|x| x.data_out != 0));
+    assert_eq!(native, compiled);
+}
+
+#[test]
+fn test_compiled_simulation_rejects_custom_hdl() {
+    #[derive(LogicBlock, Default)]
+    struct Opaque {
+        pub a: Signal<In, Bit>,
+        pub b: Signal<Out, Bit>,
+    }
+
+    impl Logic for Opaque {
+        fn update(&mut self) {
+            self.b.next = self.a.val();
+        }
+        fn connect(&mut self) {
+            self.b.connect();
+        }
+        fn hdl(&self) -> Verilog {
+            Verilog::Custom("assign b = a;".into())
+        }
+    }
+
+    let mut uut = Opaque::default();
+    uut.connect_all();
+    assert!(matches!(
+        CompiledSimulation::new(&uut),
+        Err(CompileError::UnsupportedModule { .. })
+    ));
+}
+
+#[test]
+fn test_compiled_simulation_rejects_wide_signals() {
+    #[derive(LogicBlock, Default)]
+    struct Wide {
+        pub a: Signal<In, Bits<200>>,
+        pub b: Signal<Out, Bits<200>>,
+    }
+
+    impl Logic for Wide {
+        #[hdl_gen]
+        fn update(&mut self) {
+            self.b.next = self.a.val();
+        }
+    }
+
+    let mut uut = Wide::default();
+    uut.connect_all();
+    match CompiledSimulation::new(&uut) {
+        Err(CompileError::SignalTooWide { width, .. }) => assert_eq!(width, 200),
+        _ => panic!("A 200 bit signal was not rejected"),
+    }
+}
@@ -0,0 +1,138 @@
+use rust_hdl::prelude::*;
+
+#[derive(Copy, Clone, Debug, PartialEq, LogicState)]
+enum State {
+    Idle,
+    Run,
+    Done,
+    Fault,
+}
+
+#[derive(LogicBlock, Default)]
+struct Sequencer {
+    pub clock: Signal<In, Clock>,
+    pub start: Signal<In, Bit>,
+    pub abort: Signal<In, Bit>,
+    pub busy: Signal<Out, Bit>,
+    state: DFF<State>,
+    count: DFF<Bits<4>>,
+}
+
+impl Logic for Sequencer {
+    #[hdl_gen(coverage)]
+    fn update(&mut self) {
+        dff_setup!(self, clock, state, count);
+        self.busy.next = false;
+        match self.state.q.val() {
+            State::Idle => {
+                if self.start.val() {
+                    self.state.d.next = State::Run;
+                    self.count.d.next = 0.into();
+                }
+            }
+            State::Run => {
+                self.busy.next = true;
+                self.count.d.next = self.count.q.val() + 1;
+                if self.abort.val() {
+                    self.state.d.next = State::Fault;
+                } else if self.count.q.val() == 5 {
+                    self.state.d.next = State::Done;
+                }
+            }
+            State::Done => self.state.d.next = State::Idle,
+            State::Fault => self.state.d.next = State::Idle,
+        }
+    }
+}
+
+fn run(abort: bool, coverage: &mut Coverage) {
+    let mut sim = Simulation::new();
+    sim.add_clock(5, |x: &mut Box<Sequencer>| x.clock.next = !x.clock.val());
+    sim.add_testbench(move |mut sim: Sim<Sequencer>| {
+        let mut x = sim.init()?;
+        wait_clock_cycles!(sim, clock, x, 2);
+        x.start.next = true;
+        wait_clock_cycle!(sim, clock, x);
+        x.start.next = false;
+        if abort {
+            wait_clock_cycles!(sim, clock, x, 2);
+            x.abort.next = true;
+            wait_clock_cycle!(sim, clock, x);
+            x.abort.next = false;
+        }
+        wait_clock_cycles!(sim, clock, x, 10);
+        sim.done(x)
+    });
+    sim.run_covered(Box::new(Sequencer::default()), 10_000, coverage)
+        .unwrap();
+}
+
+fn abort_branch(coverage: &Coverage) -> &BranchCoverage {
+    coverage.branches().find(|x| x.arms.len() == 3).unwrap()
+}
+
+#[test]
+fn test_coverage_of_a_single_run() {
+    let mut coverage = Coverage::default();
+    run(false, &mut coverage);
+    let fsm = coverage.fsm("uut.state.q").unwrap();
+    let entered = |state: &str| fsm.states.iter().find(|x| x.0 == state).unwrap().1;
+    assert_eq!(entered("Run"), 1);
+    assert_eq!(entered("Done"), 1);
+    assert_eq!(entered("Fault"), 0);
+    assert_eq!(fsm.transitions[&("Idle".into(), "Run".into())], 1);
+    assert_eq!(fsm.transitions.len(), 3);
+    // The counter only reaches 6
+    let count = coverage.toggle("uut.count.q").unwrap();
+    assert!(count.is_covered(0));
+    assert!(!count.is_covered(3));
+    assert!(coverage.toggle("uut.clock").unwrap().is_covered(0));
+    // Only the kernel of the sequencer is instrumented
+    assert_eq!(coverage.branches().count(), 3);
+    assert!(coverage
+        .branches()
+        .all(|x| x.file.ends_with("core_coverage.rs")));
+    let branch = abort_branch(&coverage);
+    assert!(branch.file.ends_with("core_coverage.rs"));
+    assert_eq!(branch.arms[0].0, "if");
+    assert_eq!(branch.arms[0].1, 0);
+    assert!(branch.arms[1].1 > 0);
+    let summary = coverage.summary();
+    assert_eq!(summary.states, (3, 4));
+    assert!(summary.branches.0 < summary.branches.1);
+    let report = coverage.to_string();
+    assert!(report.contains("State coverage: 3/4 states"));
+    assert!(report.contains("uut.state.q: Fault never entered"));
+}
+
+#[test]
+fn test_coverage_is_merged_across_runs() {
+    let mut coverage = Coverage::default();
+    run(false, &mut coverage);
+    let first = coverage.summary();
+    run(true, &mut coverage);
+    let summary = coverage.summary();
+    assert_eq!(summary.states, (4, 4));
+    assert!(summary.transitions > first.transitions);
+    assert!(summary.branches.0 > first.branches.0);
+    assert_eq!(summary.branches.1, first.branches.1);
+    assert!(abort_branch(&coverage).arms[0].1 > 0);
+    let fsm = coverage.fsm("uut.state.q").unwrap();
+    assert_eq!(fsm.states[1].1, 2);
+    let mut lcov = vec![];
+    coverage.write_lcov(&mut lcov).unwrap();
+    let lcov = String::from_utf8(lcov).unwrap();
+    assert!(lcov.contains("SF:uut.state.q\n"));
+    assert!(lcov.contains("SF:uut.count.q\n"));
+    let brda = lcov
+        .lines()
+        .skip_while(|x| !x.ends_with("core_coverage.rs"))
+        .take_while(|x| *x != "end_of_record")
+        .filter(|x| x.starts_with("BRDA:"))
+        .count();
+    assert_eq!(brda, summary.branches.1);
+    assert_eq!(
+        lcov.matches("end_of_record").count(),
+        lcov.matches("SF:").count()
+    );
+}
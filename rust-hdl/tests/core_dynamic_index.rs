@@ -0,0 +1,178 @@
+use rust_hdl::prelude::*;
+
+#[derive(LogicBlock, Default)]
+struct RegisterFile {
+    pub clock: Signal<In, Clock>,
+    pub write_enable: Signal<In, Bit>,
+    pub write_addr: Signal<In, Bits<2>>,
+    pub write_data: Signal<In, Bits<8>>,
+    pub read_addr: Signal<In, Bits<2>>,
+    pub read_data: Signal<Out, Bits<8>>,
+    regs: [DFF<Bits<8>>; 4],
+}
+
+impl Logic for RegisterFile {
+    #[hdl_gen]
+    fn update(&mut self) {
+        for i in 0..4 {
+            self.regs[i].clock.next = self.clock.val();
+            self.regs[i].d.next = self.regs[i].q.val();
+        }
+        if self.write_enable.val() {
+            self.regs[self.write_addr.val().index()].d.next = self.write_data.val();
+        }
+        self.read_data.next = self.regs[self.read_addr.val().index()].q.val();
+    }
+}
+
+#[test]
+fn test_register_file_simulates() {
+    let mut uut = RegisterFile::default();
+    uut.connect_all();
+    let mut sim = Simulation::new();
+    sim.add_clock(5, |x: &mut Box<RegisterFile>| x.clock.next = !x.clock.val());
+    sim.add_testbench(move |mut sim: Sim<RegisterFile>| {
+        let mut x = sim.init()?;
+        for (addr, data) in [(0_u64, 0x12_u64), (3, 0x34), (1, 0x56), (2, 0x78)] {
+            x.write_enable.next = true;
+            x.write_addr.next = addr.to_bits();
+            x.write_data.next = data.to_bits();
+            wait_clock_cycle!(sim, clock, x);
+        }
+        x.write_enable.next = false;
+        for (addr, data) in [(0_u64, 0x12_u64), (1, 0x56), (2, 0x78), (3, 0x34)] {
+            x.read_addr.next = addr.to_bits();
+            x = sim.wait(1, x)?;
+            sim_assert_eq!(sim, x.read_data.val(), data, x);
+        }
+        sim.done(x)
+    });
+    sim.run(Box::new(uut), 1000).unwrap();
+}
+
+#[test]
+fn test_register_file_is_a_mux() {
+    let mut uut = RegisterFile::default();
+    uut.connect_all();
+    let vlog = generate_verilog(&uut);
+    assert!(vlog.contains("reg  [7:0] regs$q$sel;"));
+    assert!(vlog.contains("regs$q$sel = 8'h0;"));
+    for i in 0..4 {
+        assert!(vlog.contains(&format!("if (read_addr == {}) begin", i)));
+        assert!(vlog.contains(&format!("regs$q$sel = regs${}$q;", i)));
+        assert!(vlog.contains(&format!("if (write_addr == {}) begin", i)));
+        assert!(vlog.contains(&format!("regs${}$d = write_data;", i)));
+    }
+    assert!(vlog.contains("read_data = regs$q$sel;"));
+    let vhdl = generate_vhdl(&uut);
+    assert!(vhdl.contains("variable \\regs$q$sel$next\\ : unsigned(7 downto 0);"));
+}
+
+#[derive(LogicBlock, Default)]
+struct Crossbar {
+    pub inputs: [Signal<In, Bits<8>>; 4],
+    pub select: [Signal<In, Bits<2>>; 4],
+    pub outputs: [Signal<Out, Bits<8>>; 4],
+}
+
+impl Logic for Crossbar {
+    #[hdl_gen]
+    fn update(&mut self) {
+        for i in 0..4 {
+            let sel = self.select[i].val();
+            self.outputs[i].next = self.inputs[sel.index()].val();
+        }
+    }
+}
+
+const ROUTES: [[u64; 4]; 3] = [[0, 1, 2, 3], [3, 2, 1, 0], [2, 2, 0, 1]];
+
+#[test]
+fn test_crossbar_simulates() {
+    let mut sim = Simulation::new();
+    sim.add_testbench(move |mut sim: Sim<Crossbar>| {
+        let mut x = sim.init()?;
+        for i in 0..4 {
+            x.inputs[i].next = (0x10 * i as u64 + 1).to_bits();
+        }
+        for route in ROUTES {
+            for (select, input) in x.select.iter_mut().zip(route) {
+                select.next = input.to_bits();
+            }
+            x = sim.wait(1, x)?;
+            for (output, input) in x.outputs.iter().zip(route) {
+                sim_assert_eq!(sim, output.val(), 0x10 * input + 1, x);
+            }
+        }
+        sim.done(x)
+    });
+    let mut uut = Crossbar::default();
+    uut.connect_all();
+    sim.run(Box::new(uut), 100).unwrap();
+}
+
+#[test]
+fn test_crossbar_compiles() {
+    let mut uut = Crossbar::default();
+    uut.connect_all();
+    let vlog = generate_verilog(&uut);
+    assert!(vlog.contains("if (sel$let == 2) begin"));
+    assert!(vlog.contains("inputs$sel = inputs$2;"));
+    assert!(vlog.contains("outputs$3 = inputs$sel;"));
+    let mut sim = CompiledSimulation::new(&uut).unwrap();
+    let inputs = [0, 1, 2, 3].map(|i| sim.signal(&format!("inputs${}", i)).unwrap());
+    let select = [0, 1, 2, 3].map(|i| sim.signal(&format!("select${}", i)).unwrap());
+    let outputs = [0, 1, 2, 3].map(|i| sim.signal(&format!("outputs${}", i)).unwrap());
+    for (i, input) in inputs.into_iter().enumerate() {
+        sim.write(input, (0x10 * i as u64 + 1).to_bits::<8>());
+    }
+    for route in ROUTES {
+        for i in 0..4 {
+            sim.write(select[i], route[i].to_bits::<2>());
+        }
+        sim.settle().unwrap();
+        for i in 0..4 {
+            assert_eq!(sim.get(outputs[i]), 0x10 * route[i] as u128 + 1);
+        }
+    }
+}
+
+// Only three of the four values of the index are in range
+#[derive(LogicBlock, Default)]
+struct Picker {
+    pub inputs: [Signal<In, Bits<8>>; 3],
+    pub select: Signal<In, Bits<2>>,
+    pub picked: Signal<Out, Bits<8>>,
+}
+
+impl Logic for Picker {
+    #[hdl_gen]
+    fn update(&mut self) {
+        self.picked.next = self.inputs[self.select.val().index()].val();
+    }
+}
+
+#[test]
+fn test_out_of_range_index_panics_in_rust_and_reads_zero_in_hdl() {
+    let mut uut = Picker::default();
+    uut.connect_all();
+    uut.select.next = 3.into();
+    uut.update_all();
+    let panicked = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| uut.update()));
+    assert!(panicked.is_err());
+    let mut uut = Picker::default();
+    uut.connect_all();
+    let mut sim = CompiledSimulation::new(&uut).unwrap();
+    let inputs = [0, 1, 2].map(|i| sim.signal(&format!("inputs${}", i)).unwrap());
+    let select = sim.signal("select").unwrap();
+    let picked = sim.signal("picked").unwrap();
+    for (i, input) in inputs.into_iter().enumerate() {
+        sim.write(input, (0x10 * i as u64 + 1).to_bits::<8>());
+    }
+    sim.write(select, 2_u64.to_bits::<2>());
+    sim.settle().unwrap();
+    assert_eq!(sim.get(picked), 0x21);
+    sim.write(select, 3_u64.to_bits::<2>());
+    sim.settle().unwrap();
+    assert_eq!(sim.get(picked), 0);
+}
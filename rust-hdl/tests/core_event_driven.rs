@@ -0,0 +1,27 @@
+mod common;
+
+use common::run_channel_bank;
+
+#[test]
+fn test_event_driven_matches_full_update() {
+    let (full, _) = run_channel_bank(false, 200);
+    let (event, _) = run_channel_bank(true, 200);
+    assert_eq!(full.len(), 200);
+    assert_ne!(full.last(), Some(&0));
+    assert_eq!(full, event);
+}
+
+#[test]
+fn test_event_driven_skips_idle_blocks() {
+    let (_, (front_full, idle_full)) = run_channel_bank(false, 200);
+    let (_, (front_event, idle_event)) = run_channel_bank(true, 200);
+    // Without events, every block is updated on every pass
+    assert!(front_full > 200);
+    assert!(idle_full.iter().all(|x| *x == front_full));
+    // The front channel gets a new sample every cycle, so it is still updated
+    // each time, but the idle channels are only updated while the circuit is
+    // initialized and when the setting is written
+    assert!(front_event >= 200);
+    assert!(front_event < front_full);
+    assert!(idle_event.iter().all(|x| *x < 10));
+}
@@ -267,7 +267,7 @@ fn test_fifo_can_be_filled_synchronous_fifo() {
     yosys_validate("fifo_3", &generate_verilog(&uut)).unwrap();
     let mut sim = Simulation::new();
     let rdata = (0..16)
-        .map(|_| rand::random::<u16>().to_bits())
+        .map(|_| sim.rng().gen::<u16>().to_bits())
         .collect::<Vec<_>>();
     sim.add_clock(5, |x: &mut Box<SynchronousFIFOTest>| {
         x.clock.next = !x.clock.val()
@@ -311,7 +311,7 @@ fn test_fifo_works_synchronous_fifo() {
     yosys_validate("fifo_4", &generate_verilog(&uut)).unwrap();
     let mut sim = Simulation::new();
     let rdata = (0..1024)
-        .map(|_| rand::random::<u16>().to_bits())
+        .map(|_| sim.rng().gen::<u16>().to_bits())
         .collect::<Vec<_>>();
     let rdata_read = rdata.clone();
     sim.add_clock(5, |x: &mut Box<SynchronousFIFOTest>| {
@@ -326,8 +326,8 @@ fn test_fifo_works_synchronous_fifo() {
             x.fifo.write.next = true;
             wait_clock_cycle!(sim, clock, x);
             x.fifo.write.next = false;
-            if rand::thread_rng().gen::<f64>() < 0.3 {
-                for _ in 0..(rand::thread_rng().gen::<u8>() % 40) {
+            if sim.rng().gen::<f64>() < 0.3 {
+                for _ in 0..(sim.rng().gen::<u8>() % 40) {
                     wait_clock_cycle!(sim, clock, x);
                 }
             }
@@ -346,8 +346,8 @@ fn test_fifo_works_synchronous_fifo() {
             x.fifo.read.next = true;
             wait_clock_cycle!(sim, clock, x);
             x.fifo.read.next = false;
-            if rand::thread_rng().gen::<f64>() < 0.3 {
-                for _ in 0..(rand::thread_rng().gen::<u8>() % 40) {
+            if sim.rng().gen::<f64>() < 0.3 {
+                for _ in 0..(sim.rng().gen::<u8>() % 40) {
                     wait_clock_cycle!(sim, clock, x);
                 }
             }
@@ -396,7 +396,7 @@ fn test_fifo_works_asynchronous_fifo() {
     yosys_validate("fifo_5", &generate_verilog(&uut)).unwrap();
     let mut sim = Simulation::new();
     let rdata = (0..1024)
-        .map(|_| rand::random::<u16>().to_bits())
+        .map(|_| sim.rng().gen::<u16>().to_bits())
         .collect::<Vec<_>>();
     let rdata_read = rdata.clone();
     sim.add_clock(5, |x: &mut Box<AsynchronousFIFOTest>| {
@@ -414,8 +414,8 @@ fn test_fifo_works_asynchronous_fifo() {
             x.fifo.write.next = true;
             wait_clock_cycle!(sim, write_clock, x);
             x.fifo.write.next = false;
-            if rand::thread_rng().gen::<f64>() < 0.3 {
-                for _ in 0..(rand::thread_rng().gen::<u8>() % 40) {
+            if sim.rng().gen::<f64>() < 0.3 {
+                for _ in 0..(sim.rng().gen::<u8>() % 40) {
                     wait_clock_cycle!(sim, write_clock, x);
                 }
             }
@@ -434,8 +434,8 @@ fn test_fifo_works_asynchronous_fifo() {
             x.fifo.read.next = true;
             wait_clock_cycle!(sim, read_clock, x);
             x.fifo.read.next = false;
-            if rand::thread_rng().gen::<f64>() < 0.3 {
-                for _ in 0..(rand::thread_rng().gen::<u8>() % 40) {
+            if sim.rng().gen::<f64>() < 0.3 {
+                for _ in 0..(sim.rng().gen::<u8>() % 40) {
                     wait_clock_cycle!(sim, read_clock, x);
                 }
             }
@@ -537,7 +537,7 @@ fn test_fifo_reducer_works() {
     yosys_validate("fifo_5b", &generate_verilog(&uut)).unwrap();
     let mut sim = Simulation::new();
     let rdata = (0..256)
-        .map(|_| rand::random::<u16>().to_bits())
+        .map(|_| sim.rng().gen::<u16>().to_bits())
         .collect::<Vec<_>>();
     let mut rdata_read = vec![];
     for x in &rdata {
@@ -556,8 +556,8 @@ fn test_fifo_reducer_works() {
             x.wide_fifo.write.next = true;
             wait_clock_cycle!(sim, clock, x);
             x.wide_fifo.write.next = false;
-            if rand::thread_rng().gen::<f64>() < 0.3 {
-                for _ in 0..(rand::thread_rng().gen::<u8>() % 40) {
+            if sim.rng().gen::<f64>() < 0.3 {
+                for _ in 0..(sim.rng().gen::<u8>() % 40) {
                     wait_clock_cycle!(sim, clock, x);
                 }
             }
@@ -576,8 +576,8 @@ fn test_fifo_reducer_works() {
             x.narrow_fifo.read.next = true;
             wait_clock_cycle!(sim, clock, x);
             x.narrow_fifo.read.next = false;
-            if rand::thread_rng().gen::<f64>() < 0.3 {
-                for _ in 0..(rand::thread_rng().gen::<u8>() % 40) {
+            if sim.rng().gen::<f64>() < 0.3 {
+                for _ in 0..(sim.rng().gen::<u8>() % 40) {
                     wait_clock_cycle!(sim, clock, x);
                 }
             }
@@ -0,0 +1,128 @@
+use rust_hdl::prelude::*;
+use std::io::BufReader;
+
+// Tracks the number of items held in a FIFO of depth 8
+#[derive(LogicBlock, Default)]
+struct Occupancy {
+    pub clock: Signal<In, Clock>,
+    pub write: Signal<In, Bit>,
+    pub read: Signal<In, Bit>,
+    pub full: Signal<Out, Bit>,
+    pub empty: Signal<Out, Bit>,
+    count: DFF<Bits<4>>,
+}
+
+impl Logic for Occupancy {
+    #[hdl_gen]
+    fn update(&mut self) {
+        dff_setup!(self, clock, count);
+        self.full.next = self.count.q.val() == 8;
+        self.empty.next = self.count.q.val() == 0;
+        if self.write.val() && !self.read.val() {
+            self.count.d.next = self.count.q.val() + 1;
+        } else if self.read.val() && !self.write.val() {
+            self.count.d.next = self.count.q.val() - 1;
+        }
+    }
+    fn properties(&self) -> Vec<Property> {
+        vec![
+            Property::assume("no_write_when_full", "!(write && full)").on_clock("clock"),
+            Property::assume("no_read_when_empty", "!(read && empty)").on_clock("clock"),
+            Property::assert("no_overflow", "count$q <= 8"),
+            Property::cover("fills", "full").on_clock("clock"),
+        ]
+    }
+}
+
+#[test]
+fn test_properties_are_emitted_for_formal_tools() {
+    let mut uut = Occupancy::default();
+    uut.connect_all();
+    let vlog = generate_verilog(&uut);
+    let formal = vlog
+        .lines()
+        .skip_while(|x| !x.contains("`ifdef FORMAL"))
+        .take_while(|x| !x.contains("`endif"))
+        .map(|x| x.trim())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        formal,
+        vec![
+            "`ifdef FORMAL",
+            "no_write_when_full: assume property (@(posedge clock) !(write && full));",
+            "no_read_when_empty: assume property (@(posedge clock) !(read && empty));",
+            "always @(*) no_overflow: assert (count$q <= 8);",
+            "fills: cover property (@(posedge clock) full);",
+        ]
+    );
+    // Only the top module has properties
+    assert_eq!(vlog.matches("`ifdef FORMAL").count(), 1);
+    assert!(generate_system_verilog(&uut).contains("no_overflow: assert (count$q <= 8);"));
+}
+
+#[test]
+fn test_sby_script() {
+    let options = FormalOptions {
+        mode: FormalMode::Prove,
+        depth: 12,
+        ..Default::default()
+    };
+    let sby = generate_sby(&options);
+    assert!(sby.starts_with("[options]\nmode prove\ndepth 12\n\n[engines]\nsmtbmc\n"));
+    assert!(sby.contains("[script]\nread -formal top.sv\nprep -top top\n"));
+    assert!(sby.ends_with("[files]\ntop.sv\n"));
+    let dir = std::env::temp_dir().join("formal_export_occupancy");
+    let mut uut = Occupancy::default();
+    uut.connect_all();
+    formal_export(&uut, &dir, &options).unwrap();
+    assert_eq!(std::fs::read_to_string(dir.join("top.sby")).unwrap(), sby);
+    assert!(std::fs::read_to_string(dir.join("top.sv"))
+        .unwrap()
+        .contains("`ifdef FORMAL"));
+}
+
+#[test]
+fn test_formal_verify_occupancy() {
+    let mut uut = Occupancy::default();
+    uut.connect_all();
+    match formal_verify(&uut, "formal_occupancy", &FormalOptions::default()) {
+        Err(FormalError::SbyNotFound) => println!("Skipping - sby is not installed"),
+        x => assert_eq!(x.unwrap(), FormalResult::Pass),
+    }
+}
+
+#[test]
+fn test_counterexample_uses_simulation_names() {
+    let trace = r#"$timescale 1ns $end
+$scope module top $end
+$var wire 1 ! clock $end
+$var wire 4 " count$q $end
+$var wire 1 # $auto$rtlil.cc:1234 $end
+$var wire 1 $ _witness_.anyinit_q $end
+$scope module count $end
+$var wire 4 % q $end
+$upscope $end
+$upscope $end
+$enddefinitions $end
+#0
+0!
+b1001 "
+0#
+1$
+b1001 %
+#5
+1!
+"#;
+    let mut out = vec![];
+    remap_trace(BufReader::new(trace.as_bytes()), &mut out).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.contains("$scope module uut $end"));
+    assert!(!out.contains("top"));
+    assert!(!out.contains("count$q"));
+    assert!(!out.contains("_witness_"));
+    assert!(!out.contains("rtlil"));
+    assert!(out.contains("$var wire 4 % q $end"));
+    assert!(out.contains("b1001 %"));
+    assert!(!out.contains("b1001 \""));
+    assert!(out.contains("#5\n1!"));
+}
@@ -0,0 +1,184 @@
+use rust_hdl::prelude::*;
+
+#[derive(LogicBlock, Default)]
+struct BusPoint {
+    pub bus_wire: Signal<InOut, Bits<8>>,
+    pub buffer: TristateBuffer<Bits<8>>,
+}
+
+impl Logic for BusPoint {
+    fn update(&mut self) {
+        self.bus_wire.link(&mut self.buffer.bus);
+    }
+
+    fn connect(&mut self) {
+        self.bus_wire.connect();
+    }
+}
+
+#[derive(LogicBlock, Default)]
+struct BusTest {
+    pub left: BusPoint,
+    pub right: BusPoint,
+}
+
+impl Logic for BusTest {
+    fn update(&mut self) {
+        self.left
+            .bus_wire
+            .simulate_connected_tristate(&mut self.right.bus_wire);
+    }
+}
+
+#[test]
+fn test_tristate_contention_is_unknown() {
+    let mut uut = BusTest::default();
+    uut.left.buffer.write_data.connect();
+    uut.left.buffer.write_enable.connect();
+    uut.right.buffer.write_data.connect();
+    uut.right.buffer.write_enable.connect();
+    uut.connect_all();
+    let mut sim = Simulation::new();
+    sim.add_testbench(move |mut sim: Sim<BusTest>| {
+        let mut x = sim.init()?;
+        x.left.buffer.write_enable.next = true;
+        x.left.buffer.write_data.next = 0x42.into();
+        x = sim.wait(10, x)?;
+        sim_assert!(sim, !x.left.bus_wire.is_contended(), x);
+        // Both ends drive the bus at the same time
+        x.right.buffer.write_enable.next = true;
+        x.right.buffer.write_data.next = 0x13.into();
+        x = sim.wait(10, x)?;
+        sim_assert!(sim, x.left.bus_wire.is_contended(), x);
+        sim_assert!(sim, x.right.bus_wire.is_contended(), x);
+        x.left.buffer.write_enable.next = false;
+        x = sim.wait(10, x)?;
+        sim_assert!(sim, !x.left.bus_wire.is_contended(), x);
+        sim.done(x)
+    });
+    let mut trace = vec![];
+    sim.run_traced(Box::new(uut), 100, &mut trace).unwrap();
+    let trace = String::from_utf8(trace).unwrap();
+    assert!(trace.contains("bxxxxxxxx"));
+}
+
+#[derive(LogicBlock, Default)]
+struct Counter {
+    pub clock: Signal<In, Clock>,
+    pub reset: Signal<In, Bit>,
+    pub count: Signal<Out, Bits<8>>,
+    dff: DFF<Bits<8>>,
+}
+
+impl Logic for Counter {
+    #[hdl_gen]
+    fn update(&mut self) {
+        dff_setup!(self, clock, dff);
+        self.dff.d.next = self.dff.q.val() + 1;
+        if self.reset.val() {
+            self.dff.d.next = 0.into();
+        }
+        self.count.next = self.dff.q.val();
+    }
+}
+
+fn clock_cycle(sim: &mut CompiledSimulation, clock: SignalId) {
+    sim.set(clock, 1);
+    sim.settle().unwrap();
+    sim.set(clock, 0);
+    sim.settle().unwrap();
+}
+
+#[test]
+fn test_unreset_register_is_unknown() {
+    let mut uut = Counter::default();
+    uut.connect_all();
+    let mut sim = CompiledSimulation::new_four_state(&uut).unwrap();
+    let clock = sim.signal("clock").unwrap();
+    let reset = sim.signal("reset").unwrap();
+    let count = sim.signal("count").unwrap();
+    let mut buffer = vec![];
+    let mut trace = sim.trace(&mut buffer).unwrap();
+    sim.set(clock, 0);
+    sim.settle().unwrap();
+    trace.sample(0, &sim).unwrap();
+    assert_eq!(sim.unknown(count), 0xFF);
+    // Without a reset, the register never takes a known value
+    sim.set(reset, 0);
+    for _ in 0..4 {
+        clock_cycle(&mut sim, clock);
+    }
+    trace.sample(10, &sim).unwrap();
+    assert_eq!(sim.unknown(count), 0xFF);
+    sim.set(reset, 1);
+    clock_cycle(&mut sim, clock);
+    trace.sample(20, &sim).unwrap();
+    assert_eq!(sim.unknown(count), 0);
+    assert_eq!(sim.get(count), 0);
+    sim.set(reset, 0);
+    for _ in 0..3 {
+        clock_cycle(&mut sim, clock);
+    }
+    trace.sample(30, &sim).unwrap();
+    assert_eq!(sim.unknown(count), 0);
+    assert_eq!(sim.get(count), 3);
+    drop(trace);
+    let trace = String::from_utf8(buffer).unwrap();
+    assert!(trace.contains("bxxxxxxxx"));
+    assert!(trace.contains("b00000011"));
+}
+
+#[test]
+fn test_two_state_register_starts_at_zero() {
+    let mut uut = Counter::default();
+    uut.connect_all();
+    let mut sim = CompiledSimulation::new(&uut).unwrap();
+    let clock = sim.signal("clock").unwrap();
+    let count = sim.signal("count").unwrap();
+    sim.settle().unwrap();
+    clock_cycle(&mut sim, clock);
+    assert_eq!(sim.unknown(count), 0);
+    assert_eq!(sim.get(count), 1);
+}
+
+#[test]
+fn test_simulation_tracks_unknown_values() {
+    let mut uut = Counter::default();
+    uut.connect_all();
+    let mut sim = Simulation::new();
+    sim.set_four_state(&uut).unwrap();
+    sim.add_clock(5, |x: &mut Box<Counter>| x.clock.next = !x.clock.val());
+    sim.add_testbench(move |mut sim: Sim<Counter>| {
+        let mut x = sim.init()?;
+        // The native simulation starts the register at zero, but without a
+        // reset it never takes a known value
+        wait_clock_cycles!(sim, clock, x, 4);
+        sim_assert!(sim, x.count.is_unknown(), x);
+        sim_assert_eq!(sim, x.count.unknown_bits(), 0xFF, x);
+        x.reset.next = true;
+        wait_clock_cycle!(sim, clock, x);
+        x.reset.next = false;
+        wait_clock_cycles!(sim, clock, x, 3);
+        sim_assert!(sim, !x.count.is_unknown(), x);
+        sim.done(x)
+    });
+    let mut trace = vec![];
+    sim.run_traced(Box::new(uut), 1000, &mut trace).unwrap();
+    let trace = String::from_utf8(trace).unwrap();
+    assert!(trace.contains("bxxxxxxxx"));
+}
+
+#[test]
+fn test_simulation_is_two_state_by_default() {
+    let mut uut = Counter::default();
+    uut.connect_all();
+    let mut sim = Simulation::new();
+    sim.add_clock(5, |x: &mut Box<Counter>| x.clock.next = !x.clock.val());
+    sim.add_testbench(move |mut sim: Sim<Counter>| {
+        let mut x = sim.init()?;
+        wait_clock_cycles!(sim, clock, x, 4);
+        sim_assert!(sim, !x.count.is_unknown(), x);
+        sim.done(x)
+    });
+    sim.run(Box::new(uut), 1000).unwrap();
+}
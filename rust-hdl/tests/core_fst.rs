@@ -0,0 +1,197 @@
+use fst_reader::{FstFilter, FstHierarchyEntry, FstReader, FstSignalValue};
+use rust_hdl::docs::vcd2svg::vcd_to_txt;
+use rust_hdl::prelude::*;
+use std::collections::{BTreeMap, HashMap};
+
+#[derive(Copy, Clone, Debug, PartialEq, LogicState)]
+enum Phase {
+    Fill,
+    Drain,
+    Hold,
+}
+
+#[derive(LogicBlock, Default)]
+struct Sweeper {
+    pub clock: Signal<In, Clock>,
+    pub count: Signal<Out, Bits<16>>,
+    counter: DFF<Bits<16>>,
+    phase: DFF<Phase>,
+}
+
+impl Logic for Sweeper {
+    #[hdl_gen]
+    fn update(&mut self) {
+        dff_setup!(self, clock, counter, phase);
+        self.counter.d.next = self.counter.q.val() + 1;
+        self.count.next = self.counter.q.val();
+        match self.phase.q.val() {
+            Phase::Fill => self.phase.d.next = Phase::Drain,
+            Phase::Drain => self.phase.d.next = Phase::Hold,
+            Phase::Hold => self.phase.d.next = Phase::Fill,
+        }
+    }
+}
+
+fn run_to(name: &str) -> Vec<u8> {
+    let mut sim = Simulation::new();
+    sim.add_clock(5, |x: &mut Box<Sweeper>| x.clock.next = !x.clock.val());
+    sim.add_testbench(move |mut sim: Sim<Sweeper>| {
+        let mut x = sim.init()?;
+        wait_clock_cycles!(sim, clock, x, 5000);
+        sim.done(x)
+    });
+    let path = vcd_path!(name);
+    sim.run_to_file(Box::new(Sweeper::default()), 1_000_000, &path)
+        .unwrap();
+    std::fs::read(&path).unwrap()
+}
+
+// The waveform of each signal in a trace, keyed by its path, as the times at
+// which it changed and its new value (with the most significant bit first)
+type Waves = BTreeMap<String, Vec<(u64, String)>>;
+
+fn add_change(waves: &mut Waves, path: &str, time: u64, value: String) {
+    let wave = waves.entry(path.to_owned()).or_default();
+    if wave.last().map(|x| &x.1) != Some(&value) {
+        wave.push((time, value));
+    }
+}
+
+fn read_fst(fst: &[u8]) -> Waves {
+    let mut reader = FstReader::open(std::io::Cursor::new(fst)).unwrap();
+    let mut scopes = vec![];
+    let mut paths = HashMap::new();
+    reader
+        .read_hierarchy(|entry| match entry {
+            FstHierarchyEntry::Scope { name, .. } => scopes.push(name),
+            FstHierarchyEntry::UpScope => {
+                scopes.pop();
+            }
+            FstHierarchyEntry::Var { name, handle, .. } => {
+                paths.insert(handle.get_index(), format!("{}.{}", scopes.join("."), name));
+            }
+            _ => {}
+        })
+        .unwrap();
+    let mut waves = Waves::new();
+    reader
+        .read_signals(&FstFilter::all(), |time, handle, value| {
+            if let FstSignalValue::String(bits) = value {
+                let value = String::from_utf8(bits.to_vec()).unwrap();
+                add_change(&mut waves, &paths[&handle.get_index()], time, value);
+            }
+        })
+        .unwrap();
+    waves
+}
+
+// Enums are written to the VCD file by name, and to the FST file as the bits
+// of their value
+fn read_vcd(vcd: &[u8]) -> Waves {
+    let mut parser = vcd::Parser::new(vcd);
+    let header = parser.parse_header().unwrap();
+    let mut paths = HashMap::new();
+    let mut scopes = vec![(vec![], &header.items)];
+    while let Some((path, items)) = scopes.pop() {
+        for item in items {
+            match item {
+                vcd::ScopeItem::Scope(scope) => {
+                    let mut path = path.clone();
+                    path.push(scope.identifier.clone());
+                    scopes.push((path, &scope.children));
+                }
+                vcd::ScopeItem::Var(var) => {
+                    paths.insert(var.code, format!("{}.{}", path.join("."), var.reference));
+                }
+            }
+        }
+    }
+    let bits = |x: &[vcd::Value]| x.iter().map(|x| x.to_string()).collect::<String>();
+    let phase = |x: &str| match x {
+        "Fill" => "00",
+        "Drain" => "01",
+        "Hold" => "10",
+        _ => panic!("unexpected phase {}", x),
+    };
+    let mut waves = Waves::new();
+    let mut time = 0;
+    for command in parser {
+        match command.unwrap() {
+            vcd::Command::Timestamp(t) => time = t,
+            vcd::Command::ChangeScalar(id, x) => {
+                add_change(&mut waves, &paths[&id], time, bits(&[x]))
+            }
+            vcd::Command::ChangeVector(id, x) => {
+                add_change(&mut waves, &paths[&id], time, bits(&x))
+            }
+            vcd::Command::ChangeString(id, x) => {
+                add_change(&mut waves, &paths[&id], time, phase(&x).to_owned())
+            }
+            _ => {}
+        }
+    }
+    waves
+}
+
+#[test]
+fn test_fst_output_is_selected_by_extension() {
+    let fst = run_to("sweeper.fst");
+    let vcd = run_to("sweeper.vcd");
+    assert!(fst.len() * 10 < vcd.len());
+    let fst = read_fst(&fst);
+    let vcd = read_vcd(&vcd);
+    assert_eq!(
+        fst.keys().collect::<Vec<_>>(),
+        [
+            "uut.clock",
+            "uut.count",
+            "uut.counter.clock",
+            "uut.counter.d",
+            "uut.counter.q",
+            "uut.phase.clock",
+            "uut.phase.d",
+            "uut.phase.q"
+        ]
+    );
+    assert_eq!(fst["uut.count"].len(), 5001);
+    assert_eq!(fst["uut.phase.q"][1], (5, "01".to_owned()));
+    assert_eq!(fst, vcd);
+}
+
+// Converts the FST file back to VCD with `fst2vcd` (from GTKWave), and checks
+// that it holds the same waveforms as the VCD written by the simulation.
+// Run with `cargo test -- --ignored`
+#[test]
+#[ignore]
+fn test_fst_round_trips_through_fst2vcd() {
+    run_to("sweeper_round_trip.fst");
+    run_to("sweeper_round_trip.vcd");
+    let fst = vcd_path!("sweeper_round_trip.fst");
+    let vcd = vcd_path!("sweeper_round_trip.vcd");
+    let converted = vcd_path!("sweeper_round_trip_fst2vcd.vcd");
+    let status = std::process::Command::new("fst2vcd")
+        .args(["-f", &fst, "-o", &converted])
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let signals = ["uut.clock", "uut.count", "uut.counter.q"];
+    for (start, stop) in [(0, 500), (40_000, 41_000)] {
+        assert_eq!(
+            vcd_to_txt(&converted, &signals, start, stop, 120).unwrap(),
+            vcd_to_txt(&vcd, &signals, start, stop, 120).unwrap()
+        );
+    }
+}
+
+#[test]
+fn test_run_to_file_reports_io_errors() {
+    for name in ["missing_dir/sweeper.fst", "missing_dir/sweeper.vcd"] {
+        let mut sim = Simulation::new();
+        sim.add_clock(5, |x: &mut Box<Sweeper>| x.clock.next = !x.clock.val());
+        let path = vcd_path!(name);
+        assert!(matches!(
+            sim.run_to_file(Box::new(Sweeper::default()), 1_000, &path),
+            Err(SimError::IOError(_))
+        ));
+    }
+}
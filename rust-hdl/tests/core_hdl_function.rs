@@ -0,0 +1,251 @@
+use rust_hdl::prelude::*;
+
+#[hdl_function]
+fn saturating_add(a: Bits<8>, b: Bits<8>) -> Bits<8> {
+    let sum = bit_cast::<9, 8>(a) + bit_cast::<9, 8>(b);
+    let mut result = bit_cast::<8, 9>(sum);
+    if sum.get_bit(8) {
+        result = 0xFF.into();
+    }
+    result
+}
+
+#[hdl_function]
+fn parity<const N: usize>(x: Bits<N>) -> Bit {
+    let mut p = false;
+    for i in 0..N {
+        p = p != x.get_bit(i);
+    }
+    p
+}
+
+#[hdl_function]
+fn clamped_add(a: Bits<8>, b: Bits<8>, limit: Bits<8>) -> Bits<8> {
+    let sum = saturating_add(a, b);
+    let mut result = sum;
+    if sum > limit {
+        result = limit;
+    }
+    result
+}
+
+#[derive(LogicBlock, Default)]
+struct Accumulate {
+    pub a: Signal<In, Bits<8>>,
+    pub b: Signal<In, Bits<8>>,
+    pub sum: Signal<Out, Bits<8>>,
+    pub clamped: Signal<Out, Bits<8>>,
+    pub parity: Signal<Out, Bit>,
+}
+
+impl Logic for Accumulate {
+    #[hdl_gen]
+    fn update(&mut self) {
+        self.sum.next = saturating_add(self.a.val(), self.b.val());
+        self.clamped.next = clamped_add(self.a.val(), self.b.val(), 200.into());
+        self.parity.next = parity::<8>(self.sum.val());
+    }
+}
+
+#[derive(LogicBlock, Default)]
+struct Nibbles {
+    pub x: Signal<In, Bits<8>>,
+    pub doubled: Signal<Out, Bits<8>>,
+    pub parity: Signal<Out, Bit>,
+}
+
+impl Logic for Nibbles {
+    #[hdl_gen]
+    fn update(&mut self) {
+        self.doubled.next = saturating_add(self.x.val(), self.x.val());
+        self.parity.next = parity(self.x.val().get_bits::<4>(0));
+    }
+}
+
+#[derive(LogicBlock, Default)]
+struct Both {
+    pub a: Signal<In, Bits<8>>,
+    pub b: Signal<In, Bits<8>>,
+    pub sum: Signal<Out, Bits<8>>,
+    pub clamped: Signal<Out, Bits<8>>,
+    pub parity: Signal<Out, Bit>,
+    pub doubled: Signal<Out, Bits<8>>,
+    pub nibble_parity: Signal<Out, Bit>,
+    accumulate: Accumulate,
+    nibbles: Nibbles,
+}
+
+impl Logic for Both {
+    #[hdl_gen]
+    fn update(&mut self) {
+        self.accumulate.a.next = self.a.val();
+        self.accumulate.b.next = self.b.val();
+        self.nibbles.x.next = self.a.val();
+        self.sum.next = self.accumulate.sum.val();
+        self.clamped.next = self.accumulate.clamped.val();
+        self.parity.next = self.accumulate.parity.val();
+        self.doubled.next = self.nibbles.doubled.val();
+        self.nibble_parity.next = self.nibbles.parity.val();
+    }
+}
+
+// The functions are ordinary Rust functions too
+fn expected(a: u64, b: u64) -> [u64; 5] {
+    let (a, b) = (a.to_bits::<8>(), b.to_bits::<8>());
+    let sum = saturating_add(a, b);
+    [
+        sum.index() as u64,
+        clamped_add(a, b, 200.into()).index() as u64,
+        parity(sum) as u64,
+        saturating_add(a, a).index() as u64,
+        parity(a.get_bits::<4>(0)) as u64,
+    ]
+}
+
+const CASES: [(u64, u64); 5] = [(0, 0), (3, 5), (100, 99), (200, 100), (0x7F, 0x0F)];
+
+#[test]
+fn test_hdl_functions_match_rust() {
+    assert_eq!(expected(200, 100), [255, 200, 0, 255, 1]);
+    assert_eq!(expected(3, 5), [8, 8, 1, 6, 0]);
+}
+
+#[test]
+fn test_hdl_functions_simulate() {
+    let mut sim = Simulation::new();
+    sim.add_testbench(move |mut sim: Sim<Both>| {
+        let mut x = sim.init()?;
+        for (a, b) in CASES {
+            x.a.next = a.to_bits();
+            x.b.next = b.to_bits();
+            x = sim.wait(1, x)?;
+            let [sum, clamped, parity, doubled, nibble_parity] = expected(a, b);
+            sim_assert_eq!(sim, x.sum.val(), sum, x);
+            sim_assert_eq!(sim, x.clamped.val(), clamped, x);
+            sim_assert_eq!(sim, x.parity.val(), parity != 0, x);
+            sim_assert_eq!(sim, x.doubled.val(), doubled, x);
+            sim_assert_eq!(sim, x.nibble_parity.val(), nibble_parity != 0, x);
+        }
+        sim.done(x)
+    });
+    let mut uut = Both::default();
+    uut.connect_all();
+    sim.run(Box::new(uut), 100).unwrap();
+}
+
+#[test]
+fn test_hdl_functions_are_verilog_functions() {
+    let mut uut = Both::default();
+    uut.connect_all();
+    let vlog = generate_verilog(&uut);
+    // Each module declares the functions it calls, callees first
+    assert_eq!(
+        vlog.matches("function  [7:0] core_hdl_function$saturating_add$8$8$8;")
+            .count(),
+        2
+    );
+    assert_eq!(
+        vlog.matches("function  [7:0] core_hdl_function$clamped_add$8$8$8$8;")
+            .count(),
+        1
+    );
+    assert!(
+        vlog.find("function  [7:0] core_hdl_function$saturating_add$8$8$8;")
+            .unwrap()
+            < vlog
+                .find("function  [7:0] core_hdl_function$clamped_add$8$8$8$8;")
+                .unwrap()
+    );
+    assert!(vlog.contains("function  core_hdl_function$parity$8$8$1;"));
+    assert!(vlog.contains("function  core_hdl_function$parity$4$4$1;"));
+    assert!(vlog.contains("input  [7:0] limit;"));
+    assert!(vlog.contains("reg  [8:0] sum$let;"));
+    assert!(vlog.contains("core_hdl_function$saturating_add$8$8$8 = result$let;"));
+    assert!(vlog.contains("sum = core_hdl_function$saturating_add$8$8$8(a, b);"));
+    assert!(vlog.contains("clamped = core_hdl_function$clamped_add$8$8$8$8(a, b, 32'hc8);"));
+    assert!(vlog.contains("endfunction"));
+    // Functions are named after the module they are defined in
+    assert!(!vlog.contains("function  [7:0] saturating_add;"));
+    let sv = generate_system_verilog(&uut);
+    assert_eq!(
+        sv.matches("function  [7:0] core_hdl_function$saturating_add$8$8$8;")
+            .count(),
+        2
+    );
+    let vhdl = generate_vhdl(&uut);
+    assert!(vhdl.contains(
+        "function \\core_hdl_function$saturating_add$8$8$8\\(a : unsigned(7 downto 0); b : unsigned(7 downto 0)) return unsigned is"
+    ));
+    assert!(vhdl.contains("return \\core_hdl_function$saturating_add$8$8$8$next\\;"));
+    assert!(vhdl.contains("\\sum$next\\ := \\core_hdl_function$saturating_add$8$8$8\\(a, b);"));
+}
+
+// A function that shares its name with a signal of the calling module
+#[derive(LogicBlock, Default)]
+struct Clash {
+    pub a: Signal<In, Bits<8>>,
+    pub twice: Signal<Out, Bits<8>>,
+}
+
+impl Logic for Clash {
+    fn update(&mut self) {}
+    fn connect(&mut self) {
+        self.twice.connect();
+    }
+    fn hdl(&self) -> Verilog {
+        let twice = ast::VerilogFunction {
+            name: "twice".into(),
+            args: vec![ast::VerilogLocal::new("x", &self.a.val())],
+            result: ast::VerilogLocal::new("twice", &self.a.val()),
+            body: vec![ast::VerilogStatement::Assignment(
+                ast::VerilogExpression::Signal("twice".into()),
+                ast::VerilogExpression::Signal("x".into()),
+            )],
+        };
+        Verilog::Combinatorial(vec![ast::VerilogStatement::Assignment(
+            ast::VerilogExpression::Signal("twice$next".into()),
+            ast::VerilogExpression::Call(
+                Box::new(twice),
+                vec![ast::VerilogExpression::Signal("a".into())],
+            ),
+        )])
+    }
+}
+
+#[test]
+fn test_hdl_function_named_like_a_signal_is_rejected() {
+    let mut uut = Clash::default();
+    uut.connect_all();
+    match check_all(&uut) {
+        Err(CheckError::FunctionNameClashes(list)) => {
+            assert_eq!(list.len(), 1);
+            assert_eq!(list[0].name, "twice");
+        }
+        x => panic!("Expected a function name clash, got {:?}", x),
+    }
+    assert!(try_generate_verilog(&uut).is_err());
+}
+
+#[test]
+fn test_hdl_functions_compile() {
+    let mut uut = Both::default();
+    uut.connect_all();
+    let mut sim = CompiledSimulation::new(&uut).unwrap();
+    let [a, b, sum, clamped, parity, doubled, nibble_parity] = [
+        "a",
+        "b",
+        "sum",
+        "clamped",
+        "parity",
+        "doubled",
+        "nibble_parity",
+    ]
+    .map(|x| sim.signal(x).unwrap());
+    for (x, y) in CASES {
+        sim.write(a, x.to_bits::<8>());
+        sim.write(b, y.to_bits::<8>());
+        sim.settle().unwrap();
+        let outputs = [sum, clamped, parity, doubled, nibble_parity].map(|s| sim.get(s) as u64);
+        assert_eq!(outputs, expected(x, y));
+    }
+}
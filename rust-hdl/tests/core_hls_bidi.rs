@@ -16,21 +16,21 @@ struct BusTest {
     pub clock: Signal<In, Clock>,
 }
 
-impl Default for BusTest {
-    fn default() -> Self {
+impl BusTest {
+    fn new<R: Rng>(rng: &mut R) -> Self {
         let dlen = 256;
         let data1 = (0..dlen)
-            .map(|_| rand::thread_rng().gen::<u8>().to_bits())
+            .map(|_| rng.gen::<u8>().to_bits())
             .collect::<Vec<_>>();
         let data2 = (0..dlen)
-            .map(|_| rand::thread_rng().gen::<u8>().to_bits())
+            .map(|_| rng.gen::<u8>().to_bits())
             .collect::<Vec<_>>();
 
         Self {
-            dtm_feeder: LazyFIFOFeeder::new(&data1, &bursty_vec(data1.len())),
-            dtm_reader: LazyFIFOReader::new(&data1, &bursty_vec(data1.len())),
-            mtd_feeder: LazyFIFOFeeder::new(&data2, &bursty_vec(data2.len())),
-            mtd_reader: LazyFIFOReader::new(&data2, &bursty_vec(data2.len())),
+            dtm_feeder: LazyFIFOFeeder::new(&data1, &bursty_vec(rng, data1.len())),
+            dtm_reader: LazyFIFOReader::new(&data1, &bursty_vec(rng, data1.len())),
+            mtd_feeder: LazyFIFOFeeder::new(&data2, &bursty_vec(rng, data2.len())),
+            mtd_reader: LazyFIFOReader::new(&data2, &bursty_vec(rng, data2.len())),
             device_to_bus_fifo: Default::default(),
             device_from_bus_fifo: Default::default(),
             device: Default::default(),
@@ -99,7 +99,7 @@ impl Logic for BusTest {
 
 #[test]
 fn test_bidi2_bus_test_synthesizes() {
-    let mut uut = BusTest::default();
+    let mut uut = BusTest::new(Simulation::<BusTest>::new().rng());
     uut.mtd_feeder.start.connect();
     uut.mtd_reader.start.connect();
     uut.dtm_feeder.start.connect();
@@ -112,7 +112,8 @@ fn test_bidi2_bus_test_synthesizes() {
 
 #[test]
 fn test_bidi2_bus_works() {
-    let mut uut = BusTest::default();
+    let mut sim = Simulation::new();
+    let mut uut = BusTest::new(sim.rng());
     uut.mtd_feeder.start.connect();
     uut.mtd_reader.start.connect();
     uut.dtm_feeder.start.connect();
@@ -121,7 +122,6 @@ fn test_bidi2_bus_works() {
     uut.connect_all();
     let vlog = generate_verilog(&uut);
     yosys_validate("tribus_0", &vlog).unwrap();
-    let mut sim = Simulation::new();
     sim.add_clock(5, |x: &mut Box<BusTest>| x.clock.next = !x.clock.val());
     sim.add_testbench(move |mut sim: Sim<BusTest>| {
         let mut x = sim.init()?;
@@ -325,8 +325,8 @@ fn test_stream_command_works() {
             x = sim.watch(|x| x.iport.strobe_out.val(), x)?;
             wait_clock_cycle!(sim, clock, x);
             x.iport.ready_in.next = false;
-            if rand::thread_rng().gen::<f64>() < 0.3 {
-                for _ in 0..(rand::thread_rng().gen::<u8>() % 40) {
+            if sim.rng().gen::<f64>() < 0.3 {
+                for _ in 0..(sim.rng().gen::<u8>() % 40) {
                     wait_clock_cycle!(sim, clock, x);
                 }
             }
@@ -20,10 +20,10 @@ impl Logic for CrossWidenTestFixture {
     }
 }
 
-impl Default for CrossWidenTestFixture {
-    fn default() -> Self {
+impl CrossWidenTestFixture {
+    fn new<R: Rng>(rng: &mut R) -> Self {
         let data1 = (0..256)
-            .map(|_| rand::thread_rng().gen::<u16>().to_bits())
+            .map(|_| rng.gen::<u16>().to_bits())
             .collect::<Vec<_>>();
         let mut data2 = vec![];
         for x in &data1 {
@@ -32,9 +32,9 @@ impl Default for CrossWidenTestFixture {
             }
         }
         Self {
-            feeder: LazyFIFOFeeder::new(&data2, &bursty_vec(1024)),
+            feeder: LazyFIFOFeeder::new(&data2, &bursty_vec(rng, 1024)),
             cross: CrossWiden::new(WordOrder::LeastSignificantFirst),
-            reader: LazyFIFOReader::new(&data1, &bursty_vec(256)),
+            reader: LazyFIFOReader::new(&data1, &bursty_vec(rng, 256)),
             clock: Default::default(),
         }
     }
@@ -42,12 +42,12 @@ impl Default for CrossWidenTestFixture {
 
 #[test]
 fn test_cross_widen_test_fixture() {
-    let mut uut = CrossWidenTestFixture::default();
+    let mut sim = Simulation::new();
+    let mut uut = CrossWidenTestFixture::new(sim.rng());
     uut.clock.connect();
     uut.feeder.start.connect();
     uut.reader.start.connect();
     uut.connect_all();
-    let mut sim = Simulation::new();
     sim.add_clock(5, |x: &mut Box<CrossWidenTestFixture>| {
         x.clock.next = !x.clock.val()
     });
@@ -89,10 +89,10 @@ impl Logic for CrossNarrowTestFixture {
     }
 }
 
-impl Default for CrossNarrowTestFixture {
-    fn default() -> Self {
+impl CrossNarrowTestFixture {
+    fn new<R: Rng>(rng: &mut R) -> Self {
         let data1 = (0..256)
-            .map(|_| rand::thread_rng().gen::<u16>().to_bits())
+            .map(|_| rng.gen::<u16>().to_bits())
             .collect::<Vec<_>>();
         let mut data2 = vec![];
         for x in &data1 {
@@ -101,9 +101,9 @@ impl Default for CrossNarrowTestFixture {
             }
         }
         Self {
-            feeder: LazyFIFOFeeder::new(&data1, &bursty_vec(256)),
+            feeder: LazyFIFOFeeder::new(&data1, &bursty_vec(rng, 256)),
             cross: CrossNarrow::new(WordOrder::LeastSignificantFirst),
-            reader: LazyFIFOReader::new(&data2, &bursty_vec(1024)),
+            reader: LazyFIFOReader::new(&data2, &bursty_vec(rng, 1024)),
             clock: Default::default(),
         }
     }
@@ -111,12 +111,12 @@ impl Default for CrossNarrowTestFixture {
 
 #[test]
 fn test_cross_narrow_test_fixture() {
-    let mut uut = CrossNarrowTestFixture::default();
+    let mut sim = Simulation::new();
+    let mut uut = CrossNarrowTestFixture::new(sim.rng());
     uut.clock.connect();
     uut.feeder.start.connect();
     uut.reader.start.connect();
     uut.connect_all();
-    let mut sim = Simulation::new();
     sim.add_clock(5, |x: &mut Box<CrossNarrowTestFixture>| {
         x.clock.next = !x.clock.val()
     });
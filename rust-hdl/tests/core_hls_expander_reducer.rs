@@ -28,10 +28,10 @@ impl Logic for ReducerTestFixture {
     }
 }
 
-impl Default for ReducerTestFixture {
-    fn default() -> Self {
+impl ReducerTestFixture {
+    fn new<R: Rng>(rng: &mut R) -> Self {
         let data1 = (0..256)
-            .map(|_| rand::thread_rng().gen::<u16>().to_bits())
+            .map(|_| rng.gen::<u16>().to_bits())
             .collect::<Vec<_>>();
         let mut data2 = vec![];
         for x in &data1 {
@@ -40,11 +40,11 @@ impl Default for ReducerTestFixture {
             }
         }
         Self {
-            feeder: LazyFIFOFeeder::new(&data1, &bursty_vec(256)),
+            feeder: LazyFIFOFeeder::new(&data1, &bursty_vec(rng, 256)),
             wide_fifo: Default::default(),
             reducer: Reducer::new(WordOrder::LeastSignificantFirst),
             narrow_fifo: Default::default(),
-            reader: LazyFIFOReader::new(&data2, &bursty_vec(1024)),
+            reader: LazyFIFOReader::new(&data2, &bursty_vec(rng, 1024)),
             clock: Default::default(),
         }
     }
@@ -52,7 +52,7 @@ impl Default for ReducerTestFixture {
 
 #[test]
 fn test_reducer_test_fixture_synthesizes() {
-    let mut uut = ReducerTestFixture::default();
+    let mut uut = ReducerTestFixture::new(Simulation::<ReducerTestFixture>::new().rng());
     uut.feeder.start.connect();
     uut.reader.start.connect();
     uut.connect_all();
@@ -62,11 +62,11 @@ fn test_reducer_test_fixture_synthesizes() {
 
 #[test]
 fn test_reducer_test_fixture_operation() {
-    let mut uut = ReducerTestFixture::default();
+    let mut sim = Simulation::new();
+    let mut uut = ReducerTestFixture::new(sim.rng());
     uut.feeder.start.connect();
     uut.reader.start.connect();
     uut.connect_all();
-    let mut sim = Simulation::new();
     sim.add_clock(5, |x: &mut Box<ReducerTestFixture>| {
         x.clock.next = !x.clock.val()
     });
@@ -124,10 +124,10 @@ impl Logic for ExpanderTestFixture {
     }
 }
 
-impl Default for ExpanderTestFixture {
-    fn default() -> Self {
+impl ExpanderTestFixture {
+    fn new<R: Rng>(rng: &mut R) -> Self {
         let data1 = (0..256)
-            .map(|_| rand::thread_rng().gen::<u16>().to_bits())
+            .map(|_| rng.gen::<u16>().to_bits())
             .collect::<Vec<_>>();
         let mut data2 = vec![];
         for x in &data1 {
@@ -136,11 +136,11 @@ impl Default for ExpanderTestFixture {
             }
         }
         Self {
-            feeder: LazyFIFOFeeder::new(&data2, &bursty_vec(1024)),
+            feeder: LazyFIFOFeeder::new(&data2, &bursty_vec(rng, 1024)),
             nibble_fifo: Default::default(),
             expander: Expander::new(WordOrder::LeastSignificantFirst),
             word_fifo: Default::default(),
-            reader: LazyFIFOReader::new(&data1, &bursty_vec(256)),
+            reader: LazyFIFOReader::new(&data1, &bursty_vec(rng, 256)),
             clock: Default::default(),
         }
     }
@@ -148,7 +148,7 @@ impl Default for ExpanderTestFixture {
 
 #[test]
 fn test_expander_test_fixture() {
-    let mut uut = ExpanderTestFixture::default();
+    let mut uut = ExpanderTestFixture::new(Simulation::<ExpanderTestFixture>::new().rng());
     uut.feeder.start.connect();
     uut.reader.start.connect();
     uut.connect_all();
@@ -158,11 +158,11 @@ fn test_expander_test_fixture() {
 
 #[test]
 fn test_expander_test_fixture_operation() {
-    let mut uut = ExpanderTestFixture::default();
+    let mut sim = Simulation::new();
+    let mut uut = ExpanderTestFixture::new(sim.rng());
     uut.feeder.start.connect();
     uut.reader.start.connect();
     uut.connect_all();
-    let mut sim = Simulation::new();
     sim.add_clock(5, |x: &mut Box<ExpanderTestFixture>| {
         x.clock.next = !x.clock.val()
     });
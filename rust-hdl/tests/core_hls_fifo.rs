@@ -23,11 +23,11 @@ fn test_hls_fifo_works() {
     uut.fifo.bus_read.read.connect();
     uut.fifo.bus_read.data.connect();
     uut.connect_all();
+    let mut sim = Simulation::new();
     let rdata = (0..128)
-        .map(|_| rand::random::<u8>().to_bits())
+        .map(|_| sim.rng().gen::<u8>().to_bits())
         .collect::<Vec<_>>();
     let data = rdata.clone();
-    let mut sim = Simulation::new();
     sim.add_clock(5, |x: &mut Box<HLSFIFOTest>| x.clock.next = !x.clock.val());
     sim.add_testbench(move |mut sim: Sim<HLSFIFOTest>| {
         let mut x = sim.init()?;
@@ -38,8 +38,8 @@ fn test_hls_fifo_works() {
             x.fifo.bus_write.write.next = true;
             wait_clock_cycle!(sim, clock, x);
             x.fifo.bus_write.write.next = false;
-            if rand::thread_rng().gen::<f64>() < 0.3 {
-                for _ in 0..(rand::thread_rng().gen::<u8>() % 40) {
+            if sim.rng().gen::<f64>() < 0.3 {
+                for _ in 0..(sim.rng().gen::<u8>() % 40) {
                     wait_clock_cycle!(sim, clock, x);
                 }
             }
@@ -55,8 +55,8 @@ fn test_hls_fifo_works() {
             x.fifo.bus_read.read.next = true;
             wait_clock_cycle!(sim, clock, x);
             x.fifo.bus_read.read.next = false;
-            if rand::thread_rng().gen::<f64>() < 0.3 {
-                for _ in 0..(rand::thread_rng().gen::<u8>() % 40) {
+            if sim.rng().gen::<f64>() < 0.3 {
+                for _ in 0..(sim.rng().gen::<u8>() % 40) {
                     wait_clock_cycle!(sim, clock, x);
                 }
             }
@@ -89,17 +89,11 @@ impl Logic for FIFOTestFixture {
 }
 
 impl FIFOTestFixture {
-    pub fn new(data: &[Bits<8>]) -> FIFOTestFixture {
+    pub fn new<R: Rng>(data: &[Bits<8>], rng: &mut R) -> FIFOTestFixture {
         FIFOTestFixture {
-            feeder: LazyFIFOFeeder::new(
-                data,
-                &(0..data.len()).map(|_| bursty_rand()).collect::<Vec<_>>(),
-            ),
+            feeder: LazyFIFOFeeder::new(data, &bursty_vec(rng, data.len())),
             fifo: SyncFIFO::default(),
-            reader: LazyFIFOReader::new(
-                data,
-                &(0..data.len()).map(|_| bursty_rand()).collect::<Vec<_>>(),
-            ),
+            reader: LazyFIFOReader::new(data, &bursty_vec(rng, data.len())),
             clock: Default::default(),
         }
     }
@@ -107,16 +101,16 @@ impl FIFOTestFixture {
 
 #[test]
 fn test_feeder_works() {
+    let mut sim = Simulation::new();
     let data = (0..256)
-        .map(|_| rand::thread_rng().gen::<u8>().to_bits())
+        .map(|_| sim.rng().gen::<u8>().to_bits())
         .collect::<Vec<_>>();
-    let mut uut = FIFOTestFixture::new(&data);
+    let mut uut = FIFOTestFixture::new(&data, sim.rng());
     uut.feeder.start.connect();
     uut.reader.start.connect();
     uut.connect_all();
     let vlog = generate_verilog(&uut);
     yosys_validate("fifo_feed", &vlog).unwrap();
-    let mut sim = Simulation::new();
     sim.add_clock(5, |x: &mut Box<FIFOTestFixture>| {
         x.clock.next = !x.clock.val()
     });
@@ -159,17 +153,11 @@ impl Logic for FIFOTestFixtureAsync {
 }
 
 impl FIFOTestFixtureAsync {
-    pub fn new(data: &[Bits<8>]) -> FIFOTestFixtureAsync {
+    pub fn new<R: Rng>(data: &[Bits<8>], rng: &mut R) -> FIFOTestFixtureAsync {
         Self {
-            feeder: LazyFIFOFeeder::new(
-                data,
-                &(0..data.len()).map(|_| bursty_rand()).collect::<Vec<_>>(),
-            ),
+            feeder: LazyFIFOFeeder::new(data, &bursty_vec(rng, data.len())),
             fifo: Default::default(),
-            reader: LazyFIFOReader::new(
-                data,
-                &(0..data.len()).map(|_| bursty_rand()).collect::<Vec<_>>(),
-            ),
+            reader: LazyFIFOReader::new(data, &bursty_vec(rng, data.len())),
             clock_write: Default::default(),
             clock_read: Default::default(),
         }
@@ -178,10 +166,11 @@ impl FIFOTestFixtureAsync {
 
 #[test]
 fn test_feeder_async_works() {
+    let mut sim = Simulation::new();
     let data = (0..256)
-        .map(|_| rand::thread_rng().gen::<u8>().to_bits())
+        .map(|_| sim.rng().gen::<u8>().to_bits())
         .collect::<Vec<_>>();
-    let mut uut = FIFOTestFixtureAsync::new(&data);
+    let mut uut = FIFOTestFixtureAsync::new(&data, sim.rng());
     uut.clock_read.connect();
     uut.clock_write.connect();
     uut.feeder.start.connect();
@@ -189,7 +178,6 @@ fn test_feeder_async_works() {
     uut.connect_all();
     let vlog = generate_verilog(&uut);
     yosys_validate("fifo_feed_async", &vlog).unwrap();
-    let mut sim = Simulation::new();
     sim.add_clock(5, |x: &mut Box<FIFOTestFixtureAsync>| {
         x.clock_read.next = !x.clock_read.val()
     });
@@ -0,0 +1,170 @@
+use rust_hdl::prelude::*;
+
+#[derive(LogicBlock, Default)]
+struct Mixer {
+    pub a: Signal<In, Bits<8>>,
+    pub b: Signal<In, Bits<8>>,
+    pub sel: Signal<In, Bit>,
+    pub sum: Signal<Out, Bits<8>>,
+    pub wide: Signal<Out, Bits<16>>,
+    pub flags: Signal<Out, Bits<4>>,
+}
+
+impl Logic for Mixer {
+    #[hdl_gen]
+    fn update(&mut self) {
+        let total = self.a.val() + self.b.val();
+        let wide: Bits<16> = bit_cast::<16, 8>(total);
+        let total = total ^ self.b.val();
+        self.wide.next = wide + 1;
+        self.sum.next = total;
+        if self.sel.val() {
+            let total = self.a.val();
+            self.sum.next = total;
+        }
+        let mut flags: Bits<4> = 0.into();
+        for i in 0..4 {
+            let bit = total.get_bit(i);
+            flags = flags.replace_bit(i, bit);
+        }
+        self.flags.next = flags;
+    }
+}
+
+fn mixer_expected(a: u64, b: u64, sel: bool) -> (u64, u64, u64) {
+    let total = (a + b) & 0xFF;
+    let wide = total + 1;
+    let total = total ^ b;
+    let sum = if sel { a } else { total };
+    (sum, wide, total & 0xF)
+}
+
+const CASES: [(u64, u64, bool); 5] = [
+    (0, 0, false),
+    (3, 5, false),
+    (200, 100, false),
+    (0xAA, 0x0F, true),
+    (255, 255, false),
+];
+
+#[test]
+fn test_let_bindings_simulate() {
+    let mut sim = Simulation::new();
+    sim.add_testbench(move |mut sim: Sim<Mixer>| {
+        let mut x = sim.init()?;
+        for (a, b, sel) in CASES {
+            x.a.next = a.to_bits();
+            x.b.next = b.to_bits();
+            x.sel.next = sel;
+            x = sim.wait(1, x)?;
+            let (sum, wide, flags) = mixer_expected(a, b, sel);
+            sim_assert_eq!(sim, x.sum.val(), sum, x);
+            sim_assert_eq!(sim, x.wide.val(), wide, x);
+            sim_assert_eq!(sim, x.flags.val(), flags, x);
+        }
+        sim.done(x)
+    });
+    sim.run(Box::new(Mixer::default()), 100).unwrap();
+}
+
+#[test]
+fn test_let_bindings_are_local_regs() {
+    let mut uut = Mixer::default();
+    uut.connect_all();
+    let vlog = generate_verilog(&uut);
+    assert!(vlog.contains("reg  [7:0] total$let;"));
+    assert!(vlog.contains("reg  [15:0] wide$let;"));
+    assert!(vlog.contains("reg  [7:0] total$let$1;"));
+    assert!(vlog.contains("reg  [7:0] total$let$2;"));
+    assert!(vlog.contains("reg  [3:0] flags$let;"));
+    assert!(vlog.contains("reg  bit$let;"));
+    assert!(vlog.contains("total$let = a + b;"));
+    assert!(vlog.contains("total$let$1 = total$let ^ b;"));
+    assert!(vlog.contains("sum = total$let$1;"));
+    assert!(vlog.contains("sum = total$let$2;"));
+    assert!(vlog.contains("bit$let = total$let$1[3];"));
+    assert!(vlog.contains("flags = flags$let;"));
+    // Only assigned when sel is set, so it needs a default to avoid a latch
+    assert!(vlog.contains("total$let$2 = 8'h0;"));
+    assert!(!vlog.contains("bit$let = 1'h0;"));
+    assert!(generate_system_verilog(&uut).contains("reg  [7:0] total$let;"));
+    let vhdl = generate_vhdl(&uut);
+    assert!(vhdl.contains("variable \\total$let$next\\ : unsigned(7 downto 0);"));
+}
+
+#[test]
+fn test_let_bindings_compile() {
+    let mut uut = Mixer::default();
+    uut.connect_all();
+    let mut sim = CompiledSimulation::new(&uut).unwrap();
+    let [a, b, sel, sum, wide, flags] =
+        ["a", "b", "sel", "sum", "wide", "flags"].map(|x| sim.signal(x).unwrap());
+    for (x, y, s) in CASES {
+        sim.write(a, x.to_bits::<8>());
+        sim.write(b, y.to_bits::<8>());
+        sim.write(sel, s);
+        sim.settle().unwrap();
+        let (x, y, z) = mixer_expected(x, y, s);
+        assert_eq!(
+            (sim.get(sum), sim.get(wide), sim.get(flags)),
+            (x as u128, y as u128, z as u128)
+        );
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, LogicState)]
+enum Opcode {
+    Nop,
+    Load,
+    Store,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Default, LogicStruct)]
+struct Command {
+    op: Opcode,
+    length: Bits<6>,
+}
+
+#[derive(LogicBlock, Default)]
+struct Decoder {
+    pub cmd: Signal<In, Command>,
+    pub length: Signal<Out, Bits<6>>,
+    pub is_store: Signal<Out, Bit>,
+}
+
+impl Logic for Decoder {
+    #[hdl_gen]
+    fn update(&mut self) {
+        let cmd = self.cmd.val();
+        let op = cmd.op;
+        self.length.next = cmd.length;
+        self.is_store.next = false;
+        if op == Opcode::Store {
+            self.is_store.next = true;
+        }
+    }
+}
+
+#[test]
+fn test_let_bindings_of_enums_and_structs() {
+    let mut uut = Decoder::default();
+    uut.connect_all();
+    let vlog = generate_verilog(&uut);
+    assert!(vlog.contains("reg  [7:0] cmd$let;"));
+    assert!(vlog.contains("reg  [1:0] op$let;"));
+    assert!(vlog.contains("localparam Opcode$Store = 2;"));
+    let mut sim = CompiledSimulation::new(&uut).unwrap();
+    let cmd = sim.signal("cmd").unwrap();
+    let length = sim.signal("length").unwrap();
+    let is_store = sim.signal("is_store").unwrap();
+    for (op, len) in [(Opcode::Load, 12_u64), (Opcode::Store, 33)] {
+        let value = Command {
+            op,
+            length: len.to_bits(),
+        };
+        sim.write(cmd, value);
+        sim.settle().unwrap();
+        assert_eq!(sim.get(length), len as u128);
+        assert_eq!(sim.get(is_store), (op == Opcode::Store) as u128);
+    }
+}
@@ -0,0 +1,114 @@
+#![allow(unreachable_patterns)]
+use rust_hdl::prelude::*;
+
+#[derive(Copy, Clone, Debug, PartialEq, LogicState)]
+enum Mode {
+    Idle,
+    Run,
+    Pause,
+    Done,
+}
+
+#[derive(LogicBlock)]
+struct Sloppy {
+    pub clock: Signal<In, Clock>,
+    pub enable: Signal<In, Bit>,
+    pub value: Signal<Out, Bits<8>>,
+    pub forgotten: Signal<Out, Bit>,
+    latched: Signal<Local, Bits<8>>,
+    scratch: Signal<Local, Bit>,
+    big: Constant<Bits<12>>,
+    state: DFF<Mode>,
+}
+
+impl Default for Sloppy {
+    fn default() -> Self {
+        Self {
+            clock: Default::default(),
+            enable: Default::default(),
+            value: Default::default(),
+            forgotten: Default::default(),
+            latched: Default::default(),
+            scratch: Default::default(),
+            big: Constant::new(0x3FF.into()),
+            state: Default::default(),
+        }
+    }
+}
+
+impl Logic for Sloppy {
+    #[hdl_gen]
+    fn update(&mut self) {
+        dff_setup!(self, clock, state);
+        if self.enable.val() {
+            self.latched.next = bit_cast::<8, 12>(self.big.val());
+        }
+        self.scratch.next = self.enable.val();
+        match self.state.q.val() {
+            Mode::Idle => self.state.d.next = Mode::Run,
+            Mode::Run => self.state.d.next = Mode::Pause,
+            Mode::Pause => self.state.d.next = Mode::Done,
+            Mode::Done => self.state.d.next = Mode::Idle,
+            _ => self.state.d.next = Mode::Idle,
+        }
+        self.value.next = self.latched.val();
+    }
+}
+
+fn has_lint(lints: &[Lint], name: &str, kind: LintKind) -> bool {
+    lints
+        .iter()
+        .any(|x| x.location.path == "uut" && x.location.name == name && x.kind == kind)
+}
+
+#[test]
+fn test_lint_finds_problems() {
+    let mut uut = Sloppy::default();
+    uut.connect_all();
+    let lints = lint(&uut);
+    for x in &lints {
+        println!("{}", x);
+    }
+    assert!(has_lint(&lints, "latched", LintKind::Latch));
+    assert!(has_lint(&lints, "scratch", LintKind::Unused));
+    assert!(has_lint(&lints, "forgotten", LintKind::UndrivenOutput));
+    assert!(has_lint(
+        &lints,
+        "big",
+        LintKind::Truncation {
+            value: "12'h3ff".into(),
+            width: 8
+        }
+    ));
+    assert!(has_lint(&lints, "default", LintKind::UnreachableArm));
+    assert_eq!(lints.len(), 5);
+}
+
+#[derive(LogicBlock, Default)]
+struct Tidy {
+    pub clock: Signal<In, Clock>,
+    pub enable: Signal<In, Bit>,
+    pub count: Signal<Out, Bits<8>>,
+    step: Signal<Local, Bits<8>>,
+    counter: DFF<Bits<8>>,
+}
+
+impl Logic for Tidy {
+    #[hdl_gen]
+    fn update(&mut self) {
+        dff_setup!(self, clock, counter);
+        self.step.next = 0.into();
+        if self.enable.val() {
+            self.step.next = 1.into();
+        }
+        self.counter.d.next = self.counter.q.val() + self.step.val();
+        self.count.next = self.counter.q.val();
+    }
+}
+
+#[test]
+fn test_lint_clean_design() {
+    let mut uut = Tidy::default();
+    uut.connect_all();
+    assert!(lint(&uut).is_empty());
+}
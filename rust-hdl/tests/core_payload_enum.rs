@@ -0,0 +1,268 @@
+use rust_hdl::prelude::*;
+
+#[derive(Copy, Clone, Debug, PartialEq, LogicState)]
+enum Command {
+    Idle,
+    Read(Bits<8>),
+    Write { addr: Bits<8>, data: Bits<16> },
+}
+
+#[test]
+fn test_payload_enum_layout() {
+    assert_eq!(Command::BITS, 2 + 24);
+    assert_eq!(Command::default(), Command::Idle);
+    let cmd = Command::Write {
+        addr: 0x12.into(),
+        data: 0x3456.into(),
+    };
+    let bits: Bits<26> = cmd.into();
+    assert_eq!(bits, (2_u64 | (0x12 << 2) | (0x3456 << 10)).to_bits::<26>());
+    let read: Bits<26> = Command::Read(0xAB.into()).into();
+    assert_eq!(read, (1_u64 | (0xAB << 2)).to_bits::<26>());
+    let unpack = |x: Bits<26>| {
+        let bits = (0..26).map(|i| x.get_bit(i)).collect::<Vec<_>>();
+        Command::from_bits(&bits)
+    };
+    assert_eq!(unpack(bits), Some(cmd));
+    assert_eq!(unpack(read), Some(Command::Read(0xAB.into())));
+    assert_eq!(unpack(3_u64.to_bits()), None);
+    match Command::descriptor().kind {
+        TypeKind::Composite(fields) => {
+            assert_eq!(fields[0].fieldname, "tag");
+            assert!(matches!(&fields[0].kind.kind, TypeKind::Enum(x) if x.len() == 3));
+            assert!(matches!(fields[1].kind.kind, TypeKind::Bits(24)));
+        }
+        _ => panic!("Expected a tagged union"),
+    }
+}
+
+#[derive(LogicBlock, Default)]
+struct Encoder {
+    pub op: Signal<In, Bits<2>>,
+    pub addr: Signal<In, Bits<8>>,
+    pub data: Signal<In, Bits<16>>,
+    pub cmd: Signal<Out, Command>,
+}
+
+impl Logic for Encoder {
+    #[hdl_gen]
+    fn update(&mut self) {
+        self.cmd.next = Command::Idle;
+        if self.op.val() == 1 {
+            self.cmd.next = Command::Read(self.addr.val());
+        }
+        if self.op.val() == 2 {
+            self.cmd.next = Command::Write {
+                addr: self.addr.val(),
+                data: self.data.val(),
+            };
+        }
+    }
+}
+
+#[derive(LogicBlock, Default)]
+struct Decoder {
+    pub cmd: Signal<In, Command>,
+    pub read: Signal<Out, Bit>,
+    pub write: Signal<Out, Bit>,
+    pub addr: Signal<Out, Bits<8>>,
+    pub data: Signal<Out, Bits<16>>,
+}
+
+impl Logic for Decoder {
+    #[hdl_gen]
+    fn update(&mut self) {
+        self.read.next = false;
+        self.write.next = false;
+        self.addr.next = 0.into();
+        self.data.next = 0.into();
+        match self.cmd.val() {
+            Command::Idle => {}
+            Command::Read(addr) => {
+                self.read.next = true;
+                self.addr.next = addr;
+            }
+            Command::Write { addr, data } => {
+                self.write.next = true;
+                self.addr.next = addr;
+                self.data.next = data;
+            }
+        }
+    }
+}
+
+#[derive(LogicBlock, Default)]
+struct Loopback {
+    pub op: Signal<In, Bits<2>>,
+    pub addr: Signal<In, Bits<8>>,
+    pub data: Signal<In, Bits<16>>,
+    pub read: Signal<Out, Bit>,
+    pub write: Signal<Out, Bit>,
+    pub addr_out: Signal<Out, Bits<8>>,
+    pub data_out: Signal<Out, Bits<16>>,
+    encoder: Encoder,
+    decoder: Decoder,
+}
+
+impl Logic for Loopback {
+    #[hdl_gen]
+    fn update(&mut self) {
+        self.encoder.op.next = self.op.val();
+        self.encoder.addr.next = self.addr.val();
+        self.encoder.data.next = self.data.val();
+        self.decoder.cmd.next = self.encoder.cmd.val();
+        self.read.next = self.decoder.read.val();
+        self.write.next = self.decoder.write.val();
+        self.addr_out.next = self.decoder.addr.val();
+        self.data_out.next = self.decoder.data.val();
+    }
+}
+
+// (op, addr, data) -> (read, write, addr, data)
+const CASES: [(u64, u64, u64, [u64; 4]); 4] = [
+    (0, 0x12, 0x3456, [0, 0, 0, 0]),
+    (1, 0x12, 0x3456, [1, 0, 0x12, 0]),
+    (2, 0xAB, 0xCDEF, [0, 1, 0xAB, 0xCDEF]),
+    (3, 0xAB, 0xCDEF, [0, 0, 0, 0]),
+];
+
+#[test]
+fn test_payload_enum_simulates() {
+    let mut sim = Simulation::new();
+    sim.add_testbench(move |mut sim: Sim<Loopback>| {
+        let mut x = sim.init()?;
+        for (op, addr, data, expected) in CASES {
+            x.op.next = op.to_bits();
+            x.addr.next = addr.to_bits();
+            x.data.next = data.to_bits();
+            x = sim.wait(1, x)?;
+            sim_assert_eq!(sim, x.read.val(), expected[0] != 0, x);
+            sim_assert_eq!(sim, x.write.val(), expected[1] != 0, x);
+            sim_assert_eq!(sim, x.addr_out.val(), expected[2], x);
+            sim_assert_eq!(sim, x.data_out.val(), expected[3], x);
+        }
+        sim.done(x)
+    });
+    let mut uut = Loopback::default();
+    uut.connect_all();
+    sim.run(Box::new(uut), 100).unwrap();
+}
+
+#[test]
+fn test_payload_enum_slices_the_payload() {
+    let mut uut = Loopback::default();
+    uut.connect_all();
+    let vlog = generate_verilog(&uut);
+    assert!(vlog.contains("localparam Command$Write = 2;"));
+    assert!(vlog.contains("case (cmd[(32'h0)+:(2)])"));
+    assert!(vlog.contains("Command$Write:\n"));
+    assert!(vlog.contains("reg  [15:0] data$let;"));
+    assert!(vlog.contains("addr$let = cmd[(64'h2)+:(8)];"));
+    assert!(vlog.contains("data$let = cmd[(64'ha)+:(16)];"));
+    assert!(vlog.contains("reg  [25:0] Command$Write$new;"));
+    assert!(vlog.contains("Command$Write$new = Command$Write;"));
+    assert!(vlog.contains("Command$Write$new[(64'ha)+:(16)] = data;"));
+    assert!(vlog.contains("cmd = Command$Write$new;"));
+    let vhdl = generate_vhdl(&uut);
+    assert!(vhdl.contains(
+        "type \\Command$tag\\ is (\\Command$Idle\\, \\Command$Read\\, \\Command$Write\\);"
+    ));
+    let sv = generate_system_verilog(&uut);
+    assert!(sv.contains("Command$tag tag;"));
+}
+
+#[test]
+fn test_payload_enum_compiles() {
+    let mut uut = Loopback::default();
+    uut.connect_all();
+    let mut sim = CompiledSimulation::new(&uut).unwrap();
+    let [op, addr, data, read, write, addr_out, data_out] = [
+        "op", "addr", "data", "read", "write", "addr_out", "data_out",
+    ]
+    .map(|x| sim.signal(x).unwrap());
+    for (x, a, d, expected) in CASES {
+        sim.write(op, x.to_bits::<2>());
+        sim.write(addr, a.to_bits::<8>());
+        sim.write(data, d.to_bits::<16>());
+        sim.settle().unwrap();
+        let outputs = [read, write, addr_out, data_out].map(|s| sim.get(s) as u64);
+        assert_eq!(outputs, expected);
+    }
+}
+
+// A state machine that keeps a count in the state that uses it
+#[derive(Copy, Clone, Debug, PartialEq, LogicState)]
+enum Phase {
+    Idle,
+    Count { remaining: Bits<4> },
+    Done,
+}
+
+#[derive(LogicBlock, Default)]
+struct Countdown {
+    pub clock: Signal<In, Clock>,
+    pub start: Signal<In, Bit>,
+    pub load: Signal<In, Bits<4>>,
+    pub done: Signal<Out, Bit>,
+    state: DFF<Phase>,
+}
+
+impl Logic for Countdown {
+    #[hdl_gen]
+    fn update(&mut self) {
+        dff_setup!(self, clock, state);
+        self.done.next = false;
+        match self.state.q.val() {
+            Phase::Idle => {
+                if self.start.val() {
+                    self.state.d.next = Phase::Count {
+                        remaining: self.load.val(),
+                    };
+                }
+            }
+            Phase::Count { remaining } => {
+                if remaining == 0 {
+                    self.state.d.next = Phase::Done;
+                } else {
+                    self.state.d.next = Phase::Count {
+                        remaining: remaining - 1,
+                    };
+                }
+            }
+            Phase::Done => {
+                self.done.next = true;
+                self.state.d.next = Phase::Idle;
+            }
+        }
+    }
+}
+
+#[test]
+fn test_payload_enum_state_machine() {
+    let mut uut = Countdown::default();
+    uut.connect_all();
+    let mut sim = Simulation::new();
+    sim.add_clock(5, |x: &mut Box<Countdown>| x.clock.next = !x.clock.val());
+    sim.add_testbench(move |mut sim: Sim<Countdown>| {
+        let mut x = sim.init()?;
+        x.load.next = 3.into();
+        x.start.next = true;
+        wait_clock_cycle!(sim, clock, x);
+        x.start.next = false;
+        // Count 3, 2, 1, 0 and then finish
+        for _ in 0..4 {
+            sim_assert!(sim, !x.done.val(), x);
+            wait_clock_cycle!(sim, clock, x);
+        }
+        sim_assert!(sim, x.done.val(), x);
+        wait_clock_cycle!(sim, clock, x);
+        sim_assert!(sim, !x.done.val(), x);
+        sim.done(x)
+    });
+    sim.run_to_file(Box::new(uut), 1000, &vcd_path!("payload_enum.vcd"))
+        .unwrap();
+    let mut uut = Countdown::default();
+    uut.connect_all();
+    let vlog = generate_verilog(&uut);
+    assert!(vlog.contains("remaining$let = state$q[(64'h2)+:(4)];"));
+}
@@ -1,3 +1,4 @@
+use rand::Rng;
 use rust_hdl::prelude::*;
 
 #[derive(LogicBlock)]
@@ -46,7 +47,7 @@ fn test_ram_works() {
     yosys_validate("ram", &generate_verilog(&uut)).unwrap();
     let mut sim = Simulation::new();
     let rdata = (0..32)
-        .map(|_| rand::random::<u16>().to_bits())
+        .map(|_| sim.rng().gen::<u16>().to_bits())
         .collect::<Vec<_>>();
     sim.add_clock(5, |x: &mut Box<RAMTest>| x.clock.next = !x.clock.val());
     sim.add_testbench(move |mut sim: Sim<RAMTest>| {
@@ -31,7 +31,7 @@ fn test_register_fifo_works() {
     uut.connect_all();
     let mut sim = Simulation::new();
     let rdata = (0..256)
-        .map(|_| rand::random::<u16>().to_bits())
+        .map(|_| sim.rng().gen::<u16>().to_bits())
         .collect::<Vec<_>>();
     let rdata_read = rdata.clone();
     sim.add_clock(5, |x: &mut Box<RegFIFOTest>| x.clock.next = !x.clock.val());
@@ -44,8 +44,8 @@ fn test_register_fifo_works() {
             x.in_fifo.write.next = true;
             wait_clock_cycle!(sim, clock, x);
             x.in_fifo.write.next = false;
-            if rand::thread_rng().gen::<f64>() < 0.3 {
-                for _ in 0..(rand::thread_rng().gen::<u8>() % 40) {
+            if sim.rng().gen::<f64>() < 0.3 {
+                for _ in 0..(sim.rng().gen::<u8>() % 40) {
                     wait_clock_cycle!(sim, clock, x);
                 }
             }
@@ -62,8 +62,8 @@ fn test_register_fifo_works() {
             x.out_fifo.read.next = true;
             wait_clock_cycle!(sim, clock, x);
             x.out_fifo.read.next = false;
-            if rand::thread_rng().gen::<f64>() < 0.3 {
-                for _ in 0..(rand::thread_rng().gen::<u8>() % 40) {
+            if sim.rng().gen::<f64>() < 0.3 {
+                for _ in 0..(sim.rng().gen::<u8>() % 40) {
                     wait_clock_cycle!(sim, clock, x);
                 }
             }
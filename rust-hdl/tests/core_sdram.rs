@@ -111,9 +111,7 @@ fn test_unit_writes() {
     use rand::Rng;
     let uut = make_test_device();
     let mut sim = Simulation::new();
-    let test_data = (0..256)
-        .map(|_| rand::thread_rng().gen::<u64>())
-        .collect::<Vec<_>>();
+    let test_data = (0..256).map(|_| sim.rng().gen::<u64>()).collect::<Vec<_>>();
     sim.add_clock(5000, |x: &mut Box<TestSDRAMDevice>| {
         x.clock.next = !x.clock.val()
     });
@@ -125,11 +125,7 @@ fn test_unit_writes() {
     let uut = make_test_device();
     let mut sim = Simulation::new();
     let test_data = (0..256)
-        .map(|_| {
-            (0..4)
-                .map(|_| rand::thread_rng().gen::<u16>())
-                .collect::<Vec<_>>()
-        })
+        .map(|_| (0..4).map(|_| sim.rng().gen::<u16>()).collect::<Vec<_>>())
         .collect::<Vec<_>>();
     sim.add_clock(5000, |x: &mut Box<TestSDRAMDevice>| {
         x.clock.next = !x.clock.val()
@@ -56,8 +56,10 @@ fn to_signed_bits_inline_works_issue_2() {
     assert!(vlog.contains("$signed(x)"));
     assert!(vlog.contains("$signed(y)"));
     yosys_validate("to_signed_bits", &vlog).unwrap();
+    // Only used for its seeded random number generator
+    let mut sim = Simulation::<TestCircuit>::new();
     (0..100_000).for_each(|_| {
-        let (x, y) = rand::thread_rng().gen::<(u32, u32)>();
+        let (x, y) = sim.rng().gen::<(u32, u32)>();
         uut.x.next = (x as u64).into();
         uut.y.next = (y as u64).into();
         assert!(simulate(&mut uut, 10));
@@ -0,0 +1,62 @@
+use rust_hdl::prelude::*;
+
+#[derive(LogicBlock, Default)]
+struct Counter {
+    pub clock: Signal<In, Clock>,
+    pub count: Signal<Out, Bits<8>>,
+    dff: DFF<Bits<8>>,
+}
+
+impl Logic for Counter {
+    #[hdl_gen]
+    fn update(&mut self) {
+        dff_setup!(self, clock, dff);
+        self.dff.d.next = self.dff.q.val() + 1;
+        self.count.next = self.dff.q.val();
+    }
+}
+
+#[test]
+fn test_sim_assert_eq_reports_values() {
+    let mut sim = Simulation::new();
+    sim.add_clock(5, |x: &mut Box<Counter>| x.clock.next = !x.clock.val());
+    sim.add_testbench(move |mut sim: Sim<Counter>| {
+        let mut x = sim.init()?;
+        wait_clock_cycles!(sim, clock, x, 4);
+        sim_assert_eq!(sim, x.count.val().index(), 3, x);
+        sim.done(x)
+    });
+    let result = sim.run(Box::new(Counter::default()), 10_000);
+    let Err(SimError::SimHalted(halt)) = result else {
+        panic!("Expected the simulation to halt, got {:?}", result);
+    };
+    assert_eq!(halt.expression, "x.count.val().index() == 3");
+    assert_eq!(halt.rhs.as_deref(), Some("3"));
+    assert_ne!(halt.lhs, halt.rhs);
+    assert_eq!(halt.testbench, 1);
+    assert_ne!(halt.time, 0);
+    assert!(halt.file.ends_with("core_sim_halt.rs"));
+    assert!(halt.to_string().contains(", right: 3"));
+}
+
+#[test]
+fn test_sim_assert_reports_expression() {
+    let mut sim = Simulation::new();
+    sim.add_testbench(move |mut sim: Sim<Counter>| {
+        let mut x = sim.init()?;
+        x = sim.wait(100, x)?;
+        sim_assert!(sim, x.count.val() == 1, x);
+        sim.done(x)
+    });
+    let mut uut = Counter::default();
+    uut.clock.connect();
+    match sim.run(Box::new(uut), 10_000) {
+        Err(SimError::SimHalted(halt)) => {
+            assert_eq!(halt.expression, "x.count.val() == 1");
+            assert_eq!(halt.lhs, None);
+            assert_eq!(halt.time, 100);
+            assert_eq!(halt.testbench, 0);
+        }
+        x => panic!("Expected the simulation to halt, got {:?}", x),
+    }
+}
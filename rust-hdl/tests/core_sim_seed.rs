@@ -0,0 +1,73 @@
+use rand::Rng;
+use rust_hdl::prelude::*;
+
+#[derive(LogicBlock, Default)]
+struct Latch {
+    pub clock: Signal<In, Clock>,
+    pub data_in: Signal<In, Bits<8>>,
+    pub data_out: Signal<Out, Bits<8>>,
+    held: DFF<Bits<8>>,
+}
+
+impl Logic for Latch {
+    #[hdl_gen]
+    fn update(&mut self) {
+        dff_setup!(self, clock, held);
+        self.held.d.next = self.data_in.val();
+        self.data_out.next = self.held.q.val();
+    }
+}
+
+// Drive random values into the latch, and return the values seen at its
+// output, along with the values drawn from the simulation before the run.
+fn random_run(mut sim: Simulation<Latch>) -> (Vec<u8>, Vec<u64>) {
+    let setup = (0..4).map(|_| sim.rng().gen::<u8>()).collect();
+    sim.add_clock(5, |x: &mut Box<Latch>| x.clock.next = !x.clock.val());
+    sim.add_testbench(move |mut sim: Sim<Latch>| {
+        let mut x = sim.init()?;
+        for _ in 0..32 {
+            x.data_in.next = sim.rng().gen::<u8>().to_bits();
+            wait_clock_cycle!(sim, clock, x);
+        }
+        sim.done(x)
+    });
+    let mut seen = vec![];
+    sim.run_observed(Box::new(Latch::default()), 10_000, |_, x| {
+        seen.push(x.data_out.val().to_u64())
+    })
+    .unwrap();
+    (setup, seen)
+}
+
+#[test]
+fn test_seeded_simulations_are_reproducible() {
+    let first = random_run(Simulation::with_seed(0x1234));
+    let second = random_run(Simulation::with_seed(0x1234));
+    assert_eq!(first, second);
+    let other = random_run(Simulation::with_seed(0x4321));
+    assert_ne!(first.0, other.0);
+    assert_ne!(first.1, other.1);
+}
+
+#[test]
+fn test_testbenches_get_independent_generators() {
+    let mut sim: Simulation<Latch> = Simulation::with_seed(7);
+    let setup = sim.rng().gen::<u64>();
+    let mut first = sim.endpoint();
+    let mut second = sim.endpoint();
+    let a = first.rng().gen::<u64>();
+    let b = second.rng().gen::<u64>();
+    assert_ne!(a, b);
+    assert_ne!(setup, a);
+    let mut again: Simulation<Latch> = Simulation::with_seed(7);
+    let _ = again.endpoint();
+    assert_eq!(again.endpoint().rng().gen::<u64>(), b);
+}
+
+#[test]
+fn test_seed_is_taken_from_the_environment() {
+    std::env::set_var(SIMULATION_SEED_VAR, "98765");
+    let sim: Simulation<Latch> = Simulation::new();
+    std::env::remove_var(SIMULATION_SEED_VAR);
+    assert_eq!(sim.seed(), 98765);
+}
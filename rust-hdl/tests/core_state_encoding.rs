@@ -0,0 +1,167 @@
+use rust_hdl::prelude::*;
+
+#[derive(Copy, Clone, Debug, PartialEq, LogicState)]
+#[encoding(one_hot)]
+enum Light {
+    Red,
+    Green,
+    Yellow,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, LogicState)]
+#[encoding(gray)]
+enum Step {
+    A,
+    B,
+    C,
+    D,
+    E,
+}
+
+// The values a datasheet gives for a status register
+#[derive(Copy, Clone, Debug, PartialEq, LogicState)]
+enum Status {
+    Stopped = 0x1,
+    Go = 0x4,
+    Slow,
+}
+
+fn unpack<T: Synth>(x: u64) -> Option<T> {
+    let bits = (0..T::BITS).map(|i| x & (1 << i) != 0).collect::<Vec<_>>();
+    T::from_bits(&bits)
+}
+
+#[test]
+fn test_state_encoding_values() {
+    assert_eq!(Light::BITS, 3);
+    assert_eq!(Step::BITS, 3);
+    assert_eq!(Status::BITS, 3);
+    let lights: [Bits<3>; 3] = [Light::Red, Light::Green, Light::Yellow].map(|x| x.into());
+    assert_eq!(lights, [1_u64, 2, 4].map(|x| x.to_bits()));
+    let steps: [Bits<3>; 5] = [Step::A, Step::B, Step::C, Step::D, Step::E].map(|x| x.into());
+    assert_eq!(steps, [0_u64, 1, 3, 2, 6].map(|x| x.to_bits()));
+    let status: [Bits<3>; 3] = [Status::Stopped, Status::Go, Status::Slow].map(|x| x.into());
+    assert_eq!(status, [1_u64, 4, 5].map(|x| x.to_bits()));
+    assert_eq!(unpack::<Light>(4), Some(Light::Yellow));
+    assert_eq!(unpack::<Light>(3), None);
+    assert_eq!(unpack::<Step>(6), Some(Step::E));
+    assert_eq!(unpack::<Step>(7), None);
+    assert_eq!(unpack::<Status>(5), Some(Status::Slow));
+    assert_eq!(unpack::<Status>(0), None);
+    assert_eq!(Light::Yellow.verilog().to_string(), "64'h4");
+    assert_eq!(Status::default(), Status::Stopped);
+}
+
+#[derive(LogicBlock, Default)]
+struct TrafficLight {
+    pub clock: Signal<In, Clock>,
+    pub advance: Signal<In, Bit>,
+    pub light: Signal<Out, Light>,
+    pub status: Signal<Out, Status>,
+    state: DFF<Light>,
+}
+
+impl Logic for TrafficLight {
+    #[hdl_gen]
+    fn update(&mut self) {
+        dff_setup!(self, clock, state);
+        self.light.next = self.state.q.val();
+        match self.state.q.val() {
+            Light::Red => {
+                self.status.next = Status::Stopped;
+                if self.advance.val() {
+                    self.state.d.next = Light::Green;
+                }
+            }
+            Light::Green => {
+                self.status.next = Status::Go;
+                if self.advance.val() {
+                    self.state.d.next = Light::Yellow;
+                }
+            }
+            Light::Yellow => {
+                self.status.next = Status::Slow;
+                if self.advance.val() {
+                    self.state.d.next = Light::Red;
+                }
+            }
+            _ => {
+                self.status.next = Status::Stopped;
+                self.state.d.next = Light::Red;
+            }
+        }
+    }
+}
+
+const SEQUENCE: [(Light, Status); 4] = [
+    (Light::Red, Status::Stopped),
+    (Light::Green, Status::Go),
+    (Light::Yellow, Status::Slow),
+    (Light::Red, Status::Stopped),
+];
+
+#[test]
+fn test_state_encoding_simulates() {
+    let mut uut = TrafficLight::default();
+    uut.connect_all();
+    let mut sim = Simulation::new();
+    sim.add_clock(5, |x: &mut Box<TrafficLight>| x.clock.next = !x.clock.val());
+    sim.add_testbench(move |mut sim: Sim<TrafficLight>| {
+        let mut x = sim.init()?;
+        x.advance.next = true;
+        for (light, status) in SEQUENCE {
+            x = sim.wait(1, x)?;
+            sim_assert_eq!(sim, x.light.val(), light, x);
+            sim_assert_eq!(sim, x.status.val(), status, x);
+            wait_clock_cycle!(sim, clock, x);
+        }
+        sim.done(x)
+    });
+    sim.run(Box::new(uut), 1000).unwrap();
+}
+
+#[test]
+fn test_state_encoding_in_hdl() {
+    let mut uut = TrafficLight::default();
+    uut.connect_all();
+    let vlog = generate_verilog(&uut);
+    assert!(vlog.contains("localparam Light$Red = 1;"));
+    assert!(vlog.contains("localparam Light$Green = 2;"));
+    assert!(vlog.contains("localparam Light$Yellow = 4;"));
+    assert!(vlog.contains("localparam Status$Slow = 5;"));
+    assert!(vlog.contains("output reg  [2:0] status"));
+    let sv = generate_system_verilog(&uut);
+    assert!(sv.contains(
+        "typedef enum logic [2:0] {Light$Red = 3'd1, Light$Green = 3'd2, Light$Yellow = 3'd4} Light;"
+    ));
+    assert!(sv.contains("Status$Stopped = 3'd1, Status$Go = 3'd4, Status$Slow = 3'd5"));
+    // VHDL picks its own encoding for enumerated types, so these are vectors
+    let vhdl = generate_vhdl(&uut);
+    assert!(vhdl.contains("constant \\Light$Yellow\\ : std_logic_vector(2 downto 0) := \"100\";"));
+    assert!(vhdl.contains("constant \\Status$Slow\\ : std_logic_vector(2 downto 0) := \"101\";"));
+    assert!(vhdl.contains("case std_logic_vector(\\state$q\\) is"));
+    assert!(vhdl.contains("\\status$next\\ := unsigned(\\Status$Stopped\\);"));
+    assert!(vhdl.contains("when \\Light$Green\\ =>"));
+    assert!(!vhdl.contains("type \\Light\\"));
+    assert!(vhdl.contains("signal q_reg : unsigned(2 downto 0) := unsigned'(\"001\");"));
+}
+
+#[test]
+fn test_state_encoding_compiles() {
+    let mut uut = TrafficLight::default();
+    uut.connect_all();
+    let mut sim = CompiledSimulation::new(&uut).unwrap();
+    let [clock, advance, light, status] =
+        ["clock", "advance", "light", "status"].map(|x| sim.signal(x).unwrap());
+    sim.write(advance, true);
+    for (expected_light, expected_status) in SEQUENCE {
+        sim.settle().unwrap();
+        let expected_light: Bits<3> = expected_light.into();
+        let expected_status: Bits<3> = expected_status.into();
+        assert_eq!(sim.get(light), expected_light.index() as u128);
+        assert_eq!(sim.get(status), expected_status.index() as u128);
+        sim.set(clock, 1);
+        sim.settle().unwrap();
+        sim.set(clock, 0);
+    }
+}
@@ -94,8 +94,10 @@ fn test_mig_tester_synthesizes() {
 
 #[test]
 fn test_struct_pack() {
+    // Only used for its seeded random number generator
+    let mut sim = Simulation::<MIGTester>::new();
     for _ in 0..10_000 {
-        let opcode = rand::thread_rng().gen::<u8>() % 6;
+        let opcode = sim.rng().gen::<u8>() % 6;
         let instruction = match opcode {
             0 => MIGCommand::Noop,
             1 => MIGCommand::Read,
@@ -105,8 +107,8 @@ fn test_struct_pack() {
             5 => MIGCommand::Refresh,
             _ => panic!("Unexpected random enum value"),
         };
-        let burst_length = bit_cast::<6, 8>(rand::thread_rng().gen::<u8>().to_bits::<8>());
-        let byte_address = bit_cast::<30, 32>(rand::thread_rng().gen::<u32>().to_bits::<32>());
+        let burst_length = bit_cast::<6, 8>(sim.rng().gen::<u8>().to_bits::<8>());
+        let byte_address = bit_cast::<30, 32>(sim.rng().gen::<u32>().to_bits::<32>());
         let x = MIGStruct {
             instruction,
             burst_length,
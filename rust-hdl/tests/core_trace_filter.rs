@@ -0,0 +1,125 @@
+use rust_hdl::prelude::*;
+
+#[derive(LogicBlock, Default)]
+struct Ticker {
+    pub clock: Signal<In, Clock>,
+    pub enable: Signal<In, Bit>,
+    pub count: Signal<Out, Bits<8>>,
+    counter: DFF<Bits<8>>,
+}
+
+impl Logic for Ticker {
+    #[hdl_gen]
+    fn update(&mut self) {
+        dff_setup!(self, clock, counter);
+        self.count.next = self.counter.q.val();
+        if self.enable.val() {
+            self.counter.d.next = self.counter.q.val() + 1;
+        }
+    }
+}
+
+fn trace(filter: TraceFilter<Ticker>) -> Vec<u8> {
+    let mut sim = Simulation::new();
+    sim.add_clock(5, |x: &mut Box<Ticker>| x.clock.next = !x.clock.val());
+    sim.add_testbench(move |mut sim: Sim<Ticker>| {
+        let mut x = sim.init()?;
+        x.enable.next = true;
+        wait_clock_cycles!(sim, clock, x, 30);
+        sim.done(x)
+    });
+    let mut vcd = vec![];
+    sim.run_traced_filtered(Box::new(Ticker::default()), 10_000, &mut vcd, filter)
+        .unwrap();
+    vcd
+}
+
+fn to_u64(x: &[vcd::Value]) -> u64 {
+    x.iter()
+        .fold(0, |acc, b| (acc << 1) | (*b == vcd::Value::V1) as u64)
+}
+
+#[test]
+fn test_trace_filter_selects_signals_and_window() {
+    let vcd = trace(
+        TraceFilter::default()
+            .include("uut.*")
+            .exclude("uut.enable")
+            .window(100, 200),
+    );
+    let mut parser = vcd::Parser::new(&vcd[..]);
+    let header = parser.parse_header().unwrap();
+    assert!(header.find_var(&["uut", "clock"]).is_some());
+    assert!(header.find_var(&["uut", "count"]).is_some());
+    assert!(header.find_var(&["uut", "enable"]).is_none());
+    assert!(header.find_scope(&["uut", "counter"]).is_none());
+    let times = parser
+        .filter_map(|x| match x.unwrap() {
+            vcd::Command::Timestamp(t) => Some(t),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert!(!times.is_empty());
+    assert!(times.iter().all(|t| (100..200).contains(t)));
+}
+
+#[test]
+fn test_trace_filter_trigger_and_depth() {
+    let vcd = trace(
+        TraceFilter::default()
+            .max_depth(1)
+            .trigger(|x: &Ticker| x.count.val() == 3),
+    );
+    let mut parser = vcd::Parser::new(&vcd[..]);
+    let header = parser.parse_header().unwrap();
+    assert!(header.find_scope(&["uut", "counter"]).is_none());
+    let count = header.find_var(&["uut", "count"]).unwrap().code;
+    let counts = parser
+        .filter_map(|x| match x.unwrap() {
+            vcd::Command::ChangeVector(id, v) if id == count => Some(to_u64(&v)),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(counts[0], 3);
+    assert!(counts.windows(2).all(|x| x[1] == x[0] + 1));
+}
+
+#[test]
+fn test_trace_without_filter_starts_with_dump() {
+    let vcd = trace(TraceFilter::default());
+    let mut parser = vcd::Parser::new(&vcd[..]);
+    parser.parse_header().unwrap();
+    assert!(matches!(
+        parser.next().unwrap().unwrap(),
+        vcd::Command::Begin(vcd::SimulationCommand::Dumpvars)
+    ));
+}
+
+#[test]
+fn test_trace_filter_dumps_again_after_a_gap() {
+    let vcd = trace(TraceFilter::default().window(50, 100).window(200, 250));
+    let mut parser = vcd::Parser::new(&vcd[..]);
+    let header = parser.parse_header().unwrap();
+    let count = header.find_var(&["uut", "count"]).unwrap().code;
+    let mut time = 0;
+    let mut dumps = vec![];
+    let mut counts = vec![];
+    for command in parser {
+        match command.unwrap() {
+            vcd::Command::Timestamp(t) => time = t,
+            vcd::Command::Begin(vcd::SimulationCommand::Dumpvars) => dumps.push(time),
+            vcd::Command::ChangeVector(id, v) if id == count => counts.push((time, to_u64(&v))),
+            _ => {}
+        }
+    }
+    assert_eq!(dumps.len(), 2);
+    assert!((50..100).contains(&dumps[0]));
+    assert!((200..250).contains(&dumps[1]));
+    // The count kept going while the filter was not active, and the trace
+    // picks up with its current value rather than the one before the gap
+    let gap = counts.iter().position(|(t, _)| *t >= 200).unwrap();
+    let (_, last_before) = counts[gap - 1];
+    let (first_time, first_after) = counts[gap];
+    assert_eq!(first_time, dumps[1]);
+    assert!(first_after >= last_before + 9);
+}
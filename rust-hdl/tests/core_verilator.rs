@@ -0,0 +1,120 @@
+use rust_hdl::prelude::*;
+
+#[derive(LogicBlock, Default)]
+struct Accumulator {
+    pub clock: Signal<In, Clock>,
+    pub data_in: Signal<In, Bits<8>>,
+    pub sum: Signal<Out, Bits<16>>,
+    pub odd: Signal<Out, Bit>,
+    accum: DFF<Bits<16>>,
+}
+
+impl Logic for Accumulator {
+    #[hdl_gen]
+    fn update(&mut self) {
+        dff_setup!(self, clock, accum);
+        self.accum.d.next = self.accum.q.val() + bit_cast::<16, 8>(self.data_in.val());
+        self.sum.next = self.accum.q.val();
+        self.odd.next = self.accum.q.val().get_bit(0);
+    }
+}
+
+// The Verilog of this block does not match its simulation model
+#[derive(LogicBlock, Default)]
+struct Liar {
+    pub a: Signal<In, Bit>,
+    pub b: Signal<Out, Bit>,
+}
+
+impl Logic for Liar {
+    fn update(&mut self) {
+        self.b.next = self.a.val();
+    }
+    fn connect(&mut self) {
+        self.b.connect();
+    }
+    fn hdl(&self) -> Verilog {
+        Verilog::Custom("always @(*) b = ~a;".into())
+    }
+}
+
+fn accumulator_sim() -> Simulation<Accumulator> {
+    let mut sim = Simulation::new();
+    sim.add_clock(5, |x: &mut Box<Accumulator>| x.clock.next = !x.clock.val());
+    sim.add_testbench(move |mut sim: Sim<Accumulator>| {
+        let mut x = sim.init()?;
+        for i in 0..100_u64 {
+            x.data_in.next = ((i * 13) & 0xFF).into();
+            wait_clock_cycle!(sim, clock, x);
+        }
+        sim.done(x)
+    });
+    sim
+}
+
+// These tests need verilator.  Run them with `cargo test -- --ignored`.
+#[test]
+#[ignore]
+fn test_verilator_cosim_matches() {
+    let mut sim = accumulator_sim();
+    verilator_cosim(
+        &mut sim,
+        Box::new(Accumulator::default()),
+        100_000,
+        "cosim_accum",
+    )
+    .unwrap();
+}
+
+#[test]
+#[ignore]
+fn test_verilator_cosim_reports_mismatch() {
+    let mut sim = Simulation::new();
+    sim.add_testbench(move |mut sim: Sim<Liar>| {
+        let mut x = sim.init()?;
+        x.a.next = true;
+        x = sim.wait(10, x)?;
+        sim.done(x)
+    });
+    match verilator_cosim(&mut sim, Box::new(Liar::default()), 100, "cosim_liar") {
+        Err(CosimError::Mismatch { signal, .. }) => {
+            assert_eq!(signal.name, "b");
+        }
+        x => panic!("Expected a mismatch, got {:?}", x),
+    }
+}
+
+// The liar is hidden inside of a block whose outputs are correct
+#[derive(LogicBlock, Default)]
+struct Hideout {
+    pub a: Signal<In, Bit>,
+    pub b: Signal<Out, Bit>,
+    liar: Liar,
+}
+
+impl Logic for Hideout {
+    #[hdl_gen]
+    fn update(&mut self) {
+        self.liar.a.next = self.a.val();
+        self.b.next = self.a.val();
+    }
+}
+
+#[test]
+#[ignore]
+fn test_verilator_cosim_reports_internal_mismatch() {
+    let mut sim = Simulation::new();
+    sim.add_testbench(move |mut sim: Sim<Hideout>| {
+        let mut x = sim.init()?;
+        x.a.next = true;
+        x = sim.wait(10, x)?;
+        sim.done(x)
+    });
+    match verilator_cosim(&mut sim, Box::new(Hideout::default()), 100, "cosim_hideout") {
+        Err(CosimError::Mismatch { signal, .. }) => {
+            assert_eq!(signal.path, "top$liar");
+            assert_eq!(signal.name, "b");
+        }
+        x => panic!("Expected a mismatch, got {:?}", x),
+    }
+}
@@ -0,0 +1,102 @@
+use rust_hdl::docs::vcd2svg::{vcd_to_txt, waveform_to_txt};
+use rust_hdl::prelude::*;
+
+#[derive(LogicBlock, Default)]
+struct Ticker {
+    pub clock: Signal<In, Clock>,
+    pub enable: Signal<In, Bit>,
+    pub count: Signal<Out, Bits<8>>,
+    counter: DFF<Bits<8>>,
+}
+
+impl Logic for Ticker {
+    #[hdl_gen]
+    fn update(&mut self) {
+        dff_setup!(self, clock, counter);
+        self.count.next = self.counter.q.val();
+        if self.enable.val() {
+            self.counter.d.next = self.counter.q.val() + 1;
+        }
+    }
+}
+
+fn record() -> Waveform {
+    let mut sim = Simulation::new();
+    sim.add_clock(5, |x: &mut Box<Ticker>| x.clock.next = !x.clock.val());
+    sim.add_testbench(move |mut sim: Sim<Ticker>| {
+        let mut x = sim.init()?;
+        wait_clock_cycles!(sim, clock, x, 2);
+        x.enable.next = true;
+        wait_clock_cycles!(sim, clock, x, 10);
+        sim.done(x)
+    });
+    let mut waveform = Waveform::default();
+    sim.run_recorded(Box::new(Ticker::default()), 10_000, &mut waveform)
+        .unwrap();
+    waveform
+}
+
+#[test]
+fn test_waveform_queries() {
+    let waveform = record();
+    assert!(waveform.end_time() > 0);
+    let clock = waveform.trace("uut.clock").unwrap();
+    let edges = clock.rising_edges(0, 100);
+    assert_eq!(edges.len(), 10);
+    assert!(edges.windows(2).all(|x| x[1] - x[0] == 10));
+    assert!(clock
+        .falling_edges(0, 100)
+        .iter()
+        .all(|x| clock.value_at(*x) == Some(&false.into())));
+    let count = waveform.trace("uut.count").unwrap();
+    assert_eq!(count.width, 8);
+    assert_eq!(
+        waveform.value_at("uut.count", 0).unwrap().as_u128(),
+        Some(0)
+    );
+    let five = count.first_time(0, |x| x.as_u128() == Some(5)).unwrap();
+    assert_eq!(count.value_at(five - 1).unwrap().as_u128(), Some(4));
+    assert_eq!(
+        count.first_time(five, |x| x.as_u128() == Some(5)),
+        Some(five)
+    );
+    assert!(count.first_time(0, |x| x.as_u128() == Some(200)).is_none());
+    assert_eq!(count.changes_in(five, five + 1).len(), 1);
+    assert!(waveform.trace("uut.missing").is_none());
+}
+
+#[test]
+fn test_waveform_save_and_export() {
+    let waveform = record();
+    let path = vcd_path!("ticker.wave");
+    waveform.save(&path).unwrap();
+    assert_eq!(Waveform::load(&path).unwrap(), waveform);
+    let vcd = vcd_path!("ticker_wave.vcd");
+    waveform
+        .write_vcd(std::fs::File::create(&vcd).unwrap())
+        .unwrap();
+    let signals = ["uut.clock", "uut.enable", "uut.count", "uut.counter.q"];
+    assert_eq!(
+        vcd_to_txt(&vcd, &signals, 0, 200, 120).unwrap(),
+        waveform_to_txt(&waveform, &signals, 0, 200, 120).unwrap()
+    );
+}
+
+#[test]
+fn test_waveform_load_rejects_oversized_lengths() {
+    let waveform = record();
+    let path = vcd_path!("ticker_truncated.wave");
+    waveform.save(&path).unwrap();
+    let data = std::fs::read(&path).unwrap();
+    // The header, the end time, one trace, and then a path that claims to
+    // be far longer than the file
+    let magic = b"rust-hdl waveform 1\n";
+    let mut bad = magic.to_vec();
+    bad.extend([0, 1, 0xFF, 0xFF, 0xFF, 0xFF, 0x0F, b'u']);
+    std::fs::write(&path, &bad).unwrap();
+    let err = Waveform::load(&path).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    // A cut short file is an error too, rather than a partial waveform
+    std::fs::write(&path, &data[..data.len() / 2]).unwrap();
+    assert!(Waveform::load(&path).is_err());
+}